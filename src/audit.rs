@@ -0,0 +1,195 @@
+//! A bounded in-memory record of what an engine decided, for answering
+//! "what did this instance do in the last few minutes" without logging
+//! infrastructure.
+
+use crate::canonical::canonical_json;
+use crate::engine::{Decision, EngineError};
+use crate::lock;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::{Mutex, MutexGuard};
+
+/// One evaluation: the decision, or just the payload hash when no rule
+/// decided.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Unix seconds.
+    pub timestamp: u64,
+    pub ruleset_sha: String,
+    pub payload_sha: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decision: Option<Decision>,
+}
+
+impl AuditRecord {
+    /// The record as one line of canonical JSON, so equal records always
+    /// export identically.
+    pub fn to_json_line(&self) -> Result<String, EngineError> {
+        let value = serde_json::to_value(self)
+            .map_err(|e| EngineError::Execution(format!("Failed to serialize audit record: {}", e)))?;
+        Ok(canonical_json(&value))
+    }
+}
+
+/// The most recent `capacity` records, oldest first. Recording takes a
+/// short lock, so evaluation stays `&self`.
+#[derive(Debug)]
+pub struct AuditLog {
+    capacity: usize,
+    include_no_match: bool,
+    records: Mutex<VecDeque<AuditRecord>>,
+}
+
+impl AuditLog {
+    pub fn new(capacity: usize, include_no_match: bool) -> Self {
+        Self { capacity, include_no_match, records: Mutex::new(VecDeque::new()) }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Whether evaluations in which no rule decided are recorded.
+    pub fn includes_no_match(&self) -> bool {
+        self.include_no_match
+    }
+
+    /// Appends `record`, evicting the oldest once the log is full.
+    pub fn record(&self, record: AuditRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut records = self.lock();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// The last `n` records, oldest first.
+    pub fn recent(&self, n: usize) -> Vec<AuditRecord> {
+        let records = self.lock();
+        records.iter().skip(records.len().saturating_sub(n)).cloned().collect()
+    }
+
+    /// Removes and returns every record, oldest first.
+    pub fn drain(&self) -> Vec<AuditRecord> {
+        self.lock().drain(..).collect()
+    }
+
+    /// Writes every record as a JSON line, oldest first, leaving the log as
+    /// it is. Returns the number of lines written.
+    pub fn export_jsonl(&self, writer: &mut impl Write) -> Result<usize, EngineError> {
+        let records = self.recent(usize::MAX);
+        for record in &records {
+            writeln!(writer, "{}", record.to_json_line()?)
+                .map_err(|e| EngineError::Execution(format!("Failed to write audit log: {}", e)))?;
+        }
+        Ok(records.len())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<AuditRecord>> {
+        lock::lock(&self.records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::parse_yaml;
+    use crate::test_support::payload;
+    use crate::engine::RuleEngine;
+    use serde_json::json;
+
+    #[test]
+    fn test_audit_log_capacity() {
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 100 }
+    then: { outcome: { action: "review" } }
+"#).unwrap()).unwrap();
+        engine.evaluate(&payload(json!({"amount": 500}))).unwrap();
+        assert!(engine.recent_decisions(10).is_empty());
+
+        engine.enable_audit_log(3, false);
+        for amount in [200, 5, 300, 400, 500] {
+            engine.evaluate(&payload(json!({"amount": amount}))).unwrap();
+        }
+        let shas: Vec<_> = engine.recent_decisions(10).iter()
+            .map(|record| record.payload_sha.clone())
+            .collect();
+        let expected: Vec<_> = [300, 400, 500].iter().map(|amount| crate::payload_sha(&json!({"amount": amount}))).collect();
+        assert_eq!(shas, expected);
+        assert_eq!(engine.recent_decisions(1)[0].payload_sha, expected[2]);
+        assert_eq!(engine.recent_decisions(1)[0].ruleset_sha, *engine.get_ruleset_sha().unwrap());
+
+        // Clones share the log.
+        let clone = engine.clone();
+        clone.evaluate(&payload(json!({"amount": 600}))).unwrap();
+        assert_eq!(engine.drain_audit_log().len(), 3);
+        assert!(engine.recent_decisions(10).is_empty());
+
+        engine.enable_audit_log(2, true);
+        engine.evaluate(&payload(json!({"amount": 5}))).unwrap();
+        let missed = engine.recent_decisions(1).remove(0);
+        assert_eq!((missed.decision, missed.payload_sha), (None, crate::payload_sha(&json!({"amount": 5}))));
+    }
+
+    #[test]
+    fn test_audit_records_use_the_evaluation_time() {
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 100 }
+    then: { outcome: { action: "review" } }
+"#).unwrap()).unwrap();
+        engine.enable_audit_log(4, true);
+        let options = crate::engine::EvalOptions {
+            now: crate::temporal::parse_timestamp_str("2023-06-01T00:00:00Z"),
+            ..Default::default()
+        };
+        for amount in [500, 5] {
+            engine.evaluate_value_with_options(&json!({"amount": amount}), &options).unwrap();
+        }
+        let timestamps: Vec<u64> = engine.recent_decisions(4).iter().map(|record| record.timestamp).collect();
+        assert_eq!(timestamps, vec![1_685_577_600, 1_685_577_600]);
+    }
+
+    #[test]
+    fn test_audit_jsonl() {
+        let decision: Decision = serde_json::from_value(json!({
+            "decision_id": "0190b5a4-7c1e-7000-8000-000000000001",
+            "rule_id": "large",
+            "outcome": {"route": "manual", "action": "review"},
+            "matched_conditions": ["amount"],
+            "elapsed_us": 3,
+            "timestamp": 1700000000,
+            "rule_sha": "abc",
+            "rule_content_sha": "def",
+        })).unwrap();
+        let log = AuditLog::new(4, true);
+        log.record(AuditRecord { timestamp: 1700000000, ruleset_sha: "abc".to_string(), payload_sha: "p1".to_string(), decision: Some(decision) });
+        log.record(AuditRecord { timestamp: 1700000001, ruleset_sha: "abc".to_string(), payload_sha: "p2".to_string(), decision: None });
+
+        let mut out = Vec::new();
+        assert_eq!(log.export_jsonl(&mut out).unwrap(), 2);
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text, concat!(
+            r#"{"decision":{"decision_id":"0190b5a4-7c1e-7000-8000-000000000001","elapsed_ns":0,"elapsed_us":3,"matched_conditions":["amount"],"outcome":{"action":"review","route":"manual"},"payload_sha":"","rule_content_sha":"def","rule_id":"large","rule_sha":"abc","ruleset_version":"","timestamp":1700000000,"total_elapsed_ns":0},"payload_sha":"p1","ruleset_sha":"abc","timestamp":1700000000}"#,
+            "\n",
+            r#"{"payload_sha":"p2","ruleset_sha":"abc","timestamp":1700000001}"#,
+            "\n",
+        ));
+        // Exporting doesn't drain, and each line reads back as the record.
+        let records: Vec<AuditRecord> = text.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(records, log.recent(10));
+    }
+}
@@ -0,0 +1,78 @@
+//! Canonical JSON for hashing: object keys sorted by code point, no
+//! whitespace, and numbers written the same way however they were parsed
+//! (`1.0` and `1` both become `1`), so equal documents hash equally.
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// Canonical serialization of `value`.
+pub fn canonical_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(value, &mut out);
+    out
+}
+
+/// Hex SHA-256 of the canonical serialization of `value`.
+pub fn payload_sha(value: &Value) -> String {
+    sha256_hex(canonical_json(value).as_bytes())
+}
+
+/// [`payload_sha`] of an object given as its fields, without building it.
+pub(crate) fn fields_sha<'a>(fields: impl Iterator<Item = (&'a String, &'a Value)>) -> String {
+    let mut out = String::new();
+    write_object(fields, &mut out);
+    sha256_hex(out.as_bytes())
+}
+
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+fn write_value(value: &Value, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => match n.as_f64().filter(|_| n.is_f64()) {
+            // Integral floats within the exactly representable range print as
+            // integers; -0.0 becomes 0.
+            Some(f) if f.fract() == 0.0 && f.abs() < 9_007_199_254_740_992.0 => {
+                let _ = write!(out, "{}", f as i64);
+            },
+            _ => {
+                let _ = write!(out, "{}", n);
+            },
+        },
+        Value::String(s) => write_string(s, out),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(item, out);
+            }
+            out.push(']');
+        },
+        Value::Object(map) => write_object(map.iter(), out),
+    }
+}
+
+fn write_object<'a>(fields: impl Iterator<Item = (&'a String, &'a Value)>, out: &mut String) {
+    let mut fields: Vec<_> = fields.collect();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    out.push('{');
+    for (i, (key, value)) in fields.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_string(key, out);
+        out.push(':');
+        write_value(value, out);
+    }
+    out.push('}');
+}
+
+fn write_string(s: &str, out: &mut String) {
+    out.push_str(&serde_json::to_string(s).unwrap_or_default());
+}
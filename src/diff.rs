@@ -0,0 +1,282 @@
+//! Semantic differences between two rulesets: rules are matched by ID and
+//! compared by content, so formatting and key order don't show up.
+
+use crate::canonical::canonical_json;
+use crate::engine::{Rule, RuleSet};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleSetDiff {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<FieldChange>,
+    /// Per metadata key, in key order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub metadata: Vec<FieldChange>,
+    /// Other ruleset-level sections such as `definitions` or `constants`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub settings: Vec<FieldChange>,
+    /// IDs of rules only in the new ruleset, in its order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub added: Vec<String>,
+    /// IDs of rules only in the old ruleset, in its order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modified: Vec<RuleChange>,
+    /// Rules kept in both that moved relative to the other kept rules, in
+    /// the new order; the rules they moved past are not listed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reordered: Vec<String>,
+}
+
+/// A changed field; `before` or `after` is absent when it was added or
+/// removed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleChange {
+    pub rule_id: String,
+    /// Changed rule fields (`when`, `then`, `severity`, ...), in field order.
+    pub changes: Vec<FieldChange>,
+}
+
+/// Compares `old` with `new`.
+pub fn diff_rulesets(old: &RuleSet, new: &RuleSet) -> RuleSetDiff {
+    let mut diff = RuleSetDiff {
+        version: (old.version != new.version).then(|| FieldChange {
+            field: "version".to_string(),
+            before: Some(Value::String(old.version.clone())),
+            after: Some(Value::String(new.version.clone())),
+        }),
+        metadata: object_changes(&to_object(&old.metadata), &to_object(&new.metadata)),
+        ..Default::default()
+    };
+
+    let sections = |ruleset: &RuleSet| {
+        let mut object = to_object(ruleset);
+        for key in ["rules", "version", "metadata"] {
+            object.remove(key);
+        }
+        object
+    };
+    diff.settings = object_changes(&sections(old), &sections(new));
+
+    let old_ids: HashSet<&str> = old.rules.iter().map(|rule| rule.id.as_str()).collect();
+    let new_ids: HashSet<&str> = new.rules.iter().map(|rule| rule.id.as_str()).collect();
+    diff.added = new.rules.iter().filter(|rule| !old_ids.contains(rule.id.as_str())).map(|rule| rule.id.clone()).collect();
+    diff.removed = old.rules.iter().filter(|rule| !new_ids.contains(rule.id.as_str())).map(|rule| rule.id.clone()).collect();
+
+    let kept_old: HashMap<&str, (usize, &Rule)> = old
+        .rules
+        .iter()
+        .filter(|rule| new_ids.contains(rule.id.as_str()))
+        .enumerate()
+        .map(|(position, rule)| (rule.id.as_str(), (position, rule)))
+        .collect();
+    let kept_new: Vec<_> = new.rules.iter().filter(|rule| old_ids.contains(rule.id.as_str())).collect();
+    let old_positions: Vec<usize> = kept_new.iter().map(|rule| kept_old[rule.id.as_str()].0).collect();
+    for (rule, moved) in kept_new.iter().zip(moved_rules(&old_positions)) {
+        if moved {
+            diff.reordered.push(rule.id.clone());
+        }
+        let before = to_object(kept_old[rule.id.as_str()].1);
+        let changes = object_changes(&before, &to_object(rule));
+        if !changes.is_empty() {
+            diff.modified.push(RuleChange { rule_id: rule.id.clone(), changes });
+        }
+    }
+    diff
+}
+
+/// Marks the kept rules that moved, given each one's old position in the new
+/// order. Rules on a longest increasing run of old positions stayed put and
+/// everything else moved, so moving one rule flags only that rule. Among
+/// equally long runs, the one keeping the most rules at their old position
+/// wins, which makes a swap flag the two swapped rules.
+fn moved_rules(old_positions: &[usize]) -> Vec<bool> {
+    // (run length, rules at their old position) of the best run ending at each rule.
+    let mut best = vec![(0, 0); old_positions.len()];
+    let mut previous = vec![None; old_positions.len()];
+    for (position, &old_position) in old_positions.iter().enumerate() {
+        let unmoved = usize::from(old_position == position);
+        best[position] = (1, unmoved);
+        for earlier in 0..position {
+            let candidate = (best[earlier].0 + 1, best[earlier].1 + unmoved);
+            if old_positions[earlier] < old_position && candidate > best[position] {
+                best[position] = candidate;
+                previous[position] = Some(earlier);
+            }
+        }
+    }
+    let mut moved = vec![true; old_positions.len()];
+    let mut current = (0..old_positions.len()).max_by_key(|&position| best[position]);
+    while let Some(position) = current {
+        moved[position] = false;
+        current = previous[position];
+    }
+    moved
+}
+
+impl RuleSetDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == RuleSetDiff::default()
+    }
+
+    /// One line per change, for review tools and logs:
+    ///
+    /// ```text
+    /// version: "12" -> "13"
+    /// + rule emergency_block
+    /// ~ rule large.when: {...} -> {...}
+    /// ```
+    pub fn render(&self) -> String {
+        let mut lines = Vec::new();
+        let mut push_change = |prefix: &str, change: &FieldChange| {
+            let show = |value: &Option<Value>| value.as_ref().map_or("(none)".to_string(), canonical_json);
+            lines.push(format!("{}{}: {} -> {}", prefix, change.field, show(&change.before), show(&change.after)));
+        };
+        if let Some(change) = &self.version {
+            push_change("", change);
+        }
+        for change in &self.metadata {
+            push_change("metadata.", change);
+        }
+        for change in &self.settings {
+            push_change("", change);
+        }
+        for change in &self.modified {
+            for field in &change.changes {
+                push_change(&format!("~ rule {}.", change.rule_id), field);
+            }
+        }
+        lines.extend(self.added.iter().map(|id| format!("+ rule {}", id)));
+        lines.extend(self.removed.iter().map(|id| format!("- rule {}", id)));
+        if !self.reordered.is_empty() {
+            lines.push(format!("reordered: {}", self.reordered.join(", ")));
+        }
+        lines.join("\n")
+    }
+}
+
+fn to_object(value: &impl Serialize) -> Map<String, Value> {
+    match serde_json::to_value(value) {
+        Ok(Value::Object(object)) => object,
+        _ => Map::new(),
+    }
+}
+
+pub(crate) fn object_changes(before: &Map<String, Value>, after: &Map<String, Value>) -> Vec<FieldChange> {
+    let mut keys: Vec<&String> = before.keys().chain(after.keys().filter(|key| !before.contains_key(*key))).collect();
+    keys.sort();
+    keys.into_iter()
+        .filter(|key| before.get(*key) != after.get(*key))
+        .map(|key| FieldChange {
+            field: key.clone(),
+            before: before.get(key).cloned(),
+            after: after.get(key).cloned(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::parse_yaml;
+    use serde_json::json;
+
+    const BASE: &str = r#"
+version: "12"
+metadata: { owner: "risk", region: "eu" }
+rules:
+  - id: "large"
+    severity: "high"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "review" } }
+  - id: "foreign"
+    when: { type: "not_equals", field: "country", value: "US" }
+    then: { outcome: { action: "flag" } }
+  - id: "night"
+    when: { type: "in", field: "hour", values: [0, 1, 2] }
+    then: { outcome: { action: "hold" } }
+"#;
+
+    fn diff(new: &str) -> RuleSetDiff {
+        diff_rulesets(&parse_yaml(BASE).unwrap(), &parse_yaml(new).unwrap())
+    }
+
+    #[test]
+    fn test_condition_value_change() {
+        let changed = diff(&BASE.replace("value: 1000", "value: 2000"));
+        assert_eq!(changed.modified.len(), 1);
+        assert_eq!(changed.modified[0].rule_id, "large");
+        let change = &changed.modified[0].changes[0];
+        assert_eq!((change.field.as_str(), changed.modified[0].changes.len()), ("when", 1));
+        assert_eq!(change.after.as_ref().unwrap()["value"], json!(2000.0));
+        assert!(changed.added.is_empty() && changed.removed.is_empty() && changed.reordered.is_empty());
+        assert_eq!(
+            changed.render(),
+            r#"~ rule large.when: {"field":"amount","type":"greater_than","value":1000} -> {"field":"amount","type":"greater_than","value":2000}"#
+        );
+
+        // Flow style versus block style is not a change.
+        let reformatted = BASE.replace(
+            "then: { outcome: { action: \"flag\" } }",
+            "then:\n      outcome:\n        action: \"flag\"",
+        );
+        assert!(diff(&reformatted).is_empty());
+    }
+
+    #[test]
+    fn test_reordering_additions_and_removals() {
+        let swapped: RuleSet = {
+            let mut ruleset = parse_yaml(BASE).unwrap();
+            ruleset.rules.swap(0, 2);
+            ruleset
+        };
+        let pure = diff_rulesets(&parse_yaml(BASE).unwrap(), &swapped);
+        assert_eq!(pure.reordered, vec!["night", "large"]);
+        assert!(pure.modified.is_empty());
+        assert_eq!(pure.render(), "reordered: night, large");
+
+        let mut trimmed = parse_yaml(BASE).unwrap();
+        trimmed.rules.remove(1);
+        trimmed.rules.push(parse_yaml(BASE).unwrap().rules[1].clone());
+        trimmed.rules[2].id = "foreign_v2".to_string();
+        let changed = diff_rulesets(&parse_yaml(BASE).unwrap(), &trimmed);
+        assert_eq!((changed.added.clone(), changed.removed.clone()), (vec!["foreign_v2".to_string()], vec!["foreign".to_string()]));
+        assert!(changed.reordered.is_empty());
+    }
+
+    #[test]
+    fn test_moving_one_rule_flags_only_that_rule() {
+        let mut moved = parse_yaml(BASE).unwrap();
+        let night = moved.rules.remove(2);
+        moved.rules.insert(0, night);
+        let changed = diff_rulesets(&parse_yaml(BASE).unwrap(), &moved);
+        assert_eq!(changed.reordered, vec!["night"]);
+        assert_eq!(changed.render(), "reordered: night");
+    }
+
+    #[test]
+    fn test_version_and_metadata_changes() {
+        let changed = diff(&BASE.replace("\"12\"", "\"13\"").replace("region: \"eu\"", "team: \"payments\""));
+        assert_eq!(changed.version.as_ref().unwrap().after, Some(json!("13")));
+        let keys: Vec<_> = changed.metadata.iter().map(|c| (c.field.as_str(), c.before.is_some(), c.after.is_some())).collect();
+        assert_eq!(keys, vec![("region", true, false), ("team", false, true)]);
+
+        let with_constants = BASE.replace("rules:", "constants: { LIMIT: 5 }\nrules:");
+        let changed = diff(&with_constants);
+        assert_eq!(changed.settings[0].field, "constants");
+        assert_eq!(
+            diff(&BASE.replace("\"12\"", "\"13\"")).render(),
+            "version: \"12\" -> \"13\""
+        );
+    }
+}
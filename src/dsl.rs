@@ -1,45 +1,778 @@
-use crate::engine::{RuleSet, Rule, Condition, Action, EngineError};
+use crate::engine::{
+    priority_order, Action, Condition, ConstantRef, EngineError, FieldDefault, HitPolicy, IssueSeverity, Operation,
+    ParseError, Rule, RuleLifecycle, RuleSet, ValidationIssue, DEFAULT_RULE_ID,
+};
+use crate::expr::{self, BinaryOp, Expr};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde_yaml;
 use std::collections::HashMap;
 
+/// How rulesets are read from YAML or JSON.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Drop keys the DSL doesn't know instead of failing, for documents
+    /// written for a newer version. Off by default, so that a typo such as
+    /// `severty:` is an error rather than silently ignored.
+    pub allow_unknown_fields: bool,
+}
+
 pub fn parse_yaml(yaml_content: &str) -> Result<RuleSet, EngineError> {
-    serde_yaml::from_str(yaml_content)
-        .map_err(|e| EngineError::Parse(format!("YAML parse error: {}", e)))
+    parse_yaml_with_options(yaml_content, &ParseOptions::default())
+}
+
+pub fn parse_yaml_with_options(yaml_content: &str, options: &ParseOptions) -> Result<RuleSet, EngineError> {
+    parse_source(yaml_content, Format::Yaml, options)
 }
 
 pub fn parse_json(json_content: &str) -> Result<RuleSet, EngineError> {
-    serde_json::from_str(json_content)
-        .map_err(|e| EngineError::Parse(format!("JSON parse error: {}", e)))
+    parse_json_with_options(json_content, &ParseOptions::default())
+}
+
+pub fn parse_json_with_options(json_content: &str, options: &ParseOptions) -> Result<RuleSet, EngineError> {
+    parse_source(json_content, Format::Json, options)
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Yaml,
+    Json,
+}
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Format::Yaml => "YAML",
+            Format::Json => "JSON",
+        }
+    }
+
+    fn read<T: DeserializeOwned>(self, source: &str) -> Result<T, SourceError> {
+        match self {
+            Format::Yaml => serde_yaml::from_str(source).map_err(|e| {
+                let position = e.location().map(|location| (location.line(), location.column()));
+                SourceError::new(e.to_string(), position)
+            }),
+            Format::Json => serde_json::from_str(source).map_err(|e| {
+                let position = (e.line() > 0).then(|| (e.line(), e.column()));
+                SourceError::new(e.to_string(), position)
+            }),
+        }
+    }
+}
+
+/// A serde error as its message and 1-based line and column.
+struct SourceError {
+    message: String,
+    position: Option<(usize, usize)>,
+}
+
+impl SourceError {
+    /// Drops serde's own `at line L column C` suffix; [`ParseError`]
+    /// reports the position itself.
+    fn new(message: String, position: Option<(usize, usize)>) -> Self {
+        let message = match position {
+            Some((line, column)) => message.strip_suffix(&format!(" at line {} column {}", line, column))
+                .map(str::to_string)
+                .unwrap_or(message),
+            None => message,
+        };
+        Self { message, position }
+    }
+}
+
+/// Reads a ruleset, substituting constants and, in lenient mode, dropping
+/// unknown keys one at a time until the document reads. Errors say where in
+/// the source serde stopped and, when it is found in the document, the
+/// innermost failing part and the rule it belongs to.
+fn parse_source(source: &str, format: Format, options: &ParseOptions) -> Result<RuleSet, EngineError> {
+    let fail = |message: &dyn std::fmt::Display, position: Option<(usize, usize)>, rule_id: Option<String>| {
+        let message = format!("{} parse error: {}", format.name(), message);
+        EngineError::Parse(source_parse_error(source, message, position, rule_id))
+    };
+    // Documents without constants read straight into the typed ruleset; the
+    // document is only read again to explain a failure.
+    if !source.contains("$const") && !options.allow_unknown_fields {
+        let error = match format.read(source) {
+            Ok(ruleset) => return Ok(ruleset),
+            Err(error) => error,
+        };
+        let explained = format.read(source).ok().and_then(|raw| explain(&raw, &error.message));
+        return Err(match explained {
+            Some((message, rule_id)) => fail(&message, error.position, rule_id),
+            None => fail(&error.message, error.position, None),
+        });
+    }
+    let raw = format.read(source).map_err(|error| fail(&error.message, error.position, None))?;
+    let (mut raw, refs) = substitute_constants(raw)?;
+    loop {
+        let error = match RuleSet::deserialize(&raw) {
+            Ok(mut ruleset) => {
+                ruleset.constant_refs = refs;
+                return Ok(ruleset);
+            },
+            Err(error) => error.to_string(),
+        };
+        if options.allow_unknown_fields {
+            let dropped = locate_error(&raw).and_then(|located| {
+                let key = located.unknown_field()?;
+                raw.pointer_mut(&located.pointer)?.as_object_mut()?.remove(&key)
+            });
+            if dropped.is_some() {
+                continue;
+            }
+        }
+        // The document read here is not the source text, so serde has no
+        // position for the error.
+        return Err(match explain(&raw, &error) {
+            Some((message, rule_id)) => fail(&message, None, rule_id),
+            None => fail(&error, None, None),
+        });
+    }
+}
+
+/// The innermost part of `raw` that fails to read, as the message to report
+/// and the rule it is in, when its error is the one serde reported as
+/// `message`.
+fn explain(raw: &serde_json::Value, message: &str) -> Option<(String, Option<String>)> {
+    let located = locate_error(raw)?;
+    message.ends_with(&located.error.to_string()).then(|| (located.to_string(), located.rule_id(raw)))
+}
+
+/// A [`ParseError`] with up to two lines of `source` either side of the
+/// problem.
+fn source_parse_error(source: &str, message: String, position: Option<(usize, usize)>, rule_id: Option<String>) -> ParseError {
+    let context = position
+        .map(|(line, _)| {
+            source.lines()
+                .enumerate()
+                .map(|(index, text)| (index + 1, text.to_string()))
+                .skip(line.saturating_sub(3))
+                .take(if line > 2 { 5 } else { line + 2 })
+                .collect()
+        })
+        .unwrap_or_default();
+    ParseError {
+        message,
+        line: position.map(|(line, _)| line),
+        column: position.map(|(_, column)| column),
+        rule_id,
+        context,
+    }
+}
+
+/// The innermost rule, condition, action or operation of a document that
+/// fails to read, with the error for it.
+struct LocatedError {
+    pointer: String,
+    context: Option<String>,
+    error: serde_json::Error,
+}
+
+impl LocatedError {
+    /// The ID of the rule the error is in.
+    fn rule_id(&self, raw: &serde_json::Value) -> Option<String> {
+        let index = self.pointer.strip_prefix("/rules/")?.split('/').next()?;
+        Some(raw.pointer(&format!("/rules/{}/id", index))?.as_str()?.to_string())
+    }
+
+    /// The key of an unknown-field error.
+    fn unknown_field(&self) -> Option<String> {
+        let message = self.error.to_string();
+        let rest = message.strip_prefix("unknown field `")?;
+        Some(rest[..rest.find('`')?].to_string())
+    }
+}
+
+impl std::fmt::Display for LocatedError {
+    /// `Rule large: unknown field `filed`, expected ... at /when/conditions/1`,
+    /// with the path inside the rule or definition, or the full pointer
+    /// outside one.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let inner = match &self.context {
+            Some(_) => self.pointer.splitn(4, '/').nth(3).map(|rest| format!("/{}", rest)).unwrap_or_default(),
+            None => self.pointer.clone(),
+        };
+        if let Some(context) = &self.context {
+            write!(f, "{}: ", context)?;
+        }
+        write!(f, "{}", self.error)?;
+        if !inner.is_empty() {
+            write!(f, " at {}", inner)?;
+        }
+        Ok(())
+    }
 }
 
+#[derive(Clone, Copy)]
+enum Part {
+    RuleSet,
+    Rule,
+    Condition,
+    Action,
+    Operation,
+}
+
+fn locate_error(raw: &serde_json::Value) -> Option<LocatedError> {
+    locate(raw, raw, String::new(), Part::RuleSet)
+}
+
+fn locate(document: &serde_json::Value, value: &serde_json::Value, pointer: String, part: Part) -> Option<LocatedError> {
+    fn check<T: DeserializeOwned>(value: &serde_json::Value) -> Result<(), serde_json::Error> {
+        T::deserialize(value).map(|_| ())
+    }
+    let error = match part {
+        Part::RuleSet => check::<RuleSet>(value),
+        Part::Rule => check::<Rule>(value),
+        Part::Condition => check::<Condition>(value),
+        Part::Action => check::<Action>(value),
+        Part::Operation => check::<Operation>(value),
+    }.err()?;
+
+    let children: &[(&str, Part, bool)] = match part {
+        Part::RuleSet => &[("rules", Part::Rule, true), ("definitions", Part::Condition, true), ("default_action", Part::Action, false)],
+        Part::Rule => &[("when", Part::Condition, false), ("then", Part::Action, false), ("else_then", Part::Action, false)],
+        Part::Condition => &[
+            ("conditions", Part::Condition, true),
+            ("condition", Part::Condition, false),
+            ("if", Part::Condition, false),
+            ("then", Part::Condition, false),
+        ],
+        Part::Action => &[("operations", Part::Operation, true)],
+        Part::Operation => &[],
+    };
+    for &(key, child_part, many) in children {
+        let Some(child) = value.get(key) else { continue };
+        let pointer = format!("{}/{}", pointer, escape_pointer_token(key));
+        let found = match (many, child) {
+            (true, serde_json::Value::Array(items)) => items.iter().enumerate()
+                .find_map(|(index, item)| locate(document, item, format!("{}/{}", pointer, index), child_part)),
+            (true, serde_json::Value::Object(map)) => map.iter()
+                .find_map(|(name, item)| locate(document, item, format!("{}/{}", pointer, escape_pointer_token(name)), child_part)),
+            _ => locate(document, child, pointer, child_part),
+        };
+        if found.is_some() {
+            return found;
+        }
+    }
+    Some(LocatedError { context: pointer_context(document, &pointer), pointer, error })
+}
+
+/// Replaces every `{"$const": name}` outside the `constants` section with
+/// the named constant, before the document is read into typed conditions.
+fn substitute_constants(mut raw: serde_json::Value) -> Result<(serde_json::Value, Vec<ConstantRef>), EngineError> {
+    let mut refs = Vec::new();
+    if let Some(document) = raw.as_object() {
+        for (key, value) in document.iter().filter(|(key, _)| *key != "constants") {
+            find_constant_refs(value, &format!("/{}", escape_pointer_token(key)), &mut refs);
+        }
+    }
+    let constants = raw.get("constants").and_then(|c| c.as_object()).cloned().unwrap_or_default();
+    for reference in &refs {
+        let Some(value) = constants.get(&reference.name) else {
+            return Err(EngineError::RuleValidation(unknown_constant_message(&raw, reference)));
+        };
+        if let Some(slot) = raw.pointer_mut(&reference.pointer) {
+            *slot = value.clone();
+        }
+    }
+    Ok((raw, refs))
+}
+
+/// Reads a ruleset document that may reference constants, such as a
+/// serialized [`RuleSet`].
+pub(crate) fn ruleset_from_value(raw: serde_json::Value) -> Result<RuleSet, EngineError> {
+    let (raw, refs) = substitute_constants(raw)?;
+    let mut ruleset: RuleSet = serde_json::from_value(raw).map_err(|e| EngineError::Parse(ParseError::new(e.to_string())))?;
+    ruleset.constant_refs = refs;
+    Ok(ruleset)
+}
+
+fn find_constant_refs(value: &serde_json::Value, pointer: &str, refs: &mut Vec<ConstantRef>) {
+    match value {
+        serde_json::Value::Object(map) => match (map.len(), map.get("$const")) {
+            (1, Some(serde_json::Value::String(name))) => {
+                refs.push(ConstantRef { pointer: pointer.to_string(), name: name.clone() });
+            },
+            _ => {
+                for (key, item) in map {
+                    find_constant_refs(item, &format!("{}/{}", pointer, escape_pointer_token(key)), refs);
+                }
+            },
+        },
+        serde_json::Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                find_constant_refs(item, &format!("{}/{}", pointer, index), refs);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Names the rule or definition containing an unknown reference, with the
+/// path inside it.
+fn unknown_constant_message(raw: &serde_json::Value, reference: &ConstantRef) -> String {
+    let inner = reference.pointer.splitn(4, '/').nth(3).map(|rest| format!("/{}", rest)).unwrap_or_default();
+    match pointer_context(raw, &reference.pointer) {
+        Some(context) => format!("{}: unknown constant '{}' at {}", context, reference.name, inner),
+        None => format!("Unknown constant '{}' at {}", reference.name, reference.pointer),
+    }
+}
+
+/// `Rule <id>` or `Rule <name> (definition)` for a pointer into a rule or
+/// definition.
+fn pointer_context(raw: &serde_json::Value, pointer: &str) -> Option<String> {
+    let mut tokens = pointer.splitn(4, '/').skip(1);
+    let (section, key) = (tokens.next().unwrap_or_default(), tokens.next()?);
+    match section {
+        "rules" => raw.pointer(&format!("/rules/{}/id", key))
+            .and_then(|id| id.as_str())
+            .map(|id| format!("Rule {}", id)),
+        "definitions" => Some(format!("Rule {} (definition)", key.replace("~1", "/").replace("~0", "~"))),
+        _ => None,
+    }
+}
+
+pub(crate) fn escape_pointer_token(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Bounds on rule conditions, enforced when a ruleset is loaded. Evaluation
+/// recurses through conditions, so without them a generated or hostile
+/// ruleset could exhaust the stack or make every event expensive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SafetyLimits {
+    /// Nesting depth of a rule's condition as in
+    /// [`RuleSet::condition_depth`], where a single leaf has depth 1.
+    pub max_condition_depth: usize,
+    /// Conditions in one rule, counting a referenced definition at each use.
+    pub max_conditions_per_rule: usize,
+    /// Values in one `in` or `not_in` list.
+    pub max_in_list: usize,
+    /// Bytes in one `matches` regex or `matches_glob` pattern.
+    pub max_pattern_length: usize,
+}
+
+impl Default for SafetyLimits {
+    fn default() -> Self {
+        Self {
+            max_condition_depth: 64,
+            max_conditions_per_rule: 1_000,
+            max_in_list: 10_000,
+            max_pattern_length: 1_000,
+        }
+    }
+}
+
+/// [`dsl_safety_issues`] under the default limits, failing with the first.
 pub fn validate_dsl_safety(ruleset: &RuleSet) -> Result<(), EngineError> {
-    // Static analysis to ensure no forbidden operations
-    for rule in &ruleset.rules {
-        validate_condition_safety(&rule.when)?;
+    match dsl_safety_issues(ruleset, &SafetyLimits::default()).into_iter().next() {
+        Some(issue) => Err(EngineError::RuleValidation(issue.message)),
+        None => Ok(()),
+    }
+}
+
+/// Every DSL safety violation, as error-severity issues: conditions beyond
+/// `limits`, and outcome expressions outside the safe subset. When a
+/// condition is nested too deeply, only the depth is reported, since the
+/// other checks would recurse through it.
+pub fn dsl_safety_issues(ruleset: &RuleSet, limits: &SafetyLimits) -> Vec<ValidationIssue> {
+    let too_deep = condition_depth_issues(ruleset, limits);
+    if !too_deep.is_empty() {
+        return too_deep;
+    }
+    let mut issues = Vec::new();
+    let mut check = |rule_id: Option<&str>, path: String, result: Result<(), EngineError>| {
+        if let Err(error) = result {
+            issues.push(ValidationIssue::from_error(rule_id, path, error));
+        }
+    };
+    for (index, rule) in ruleset.rules.iter().enumerate() {
+        let id = Some(rule.id.as_str());
+        if count_conditions(ruleset, &rule.when, &mut Vec::new(), limits.max_conditions_per_rule) > limits.max_conditions_per_rule {
+            check(id, format!("/rules/{}/when", index), Err(EngineError::RuleValidation(format!(
+                "Rule {}: more than {} conditions, the limit per rule",
+                rule.id, limits.max_conditions_per_rule
+            ))));
+        }
+        check(id, format!("/rules/{}/when", index), validate_condition_safety(&rule.id, &rule.when, limits));
+        for (key, action) in std::iter::once(("then", &rule.then)).chain(rule.else_then.as_ref().map(|action| ("else_then", action))) {
+            let result = action.outcome_values().try_for_each(|(_, value)| validate_outcome_safety(&rule.id, value));
+            check(id, format!("/rules/{}/{}", index, key), result);
+        }
+    }
+    let mut names: Vec<&String> = ruleset.definitions.keys().collect();
+    names.sort();
+    for name in names {
+        let context = format!("{} (definition)", name);
+        let result = validate_condition_safety(&context, &ruleset.definitions[name], limits);
+        check(None, format!("/definitions/{}", escape_pointer_token(name)), result);
+    }
+    if let Some(default_action) = &ruleset.default_action {
+        let result = default_action.outcome_values().try_for_each(|(_, value)| validate_outcome_safety(DEFAULT_RULE_ID, value));
+        check(Some(DEFAULT_RULE_ID), "/default_action".to_string(), result);
+    }
+    issues
+}
+
+/// Rules and definitions nested deeper than `limits` allow. The check
+/// itself recurses no deeper than the limit, so it is safe on any ruleset
+/// and should run before anything else walks its conditions.
+pub fn condition_depth_issues(ruleset: &RuleSet, limits: &SafetyLimits) -> Vec<ValidationIssue> {
+    let max = limits.max_condition_depth;
+    let too_deep = |rule_id: Option<&str>, context: &str, path: String| ValidationIssue {
+        rule_id: rule_id.map(str::to_string),
+        path,
+        message: format!("Rule {}: condition nesting exceeds the limit of {} levels", context, max),
+        severity: IssueSeverity::Error,
+    };
+    let mut issues: Vec<ValidationIssue> = ruleset.rules.iter()
+        .enumerate()
+        .filter(|(_, rule)| ruleset.condition_deeper_than(&rule.when, max))
+        .map(|(index, rule)| too_deep(Some(&rule.id), &rule.id, format!("/rules/{}/when", index)))
+        .collect();
+    let mut names: Vec<&String> = ruleset.definitions.keys().collect();
+    names.sort();
+    for name in names.into_iter().filter(|name| ruleset.condition_deeper_than(&ruleset.definitions[*name], max)) {
+        let path = format!("/definitions/{}", escape_pointer_token(name));
+        issues.push(too_deep(None, &format!("{} (definition)", name), path));
+    }
+    issues
+}
+
+/// Conditions evaluated for `condition`, counting each use of a definition.
+/// Counting stops once it passes `cap`, since definitions that use each
+/// other twice over double the count with every level.
+pub(crate) fn count_conditions<'a>(ruleset: &'a RuleSet, condition: &'a Condition, refs: &mut Vec<&'a str>, cap: usize) -> usize {
+    if let Condition::Ref { name } = condition {
+        let Some(definition) = ruleset.definitions.get(name).filter(|_| !refs.contains(&name.as_str())) else {
+            return 1;
+        };
+        refs.push(name);
+        let count = count_conditions(ruleset, definition, refs, cap);
+        refs.pop();
+        return count;
     }
-    Ok(())
+    let mut count = 1;
+    for child in condition.children() {
+        if count > cap {
+            break;
+        }
+        count += count_conditions(ruleset, child, refs, cap);
+    }
+    count
+}
+
+/// Warnings about rules that can't work as written: conditions that can
+/// never match, and rules that never decide because an earlier rule always
+/// decides first. Both checks are structural and only report what they can
+/// prove, so an empty result doesn't mean every rule is reachable.
+pub fn lint_ruleset(ruleset: &RuleSet) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    for (index, rule) in ruleset.rules.iter().enumerate() {
+        contradictions(ruleset, &rule.id, &rule.when, &format!("/rules/{}/when", index), 0, &mut issues);
+    }
+    issues.extend(shadowed_rules(ruleset));
+    issues
 }
 
-fn validate_condition_safety(condition: &Condition) -> Result<(), EngineError> {
+/// Rules that can never decide because a rule evaluated before them decides
+/// every event they match, as warnings naming both rules.
+///
+/// The analysis is structural rather than logical: a rule is shadowed when
+/// the earlier rule's conditions are a subset of its own conjunction, with
+/// `and`s flattened and `ref`s resolved. Identical conditions and an
+/// always-true earlier rule (an empty `and`) are special cases of this.
+/// Implications through values or other operators, such as `> 1000` after
+/// `> 500` or a condition after an `or` containing it, are not detected.
+/// Only rules with no effective window or the same one, and in the same
+/// group, shadow each other; nothing is reported under the `collect` hit
+/// policy, in which every matching rule decides.
+fn shadowed_rules(ruleset: &RuleSet) -> Vec<ValidationIssue> {
+    let order: Vec<usize> = match ruleset.hit_policy {
+        HitPolicy::Collect => return Vec::new(),
+        HitPolicy::Priority => priority_order(ruleset),
+        HitPolicy::First | HitPolicy::Unique => (0..ruleset.rules.len()).collect(),
+    };
+    let conjunctions: Vec<_> = ruleset.rules.iter().map(|rule| conjuncts(ruleset, &rule.when, 0)).collect();
+    let mut issues = Vec::new();
+    for (position, &index) in order.iter().enumerate() {
+        let rule = &ruleset.rules[index];
+        let Some(conditions) = conjunctions[index].as_ref().filter(|_| rule.is_live(true)) else {
+            continue;
+        };
+        let shadowing = order[..position].iter().find_map(|&earlier_index| {
+            let earlier = &ruleset.rules[earlier_index];
+            let earlier_conditions = conjunctions[earlier_index].as_ref()?;
+            let unbounded = earlier.effective_from.is_none() && earlier.effective_until.is_none();
+            let same_window = earlier.effective_from == rule.effective_from && earlier.effective_until == rule.effective_until;
+            let shadows = earlier.is_live(rule.status == RuleLifecycle::Draft)
+                && earlier.group == rule.group
+                && (unbounded || same_window)
+                && earlier_conditions.iter().all(|condition| conditions.contains(condition));
+            shadows.then_some((earlier, earlier_conditions))
+        });
+        let Some((earlier, earlier_conditions)) = shadowing else {
+            continue;
+        };
+        let reason = if earlier_conditions.is_empty() {
+            "always matches"
+        } else if conditions.iter().all(|condition| earlier_conditions.contains(condition)) {
+            "has the same conditions"
+        } else {
+            "requires only some of its conditions"
+        };
+        issues.push(ValidationIssue {
+            rule_id: Some(rule.id.clone()),
+            path: format!("/rules/{}/when", index),
+            message: format!("Rule {}: unreachable, rule {} runs first and {}", rule.id, earlier.id, reason),
+            severity: IssueSeverity::Warning,
+        });
+    }
+    issues
+}
+
+/// The conditions `condition` requires together, with `and`s flattened and
+/// `ref`s resolved; `None` for an unknown or cyclic reference.
+fn conjuncts(ruleset: &RuleSet, condition: &Condition, refs: usize) -> Option<Vec<serde_json::Value>> {
     match condition {
-        Condition::And { conditions } | Condition::Or { conditions } => {
-            for cond in conditions {
-                validate_condition_safety(cond)?;
+        Condition::And { conditions } => conditions.iter().try_fold(Vec::new(), |mut all, condition| {
+            all.extend(conjuncts(ruleset, condition, refs)?);
+            Some(all)
+        }),
+        Condition::Ref { name } if refs < ruleset.definitions.len() => {
+            conjuncts(ruleset, ruleset.definitions.get(name)?, refs + 1)
+        },
+        Condition::Ref { .. } => None,
+        other => serde_json::to_value(other).ok().map(|value| vec![value]),
+    }
+}
+
+/// Reports, under `pointer`, each pair of conditions in one conjunction that
+/// constrain the same field in mutually exclusive ways (`equals` and `in`
+/// sets without a common value, or numeric bounds with an empty
+/// intersection), and each empty `or`. Conditions with a `default` or on a
+/// wildcard path are never paired, since their operands can differ.
+fn contradictions(
+    ruleset: &RuleSet,
+    rule_id: &str,
+    condition: &Condition,
+    pointer: &str,
+    refs: usize,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let mut report = |message: String| issues.push(ValidationIssue {
+        rule_id: Some(rule_id.to_string()),
+        path: pointer.to_string(),
+        message: format!("Rule {}: {}", rule_id, message),
+        severity: IssueSeverity::Warning,
+    });
+    let children = match condition {
+        Condition::And { .. } => {
+            let mut conjuncts = Vec::new();
+            flatten_and(ruleset, condition, pointer.to_string(), refs, &mut conjuncts);
+            let constrained: Vec<_> = conjuncts.iter()
+                .filter_map(|(pointer, condition, _)| Some((pointer, field_constraint(condition)?)))
+                .collect();
+            for (position, (first, (field, constraint))) in constrained.iter().enumerate() {
+                for (second, (other_field, other)) in &constrained[position + 1..] {
+                    if field == other_field && constraint.excludes(other) {
+                        report(format!("conditions at {} and {} can never both match on field '{}'", first, second, field));
+                    }
+                }
             }
+            conjuncts
+        },
+        Condition::Ref { name } => match ruleset.definitions.get(name).filter(|_| refs < ruleset.definitions.len()) {
+            Some(definition) => vec![(format!("/definitions/{}", escape_pointer_token(name)), definition, refs + 1)],
+            None => Vec::new(),
         },
-        Condition::Not { condition } => {
-            validate_condition_safety(condition)?;
+        Condition::Or { conditions } if conditions.is_empty() => {
+            report(format!("empty or at {} never matches", pointer));
+            Vec::new()
         },
-        _ => {
-            // All other conditions are safe by design
+        _ => child_pointers(condition, pointer).into_iter().map(|(pointer, child)| (pointer, child, refs)).collect(),
+    };
+    for (pointer, child, refs) in children {
+        contradictions(ruleset, rule_id, child, &pointer, refs, issues);
+    }
+}
+
+/// The conditions of an `and`, with nested `and`s and the references to
+/// them flattened, each with its pointer and reference depth.
+fn flatten_and<'a>(
+    ruleset: &'a RuleSet,
+    condition: &'a Condition,
+    pointer: String,
+    refs: usize,
+    conjuncts: &mut Vec<(String, &'a Condition, usize)>,
+) {
+    match condition {
+        Condition::And { conditions } => {
+            for (index, condition) in conditions.iter().enumerate() {
+                flatten_and(ruleset, condition, format!("{}/conditions/{}", pointer, index), refs, conjuncts);
+            }
+        },
+        Condition::Ref { name } if refs < ruleset.definitions.len() => {
+            if let Some(definition) = ruleset.definitions.get(name) {
+                let pointer = format!("/definitions/{}", escape_pointer_token(name));
+                flatten_and(ruleset, definition, pointer, refs + 1, conjuncts);
+            }
+        },
+        _ => conjuncts.push((pointer, condition, refs)),
+    }
+}
+
+/// [`Condition::children`], each with its pointer below `pointer`.
+fn child_pointers<'a>(condition: &'a Condition, pointer: &str) -> Vec<(String, &'a Condition)> {
+    condition.children()
+        .into_iter()
+        .enumerate()
+        .map(|(index, child)| {
+            let member = match condition {
+                Condition::Implies { .. } => ["if", "then"][index].to_string(),
+                Condition::Not { .. } | Condition::Any { .. } | Condition::All { .. } => "condition".to_string(),
+                _ => format!("conditions/{}", index),
+            };
+            (format!("{}/{}", pointer, member), child)
+        })
+        .collect()
+}
+
+/// What a leaf condition requires of its field's value.
+enum Constraint<'a> {
+    OneOf(&'a [serde_json::Value]),
+    /// Lower and upper bounds, each with whether it is inclusive.
+    Range(Option<(f64, bool)>, Option<(f64, bool)>),
+}
+
+impl Constraint<'_> {
+    /// Whether no value can satisfy both constraints.
+    fn excludes(&self, other: &Constraint<'_>) -> bool {
+        match (self, other) {
+            (Constraint::OneOf(values), Constraint::OneOf(others)) => !values.iter().any(|value| {
+                others.iter().any(|other| value == other || value.as_f64().is_some_and(|n| other.as_f64() == Some(n)))
+            }),
+            (Constraint::OneOf(values), range @ Constraint::Range(..)) | (range @ Constraint::Range(..), Constraint::OneOf(values)) => {
+                values.iter().all(|value| value.as_f64().is_some_and(|n| !range.admits(n)))
+            },
+            (Constraint::Range(lower, upper), Constraint::Range(other_lower, other_upper)) => {
+                // The tighter bound on each side; exclusive wins a tie.
+                let lower = match (lower, other_lower) {
+                    (Some(a), Some(b)) => Some(if a.0 > b.0 || (a.0 == b.0 && !a.1) { *a } else { *b }),
+                    (bound, None) | (None, bound) => *bound,
+                };
+                let upper = match (upper, other_upper) {
+                    (Some(a), Some(b)) => Some(if a.0 < b.0 || (a.0 == b.0 && !a.1) { *a } else { *b }),
+                    (bound, None) | (None, bound) => *bound,
+                };
+                match (lower, upper) {
+                    (Some((low, low_inclusive)), Some((high, high_inclusive))) => {
+                        low > high || (low == high && !(low_inclusive && high_inclusive))
+                    },
+                    _ => false,
+                }
+            },
+        }
+    }
+
+    fn admits(&self, value: f64) -> bool {
+        match self {
+            Constraint::Range(lower, upper) => {
+                lower.is_none_or(|(low, inclusive)| value > low || (inclusive && value == low))
+                    && upper.is_none_or(|(high, inclusive)| value < high || (inclusive && value == high))
+            },
+            Constraint::OneOf(values) => values.iter().any(|v| v.as_f64() == Some(value)),
         }
     }
-    Ok(())
+}
+
+/// The field a leaf condition constrains and how, for the conditions whose
+/// constraints [`Constraint::excludes`] compares.
+fn field_constraint(condition: &Condition) -> Option<(&str, Constraint<'_>)> {
+    let (field, constraint) = match condition {
+        Condition::Equals { field, value, default: FieldDefault { value: None, .. }, .. } => {
+            (field, Constraint::OneOf(std::slice::from_ref(value)))
+        },
+        Condition::In { field, values, default: FieldDefault { value: None, .. }, .. } => {
+            (field, Constraint::OneOf(values))
+        },
+        Condition::GreaterThan { field, value, default: FieldDefault { value: None, .. }, .. } => {
+            (field, Constraint::Range(Some((*value, false)), None))
+        },
+        Condition::GreaterThanOrEqual { field, value, default: FieldDefault { value: None, .. }, .. } => {
+            (field, Constraint::Range(Some((*value, true)), None))
+        },
+        Condition::LessThan { field, value, default: FieldDefault { value: None, .. }, .. } => {
+            (field, Constraint::Range(None, Some((*value, false))))
+        },
+        Condition::LessThanOrEqual { field, value, default: FieldDefault { value: None, .. }, .. } => {
+            (field, Constraint::Range(None, Some((*value, true))))
+        },
+        Condition::Between { field, min, max, inclusive_min, inclusive_max, default: FieldDefault { value: None, .. }, .. } => {
+            (field, Constraint::Range(Some((*min, *inclusive_min)), Some((*max, *inclusive_max))))
+        },
+        _ => return None,
+    };
+    (!field.contains('*')).then_some((field.as_str(), constraint))
+}
+
+/// Outcome `$expr` values may only combine payload fields and numbers with
+/// `+ - * /`, `min`, `max` and `round`.
+fn validate_outcome_safety(rule_id: &str, value: &serde_json::Value) -> Result<(), EngineError> {
+    if let Some(parsed) = expr::outcome_expr(value) {
+        let expr = parsed.map_err(|e| EngineError::RuleValidation(format!("Rule {}: {}", rule_id, e)))?;
+        return if expr_is_safe(&expr) {
+            Ok(())
+        } else {
+            Err(EngineError::RuleValidation(format!(
+                "Rule {}: outcome $expr may only use payload fields, numbers, + - * /, min, max and round",
+                rule_id
+            )))
+        };
+    }
+    match value {
+        serde_json::Value::Array(items) => items.iter().try_for_each(|item| validate_outcome_safety(rule_id, item)),
+        serde_json::Value::Object(map) => map.values().try_for_each(|item| validate_outcome_safety(rule_id, item)),
+        _ => Ok(()),
+    }
+}
+
+fn expr_is_safe(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(serde_json::Value::Number(_)) | Expr::Field(_) => true,
+        Expr::Neg(inner) | Expr::Round(inner, _) => expr_is_safe(inner),
+        Expr::Binary(BinaryOp::Rem, _, _) => false,
+        Expr::Binary(_, lhs, rhs) => expr_is_safe(lhs) && expr_is_safe(rhs),
+        Expr::Min(args) | Expr::Max(args) => args.iter().all(expr_is_safe),
+        Expr::Literal(_) | Expr::Coalesce(_) => false,
+    }
+}
+
+/// The list and pattern limits, for `condition` and everything nested in
+/// it. References are checked where the definition is.
+fn validate_condition_safety(context: &str, condition: &Condition, limits: &SafetyLimits) -> Result<(), EngineError> {
+    match condition {
+        Condition::In { values, .. } | Condition::NotIn { values, .. } if values.len() > limits.max_in_list => {
+            Err(EngineError::RuleValidation(format!(
+                "Rule {}: {} list of {} values exceeds the limit of {}",
+                context, condition.type_name(), values.len(), limits.max_in_list
+            )))
+        },
+        Condition::Matches { pattern, .. } | Condition::MatchesGlob { pattern, .. } if pattern.len() > limits.max_pattern_length => {
+            Err(EngineError::RuleValidation(format!(
+                "Rule {}: {} pattern of {} bytes exceeds the limit of {}",
+                context, condition.type_name(), pattern.len(), limits.max_pattern_length
+            )))
+        },
+        _ => condition.children().into_iter().try_for_each(|child| validate_condition_safety(context, child, limits)),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::engine::{CompareOp, RuleEngine};
 
     #[test]
     fn test_yaml_parsing() {
@@ -61,4 +794,655 @@ metadata: {}
         let result = parse_yaml(yaml);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_inclusive_comparisons_parse() {
+        let yaml = r#"
+rules:
+  - id: "gte_rule"
+    when:
+      type: "gte"
+      field: "amount"
+      value: 100
+    then:
+      outcome: {}
+  - id: "lte_rule"
+    when:
+      type: "less_than_or_equal"
+      field: "amount"
+      value: 5.5
+    then:
+      outcome: {}
+version: "1.0"
+metadata: {}
+"#;
+        let ruleset = parse_yaml(yaml).unwrap();
+        assert!(matches!(ruleset.rules[0].when, Condition::GreaterThanOrEqual { value, .. } if value == 100.0));
+        assert!(matches!(ruleset.rules[1].when, Condition::LessThanOrEqual { value, .. } if value == 5.5));
+
+        let json = r#"{"version": "1.0", "metadata": {}, "rules": [
+            {"id": "r", "when": {"type": "lte", "field": "amount", "value": 3}, "then": {"outcome": {}}}
+        ]}"#;
+        let ruleset = parse_json(json).unwrap();
+        assert!(matches!(ruleset.rules[0].when, Condition::LessThanOrEqual { .. }));
+    }
+
+    #[test]
+    fn test_not_equals_round_trip() {
+        let json = r#"{"version": "1.0", "metadata": {}, "rules": [
+            {"id": "r", "when": {"type": "not_equals", "field": "status", "value": "active"}, "then": {"outcome": {}}}
+        ]}"#;
+        let ruleset = parse_json(json).unwrap();
+        assert!(matches!(ruleset.rules[0].when, Condition::NotEquals { missing_is_match: false, .. }));
+
+        let yaml = serde_yaml::to_string(&ruleset).unwrap();
+        let reparsed = parse_yaml(&yaml).unwrap();
+        assert!(matches!(reparsed.rules[0].when, Condition::NotEquals { ref field, .. } if field == "status"));
+    }
+
+    #[test]
+    fn test_between_defaults_to_inclusive() {
+        let yaml = r#"
+rules:
+  - id: "range"
+    when:
+      type: "between"
+      field: "score"
+      min: 300
+      max: 700
+      inclusive_max: false
+    then:
+      outcome: {}
+version: "1.0"
+metadata: {}
+"#;
+        let ruleset = parse_yaml(yaml).unwrap();
+        assert!(matches!(
+            ruleset.rules[0].when,
+            Condition::Between { inclusive_min: true, inclusive_max: false, .. }
+        ));
+    }
+
+    #[test]
+    fn test_prefix_suffix_conditions_are_safe() {
+        let yaml = r#"
+rules:
+  - id: "not_grad"
+    when:
+      type: "not"
+      condition:
+        type: "ends_with"
+        field: "city"
+        value: "grad"
+    then:
+      outcome: {}
+  - id: "zurich"
+    when:
+      type: "starts_with"
+      field: "city"
+      value: "Zürich"
+    then:
+      outcome: {}
+version: "1.0"
+metadata: {}
+"#;
+        let ruleset = parse_yaml(yaml).unwrap();
+        assert!(validate_dsl_safety(&ruleset).is_ok());
+        assert!(matches!(ruleset.rules[1].when, Condition::StartsWith { ref value, .. } if value == "Zürich"));
+    }
+
+    #[test]
+    fn test_not_in_round_trip() {
+        let yaml = r#"
+rules:
+  - id: "blocked"
+    when:
+      type: "not_in"
+      field: "country"
+      values: ["DE", "FR"]
+      missing_is_match: true
+    then:
+      outcome: {}
+version: "1.0"
+metadata: {}
+"#;
+        let ruleset = parse_yaml(yaml).unwrap();
+        assert!(validate_dsl_safety(&ruleset).is_ok());
+
+        let json = serde_json::to_string(&ruleset).unwrap();
+        let reparsed = parse_json(&json).unwrap();
+        assert!(matches!(
+            reparsed.rules[0].when,
+            Condition::NotIn { ref values, missing_is_match: true, .. } if values.len() == 2
+        ));
+    }
+
+    #[test]
+    fn test_length_operators_parse() {
+        for operator in ["eq", "gt", "lt", "gte", "lte"] {
+            let json = format!(
+                r#"{{"version": "1.0", "metadata": {{}}, "rules": [
+                    {{"id": "r", "when": {{"type": "length", "field": "items", "operator": "{}", "value": 2}}, "then": {{"outcome": {{}}}}}}
+                ]}}"#,
+                operator
+            );
+            let ruleset = parse_json(&json).unwrap();
+            assert!(matches!(ruleset.rules[0].when, Condition::Length { ref operator, value: 2, .. } if CompareOp::from_name(operator).is_some()));
+        }
+    }
+
+    #[test]
+    fn test_deep_equals_round_trip() {
+        let yaml = r#"
+rules:
+  - id: "shape"
+    when:
+      type: "deep_equals"
+      field: "customer"
+      value:
+        tier: "gold"
+        regions: ["eu", "us"]
+        limits: { daily: 500, monthly: [1000, 2000] }
+      ignore_extra_keys: true
+    then:
+      outcome: {}
+version: "1.0"
+metadata: {}
+"#;
+        let ruleset = parse_yaml(yaml).unwrap();
+        let reparsed = parse_yaml(&serde_yaml::to_string(&ruleset).unwrap()).unwrap();
+        match &reparsed.rules[0].when {
+            Condition::DeepEquals { value, ignore_extra_keys, .. } => {
+                assert!(*ignore_extra_keys);
+                assert_eq!(value["limits"]["monthly"][1], 2000);
+                assert_eq!(value["regions"], serde_json::json!(["eu", "us"]));
+            },
+            other => panic!("unexpected condition {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_combinators_round_trip() {
+        let json = r#"{"version": "1.0", "metadata": {}, "rules": [
+            {"id": "r", "when": {"type": "and", "conditions": [
+                {"type": "or", "conditions": [{"type": "exists", "field": "a"}]},
+                {"type": "xor", "conditions": [{"type": "exists", "field": "b"}, {"type": "exists", "field": "c"}]}
+            ]}, "then": {"outcome": {}}}
+        ]}"#;
+        let ruleset = parse_json(json).unwrap();
+        let serialized = serde_json::to_value(&ruleset.rules[0].when).unwrap();
+        assert_eq!(serialized["conditions"][1]["type"], "xor");
+        assert_eq!(serialized["conditions"][1]["exactly"], 1);
+
+        let reparsed: Condition = serde_json::from_value(serialized).unwrap();
+        match reparsed {
+            Condition::And { conditions } => {
+                assert!(matches!(conditions[0], Condition::Or { .. }));
+                assert!(matches!(conditions[1], Condition::Xor { exactly: 1, ref conditions } if conditions.len() == 2));
+            },
+            other => panic!("unexpected condition {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_outcome_expr_safety() {
+        let ruleset = |expr: &str| {
+            parse_json(&serde_json::json!({
+                "version": "1.0",
+                "metadata": {},
+                "rules": [{
+                    "id": "fee",
+                    "when": { "type": "exists", "field": "amount" },
+                    "then": { "outcome": { "nested": { "fee": { "$expr": expr } } } }
+                }]
+            }).to_string()).unwrap()
+        };
+        assert!(validate_dsl_safety(&ruleset("round(max(amount * 0.029 + 0.30, 1), 2)")).is_ok());
+        assert!(validate_dsl_safety(&ruleset("-min(amount / 2, cap)")).is_ok());
+        for unsafe_expr in ["coalesce(amount, 0)", "amount % 7", "name + \"!\"", "amount + true", "exec(amount)"] {
+            let err = validate_dsl_safety(&ruleset(unsafe_expr)).unwrap_err();
+            assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("fee")), "{}", unsafe_expr);
+        }
+    }
+
+    #[test]
+    fn test_constants() {
+        let yaml = r#"
+version: "1.0"
+metadata: {}
+constants:
+  HIGH_RISK_THRESHOLD: 0.85
+  BLOCKED_COUNTRIES: ["KP", "IR"]
+  REVIEW: { decision: "review", queue: "risk" }
+definitions:
+  risky: { type: "greater_than", field: "risk", value: { $const: "HIGH_RISK_THRESHOLD" } }
+rules:
+  - id: "blocked_country"
+    when: { type: "in", field: "country", values: { $const: "BLOCKED_COUNTRIES" } }
+    then: { outcome: { decision: "decline" } }
+  - id: "high_risk"
+    when:
+      type: "or"
+      conditions:
+        - { type: "ref", name: "risky" }
+        - { type: "in", field: "country", values: ["CU", { $const: "HIGH_RISK_THRESHOLD" }] }
+    then: { outcome: { result: { $const: "REVIEW" }, threshold: { $const: "HIGH_RISK_THRESHOLD" } } }
+"#;
+        let ruleset = parse_yaml(yaml).unwrap();
+        assert!(matches!(ruleset.definitions["risky"], Condition::GreaterThan { value, .. } if value == 0.85));
+        assert!(matches!(ruleset.rules[0].when, Condition::In { ref values, .. } if values.len() == 2));
+        assert_eq!(ruleset.rules[1].then.outcome["result"], serde_json::json!({"decision": "review", "queue": "risk"}));
+        assert_eq!(ruleset.rules[1].then.outcome["threshold"], serde_json::json!(0.85));
+        assert_eq!(ruleset.constant_refs.len(), 5);
+
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(ruleset).unwrap();
+        let event = |risk: f64, country: &str| serde_json::json!({"risk": risk, "country": country});
+        assert_eq!(engine.evaluate_value(&event(0.1, "IR")).unwrap().unwrap().rule_id, "blocked_country");
+        assert_eq!(engine.evaluate_value(&event(0.9, "FR")).unwrap().unwrap().rule_id, "high_risk");
+        assert!(engine.evaluate_value(&event(0.8, "FR")).unwrap().is_none());
+
+        // The SHA covers the references and the constants they point to.
+        let sha = |yaml: &str| {
+            let mut engine = RuleEngine::new();
+            engine.load_ruleset(parse_yaml(yaml).unwrap()).unwrap();
+            engine.get_ruleset_sha().unwrap().clone()
+        };
+        assert_eq!(sha(yaml), sha(yaml));
+        assert_ne!(sha(yaml), sha(&yaml.replace("HIGH_RISK_THRESHOLD: 0.85", "HIGH_RISK_THRESHOLD: 0.9")));
+        let inlined = yaml.replace(r#"value: { $const: "HIGH_RISK_THRESHOLD" }"#, "value: 0.85");
+        assert_ne!(sha(yaml), sha(&inlined));
+
+        let err = parse_yaml(&yaml.replace(r#"$const: "BLOCKED_COUNTRIES""#, r#"$const: "BLOCKED""#)).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg)
+            if msg == "Rule blocked_country: unknown constant 'BLOCKED' at /when/values"), "{:?}", err);
+        let err = parse_yaml(&yaml.replace(r#"value: { $const: "HIGH_RISK_THRESHOLD" }"#, r#"value: { $const: "LIMIT" }"#)).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.starts_with("Rule risky (definition)")));
+    }
+
+    #[test]
+    fn test_constants_survive_serialization() {
+        let ruleset = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+constants:
+  LIMIT: 1000
+  BLOCKED: ["KP", "IR"]
+rules:
+  - id: "blocked"
+    when: { type: "in", field: "country", values: { $const: "BLOCKED" } }
+    then: { outcome: { limit: { $const: "LIMIT" } } }
+"#).unwrap();
+        let json = serde_json::to_value(&ruleset).unwrap();
+        assert_eq!(json["rules"][0]["when"]["values"], serde_json::json!({"$const": "BLOCKED"}));
+        assert_eq!(json["rules"][0]["then"]["outcome"]["limit"], serde_json::json!({"$const": "LIMIT"}));
+
+        let from_json = parse_json(&json.to_string()).unwrap();
+        let from_yaml = parse_yaml(&serde_yaml::to_string(&ruleset).unwrap()).unwrap();
+        for reparsed in [from_json, from_yaml] {
+            assert_eq!(reparsed.constant_refs, ruleset.constant_refs);
+            assert_eq!(reparsed.sha().unwrap(), ruleset.sha().unwrap());
+        }
+    }
+
+    const UNKNOWN_FIELDS_YAML: &str = r#"
+version: "1.0"
+metadata: {}
+definitions:
+  large: { type: "greater_than", field: "amount", value: 1000 }
+rules:
+  - id: "large"
+    severity: "high"
+    when:
+      type: "and"
+      conditions:
+        - { type: "ref", name: "large" }
+        - { type: "equals", field: "country", value: "US" }
+    then:
+      outcome: { action: "review" }
+      operations: [{ op: "add_tag", tag: "big" }]
+"#;
+
+    /// Replacing `from` with `to` in [`UNKNOWN_FIELDS_YAML`] fails with
+    /// `message` at `location`, in YAML and JSON, and lenient mode reads the
+    /// document once the key is dropped if `readable`.
+    fn assert_unknown_field(from: &str, to: &str, message: &str, location: &str, readable: bool) {
+        let typo = UNKNOWN_FIELDS_YAML.replace(from, to);
+        let EngineError::Parse(err) = parse_yaml(&typo).unwrap_err() else { panic!("{}", to) };
+        assert!(err.message.starts_with(&format!("YAML parse error: {}, expected ", message)), "{}", err);
+        assert!(err.message.ends_with(&format!("`{}", location)), "{}", err);
+
+        let json = serde_json::to_string(&serde_yaml::from_str::<serde_json::Value>(&typo).unwrap()).unwrap();
+        assert!(parse_json(&json).unwrap_err().to_string().contains(message));
+
+        let lenient = ParseOptions { allow_unknown_fields: true };
+        assert_eq!(parse_yaml_with_options(&typo, &lenient).is_ok(), readable, "{}", to);
+    }
+
+    #[test]
+    fn test_unknown_fields_reads_known_fields() {
+        assert!(parse_yaml(UNKNOWN_FIELDS_YAML).is_ok());
+    }
+
+    #[test]
+    fn test_unknown_field_in_ruleset() {
+        assert_unknown_field("metadata: {}", "metadata: {}\nowner: \"risk\"", "unknown field `owner`", "", true);
+    }
+
+    #[test]
+    fn test_unknown_field_in_rule() {
+        assert_unknown_field("severity: \"high\"", "severty: \"high\"", "Rule large: unknown field `severty`", "", true);
+    }
+
+    #[test]
+    fn test_unknown_field_in_condition() {
+        // Without its field the condition can't be read even leniently.
+        let message = "Rule large: unknown field `filed`";
+        assert_unknown_field("field: \"country\"", "filed: \"country\"", message, " at /when/conditions/1", false);
+        let message = "Rule large (definition): unknown field `inclusive`";
+        assert_unknown_field("field: \"amount\"", "field: \"amount\", inclusive: true", message, "", true);
+    }
+
+    #[test]
+    fn test_unknown_field_in_action() {
+        assert_unknown_field("outcome: { action", "outcom: { action", "Rule large: unknown field `outcom`", " at /then", true);
+        let message = "Rule large: unknown field `level`";
+        assert_unknown_field("tag: \"big\"", "tag: \"big\", level: 1", message, " at /then/operations/0", true);
+    }
+
+    #[test]
+    fn test_unknown_condition_type_in_lenient_mode() {
+        let lenient = ParseOptions { allow_unknown_fields: true };
+        assert!(parse_yaml_with_options(&UNKNOWN_FIELDS_YAML.replace("\"equals\"", "\"equalz\""), &lenient).is_err());
+    }
+
+    #[test]
+    fn test_parse_error_locations() {
+        let yaml = r#"version: "1.0"
+metadata: {}
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "review" } }
+  - id: "foreign"
+    when:
+      type: "not_equals"
+      field: "country"
+      value: "US"
+    then: { outcome: { action: "flag" } }
+"#;
+        let located = |source: &str, parse: fn(&str) -> Result<RuleSet, EngineError>| match parse(source).unwrap_err() {
+            EngineError::Parse(error) => error,
+            other => panic!("{}", other),
+        };
+
+        let syntax = located(&yaml.replace("field: \"country\"", "field: \"country"), parse_yaml);
+        assert_eq!(syntax.line, Some(11));
+        assert!(syntax.message.starts_with("YAML parse error: "));
+
+        // Serde places errors in a condition's own keys at its start.
+        let unknown = located(&yaml.replace("field: \"country\"", "filed: \"country\""), parse_yaml);
+        assert_eq!((unknown.line, unknown.column, unknown.rule_id.as_deref()), (Some(9), Some(7), Some("foreign")));
+        assert_eq!(unknown.context.iter().map(|(line, _)| *line).collect::<Vec<_>>(), vec![7, 8, 9, 10, 11]);
+        let rendered = unknown.render();
+        assert!(rendered.contains(">  9 |       type: \"not_equals\""), "{}", rendered);
+        assert!(rendered.contains("  10 |       filed: \"country\""), "{}", rendered);
+
+        let mistyped = located(&yaml.replace("id: \"large\"", "id: [\"large\"]"), parse_yaml);
+        assert_eq!(mistyped.line, Some(4));
+
+        let json = serde_json::to_string_pretty(&serde_yaml::from_str::<serde_json::Value>(yaml).unwrap()).unwrap();
+        let broken = located(&json.replacen("\"rules\": [", "\"rules\": [,", 1), parse_json);
+        let line = json.lines().position(|line| line.contains("\"rules\"")).unwrap() + 1;
+        assert_eq!(broken.line, Some(line));
+        let unknown = located(&json.replace("\"severity\"", "\"x\"").replacen("\"field\": \"country\"", "\"filed\": \"country\"", 1), parse_json);
+        // serde_json places them at the condition's closing brace.
+        let line = json.lines().position(|line| line.contains("\"value\": \"US\"")).unwrap() + 2;
+        assert_eq!((unknown.line, unknown.rule_id.as_deref()), (Some(line), Some("foreign")));
+    }
+
+    #[test]
+    fn test_parse_error_in_a_nested_condition() {
+        let yaml = r#"version: "1.0"
+metadata: {}
+rules:
+  - id: "first"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: {} }
+  - id: "a"
+    when:
+      type: "and"
+      conditions:
+        - { type: "exists", field: "amount" }
+        - type: "bogus"
+          field: "amount"
+    then: { outcome: {} }
+"#;
+        let EngineError::Parse(error) = parse_yaml(yaml).unwrap_err() else { panic!() };
+        assert!(error.message.contains("Rule a: unknown variant `bogus`"), "{}", error);
+        assert_eq!((error.line, error.column, error.rule_id.as_deref()), (Some(12), Some(11), Some("a")));
+
+        // Serde stops at the first error in the document, and that is the
+        // one reported.
+        let earlier = yaml.replace("metadata: {}", "description: \"Earlier problem\"\nmetadata: {}");
+        let EngineError::Parse(error) = parse_yaml(&earlier).unwrap_err() else { panic!() };
+        assert!(error.message.starts_with("YAML parse error: unknown field `description`"), "{}", error);
+        assert_eq!((error.line, error.column, error.rule_id), (Some(2), Some(1), None));
+    }
+
+    #[test]
+    fn test_lint_shadowed_rules() {
+        let yaml = r#"
+version: "1.0"
+metadata: {}
+definitions:
+  large: { type: "greater_than", field: "amount", value: 1000 }
+rules:
+  - id: "large"
+    when: { type: "ref", name: "large" }
+    then: { outcome: { action: "review" } }
+  - id: "large_foreign"
+    when:
+      type: "and"
+      conditions:
+        - { type: "not_equals", field: "country", value: "US" }
+        - { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "block" } }
+  - id: "large_again"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "hold" } }
+  - id: "huge"
+    when: { type: "greater_than", field: "amount", value: 5000 }
+    then: { outcome: { action: "block" } }
+  - id: "foreign"
+    when: { type: "not_equals", field: "country", value: "US" }
+    then: { outcome: { action: "flag" } }
+  - id: "catch_all"
+    when: { type: "and", conditions: [] }
+    then: { outcome: { action: "allow" } }
+  - id: "after_catch_all"
+    when: { type: "exists", field: "merchant" }
+    then: { outcome: { action: "allow" } }
+"#;
+        let ruleset = parse_yaml(yaml).unwrap();
+        let findings: Vec<(String, String)> = lint_ruleset(&ruleset).into_iter()
+            .map(|issue| (issue.rule_id.unwrap(), issue.message))
+            .collect();
+        let expected = [
+            ("large_foreign", "Rule large_foreign: unreachable, rule large runs first and requires only some of its conditions"),
+            ("large_again", "Rule large_again: unreachable, rule large runs first and has the same conditions"),
+            ("after_catch_all", "Rule after_catch_all: unreachable, rule catch_all runs first and always matches"),
+        ];
+        assert_eq!(findings, expected.map(|(id, message)| (id.to_string(), message.to_string())));
+        let warnings = RuleEngine::new().validate(&ruleset);
+        assert!(warnings.iter().all(|issue| issue.severity == IssueSeverity::Warning));
+        assert_eq!(warnings.len(), 3);
+
+        // Value implications aren't detected, nor is shadowing by a disabled
+        // rule or one in effect for less time, or under `collect`.
+        let reordered = yaml.replace("value: 5000", "value: 500");
+        assert_eq!(lint_ruleset(&parse_yaml(&reordered).unwrap()).len(), 3);
+        let mut narrowed = ruleset.clone();
+        for index in [0, 2] {
+            narrowed.rules[index].effective_until = Some("2030-01-01T00:00:00Z".to_string());
+        }
+        narrowed.rules[5].enabled = false;
+        let remaining: Vec<_> = lint_ruleset(&narrowed).into_iter().map(|issue| issue.rule_id.unwrap()).collect();
+        assert_eq!(remaining, vec!["large_again"]);
+        let mut collected = ruleset;
+        collected.hit_policy = HitPolicy::Collect;
+        assert!(lint_ruleset(&collected).is_empty());
+    }
+
+    /// A ruleset with one rule `r` on condition `when`, next to a
+    /// definition `small` of `amount < 50`.
+    fn contradiction_ruleset(when: &str) -> RuleSet {
+        parse_yaml(&format!(r#"
+version: "1.0"
+metadata: {{}}
+definitions:
+  small: {{ type: "less_than", field: "amount", value: 50 }}
+rules:
+  - id: "r"
+    when: {}
+    then: {{ outcome: {{}} }}
+"#, when)).unwrap()
+    }
+
+    /// `(path, message)` of each lint finding on `contradiction_ruleset(when)`.
+    fn contradictions(when: &str) -> Vec<(String, String)> {
+        lint_ruleset(&contradiction_ruleset(when)).into_iter().map(|issue| (issue.path, issue.message)).collect()
+    }
+
+    fn finding(path: &str, message: &str) -> (String, String) {
+        (path.to_string(), message.to_string())
+    }
+
+    #[test]
+    fn test_lint_contradicting_equals() {
+        let when = r#"{ type: "and", conditions: [
+      { type: "equals", field: "status", value: "active" },
+      { type: "equals", field: "status", value: "closed" }] }"#;
+        assert_eq!(contradictions(when), vec![finding(
+            "/rules/0/when",
+            "Rule r: conditions at /rules/0/when/conditions/0 and /rules/0/when/conditions/1 can never both match on field 'status'",
+        )]);
+        let ruleset = contradiction_ruleset(when);
+        let issues = RuleEngine::new().validate(&ruleset);
+        assert_eq!(issues.iter().filter(|issue| issue.severity == IssueSeverity::Warning).count(), 1);
+    }
+
+    #[test]
+    fn test_lint_contradicting_ranges() {
+        // Through a definition, inside a `not`.
+        let when = r#"{ type: "not", condition: { type: "and", conditions: [
+      { type: "greater_than", field: "amount", value: 100 },
+      { type: "ref", name: "small" },
+      { type: "in", field: "country", values: ["DE", "FR"] },
+      { type: "equals", field: "country", value: "FR" }] } }"#;
+        assert_eq!(contradictions(when), vec![finding(
+            "/rules/0/when/condition",
+            "Rule r: conditions at /rules/0/when/condition/conditions/0 and /definitions/small can never both match on field 'amount'",
+        )]);
+
+        // Bounds that touch but exclude each other.
+        let when = r#"{ type: "and", conditions: [
+      { type: "gte", field: "amount", value: 100 },
+      { type: "between", field: "amount", min: 50, max: 100, inclusive_max: false }] }"#;
+        assert_eq!(contradictions(when), vec![finding(
+            "/rules/0/when",
+            "Rule r: conditions at /rules/0/when/conditions/0 and /rules/0/when/conditions/1 can never both match on field 'amount'",
+        )]);
+    }
+
+    #[test]
+    fn test_lint_empty_or() {
+        let when = r#"{ type: "and", conditions: [{ type: "exists", field: "amount" }, { type: "or", conditions: [] }] }"#;
+        assert_eq!(contradictions(when), vec![finding(
+            "/rules/0/when/conditions/1",
+            "Rule r: empty or at /rules/0/when/conditions/1 never matches",
+        )]);
+    }
+
+    #[test]
+    fn test_lint_contradictions_false_positives() {
+        // The same field under an `or`.
+        let when = r#"{ type: "or", conditions: [
+      { type: "equals", field: "status", value: "active" },
+      { type: "equals", field: "status", value: "closed" }] }"#;
+        assert!(contradictions(when).is_empty());
+
+        // Inclusive bounds that meet, and a default that may stand in for
+        // the field.
+        let when = r#"{ type: "and", conditions: [
+      { type: "gte", field: "amount", value: 100 },
+      { type: "lte", field: "amount", value: 100 },
+      { type: "equals", field: "status", value: "active", default: "closed" },
+      { type: "equals", field: "status", value: "closed" },
+      { type: "in", field: "amount", values: [100, 200] }] }"#;
+        assert!(contradictions(when).is_empty());
+    }
+
+    #[test]
+    fn test_safety_limits() {
+        let source = r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "listed"
+    when: { type: "in", field: "country", values: ["DE", "FR", "IT"] }
+    then: { outcome: {} }
+  - id: "pattern"
+    when:
+      type: "and"
+      conditions:
+        - { type: "exists", field: "email" }
+        - { type: "matches", field: "email", pattern: "^[a-z]+@example\\.com$" }
+    then: { outcome: {} }
+"#;
+        let leaf = || -> Condition { serde_json::from_value(serde_json::json!({"type": "exists", "field": "country"})).unwrap() };
+        let mut deep = parse_yaml(source).unwrap();
+        for _ in 0..10_000 {
+            let inner = std::mem::replace(&mut deep.rules[0].when, leaf());
+            deep.rules[0].when = Condition::Not { condition: Box::new(inner) };
+        }
+        assert_eq!(RuleEngine::new().validate(&deep).len(), 1);
+        let err = RuleEngine::new().load_ruleset(deep).unwrap_err().to_string();
+        assert_eq!(err, "Rule validation error: Rule listed: condition nesting exceeds the limit of 64 levels");
+
+        let strict = SafetyLimits { max_in_list: 2, max_pattern_length: 10, max_conditions_per_rule: 2, ..SafetyLimits::default() };
+        let mut engine = RuleEngine::new();
+        assert_eq!(*engine.get_safety_limits(), SafetyLimits::default());
+        assert!(engine.load_ruleset(parse_yaml(source).unwrap()).is_ok());
+        engine.set_safety_limits(strict);
+        assert_eq!(*engine.get_safety_limits(), strict);
+        let messages: Vec<String> = engine.validate(&parse_yaml(source).unwrap()).into_iter().map(|issue| issue.message).collect();
+        assert_eq!(messages, vec![
+            "Rule listed: in list of 3 values exceeds the limit of 2",
+            "Rule pattern: more than 2 conditions, the limit per rule",
+            "Rule pattern: matches pattern of 21 bytes exceeds the limit of 10",
+        ]);
+        assert!(engine.load_ruleset(parse_yaml(source).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_safety_limits_stop_counting_doubled_definitions() {
+        let doubled = |levels: usize| {
+            let mut source = String::from("version: \"1.0\"\nmetadata: {}\ndefinitions:\n  d0: { type: \"exists\", field: \"country\" }\n");
+            for level in 1..=levels {
+                source.push_str(&format!(
+                    "  d{level}: {{ type: \"and\", conditions: [{{ type: \"ref\", name: \"d{prev}\" }}, {{ type: \"ref\", name: \"d{prev}\" }}] }}\n",
+                    prev = level - 1
+                ));
+            }
+            source.push_str(&format!("rules:\n  - id: \"doubled\"\n    when: {{ type: \"ref\", name: \"d{}\" }}\n    then: {{ outcome: {{}} }}\n", levels));
+            parse_yaml(&source).unwrap()
+        };
+        // 2^64 uses of d0 would overflow the count long before finishing.
+        let huge = doubled(64);
+        assert!(count_conditions(&huge, &huge.rules[0].when, &mut Vec::new(), 1_000) > 1_000);
+
+        let err = RuleEngine::new().load_ruleset(doubled(12)).unwrap_err().to_string();
+        assert_eq!(err, "Rule validation error: Rule doubled: more than 1000 conditions, the limit per rule");
+    }
 }
@@ -1,8 +1,18 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
-use sha2::{Sha256, Digest};
+use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
+use crate::matchers::{self, Cidr, Glob, SemVer, ValueSet};
+use crate::audit::{AuditLog, AuditRecord};
+use crate::canonical;
+use crate::dsl::SafetyLimits;
+use crate::expr::{self, Expr};
+use crate::path;
+use crate::template::Template;
+use crate::temporal;
 
 #[derive(Error, Debug)]
 pub enum EngineError {
@@ -11,207 +21,9981 @@ pub enum EngineError {
     #[error("Execution error: {0}")]
     Execution(String),
     #[error("Parse error: {0}")]
-    Parse(String),
+    Parse(ParseError),
+    /// A ruleset's signature is malformed or doesn't verify.
+    #[error("Signature error: {0}")]
+    Signature(String),
+}
+
+/// Why a document couldn't be read, and where in it when that is known.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// 1-based line of the problem in the source document.
+    pub line: Option<usize>,
+    /// 1-based column of the problem on `line`.
+    pub column: Option<usize>,
+    /// The rule the problem is in, when it could be determined.
+    pub rule_id: Option<String>,
+    /// Source lines around `line`, with their 1-based numbers.
+    pub context: Vec<(usize, String)>,
+}
+
+impl ParseError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self { message: message.into(), ..Default::default() }
+    }
+
+    /// The message followed by the context lines, the problem line marked
+    /// with `>`, for terminals and logs.
+    pub fn render(&self) -> String {
+        let mut rendered = self.to_string();
+        let width = self.context.last().map_or(0, |(number, _)| number.to_string().len());
+        for (number, text) in &self.context {
+            let marker = if Some(*number) == self.line { '>' } else { ' ' };
+            rendered.push_str(&format!("\n{} {:>width$} | {}", marker, number, text, width = width));
+        }
+        rendered
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, " (line {}, column {})", line, column),
+            (Some(line), None) => write!(f, " (line {})", line),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// One problem found by [`RuleEngine::validate`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    /// The rule (or [`DEFAULT_RULE_ID`]) the problem is in; absent for
+    /// ruleset-wide problems.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
+    /// RFC 6901 pointer to the offending part of the ruleset document.
+    pub path: String,
+    pub message: String,
+    pub severity: IssueSeverity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueSeverity {
+    /// Worth fixing, but [`RuleEngine::load_ruleset`] accepts it.
+    Warning,
+    /// [`RuleEngine::load_ruleset`] rejects the ruleset.
+    Error,
+}
+
+impl ValidationIssue {
+    /// An error-severity issue from a failed check.
+    pub(crate) fn from_error(rule_id: Option<&str>, path: String, error: EngineError) -> Self {
+        let message = match error {
+            EngineError::RuleValidation(message) => message,
+            other => other.to_string(),
+        };
+        Self { rule_id: rule_id.map(str::to_string), path, message, severity: IssueSeverity::Error }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Rule {
     pub id: String,
     pub description: Option<String>,
     pub severity: Option<String>,
+    #[serde(default)]
     pub tags: Vec<String>,
     pub when: Condition,
     pub then: Action,
+    /// Applied when `when` is false. A rule with an else branch decides
+    /// every event it is effective for, so no rule after it is reached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub else_then: Option<Action>,
+    /// Written by an LLM; such a rule must carry a `prompt_sha`.
     #[serde(default)]
     pub generated_by_llm: bool,
+    /// Hex SHA-256 of the prompt that produced the rule.
     pub prompt_sha: Option<String>,
+    /// ISO 8601 start of the window in which the rule applies (inclusive).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective_from: Option<String>,
+    /// ISO 8601 end of the window in which the rule applies (exclusive).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective_until: Option<String>,
+    /// Rank under [`SelectionMode::HighestPriority`]; higher wins and a
+    /// missing priority counts as 0. Ignored in first-match mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// Whether a match stops [`RuleEngine::evaluate_collect`]. Non-terminal
+    /// rules annotate the collected result and let evaluation continue.
+    /// [`RuleEngine::evaluate`] always stops at the first match.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub terminal: bool,
+    /// Disabled rules are validated and hashed but never evaluated. See
+    /// [`RuleEngine::set_rule_enabled`].
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub enabled: bool,
+    /// Points added to the total by [`RuleEngine::evaluate_score`] when the
+    /// rule matches; may be negative. A missing score counts as 0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    /// One of the ruleset's declared `groups`. Ungrouped rules only run in
+    /// whole-ruleset evaluation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Review state. Only active rules make production decisions; drafts
+    /// run in [`RuleEngine::evaluate_including_drafts`].
+    #[serde(default, skip_serializing_if = "RuleLifecycle::is_active")]
+    pub status: RuleLifecycle,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approved_by: Option<String>,
+    /// ISO 8601 time of approval.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approved_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleLifecycle {
+    /// Awaiting review, e.g. a rule generated by an LLM.
+    Draft,
+    #[default]
+    Active,
+    /// Retired; validated and hashed but never evaluated.
+    Disabled,
+}
+
+impl RuleLifecycle {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleLifecycle::Draft => "draft",
+            RuleLifecycle::Active => "active",
+            RuleLifecycle::Disabled => "disabled",
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        *self == RuleLifecycle::Active
+    }
+}
+
+impl Rule {
+    /// A rule with every other field as it is when a ruleset leaves it out.
+    pub fn new(id: impl Into<String>, when: Condition, then: Action) -> Self {
+        Rule {
+            id: id.into(),
+            description: None,
+            severity: None,
+            tags: Vec::new(),
+            when,
+            then,
+            else_then: None,
+            generated_by_llm: false,
+            prompt_sha: None,
+            effective_from: None,
+            effective_until: None,
+            priority: None,
+            terminal: true,
+            enabled: true,
+            score: None,
+            group: None,
+            status: RuleLifecycle::Active,
+            approved_by: None,
+            approved_at: None,
+        }
+    }
+
+    /// Whether evaluation considers the rule at all: it is switched on and
+    /// active, or a draft when drafts are included.
+    pub(crate) fn is_live(&self, include_drafts: bool) -> bool {
+        self.enabled && match self.status {
+            RuleLifecycle::Active => true,
+            RuleLifecycle::Draft => include_drafts,
+            RuleLifecycle::Disabled => false,
+        }
+    }
+
+    /// Whether `now` falls inside the rule's effective window. Unparseable
+    /// bounds are rejected at load time, so they are ignored here.
+    pub fn is_effective_at(&self, now: DateTime<Utc>) -> bool {
+        let from = self.effective_from.as_deref().and_then(temporal::parse_timestamp_str);
+        let until = self.effective_until.as_deref().and_then(temporal::parse_timestamp_str);
+        from.is_none_or(|from| now >= from) && until.is_none_or(|until| now < until)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(remote = "Self", deny_unknown_fields)]
 pub struct RuleSet {
     pub rules: Vec<Rule>,
     pub version: String,
     pub metadata: HashMap<String, serde_json::Value>,
+    /// Named conditions that rules can reuse through `ref` conditions.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub definitions: HashMap<String, Condition>,
+    /// Alternative payload field names per canonical field. When the
+    /// canonical field is absent, the aliases are tried in the listed order.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub aliases: HashMap<String, Vec<String>>,
+    /// Fields derived from each event before rules run, as expressions over
+    /// payload fields such as `amount / quantity`, `first + " " + last` or
+    /// `coalesce(nickname, first)`. An expression that cannot be computed
+    /// for an event leaves its field missing.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub computed_fields: HashMap<String, String>,
+    /// Shape every rule's outcome must have, checked at load time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome_schema: Option<OutcomeSchema>,
+    /// Declared types of payload fields, keyed by path as in
+    /// [`RuleSet::referenced_fields`] (`customer.country`,
+    /// `items[*].price`). Conditions on declared fields are type-checked at
+    /// load time; see also [`RuleEngine::set_strict_input`].
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub input_schema: HashMap<String, InputField>,
+    /// Named values that condition and outcome values can reference as
+    /// `{"$const": "NAME"}`. References are substituted by the DSL parser
+    /// and written back when the ruleset is serialized.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub constants: HashMap<String, serde_json::Value>,
+    /// Where the parser substituted constants, so that the ruleset is
+    /// serialized and hashed with its references intact.
+    #[serde(skip)]
+    pub constant_refs: Vec<ConstantRef>,
+    /// Labels for [`RuleEngine::evaluate_score`] totals, in ascending order
+    /// of `min`. A total gets the label of the last threshold it reaches.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub score_thresholds: Vec<ScoreThreshold>,
+    /// Stages run in this order by [`RuleEngine::evaluate_pipeline`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<RuleGroup>,
+    /// Applied when no rule decides, as a decision from the reserved rule
+    /// [`DEFAULT_RULE_ID`]. Without it, evaluation returns no decision.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_action: Option<Action>,
+    /// How [`RuleEngine::evaluate`] combines matching rules.
+    #[serde(default, skip_serializing_if = "HitPolicy::is_first")]
+    pub hit_policy: HitPolicy,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum Condition {
-    #[serde(rename = "and")]
-    And { conditions: Vec<Condition> },
-    #[serde(rename = "or")]
-    Or { conditions: Vec<Condition> },
-    #[serde(rename = "not")]
-    Not { condition: Box<Condition> },
-    #[serde(rename = "equals")]
-    Equals { field: String, value: serde_json::Value },
-    #[serde(rename = "greater_than")]
-    GreaterThan { field: String, value: f64 },
-    #[serde(rename = "less_than")]
-    LessThan { field: String, value: f64 },
-    #[serde(rename = "contains")]
-    Contains { field: String, value: String },
-    #[serde(rename = "in")]
-    In { field: String, values: Vec<serde_json::Value> },
+/// Writes the document the ruleset was parsed from: values that came from
+/// constants go back to `{"$const": name}`, so that parsing the output gives
+/// the same ruleset and SHA.
+impl Serialize for RuleSet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.constant_refs.is_empty() {
+            return RuleSet::serialize(self, serializer);
+        }
+        let mut value = RuleSet::serialize(self, serde_json::value::Serializer).map_err(serde::ser::Error::custom)?;
+        for reference in &self.constant_refs {
+            if let Some(slot) = value.pointer_mut(&reference.pointer) {
+                *slot = serde_json::json!({ "$const": reference.name });
+            }
+        }
+        value.serialize(serializer)
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Action {
-    pub outcome: HashMap<String, serde_json::Value>,
+/// Reads a ruleset as written, without substituting constants; see
+/// [`crate::parse_yaml`] and [`crate::parse_json`] for documents that use them.
+impl<'de> Deserialize<'de> for RuleSet {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        RuleSet::deserialize(deserializer)
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Decision {
+/// Rule ID of decisions made by a ruleset's `default_action`. Rules may not
+/// use it.
+pub const DEFAULT_RULE_ID: &str = "__default__";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleGroup {
+    pub name: String,
+    #[serde(default)]
+    pub hit_policy: GroupHitPolicy,
+}
+
+/// How many decisions a group produces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupHitPolicy {
+    /// The first deciding rule, in the engine's selection order.
+    #[default]
+    FirstMatch,
+    /// Every deciding rule.
+    AllMatches,
+}
+
+impl GroupHitPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroupHitPolicy::FirstMatch => "first_match",
+            GroupHitPolicy::AllMatches => "all_matches",
+        }
+    }
+}
+
+/// Totals of at least `min` get `label`. Only the first threshold may omit
+/// `min`, which then catches every total below the next one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreThreshold {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    pub label: String,
+}
+
+/// A `{"$const": name}` reference found at `pointer` (RFC 6901, relative to
+/// the ruleset document).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstantRef {
+    pub pointer: String,
+    pub name: String,
+}
+
+/// Required keys and per-key constraints for rule outcomes. Only top-level
+/// outcome keys are checked.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutcomeSchema {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub properties: HashMap<String, OutcomeProperty>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutcomeProperty {
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub value_type: Option<OutcomeType>,
+    /// Allowed values. Templated strings pass if one of them fits the
+    /// template's static text.
+    #[serde(default, rename = "enum", skip_serializing_if = "Vec::is_empty")]
+    pub allowed: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutcomeType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Object,
+    Array,
+}
+
+impl OutcomeType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutcomeType::String => "string",
+            OutcomeType::Number => "number",
+            OutcomeType::Integer => "integer",
+            OutcomeType::Boolean => "boolean",
+            OutcomeType::Object => "object",
+            OutcomeType::Array => "array",
+        }
+    }
+}
+
+impl OutcomeSchema {
+    /// Violations of the schema by one action, each prefixed with `context`.
+    fn check(&self, context: &str, action: &Action) -> Vec<String> {
+        let mut violations = Vec::new();
+        let values: Vec<(&str, &serde_json::Value)> = action.outcome_values().collect();
+        for key in &self.required {
+            if !values.iter().any(|(name, _)| name == key) {
+                violations.push(format!("{}: missing required outcome key '{}'", context, key));
+            }
+        }
+        for (key, value) in values {
+            if let Some(property) = self.properties.get(key) {
+                if let Err(e) = property.check(value) {
+                    violations.push(format!("{}: outcome '{}' {}", context, key, e));
+                }
+            }
+        }
+        violations.sort();
+        violations
+    }
+}
+
+impl OutcomeProperty {
+    fn check(&self, value: &serde_json::Value) -> Result<(), String> {
+        // `$expr` values are always numeric; their exact value is only known
+        // at decision time.
+        if expr::outcome_expr(value).is_some() {
+            return match self.value_type {
+                Some(expected) if !matches!(expected, OutcomeType::Number | OutcomeType::Integer) => {
+                    Err(format!("is an expression but must be of type {}", expected.as_str()))
+                },
+                _ if !self.allowed.is_empty() => Err("is an expression but must be one of a fixed set of values".to_string()),
+                _ => Ok(()),
+            };
+        }
+        if let Some(expected) = self.value_type {
+            let matches = match expected {
+                OutcomeType::String => value.is_string(),
+                OutcomeType::Number => value.is_number(),
+                OutcomeType::Integer => value.is_i64() || value.is_u64(),
+                OutcomeType::Boolean => value.is_boolean(),
+                OutcomeType::Object => value.is_object(),
+                OutcomeType::Array => value.is_array(),
+            };
+            if !matches {
+                return Err(format!("must be of type {}, got {}", expected.as_str(), value));
+            }
+        }
+        if self.allowed.is_empty() || self.allowed.contains(value) {
+            return Ok(());
+        }
+        let fits_template = match value {
+            serde_json::Value::String(text) if text.contains("{{") => Template::parse(text)
+                .map(|template| self.allowed.iter().any(|allowed| {
+                    allowed.as_str().is_some_and(|allowed| template.can_render(allowed))
+                }))
+                .unwrap_or(false),
+            _ => false,
+        };
+        if fits_template {
+            return Ok(());
+        }
+        let allowed: Vec<String> = self.allowed.iter().map(|allowed| allowed.to_string()).collect();
+        Err(format!("must be one of [{}], got {}", allowed.join(", "), value))
+    }
+}
+
+/// Declared type of a payload field in a ruleset's `input_schema`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputField {
+    #[serde(rename = "type")]
+    pub value_type: JsonType,
+    /// Type of every element, for arrays.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<JsonType>,
+    /// Allowed values.
+    #[serde(default, rename = "enum", skip_serializing_if = "Vec::is_empty")]
+    pub allowed: Vec<serde_json::Value>,
+    /// Whether null is accepted as well.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub nullable: bool,
+}
+
+impl InputField {
+    fn check(&self, value: &serde_json::Value) -> Result<(), String> {
+        if value.is_null() && self.nullable {
+            return Ok(());
+        }
+        if !self.value_type.matches(value) {
+            return Err(format!("must be of type {}, got {}", self.value_type.as_str(), value));
+        }
+        if let (Some(items), Some(elements)) = (self.items, value.as_array()) {
+            if let Some(element) = elements.iter().find(|element| !items.matches(element)) {
+                return Err(format!("must only hold {} elements, got {}", items.as_str(), element));
+            }
+        }
+        if self.allowed.is_empty() || self.allowed.contains(value) {
+            return Ok(());
+        }
+        let allowed: Vec<String> = self.allowed.iter().map(|allowed| allowed.to_string()).collect();
+        Err(format!("must be one of [{}], got {}", allowed.join(", "), value))
+    }
+
+    /// Whether a condition that tests one of `accepted` can test the field.
+    /// Numbers and integers are interchangeable here, since a number field
+    /// can hold integers.
+    fn suits(&self, accepted: &[JsonType]) -> bool {
+        let numeric = |kind: &JsonType| matches!(kind, JsonType::Number | JsonType::Integer);
+        accepted.contains(&self.value_type) || (numeric(&self.value_type) && accepted.iter().any(numeric))
+    }
+}
+
+/// Aggregate figures about a ruleset, from [`RuleSet::summary`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleSetSummary {
+    pub version: String,
+    pub sha: String,
+    pub total_rules: usize,
+    /// Rules per tag; a rule counts once under each of its tags.
+    pub by_tag: BTreeMap<String, usize>,
+    /// Rules per severity; rules without one are not counted.
+    pub by_severity: BTreeMap<String, usize>,
+    /// Occurrences of each condition type in the rules and definitions as
+    /// written, so a `ref` counts as one `ref` node.
+    pub condition_types: BTreeMap<String, usize>,
+    /// Deepest rule condition, following references. See
+    /// [`RuleSet::condition_depth`].
+    pub max_condition_depth: usize,
+    pub llm_generated: usize,
+}
+
+/// Where a rule reads a field or writes an outcome key, from
+/// [`RuleSet::find_rules_by_field`] and [`RuleSet::find_rules_by_outcome_key`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleReference {
     pub rule_id: String,
-    pub outcome: HashMap<String, serde_json::Value>,
-    pub matched_conditions: Vec<String>,
-    pub elapsed_us: u64,
-    pub timestamp: u64,
-    pub rule_sha: String,
+    /// RFC 6901 pointers into the ruleset document, such as
+    /// `/rules/2/when/conditions/0/field`. Matches inside a definition the
+    /// rule refers to point into `/definitions`.
+    pub locations: Vec<String>,
 }
 
-pub struct RuleEngine {
-    ruleset: Option<RuleSet>,
-    ruleset_sha: Option<String>,
+/// How [`RuleSet::merge`] combines a base ruleset with an overlay.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergePolicy {
+    /// Rules with the same ID, and same-named definitions, aliases,
+    /// computed fields, constants and groups.
+    pub rules: ConflictPolicy,
+    /// Metadata keys present in both with different values.
+    pub metadata: ConflictPolicy,
+    pub version: VersionPolicy,
 }
 
-impl RuleEngine {
-    pub fn new() -> Self {
-        Self {
-            ruleset: None,
-            ruleset_sha: None,
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Fail the merge, naming the conflicting entries.
+    #[default]
+    Error,
+    OverlayWins,
+    BaseWins,
+}
+
+impl ConflictPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "error" => Some(ConflictPolicy::Error),
+            "overlay_wins" => Some(ConflictPolicy::OverlayWins),
+            "base_wins" => Some(ConflictPolicy::BaseWins),
+            _ => None,
         }
     }
+}
 
-    pub fn load_ruleset(&mut self, ruleset: RuleSet) -> Result<(), EngineError> {
-        // Validate ruleset
-        self.validate_ruleset(&ruleset)?;
-        
-        // Calculate SHA
-        let canonical_json = serde_json::to_string(&ruleset)
-            .map_err(|e| EngineError::Parse(e.to_string()))?;
-        let mut hasher = Sha256::new();
-        hasher.update(canonical_json.as_bytes());
-        let sha = format!("{:x}", hasher.finalize());
-        
-        self.ruleset = Some(ruleset);
-        self.ruleset_sha = Some(sha);
-        Ok(())
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VersionPolicy {
+    #[default]
+    Overlay,
+    Base,
+    /// `"<base>+<overlay>"`, e.g. `"1.4+eu.2"`.
+    Combined,
+}
+
+impl VersionPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "overlay" => Some(VersionPolicy::Overlay),
+            "base" => Some(VersionPolicy::Base),
+            "combined" => Some(VersionPolicy::Combined),
+            _ => None,
+        }
     }
+}
 
-    pub fn get_ruleset_sha(&self) -> Option<&String> {
-        self.ruleset_sha.as_ref()
+/// Merges the `key` mapping of an overlay document into that of a base
+/// document under `policy`, returning the conflicting keys that the policy
+/// rejected. Equal entries are not conflicts; they are compared as written,
+/// as the ruleset SHA sees them.
+fn merge_map(
+    base: &mut serde_json::Map<String, serde_json::Value>,
+    overlay: &serde_json::Map<String, serde_json::Value>,
+    key: &str,
+    policy: ConflictPolicy,
+) -> Vec<String> {
+    let (Some(base), Some(overlay)) = (
+        base.entry(key).or_insert_with(|| serde_json::json!({})).as_object_mut(),
+        overlay.get(key).and_then(serde_json::Value::as_object),
+    ) else {
+        return Vec::new();
+    };
+    let mut conflicts = Vec::new();
+    for (key, value) in overlay {
+        match base.get(key) {
+            Some(existing) if existing == value => {},
+            Some(_) if policy == ConflictPolicy::Error => conflicts.push(key.clone()),
+            Some(_) if policy == ConflictPolicy::BaseWins => {},
+            _ => {
+                base.insert(key.clone(), value.clone());
+            },
+        }
     }
+    conflicts.sort();
+    conflicts
+}
 
-    fn validate_ruleset(&self, ruleset: &RuleSet) -> Result<(), EngineError> {
-        // Check for duplicate rule IDs
-        let mut ids = std::collections::HashSet::new();
-        for rule in &ruleset.rules {
-            if !ids.insert(&rule.id) {
-                return Err(EngineError::RuleValidation(
-                    format!("Duplicate rule ID: {}", rule.id)
-                ));
+impl RuleSet {
+    /// Combines a base ruleset with an overlay. Base rules keep their
+    /// order; overlay rules that replace a base rule take its place and the
+    /// others are appended in overlay order. Single-valued sections
+    /// (`outcome_schema`, `default_action`, `score_thresholds`, a
+    /// non-default `hit_policy`) come from the overlay when it sets them.
+    ///
+    /// The documents are merged as written and the result is validated like
+    /// a loaded ruleset. Constant references resolve against the merged
+    /// constants, so it hashes like the same document loaded directly.
+    pub fn merge(base: &RuleSet, overlay: &RuleSet, policy: MergePolicy) -> Result<RuleSet, EngineError> {
+        let document = |ruleset: &RuleSet| ruleset.source_value().map(|value| match value {
+            serde_json::Value::Object(document) => document,
+            _ => serde_json::Map::new(),
+        });
+        let mut merged = document(base)?;
+        let source = document(overlay)?;
+        match policy.version {
+            VersionPolicy::Overlay => {
+                merged.insert("version".to_string(), source["version"].clone());
+            },
+            VersionPolicy::Base => {},
+            VersionPolicy::Combined => {
+                merged.insert("version".to_string(), format!("{}+{}", base.version, overlay.version).into());
+            },
+        }
+
+        let mut conflicts: Vec<String> = merge_map(&mut merged, &source, "metadata", policy.metadata)
+            .into_iter()
+            .map(|key| format!("metadata '{}'", key))
+            .collect();
+        let overlay_rules = source.get("rules").and_then(serde_json::Value::as_array).into_iter().flatten();
+        if let Some(rules) = merged.get_mut("rules").and_then(serde_json::Value::as_array_mut) {
+            for (rule, value) in overlay.rules.iter().zip(overlay_rules) {
+                match rules.iter().position(|existing| existing["id"] == rule.id.as_str()) {
+                    None => rules.push(value.clone()),
+                    Some(_) if policy.rules == ConflictPolicy::Error => conflicts.push(format!("rule '{}'", rule.id)),
+                    Some(position) if policy.rules == ConflictPolicy::OverlayWins => rules[position] = value.clone(),
+                    Some(_) => {},
+                }
             }
         }
-        Ok(())
+        let sections = [
+            ("definition", "definitions"),
+            ("alias", "aliases"),
+            ("computed field", "computed_fields"),
+            ("constant", "constants"),
+            ("input field", "input_schema"),
+        ];
+        for (section, key) in sections {
+            let keys = merge_map(&mut merged, &source, key, policy.rules);
+            conflicts.extend(keys.into_iter().map(|key| format!("{} '{}'", section, key)));
+        }
+        let overlay_groups = source.get("groups").and_then(serde_json::Value::as_array).into_iter().flatten();
+        if let Some(groups) = merged.entry("groups").or_insert_with(|| serde_json::json!([])).as_array_mut() {
+            for (group, value) in overlay.groups.iter().zip(overlay_groups) {
+                match groups.iter().position(|existing| existing["name"] == group.name.as_str()) {
+                    None => groups.push(value.clone()),
+                    Some(position) if groups[position] == *value => {},
+                    Some(_) if policy.rules == ConflictPolicy::Error => conflicts.push(format!("group '{}'", group.name)),
+                    Some(position) if policy.rules == ConflictPolicy::OverlayWins => groups[position] = value.clone(),
+                    Some(_) => {},
+                }
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(EngineError::RuleValidation(format!("Merge conflicts: {}", conflicts.join(", "))));
+        }
+
+        // Sections the overlay leaves at their defaults are not serialized.
+        for key in ["outcome_schema", "default_action", "score_thresholds", "hit_policy"] {
+            if let Some(value) = source.get(key) {
+                merged.insert(key.to_string(), value.clone());
+            }
+        }
+        let merged = crate::dsl::ruleset_from_value(serde_json::Value::Object(merged))?;
+        RuleEngine::new().validate_ruleset(&merged)?;
+        Ok(merged)
     }
 
-    pub fn evaluate(&self, payload: &HashMap<String, serde_json::Value>) -> Result<Option<Decision>, EngineError> {
-        let ruleset = self.ruleset.as_ref()
-            .ok_or_else(|| EngineError::Execution("No ruleset loaded".to_string()))?;
-        
-        let start_time = SystemTime::now();
-        
-        for rule in &ruleset.rules {
-            if self.evaluate_condition(&rule.when, payload)? {
-                let elapsed = start_time.elapsed()
-                    .map_err(|e| EngineError::Execution(e.to_string()))?;
-                
-                let decision = Decision {
-                    rule_id: rule.id.clone(),
-                    outcome: rule.then.outcome.clone(),
-                    matched_conditions: vec![rule.id.clone()], // Simplified
-                    elapsed_us: elapsed.as_micros() as u64,
-                    timestamp: SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs(),
-                    rule_sha: self.ruleset_sha.clone().unwrap_or_default(),
-                };
-                
-                return Ok(Some(decision));
+    /// The SHA [`RuleEngine::load_ruleset`] would record for this ruleset:
+    /// the hex SHA-256 of [`RuleSet::canonical_json`].
+    pub fn sha(&self) -> Result<String, EngineError> {
+        ruleset_sha(self)
+    }
+
+    /// The ruleset as [`canonical::canonical_json`], with values that came
+    /// from constants written back as `{"$const": name}`. This is the stable
+    /// hashing input: tools that reproduce it get the same SHA whatever order
+    /// the source listed keys in.
+    pub fn canonical_json(&self) -> Result<String, EngineError> {
+        Ok(canonical::canonical_json(&self.source_value()?))
+    }
+
+    /// Per-rule content SHAs keyed by rule ID: the hex SHA-256 of each rule's
+    /// part of [`RuleSet::canonical_json`], so a rule keeps its SHA while
+    /// other rules change. A rule that references definitions or constants
+    /// is hashed as `{"rule", "definitions", "constants"}` with the
+    /// definitions it reaches, directly or through other definitions, and
+    /// the values of the constants they all use, so editing either changes
+    /// the SHA. The `default_action`, if any, is under [`DEFAULT_RULE_ID`].
+    pub fn rule_shas(&self) -> Result<HashMap<String, String>, EngineError> {
+        let source = self.source_value()?;
+        let mut shas: HashMap<String, String> = self.rules.iter()
+            .zip(source["rules"].as_array().into_iter().flatten())
+            .enumerate()
+            .map(|(index, (rule, value))| {
+                let mut definitions = BTreeSet::new();
+                self.collect_definition_names(&rule.when, &mut definitions);
+                (rule.id.clone(), self.content_sha(&source, value, &format!("/rules/{}", index), &definitions))
+            })
+            .collect();
+        if let Some(default_action) = source.get("default_action").filter(|value| !value.is_null()) {
+            let sha = self.content_sha(&source, default_action, "/default_action", &BTreeSet::new());
+            shas.insert(DEFAULT_RULE_ID.to_string(), sha);
+        }
+        Ok(shas)
+    }
+
+    /// Names of the definitions `condition` reaches through `ref`s.
+    fn collect_definition_names<'a>(&'a self, condition: &'a Condition, names: &mut BTreeSet<&'a str>) {
+        if let Condition::Ref { name } = condition {
+            if let Some(definition) = self.definitions.get(name).filter(|_| names.insert(name)) {
+                self.collect_definition_names(definition, names);
             }
         }
-        
-        Ok(None)
+        for child in condition.children() {
+            self.collect_definition_names(child, names);
+        }
     }
 
-    pub fn evaluate_many(&self, events: &[HashMap<String, serde_json::Value>]) -> Result<Vec<Option<Decision>>, EngineError> {
-        events.iter()
-            .map(|event| self.evaluate(event))
+    /// The SHA of `value`, the part of `source` at `pointer`, together with
+    /// `definitions` and the constants used in either; see
+    /// [`RuleSet::rule_shas`].
+    fn content_sha(&self, source: &serde_json::Value, value: &serde_json::Value, pointer: &str, definitions: &BTreeSet<&str>) -> String {
+        let sha = |value: &serde_json::Value| canonical::sha256_hex(canonical::canonical_json(value).as_bytes());
+        let mut prefixes = vec![pointer.to_string()];
+        prefixes.extend(definitions.iter().map(|name| format!("/definitions/{}", crate::dsl::escape_pointer_token(name))));
+        let within = |reference: &ConstantRef| prefixes.iter().any(|prefix| {
+            reference.pointer.strip_prefix(prefix.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        });
+        let constants: serde_json::Map<String, serde_json::Value> = self.constant_refs.iter()
+            .filter(|reference| within(reference))
+            .filter_map(|reference| Some((reference.name.clone(), self.constants.get(&reference.name)?.clone())))
+            .collect();
+        if definitions.is_empty() && constants.is_empty() {
+            return sha(value);
+        }
+        let definitions: serde_json::Map<String, serde_json::Value> = definitions.iter()
+            .map(|name| (name.to_string(), source["definitions"][*name].clone()))
+            .collect();
+        sha(&serde_json::json!({"rule": value, "definitions": definitions, "constants": constants}))
+    }
+
+    /// The ruleset as written, with its constant references.
+    fn source_value(&self) -> Result<serde_json::Value, EngineError> {
+        serde_json::to_value(self).map_err(|e| EngineError::Parse(ParseError::new(e.to_string())))
+    }
+
+    pub fn summary(&self) -> Result<RuleSetSummary, EngineError> {
+        let mut summary = RuleSetSummary {
+            version: self.version.clone(),
+            sha: self.sha()?,
+            total_rules: self.rules.len(),
+            ..Default::default()
+        };
+        for rule in &self.rules {
+            for tag in &rule.tags {
+                *summary.by_tag.entry(tag.clone()).or_default() += 1;
+            }
+            if let Some(severity) = &rule.severity {
+                *summary.by_severity.entry(severity.clone()).or_default() += 1;
+            }
+            summary.llm_generated += usize::from(rule.generated_by_llm);
+            summary.max_condition_depth = summary.max_condition_depth.max(self.condition_depth(&rule.when));
+        }
+        let mut pending: Vec<&Condition> = self.rules.iter().map(|rule| &rule.when).chain(self.definitions.values()).collect();
+        while let Some(condition) = pending.pop() {
+            *summary.condition_types.entry(condition.type_name().to_string()).or_default() += 1;
+            pending.extend(condition.children());
+        }
+        Ok(summary)
+    }
+
+    /// Nesting depth of `condition`: 1 for a leaf, plus one per combinator or
+    /// quantifier above it. A `ref` counts as the definition it names; a
+    /// reference cycle stops counting where it repeats.
+    pub fn condition_depth(&self, condition: &Condition) -> usize {
+        self.depth_following_refs(condition, &mut Vec::new())
+    }
+
+    /// Whether `condition` is deeper than `max` as measured by
+    /// [`RuleSet::condition_depth`], recursing at most `max` levels.
+    pub(crate) fn condition_deeper_than(&self, condition: &Condition, max: usize) -> bool {
+        self.deeper_following_refs(condition, max, &mut Vec::new())
+    }
+
+    fn deeper_following_refs<'a>(&'a self, condition: &'a Condition, max: usize, refs: &mut Vec<&'a str>) -> bool {
+        if let Condition::Ref { name } = condition {
+            let Some(definition) = self.definitions.get(name).filter(|_| !refs.contains(&name.as_str())) else {
+                return max == 0;
+            };
+            refs.push(name);
+            let deeper = self.deeper_following_refs(definition, max, refs);
+            refs.pop();
+            return deeper;
+        }
+        max == 0 || condition.children().into_iter().any(|child| self.deeper_following_refs(child, max - 1, refs))
+    }
+
+    fn depth_following_refs<'a>(&'a self, condition: &'a Condition, refs: &mut Vec<&'a str>) -> usize {
+        if let Condition::Ref { name } = condition {
+            let Some(definition) = self.definitions.get(name).filter(|_| !refs.contains(&name.as_str())) else {
+                return 1;
+            };
+            refs.push(name);
+            let depth = self.depth_following_refs(definition, refs);
+            refs.pop();
+            return depth;
+        }
+        let children = condition.children();
+        if children.is_empty() {
+            return 1;
+        }
+        1 + children.into_iter().map(|child| self.depth_following_refs(child, refs)).max().unwrap_or(0)
+    }
+
+    /// Every payload field the ruleset can read, sorted: fields tested by
+    /// rules (through definitions too) or used in their outcomes,
+    /// the inputs of computed fields in place of the computed names, and the
+    /// aliases of aliased fields.
+    /// Fields inside `any`/`all` quantifiers are reported below the
+    /// quantified array, as in `items[*].price`.
+    pub fn referenced_fields(&self) -> BTreeSet<String> {
+        let mut fields: BTreeSet<String> = self.referenced_fields_by_rule().into_values().flatten().collect();
+        for source in self.computed_fields.values() {
+            if let Ok(expr) = Expr::parse(source) {
+                for input in expr.fields() {
+                    self.add_referenced_field(input.to_string(), &mut fields, &mut Vec::new());
+                }
+            }
+        }
+        fields
+    }
+
+    /// [`RuleSet::referenced_fields`] for each rule, keyed by rule ID.
+    pub fn referenced_fields_by_rule(&self) -> BTreeMap<String, BTreeSet<String>> {
+        self.rules.iter()
+            .map(|rule| {
+                let mut reads = Vec::new();
+                self.collect_referenced_fields(&rule.when, "", "", &mut Vec::new(), &mut reads);
+                for action in std::iter::once(&rule.then).chain(&rule.else_then) {
+                    action_reads(action, "", &mut reads);
+                }
+                let mut fields = BTreeSet::new();
+                for (_, field) in reads {
+                    self.add_referenced_field(field, &mut fields, &mut Vec::new());
+                }
+                (rule.id.clone(), fields)
+            })
             .collect()
     }
 
-    fn evaluate_condition(&self, condition: &Condition, payload: &HashMap<String, serde_json::Value>) -> Result<bool, EngineError> {
+    /// Every field `condition` reads, with its pointer.
+    fn collect_referenced_fields<'a>(
+        &'a self,
+        condition: &'a Condition,
+        pointer: &str,
+        prefix: &str,
+        refs: &mut Vec<&'a str>,
+        reads: &mut Vec<(String, String)>,
+    ) {
         match condition {
-            Condition::And { conditions } => {
-                for cond in conditions {
-                    if !self.evaluate_condition(cond, payload)? {
-                        return Ok(false);
-                    }
+            Condition::And { conditions }
+            | Condition::Or { conditions }
+            | Condition::Xor { conditions, .. }
+            | Condition::AtLeast { conditions, .. } => {
+                for (index, cond) in conditions.iter().enumerate() {
+                    self.collect_referenced_fields(cond, &format!("{}/conditions/{}", pointer, index), prefix, refs, reads);
                 }
-                Ok(true)
             },
-            Condition::Or { conditions } => {
-                for cond in conditions {
-                    if self.evaluate_condition(cond, payload)? {
-                        return Ok(true);
-                    }
+            Condition::Not { condition } => self.collect_referenced_fields(condition, &format!("{}/condition", pointer), prefix, refs, reads),
+            Condition::Implies { antecedent, consequent } => {
+                self.collect_referenced_fields(antecedent, &format!("{}/if", pointer), prefix, refs, reads);
+                self.collect_referenced_fields(consequent, &format!("{}/then", pointer), prefix, refs, reads);
+            },
+            Condition::Ref { name } => {
+                if let Some(definition) = self.definitions.get(name).filter(|_| !refs.contains(&name.as_str())) {
+                    refs.push(name);
+                    let pointer = format!("/definitions/{}", crate::dsl::escape_pointer_token(name));
+                    self.collect_referenced_fields(definition, &pointer, prefix, refs, reads);
+                    refs.pop();
                 }
-                Ok(false)
             },
-            Condition::Not { condition } => {
-                Ok(!self.evaluate_condition(condition, payload)?)
+            Condition::Any { field, condition } | Condition::All { field, condition } => {
+                let array = qualify_field(prefix, field);
+                let elements = if path::has_wildcard(field) { array.clone() } else { format!("{}[*]", array) };
+                reads.push((format!("{}/field", pointer), array));
+                self.collect_referenced_fields(condition, &format!("{}/condition", pointer), &elements, refs, reads);
             },
-            Condition::Equals { field, value } => {
-                Ok(payload.get(field) == Some(value))
+            Condition::Aggregate { field, path: Some(element_path), .. } => {
+                reads.push((format!("{}/path", pointer), format!("{}[*].{}", qualify_field(prefix, field), element_path)));
             },
-            Condition::GreaterThan { field, value } => {
-                if let Some(field_value) = payload.get(field) {
-                    if let Some(num) = field_value.as_f64() {
-                        return Ok(num > *value);
-                    }
-                }
-                Ok(false)
+            Condition::GeoWithin { other_field: Some(other), .. } => {
+                reads.push((format!("{}/other_field", pointer), qualify_field(prefix, other)));
             },
-            Condition::LessThan { field, value } => {
-                if let Some(field_value) = payload.get(field) {
-                    if let Some(num) = field_value.as_f64() {
-                        return Ok(num < *value);
-                    }
+            _ => {},
+        }
+        if let Some(subject) = condition.subject() {
+            let key = if matches!(condition, Condition::Lookup { .. }) { "key_field" } else { "field" };
+            reads.push((format!("{}/{}", pointer, key), qualify_field(prefix, subject.field)));
+        }
+    }
+
+    /// Records a field read, substituting computed fields with their inputs
+    /// and adding any aliases.
+    fn add_referenced_field<'a>(&'a self, field: String, fields: &mut BTreeSet<String>, computing: &mut Vec<&'a str>) {
+        let root = field.split(['.', '[']).next().unwrap_or_default();
+        if let Some((name, source)) = self.computed_fields.get_key_value(root) {
+            if !computing.contains(&name.as_str()) {
+                computing.push(name);
+                for input in Expr::parse(source).iter().flat_map(|expr| expr.fields()) {
+                    self.add_referenced_field(input.to_string(), fields, computing);
                 }
-                Ok(false)
-            },
-            Condition::Contains { field, value } => {
-                if let Some(field_value) = payload.get(field) {
-                    if let Some(str_val) = field_value.as_str() {
-                        return Ok(str_val.contains(value));
+                computing.pop();
+            }
+            return;
+        }
+        for alias in self.aliases.get(&field).into_iter().flatten() {
+            fields.insert(alias.clone());
+        }
+        fields.insert(field);
+    }
+
+    /// Rules that read `path`, in rule order, with every place they read it:
+    /// conditions (through definitions, and qualified inside quantifiers as
+    /// in [`RuleSet::referenced_fields`]), outcome templates and
+    /// expressions, reason messages and captures. Fields are matched as
+    /// written; computed fields and aliases aren't expanded. A `path` ending
+    /// in `.` matches every field below it, so `customer.` finds
+    /// `customer.country`. The `default_action` is reported as
+    /// [`DEFAULT_RULE_ID`].
+    pub fn find_rules_by_field(&self, path: &str) -> Vec<RuleReference> {
+        self.search(|pointer, condition, actions, locations| {
+            let mut reads = Vec::new();
+            if let Some(condition) = condition {
+                self.collect_referenced_fields(condition, &format!("{}/when", pointer), "", &mut Vec::new(), &mut reads);
+            }
+            for (key, action) in actions {
+                action_reads(action, &format!("{}/{}", pointer, key), &mut reads);
+            }
+            locations.extend(reads.into_iter().filter(|(_, field)| path_matches(path, field)).map(|(location, _)| location));
+        })
+    }
+
+    /// Rules whose `outcome` or `set_outcome` operations write `key`, in rule
+    /// order. A `key` ending in `.` matches as a prefix, as in
+    /// [`RuleSet::find_rules_by_field`].
+    pub fn find_rules_by_outcome_key(&self, key: &str) -> Vec<RuleReference> {
+        self.search(|pointer, _, actions, locations| {
+            for (branch, action) in actions {
+                let mut keys: Vec<&String> = action.outcome.keys().filter(|written| path_matches(key, written)).collect();
+                keys.sort();
+                locations.extend(keys.into_iter().map(|written| {
+                    format!("{}/{}/outcome/{}", pointer, branch, crate::dsl::escape_pointer_token(written))
+                }));
+                for (index, operation) in action.operations.iter().enumerate() {
+                    if matches!(operation, Operation::SetOutcome { key: written, .. } if path_matches(key, written)) {
+                        locations.push(format!("{}/{}/operations/{}", pointer, branch, index));
                     }
                 }
-                Ok(false)
-            },
-            Condition::In { field, values } => {
-                if let Some(field_value) = payload.get(field) {
-                    return Ok(values.contains(field_value));
-                }
-                Ok(false)
-            },
+            }
+        })
+    }
+
+    /// Runs `locate` over each rule and then the default action, with the
+    /// pointer to it, its condition and its actions keyed by member name, and
+    /// keeps those it found locations in.
+    fn search(&self, mut locate: impl FnMut(&str, Option<&Condition>, &[(&str, &Action)], &mut Vec<String>)) -> Vec<RuleReference> {
+        let mut found = Vec::new();
+        let mut report = |rule_id: &str, locations: Vec<String>| {
+            if !locations.is_empty() {
+                found.push(RuleReference { rule_id: rule_id.to_string(), locations });
+            }
+        };
+        for (index, rule) in self.rules.iter().enumerate() {
+            let mut actions = vec![("then", &rule.then)];
+            actions.extend(rule.else_then.as_ref().map(|action| ("else_then", action)));
+            let mut locations = Vec::new();
+            locate(&format!("/rules/{}", index), Some(&rule.when), &actions, &mut locations);
+            report(&rule.id, locations);
+        }
+        if let Some(default_action) = &self.default_action {
+            let mut locations = Vec::new();
+            locate("", None, &[("default_action", default_action)], &mut locations);
+            report(DEFAULT_RULE_ID, locations);
         }
+        found
     }
+
 }
 
-impl Default for RuleEngine {
+/// `field` read inside a quantifier over `prefix`, or as is at the top level.
+fn qualify_field(prefix: &str, field: &str) -> String {
+    match (prefix, field) {
+        ("", field) => field.to_string(),
+        (prefix, "") => prefix.to_string(),
+        (prefix, field) => format!("{}.{}", prefix, field),
+    }
+}
+
+/// Exact match, or prefix match for a query ending in `.`.
+fn path_matches(query: &str, path: &str) -> bool {
+    if query.ends_with('.') { path.starts_with(query) } else { path == query }
+}
+
+/// Every field an action's outcome values, reason messages and captures
+/// read, with its pointer.
+fn action_reads(action: &Action, pointer: &str, reads: &mut Vec<(String, String)>) {
+    let mut keys: Vec<&String> = action.outcome.keys().collect();
+    keys.sort();
+    for key in keys {
+        value_reads(&action.outcome[key], &format!("{}/outcome/{}", pointer, crate::dsl::escape_pointer_token(key)), reads);
+    }
+    for (index, operation) in action.operations.iter().enumerate() {
+        match operation {
+            Operation::SetOutcome { value, .. } => value_reads(value, &format!("{}/operations/{}/value", pointer, index), reads),
+            Operation::AddReason { message: Some(message), .. } => {
+                let message = serde_json::Value::String(message.clone());
+                value_reads(&message, &format!("{}/operations/{}/message", pointer, index), reads);
+            },
+            _ => {},
+        }
+    }
+    for (index, field) in action.capture.iter().enumerate() {
+        reads.push((format!("{}/capture/{}", pointer, index), field.clone()));
+    }
+}
+
+fn value_reads(value: &serde_json::Value, pointer: &str, reads: &mut Vec<(String, String)>) {
+    if let Some(Ok(expr)) = expr::outcome_expr(value) {
+        reads.extend(expr.fields().into_iter().map(|field| (pointer.to_string(), field.to_string())));
+        return;
+    }
+    match value {
+        serde_json::Value::String(text) if text.contains("{{") => {
+            let template = Template::parse(text);
+            for field in template.iter().flat_map(Template::fields).filter(|field| !field.starts_with("$captures.")) {
+                reads.push((pointer.to_string(), field.to_string()));
+            }
+        },
+        serde_json::Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                value_reads(item, &format!("{}/{}", pointer, index), reads);
+            }
+        },
+        serde_json::Value::Object(map) => {
+            for (key, item) in map {
+                value_reads(item, &format!("{}/{}", pointer, crate::dsl::escape_pointer_token(key)), reads);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// The value a leaf condition tests in place of its field when the field is
+/// absent (or null, with `apply_on_null: true`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FieldDefault {
+    #[serde(default, rename = "default", skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub apply_on_null: bool,
+}
+
+/// A rule predicate. Leaf conditions that test a payload `field` also accept
+/// an optional `default`; see [`FieldDefault`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(remote = "Self", tag = "type", deny_unknown_fields)]
+pub enum Condition {
+    #[serde(rename = "and")]
+    And { conditions: Vec<Condition> },
+    #[serde(rename = "or")]
+    Or { conditions: Vec<Condition> },
+    #[serde(rename = "not")]
+    Not { condition: Box<Condition> },
+    /// Implication: matches unless `if` matches and `then` doesn't.
+    #[serde(rename = "implies")]
+    Implies {
+        #[serde(rename = "if")]
+        antecedent: Box<Condition>,
+        #[serde(rename = "then")]
+        consequent: Box<Condition>,
+    },
+    /// Matches when exactly `exactly` of `conditions` match (one by default),
+    /// rather than any odd number of them.
+    #[serde(rename = "xor")]
+    Xor {
+        conditions: Vec<Condition>,
+        #[serde(default = "default_one")]
+        exactly: usize,
+    },
+    /// Evaluates the condition registered under `name` in the ruleset's
+    /// `definitions`.
+    #[serde(rename = "ref")]
+    Ref { name: String },
+    /// Matches when at least `n` of `conditions` match.
+    #[serde(rename = "at_least")]
+    AtLeast { n: usize, conditions: Vec<Condition> },
+    /// Matches when at least one element of the array at `field` satisfies
+    /// `condition`. Inside the nested condition, fields are looked up on the
+    /// element itself, and an empty field name refers to the whole element.
+    /// A wildcard `field` (`accounts.*`) iterates over its expansions instead.
+    ///
+    /// A leaf condition on a wildcard path behaves like `any` over the
+    /// expansions: it matches if one of them does, and never matches when
+    /// the path expands to nothing.
+    #[serde(rename = "any")]
+    Any { field: String, condition: Box<Condition> },
+    /// Matches when every element of the array at `field` (or every
+    /// expansion of a wildcard `field`) satisfies `condition`; vacuously true
+    /// for an empty array or a wildcard that expands to nothing.
+    #[serde(rename = "all")]
+    All { field: String, condition: Box<Condition> },
+    #[serde(rename = "equals")]
+    Equals {
+        field: String,
+        value: serde_json::Value,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Structural comparison of objects and arrays, ignoring key order and
+    /// comparing numbers by value (1 == 1.0). With `ignore_extra_keys`, payload
+    /// objects at any depth may carry keys that `value` doesn't mention.
+    #[serde(rename = "deep_equals")]
+    DeepEquals {
+        field: String,
+        value: serde_json::Value,
+        #[serde(default)]
+        ignore_extra_keys: bool,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the field is present and differs from `value`. A missing
+    /// field does not match unless `missing_is_match` is set.
+    #[serde(rename = "not_equals")]
+    NotEquals {
+        field: String,
+        value: serde_json::Value,
+        #[serde(default)]
+        missing_is_match: bool,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    #[serde(rename = "greater_than")]
+    GreaterThan {
+        field: String,
+        value: f64,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    #[serde(rename = "less_than")]
+    LessThan {
+        field: String,
+        value: f64,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    #[serde(rename = "gte", alias = "greater_than_or_equal")]
+    GreaterThanOrEqual {
+        field: String,
+        value: f64,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    #[serde(rename = "lte", alias = "less_than_or_equal")]
+    LessThanOrEqual {
+        field: String,
+        value: f64,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Numeric range check; both ends are inclusive unless stated otherwise.
+    #[serde(rename = "between")]
+    Between {
+        field: String,
+        min: f64,
+        max: f64,
+        #[serde(default = "default_true")]
+        inclusive_min: bool,
+        #[serde(default = "default_true")]
+        inclusive_max: bool,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the numeric field is within `epsilon` of `value`, or, with
+    /// `relative`, within `epsilon` times the larger magnitude of the two.
+    #[serde(rename = "approx_equals")]
+    ApproxEquals {
+        field: String,
+        value: f64,
+        epsilon: f64,
+        #[serde(default)]
+        relative: bool,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Substring match on string fields; membership (full JSON equality) on
+    /// array fields.
+    #[serde(rename = "contains")]
+    Contains {
+        field: String,
+        value: serde_json::Value,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the array field shares at least one element with `values`
+    /// (or, for string fields, contains at least one of them as a substring).
+    #[serde(rename = "contains_any")]
+    ContainsAny {
+        field: String,
+        values: Vec<serde_json::Value>,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the array field contains every element of `values` (or,
+    /// for string fields, every one of them as a substring). Vacuously true
+    /// for an empty `values` list on a present array or string field.
+    #[serde(rename = "contains_all")]
+    ContainsAll {
+        field: String,
+        values: Vec<serde_json::Value>,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    #[serde(rename = "starts_with")]
+    StartsWith {
+        field: String,
+        value: String,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    #[serde(rename = "ends_with")]
+    EndsWith {
+        field: String,
+        value: String,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the field is present, whatever its value (null included).
+    /// Wrap in `not` to match absent fields.
+    #[serde(rename = "exists")]
+    Exists {
+        field: String,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the field is present and explicitly null. Under `not` this
+    /// matches both absent fields and non-null values; combine with `exists`
+    /// to require a present, non-null value.
+    #[serde(rename = "is_null")]
+    IsNull {
+        field: String,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches empty strings, arrays and objects. Whitespace-only strings only
+    /// count as empty when `trim` is set; other value types never match. A
+    /// missing field matches only when `missing_is_match` is set.
+    #[serde(rename = "is_empty")]
+    IsEmpty {
+        field: String,
+        #[serde(default)]
+        trim: bool,
+        #[serde(default)]
+        missing_is_match: bool,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Compares the length of an array, string (in chars) or object (in keys)
+    /// using `operator`: one of eq, gt, lt, gte, lte.
+    #[serde(rename = "length")]
+    Length {
+        field: String,
+        operator: String,
+        value: usize,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Aggregates the numbers in the array at `field` (or at `path` within
+    /// each element, for arrays of objects) and compares the result against
+    /// `value` using `operator`. Non-numeric elements are skipped unless
+    /// `strict` is set, in which case they fail evaluation. For an empty set
+    /// of numbers, count and sum are 0 while avg, min and max have no value
+    /// and the condition does not match.
+    ///
+    /// A wildcard `field` (`accounts.*.balance`) is resolved once and
+    /// aggregated over all of its expansions, with array expansions
+    /// contributing their elements.
+    #[serde(rename = "aggregate")]
+    Aggregate {
+        field: String,
+        #[serde(default)]
+        path: Option<String>,
+        op: AggregateOp,
+        operator: String,
+        value: f64,
+        #[serde(default)]
+        strict: bool,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the payload timestamp (ISO 8601 string or epoch seconds)
+    /// is strictly before `value`, an ISO date or datetime.
+    #[serde(rename = "date_before")]
+    DateBefore {
+        field: String,
+        value: String,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the payload timestamp is strictly after `value`.
+    #[serde(rename = "date_after")]
+    DateAfter {
+        field: String,
+        value: String,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the payload timestamp lies within `duration` (e.g. `"30d"`,
+    /// `"12h"`, `"45m"`) before the evaluation time, boundaries included.
+    /// Timestamps in the future do not match.
+    #[serde(rename = "within_last")]
+    WithinLast {
+        field: String,
+        duration: String,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the timestamp at `field` (or the evaluation time when no
+    /// field is given) falls on one of `days`, in `timezone` (UTC by default).
+    #[serde(rename = "day_of_week")]
+    DayOfWeek {
+        #[serde(default)]
+        field: Option<String>,
+        days: Vec<String>,
+        #[serde(default)]
+        timezone: Option<String>,
+    },
+    /// Matches when the local wall-clock time of the timestamp at `field` (or
+    /// the evaluation time) is in `[start, end)`, both `HH:MM`. A window with
+    /// `start` after `end`, such as 22:00-06:00, wraps around midnight.
+    #[serde(rename = "time_of_day")]
+    TimeOfDay {
+        #[serde(default)]
+        field: Option<String>,
+        start: String,
+        end: String,
+        #[serde(default)]
+        timezone: Option<String>,
+    },
+    /// Matches when the field holds an IPv4 or IPv6 address inside any of
+    /// `cidrs`. The ranges are parsed once, when the ruleset is loaded.
+    #[serde(rename = "ip_in_cidr")]
+    IpInCidr {
+        field: String,
+        cidrs: Vec<String>,
+        #[serde(skip)]
+        networks: OnceLock<Vec<Cidr>>,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Compares the semantic version at `field` against `value` using
+    /// `operator` (eq, gt, lt, gte, lte), honouring pre-release precedence and
+    /// ignoring build metadata.
+    #[serde(rename = "semver")]
+    Semver {
+        field: String,
+        operator: String,
+        value: String,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Glob match of a string field against `pattern`; see [`Glob`] for the
+    /// syntax. Matching is case-sensitive unless `case_insensitive` is set.
+    #[serde(rename = "matches_glob")]
+    MatchesGlob {
+        field: String,
+        pattern: String,
+        #[serde(default)]
+        case_insensitive: bool,
+        #[serde(default)]
+        path_separators: bool,
+        #[serde(skip)]
+        compiled: OnceLock<Option<Glob>>,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Regular-expression search in a string field (unanchored; use `^` and
+    /// `$` to match the whole value). Capture groups of a matching rule are
+    /// copied into [`Decision::captures`], named groups by name and unnamed
+    /// ones by number.
+    #[serde(rename = "matches")]
+    Matches {
+        field: String,
+        pattern: String,
+        #[serde(default)]
+        case_insensitive: bool,
+        #[serde(skip)]
+        compiled: OnceLock<Option<Regex>>,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Compares the number of Unicode scalar values (chars, not bytes) in a
+    /// string field against `value` using `operator` (eq, gt, lt, gte, lte).
+    #[serde(rename = "string_length")]
+    StringLength {
+        field: String,
+        operator: String,
+        value: usize,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the integer field satisfies `field mod divisor ==
+    /// remainder`, using the Euclidean remainder (never negative, so -7 mod 10
+    /// is 3). Floats, including whole-valued ones like 10.0, never match.
+    #[serde(rename = "modulo")]
+    Modulo {
+        field: String,
+        divisor: i64,
+        remainder: i64,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the field is present and holds a value of the `expected`
+    /// JSON type. `integer` only matches numbers written without a fractional
+    /// part or exponent, while `number` matches any number.
+    #[serde(rename = "type_is")]
+    TypeIs {
+        field: String,
+        expected: JsonType,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the string field passes the checksum `algorithm`.
+    #[serde(rename = "checksum")]
+    Checksum {
+        field: String,
+        algorithm: ChecksumAlgorithm,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the string field is well-formed for `format`: one of
+    /// email, url, uuid, hex or base64.
+    #[serde(rename = "is_format")]
+    IsFormat {
+        field: String,
+        format: String,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Stable percentage rollout: matches when the value at `field` hashes
+    /// (together with `salt`) into a bucket below `percent`. Strings are hashed
+    /// as-is, other values by their JSON text; null or missing never match.
+    #[serde(rename = "percentage")]
+    Percentage {
+        field: String,
+        percent: f64,
+        #[serde(default)]
+        salt: Option<String>,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Tests the bits of an integer field against `mask`. Negative payload
+    /// values are read as 64-bit two's complement, so -1 has every bit set;
+    /// floats and strings never match.
+    #[serde(rename = "bitmask")]
+    Bitmask {
+        field: String,
+        mask: serde_json::Number,
+        mode: BitmaskMode,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the coordinate at `field` is at most `max_km` from either
+    /// the coordinate at `other_field` or the fixed `lat`/`lon`. Coordinates
+    /// are `{"lat": .., "lon": ..}` objects or `[lat, lon]` arrays; malformed or
+    /// out-of-range ones never match.
+    #[serde(rename = "geo_within")]
+    GeoWithin {
+        field: String,
+        #[serde(default)]
+        other_field: Option<String>,
+        #[serde(default)]
+        lat: Option<f64>,
+        #[serde(default)]
+        lon: Option<f64>,
+        max_km: f64,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Looks up the value at `key_field` in the named table from the ruleset
+    /// metadata and compares the result using `equals` or `in`. Keys that are
+    /// missing from the table fall back to `default`, or never match.
+    #[serde(rename = "lookup")]
+    Lookup {
+        table: String,
+        key_field: String,
+        #[serde(default)]
+        equals: Option<serde_json::Value>,
+        #[serde(default, rename = "in")]
+        one_of: Option<Vec<serde_json::Value>>,
+        #[serde(default)]
+        default: Option<serde_json::Value>,
+    },
+    /// Matches when the field equals one of `values`. The list is hashed when
+    /// the ruleset is loaded, so long lists cost no more than short ones.
+    #[serde(rename = "in")]
+    In {
+        field: String,
+        values: Vec<serde_json::Value>,
+        #[serde(skip)]
+        compiled: OnceLock<ValueSet>,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+    /// Matches when the field is present and not among `values`. A missing
+    /// field does not match unless `missing_is_match` is set.
+    #[serde(rename = "not_in")]
+    NotIn {
+        field: String,
+        values: Vec<serde_json::Value>,
+        #[serde(default)]
+        missing_is_match: bool,
+        #[serde(skip)]
+        compiled: OnceLock<ValueSet>,
+        #[serde(skip)]
+        default: FieldDefault,
+    },
+}
+
+impl Condition {
+    /// The condition's `type` tag, e.g. `"greater_than"`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Condition::And { .. } => "and",
+            Condition::Or { .. } => "or",
+            Condition::Not { .. } => "not",
+            Condition::Implies { .. } => "implies",
+            Condition::Xor { .. } => "xor",
+            Condition::Ref { .. } => "ref",
+            Condition::AtLeast { .. } => "at_least",
+            Condition::Any { .. } => "any",
+            Condition::All { .. } => "all",
+            Condition::Equals { .. } => "equals",
+            Condition::DeepEquals { .. } => "deep_equals",
+            Condition::NotEquals { .. } => "not_equals",
+            Condition::GreaterThan { .. } => "greater_than",
+            Condition::LessThan { .. } => "less_than",
+            Condition::GreaterThanOrEqual { .. } => "gte",
+            Condition::LessThanOrEqual { .. } => "lte",
+            Condition::Between { .. } => "between",
+            Condition::ApproxEquals { .. } => "approx_equals",
+            Condition::Contains { .. } => "contains",
+            Condition::ContainsAny { .. } => "contains_any",
+            Condition::ContainsAll { .. } => "contains_all",
+            Condition::StartsWith { .. } => "starts_with",
+            Condition::EndsWith { .. } => "ends_with",
+            Condition::Exists { .. } => "exists",
+            Condition::IsNull { .. } => "is_null",
+            Condition::IsEmpty { .. } => "is_empty",
+            Condition::Length { .. } => "length",
+            Condition::Aggregate { .. } => "aggregate",
+            Condition::DateBefore { .. } => "date_before",
+            Condition::DateAfter { .. } => "date_after",
+            Condition::WithinLast { .. } => "within_last",
+            Condition::DayOfWeek { .. } => "day_of_week",
+            Condition::TimeOfDay { .. } => "time_of_day",
+            Condition::IpInCidr { .. } => "ip_in_cidr",
+            Condition::Semver { .. } => "semver",
+            Condition::MatchesGlob { .. } => "matches_glob",
+            Condition::Matches { .. } => "matches",
+            Condition::StringLength { .. } => "string_length",
+            Condition::Modulo { .. } => "modulo",
+            Condition::TypeIs { .. } => "type_is",
+            Condition::Checksum { .. } => "checksum",
+            Condition::IsFormat { .. } => "is_format",
+            Condition::Percentage { .. } => "percentage",
+            Condition::Bitmask { .. } => "bitmask",
+            Condition::GeoWithin { .. } => "geo_within",
+            Condition::Lookup { .. } => "lookup",
+            Condition::In { .. } => "in",
+            Condition::NotIn { .. } => "not_in",
+        }
+    }
+
+    /// The conditions directly nested in a combinator or quantifier. A `ref`
+    /// has none; its definition is not nested.
+    pub fn children(&self) -> Vec<&Condition> {
+        match self {
+            Condition::And { conditions }
+            | Condition::Or { conditions }
+            | Condition::Xor { conditions, .. }
+            | Condition::AtLeast { conditions, .. } => conditions.iter().collect(),
+            Condition::Not { condition } | Condition::Any { condition, .. } | Condition::All { condition, .. } => vec![condition],
+            Condition::Implies { antecedent, consequent } => vec![antecedent, consequent],
+            _ => Vec::new(),
+        }
+    }
+
+    /// The field types a leaf condition can test, or `None` for any type.
+    fn input_types(&self) -> Option<&'static [JsonType]> {
+        use JsonType::*;
+        match self {
+            Condition::GreaterThan { .. }
+            | Condition::LessThan { .. }
+            | Condition::GreaterThanOrEqual { .. }
+            | Condition::LessThanOrEqual { .. }
+            | Condition::Between { .. }
+            | Condition::ApproxEquals { .. } => Some(&[Number]),
+            Condition::Modulo { .. } | Condition::Bitmask { .. } => Some(&[Integer]),
+            Condition::Contains { .. } | Condition::ContainsAny { .. } | Condition::ContainsAll { .. } => Some(&[String, Array]),
+            Condition::StartsWith { .. }
+            | Condition::EndsWith { .. }
+            | Condition::Matches { .. }
+            | Condition::MatchesGlob { .. }
+            | Condition::StringLength { .. }
+            | Condition::IpInCidr { .. }
+            | Condition::Semver { .. }
+            | Condition::Checksum { .. }
+            | Condition::IsFormat { .. } => Some(&[String]),
+            Condition::IsEmpty { .. } | Condition::Length { .. } => Some(&[String, Array, Object]),
+            Condition::Aggregate { .. } | Condition::Any { .. } | Condition::All { .. } => Some(&[Array]),
+            Condition::DateBefore { .. }
+            | Condition::DateAfter { .. }
+            | Condition::WithinLast { .. }
+            | Condition::DayOfWeek { .. }
+            | Condition::TimeOfDay { .. } => Some(&[String, Number]),
+            Condition::GeoWithin { .. } => Some(&[Object, Array]),
+            _ => None,
+        }
+    }
+
+    /// Conditions that read their field through [`EvalContext::number`].
+    fn is_numeric_comparison(&self) -> bool {
+        matches!(
+            self,
+            Condition::GreaterThan { .. }
+                | Condition::LessThan { .. }
+                | Condition::GreaterThanOrEqual { .. }
+                | Condition::LessThanOrEqual { .. }
+                | Condition::Between { .. }
+                | Condition::ApproxEquals { .. }
+        )
+    }
+
+    /// The payload field a leaf condition tests, with its fallback value.
+    /// Used to fan wildcard paths out over their expansions and to apply
+    /// defaults. Combinators, quantifiers and references have none.
+    fn subject(&self) -> Option<Subject<'_>> {
+        match self {
+            Condition::Equals { field, default, .. }
+            | Condition::DeepEquals { field, default, .. }
+            | Condition::NotEquals { field, default, .. }
+            | Condition::GreaterThan { field, default, .. }
+            | Condition::LessThan { field, default, .. }
+            | Condition::GreaterThanOrEqual { field, default, .. }
+            | Condition::LessThanOrEqual { field, default, .. }
+            | Condition::Between { field, default, .. }
+            | Condition::ApproxEquals { field, default, .. }
+            | Condition::Contains { field, default, .. }
+            | Condition::ContainsAny { field, default, .. }
+            | Condition::ContainsAll { field, default, .. }
+            | Condition::StartsWith { field, default, .. }
+            | Condition::EndsWith { field, default, .. }
+            | Condition::Exists { field, default, .. }
+            | Condition::IsNull { field, default, .. }
+            | Condition::IsEmpty { field, default, .. }
+            | Condition::Length { field, default, .. }
+            | Condition::Aggregate { field, default, .. }
+            | Condition::DateBefore { field, default, .. }
+            | Condition::DateAfter { field, default, .. }
+            | Condition::WithinLast { field, default, .. }
+            | Condition::IpInCidr { field, default, .. }
+            | Condition::Semver { field, default, .. }
+            | Condition::MatchesGlob { field, default, .. }
+            | Condition::Matches { field, default, .. }
+            | Condition::StringLength { field, default, .. }
+            | Condition::Modulo { field, default, .. }
+            | Condition::TypeIs { field, default, .. }
+            | Condition::Checksum { field, default, .. }
+            | Condition::IsFormat { field, default, .. }
+            | Condition::Percentage { field, default, .. }
+            | Condition::Bitmask { field, default, .. }
+            | Condition::GeoWithin { field, default, .. }
+            | Condition::In { field, default, .. }
+            | Condition::NotIn { field, default, .. } => Some(Subject { field, default }),
+            Condition::Lookup { key_field: field, .. } => Some(Subject { field, default: &NO_DEFAULT }),
+            Condition::DayOfWeek { field, .. } | Condition::TimeOfDay { field, .. } => {
+                field.as_deref().map(|field| Subject { field, default: &NO_DEFAULT })
+            },
+            Condition::And { .. }
+            | Condition::Or { .. }
+            | Condition::Not { .. }
+            | Condition::Implies { .. }
+            | Condition::Xor { .. }
+            | Condition::Ref { .. }
+            | Condition::AtLeast { .. }
+            | Condition::Any { .. }
+            | Condition::All { .. } => None,
+        }
+    }
+
+    /// The `default` slot of a leaf condition that takes one.
+    fn field_default_mut(&mut self) -> Option<&mut FieldDefault> {
+        match self {
+            Condition::Equals { default, .. }
+            | Condition::DeepEquals { default, .. }
+            | Condition::NotEquals { default, .. }
+            | Condition::GreaterThan { default, .. }
+            | Condition::LessThan { default, .. }
+            | Condition::GreaterThanOrEqual { default, .. }
+            | Condition::LessThanOrEqual { default, .. }
+            | Condition::Between { default, .. }
+            | Condition::ApproxEquals { default, .. }
+            | Condition::Contains { default, .. }
+            | Condition::ContainsAny { default, .. }
+            | Condition::ContainsAll { default, .. }
+            | Condition::StartsWith { default, .. }
+            | Condition::EndsWith { default, .. }
+            | Condition::Exists { default, .. }
+            | Condition::IsNull { default, .. }
+            | Condition::IsEmpty { default, .. }
+            | Condition::Length { default, .. }
+            | Condition::Aggregate { default, .. }
+            | Condition::DateBefore { default, .. }
+            | Condition::DateAfter { default, .. }
+            | Condition::WithinLast { default, .. }
+            | Condition::IpInCidr { default, .. }
+            | Condition::Semver { default, .. }
+            | Condition::MatchesGlob { default, .. }
+            | Condition::Matches { default, .. }
+            | Condition::StringLength { default, .. }
+            | Condition::Modulo { default, .. }
+            | Condition::TypeIs { default, .. }
+            | Condition::Checksum { default, .. }
+            | Condition::IsFormat { default, .. }
+            | Condition::Percentage { default, .. }
+            | Condition::Bitmask { default, .. }
+            | Condition::GeoWithin { default, .. }
+            | Condition::In { default, .. }
+            | Condition::NotIn { default, .. } => Some(default),
+            _ => None,
+        }
+    }
+}
+
+struct Subject<'a> {
+    field: &'a str,
+    default: &'a FieldDefault,
+}
+
+static NO_DEFAULT: FieldDefault = FieldDefault { value: None, apply_on_null: false };
+
+// `default` and `apply_on_null` are shared by every leaf condition, so they
+// are written here around the derived per-variant fields, and read by
+// `ConditionVisitor`.
+impl Serialize for Condition {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some(Subject { default, .. }) = self.subject().filter(|subject| *subject.default != NO_DEFAULT) else {
+            return Condition::serialize(self, serializer);
+        };
+        let mut value = Condition::serialize(self, serde_json::value::Serializer).map_err(serde::ser::Error::custom)?;
+        if let (Some(map), Ok(serde_json::Value::Object(shared))) = (value.as_object_mut(), serde_json::to_value(default)) {
+            map.extend(shared);
+        }
+        value.serialize(serializer)
+    }
+}
+
+// Nested conditions are read straight from the document, so that serde
+// places errors inside them; only the keys of one condition at a time are
+// buffered, to find its variant.
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(ConditionVisitor)
+    }
+}
+
+/// The value of a key that holds nested conditions.
+enum Nested {
+    One(Box<Condition>),
+    Many(Vec<Condition>),
+}
+
+struct ConditionVisitor;
+
+impl<'de> serde::de::Visitor<'de> for ConditionVisitor {
+    type Value = Condition;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("a condition")
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Condition, A::Error> {
+        let mut fields = serde_json::Map::new();
+        let (mut default, mut apply_on_null) = (None, None);
+        let mut nested = Vec::new();
+        while let Some(key) = map.next_key::<String>()? {
+            // Empty stand-ins keep the nested keys in the typed reading
+            // below, which checks them against the variant.
+            let value = match key.as_str() {
+                "conditions" => {
+                    nested.push((key.clone(), Nested::Many(map.next_value()?)));
+                    serde_json::json!([])
+                },
+                "condition" | "if" | "then" => {
+                    nested.push((key.clone(), Nested::One(map.next_value()?)));
+                    serde_json::json!({"type": "and", "conditions": []})
+                },
+                "default" => {
+                    default = Some(map.next_value()?);
+                    continue;
+                },
+                "apply_on_null" => {
+                    apply_on_null = Some(map.next_value()?);
+                    continue;
+                },
+                _ => map.next_value()?,
+            };
+            fields.insert(key, value);
+        }
+
+        let mut value = serde_json::Value::Object(fields);
+        let mut condition = Condition::deserialize(&value).map_err(serde::de::Error::custom)?;
+        match condition.field_default_mut() {
+            Some(slot) => *slot = FieldDefault { value: default, apply_on_null: apply_on_null.unwrap_or_default() },
+            // A `lookup` has a `default` of its own; other conditions get
+            // serde's unknown-field error.
+            None if default.is_some() || apply_on_null.is_some() => {
+                if let Some(fields) = value.as_object_mut() {
+                    fields.extend(default.map(|default| ("default".to_string(), default)));
+                    fields.extend(apply_on_null.map(|apply| ("apply_on_null".to_string(), serde_json::Value::Bool(apply))));
+                }
+                condition = Condition::deserialize(&value).map_err(serde::de::Error::custom)?;
+            },
+            None => {},
+        }
+        // The typed reading rejects nested keys the variant doesn't have.
+        for (key, nested) in nested {
+            match (&mut condition, key.as_str(), nested) {
+                (
+                    Condition::And { conditions }
+                    | Condition::Or { conditions }
+                    | Condition::Xor { conditions, .. }
+                    | Condition::AtLeast { conditions, .. },
+                    "conditions",
+                    Nested::Many(read),
+                ) => *conditions = read,
+                (
+                    Condition::Not { condition } | Condition::Any { condition, .. } | Condition::All { condition, .. },
+                    "condition",
+                    Nested::One(read),
+                ) => *condition = read,
+                (Condition::Implies { antecedent, .. }, "if", Nested::One(read)) => *antecedent = read,
+                (Condition::Implies { consequent, .. }, "then", Nested::One(read)) => *consequent = read,
+                _ => {},
+            }
+        }
+        Ok(condition)
+    }
+}
+
+/// Comparison operators accepted by conditions that take an `operator` name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Gt,
+    Lt,
+    Gte,
+    Lte,
+}
+
+impl CompareOp {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "eq" => Some(CompareOp::Eq),
+            "gt" => Some(CompareOp::Gt),
+            "lt" => Some(CompareOp::Lt),
+            "gte" => Some(CompareOp::Gte),
+            "lte" => Some(CompareOp::Lte),
+            _ => None,
+        }
+    }
+
+    pub fn apply<T: PartialOrd>(self, lhs: T, rhs: T) -> bool {
+        match self {
+            CompareOp::Eq => lhs == rhs,
+            CompareOp::Gt => lhs > rhs,
+            CompareOp::Lt => lhs < rhs,
+            CompareOp::Gte => lhs >= rhs,
+            CompareOp::Lte => lhs <= rhs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Array,
+    Object,
+    Null,
+}
+
+impl JsonType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JsonType::String => "string",
+            JsonType::Number => "number",
+            JsonType::Integer => "integer",
+            JsonType::Boolean => "boolean",
+            JsonType::Array => "array",
+            JsonType::Object => "object",
+            JsonType::Null => "null",
+        }
+    }
+
+    pub fn matches(self, value: &serde_json::Value) -> bool {
+        use serde_json::Value;
+        match self {
+            JsonType::String => value.is_string(),
+            JsonType::Number => value.is_number(),
+            JsonType::Integer => value.is_i64() || value.is_u64(),
+            JsonType::Boolean => value.is_boolean(),
+            JsonType::Array => value.is_array(),
+            JsonType::Object => value.is_object(),
+            JsonType::Null => matches!(value, Value::Null),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChecksumAlgorithm {
+    Luhn,
+    IbanMod97,
+}
+
+impl ChecksumAlgorithm {
+    pub fn is_valid(self, value: &str) -> bool {
+        match self {
+            ChecksumAlgorithm::Luhn => matchers::luhn_valid(value),
+            ChecksumAlgorithm::IbanMod97 => matchers::iban_valid(value),
+        }
+    }
+}
+
+/// Formats accepted by the `is_format` condition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringFormat {
+    Email,
+    Url,
+    Uuid,
+    Hex,
+    Base64,
+}
+
+impl StringFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "email" => Some(StringFormat::Email),
+            "url" => Some(StringFormat::Url),
+            "uuid" => Some(StringFormat::Uuid),
+            "hex" => Some(StringFormat::Hex),
+            "base64" => Some(StringFormat::Base64),
+            _ => None,
+        }
+    }
+
+    pub fn is_valid(self, value: &str) -> bool {
+        match self {
+            StringFormat::Email => matchers::is_email(value),
+            StringFormat::Url => matchers::is_url(value),
+            StringFormat::Uuid => matchers::is_uuid(value),
+            StringFormat::Hex => matchers::is_hex(value),
+            StringFormat::Base64 => matchers::is_base64(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BitmaskMode {
+    AnySet,
+    AllSet,
+    NoneSet,
+}
+
+impl BitmaskMode {
+    pub fn apply(self, bits: u64, mask: u64) -> bool {
+        match self {
+            BitmaskMode::AnySet => bits & mask != 0,
+            BitmaskMode::AllSet => bits & mask == mask,
+            BitmaskMode::NoneSet => bits & mask == 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AggregateOp {
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Count,
+}
+
+impl AggregateOp {
+    fn apply(self, numbers: &[f64]) -> Option<f64> {
+        match self {
+            AggregateOp::Sum => Some(numbers.iter().sum()),
+            AggregateOp::Count => Some(numbers.len() as f64),
+            AggregateOp::Avg if numbers.is_empty() => None,
+            AggregateOp::Avg => Some(numbers.iter().sum::<f64>() / numbers.len() as f64),
+            AggregateOp::Min => numbers.iter().copied().reduce(f64::min),
+            AggregateOp::Max => numbers.iter().copied().reduce(f64::max),
+        }
+    }
+}
+
+fn deep_equals(actual: &serde_json::Value, expected: &serde_json::Value, ignore_extra_keys: bool) -> bool {
+    use serde_json::Value;
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => {
+            (ignore_extra_keys || actual.len() == expected.len())
+                && expected.iter().all(|(key, expected)| {
+                    actual.get(key).is_some_and(|actual| deep_equals(actual, expected, ignore_extra_keys))
+                })
+        },
+        (Value::Array(actual), Value::Array(expected)) => {
+            actual.len() == expected.len()
+                && actual.iter().zip(expected).all(|(a, e)| deep_equals(a, e, ignore_extra_keys))
+        },
+        (Value::Number(actual), Value::Number(expected)) => actual.as_f64() == expected.as_f64(),
+        _ => actual == expected,
+    }
+}
+
+/// Parses the ruleset's computed fields and orders them so that every field
+/// comes after the computed fields it reads. Names must be plain keys, and
+/// references between computed fields must not form a cycle.
+fn compile_computed_fields(ruleset: &RuleSet) -> Result<Vec<(String, Expr)>, EngineError> {
+    let mut parsed = HashMap::new();
+    for (name, source) in &ruleset.computed_fields {
+        if name.is_empty() || name.contains(['.', '[', '/', '*']) {
+            return Err(EngineError::RuleValidation(format!(
+                "Computed field '{}' must be a plain field name",
+                name
+            )));
+        }
+        let expr = Expr::parse(source).map_err(|e| {
+            EngineError::RuleValidation(format!("Computed field '{}': {}", name, e))
+        })?;
+        parsed.insert(name.as_str(), expr);
+    }
+
+    fn visit<'a>(
+        name: &'a str,
+        parsed: &'a HashMap<&'a str, Expr>,
+        path: &mut Vec<&'a str>,
+        ordered: &mut Vec<(String, Expr)>,
+    ) -> Result<(), EngineError> {
+        if ordered.iter().any(|(done, _)| done == name) {
+            return Ok(());
+        }
+        if path.contains(&name) {
+            path.push(name);
+            return Err(EngineError::RuleValidation(format!(
+                "Circular reference in computed fields: {}",
+                path.join(" -> ")
+            )));
+        }
+        path.push(name);
+        for field in parsed[name].fields() {
+            let root = field.split(['.', '[']).next().unwrap_or(field);
+            if let Some((dependency, _)) = parsed.get_key_value(root) {
+                visit(dependency, parsed, path, ordered)?;
+            }
+        }
+        path.pop();
+        ordered.push((name.to_string(), parsed[name].clone()));
+        Ok(())
+    }
+
+    let mut names: Vec<&str> = parsed.keys().copied().collect();
+    names.sort();
+    let mut ordered = Vec::with_capacity(names.len());
+    for name in names {
+        visit(name, &parsed, &mut Vec::new(), &mut ordered)?;
+    }
+    Ok(ordered)
+}
+
+fn rules_by_tag(ruleset: &RuleSet) -> HashMap<String, Vec<usize>> {
+    let mut by_tag: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, rule) in ruleset.rules.iter().enumerate() {
+        for tag in &rule.tags {
+            let indices = by_tag.entry(tag.clone()).or_default();
+            if indices.last() != Some(&index) {
+                indices.push(index);
+            }
+        }
+    }
+    by_tag
+}
+
+pub(crate) fn priority_order(ruleset: &RuleSet) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..ruleset.rules.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(ruleset.rules[index].priority.unwrap_or(0)));
+    order
+}
+
+/// SHA-256 of the ruleset's JSON, with substituted constants put back as
+/// `{"$const": name}` references.
+fn ruleset_sha(ruleset: &RuleSet) -> Result<String, EngineError> {
+    Ok(canonical::sha256_hex(ruleset.canonical_json()?.as_bytes()))
+}
+
+/// A terminal rule with an empty (always true) `and` condition.
+fn default_rule(action: Action) -> Rule {
+    Rule::new(DEFAULT_RULE_ID, Condition::And { conditions: Vec::new() }, action)
+}
+
+fn compile_regex(pattern: &str, case_insensitive: bool) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(case_insensitive).build()
+}
+
+/// Checks the syntax of every template string and `$expr` inside an
+/// outcome value.
+fn validate_outcome_value(value: &serde_json::Value) -> Result<(), String> {
+    OutcomeTemplates::default().add_value(value)
+}
+
+/// The `{{field}}` templates and `$expr`s of a ruleset's outcomes and reason
+/// messages, parsed once when the ruleset is loaded and looked up by their
+/// source when a decision is rendered.
+#[derive(Debug, Clone, Default)]
+struct OutcomeTemplates {
+    templates: HashMap<String, Template>,
+    exprs: HashMap<String, Expr>,
+}
+
+impl OutcomeTemplates {
+    fn compile(ruleset: &RuleSet) -> Result<Self, EngineError> {
+        let mut compiled = Self::default();
+        let actions = ruleset.rules.iter()
+            .flat_map(|rule| std::iter::once(&rule.then).chain(&rule.else_then).map(|action| (rule.id.as_str(), action)))
+            .chain(ruleset.default_action.as_ref().map(|action| (DEFAULT_RULE_ID, action)));
+        for (rule_id, action) in actions {
+            for (key, value) in action.outcome_values() {
+                compiled.add_value(value).map_err(|e| {
+                    EngineError::RuleValidation(format!("Rule {}: outcome '{}' is invalid: {}", rule_id, key, e))
+                })?;
+            }
+            for operation in &action.operations {
+                if let Operation::AddReason { code, message: Some(message) } = operation {
+                    compiled.add_text(message).map_err(|e| {
+                        EngineError::RuleValidation(format!("Rule {}: reason '{}' message is invalid: {}", rule_id, code, e))
+                    })?;
+                }
+            }
+        }
+        Ok(compiled)
+    }
+
+    fn add_value(&mut self, value: &serde_json::Value) -> Result<(), String> {
+        if let Some(source) = expr::outcome_expr_source(value) {
+            let source = source?;
+            if !self.exprs.contains_key(source) {
+                self.exprs.insert(source.to_string(), Expr::parse(source)?);
+            }
+            return Ok(());
+        }
+        match value {
+            serde_json::Value::String(text) => self.add_text(text),
+            serde_json::Value::Array(items) => items.iter().try_for_each(|item| self.add_value(item)),
+            serde_json::Value::Object(map) => map.values().try_for_each(|item| self.add_value(item)),
+            _ => Ok(()),
+        }
+    }
+
+    fn add_text(&mut self, text: &str) -> Result<(), String> {
+        if text.contains("{{") && !self.templates.contains_key(text) {
+            self.templates.insert(text.to_string(), Template::parse(text)?);
+        }
+        Ok(())
+    }
+}
+
+/// Whether following aliases from `from` leads back to `target`.
+fn alias_reaches(ruleset: &RuleSet, from: &str, target: &str) -> bool {
+    let mut pending = vec![from];
+    let mut seen = std::collections::HashSet::new();
+    while let Some(field) = pending.pop() {
+        if field == target {
+            return true;
+        }
+        if seen.insert(field) {
+            pending.extend(ruleset.aliases.get(field).into_iter().flatten().map(String::as_str));
+        }
+    }
+    false
+}
+
+fn default_one() -> usize {
+    1
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+/// What a matching rule produces. `outcome` is applied first, then each of
+/// `operations` in order, so an operation can override an outcome key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Action {
+    #[serde(default)]
+    pub outcome: HashMap<String, serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub operations: Vec<Operation>,
+    /// Payload field paths copied verbatim into [`Decision::captured`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capture: Vec<String>,
+}
+
+impl Action {
+    /// Every outcome value the action can write, from both `outcome` and
+    /// `set_outcome` operations.
+    pub fn outcome_values(&self) -> impl Iterator<Item = (&str, &serde_json::Value)> {
+        self.outcome.iter()
+            .map(|(key, value)| (key.as_str(), value))
+            .chain(self.operations.iter().filter_map(|op| match op {
+                Operation::SetOutcome { key, value } => Some((key.as_str(), value)),
+                _ => None,
+            }))
+    }
+}
+
+/// A single effect of an [`Action`], tagged by `op`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Operation {
+    /// Writes `key` into [`Decision::outcome`], replacing any earlier value.
+    SetOutcome { key: String, value: serde_json::Value },
+    /// Appends to [`Decision::tags`] unless already present.
+    AddTag { tag: String },
+    /// Sets [`Decision::severity`]; the last one applied wins.
+    SetSeverity { level: String },
+    /// Appends to [`Decision::reasons`]. The message may use `{{field}}`
+    /// templates.
+    AddReason {
+        code: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reason {
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// The result of a matching rule.
+///
+/// [`Decision::to_json`] always writes `decision_id`, `rule_id`, `outcome`,
+/// `matched_conditions`, `elapsed_us`, `elapsed_ns`, `total_elapsed_ns`,
+/// `timestamp`, `rule_sha`, `rule_content_sha`, `payload_sha` and
+/// `ruleset_version`; `captured`, `tags`, `severity`, `description`,
+/// `reasons`, `branch`, `captures`, `metadata`, `draft`,
+/// `generated_by_llm`, `prompt_sha` and `signature` only appear when set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Decision {
+    /// Time-ordered UUIDv7 identifying this decision, unique across calls
+    /// and threads. Empty in decisions recorded before it was introduced.
+    #[serde(default)]
+    pub decision_id: String,
+    pub rule_id: String,
+    pub outcome: HashMap<String, serde_json::Value>,
+    pub matched_conditions: Vec<String>,
+    /// `total_elapsed_ns` in microseconds, kept for existing consumers.
+    pub elapsed_us: u64,
+    /// Time spent evaluating the deciding rule's own condition.
+    #[serde(default)]
+    pub elapsed_ns: u64,
+    /// Time from the start of the evaluate call until the decision was made,
+    /// including the rules evaluated before this one.
+    #[serde(default)]
+    pub total_elapsed_ns: u64,
+    /// Unix seconds of the evaluation's reference time, [`EvalOptions::now`]
+    /// or else the current time.
+    pub timestamp: u64,
+    /// SHA of the whole ruleset, named before per-rule SHAs existed.
+    pub rule_sha: String,
+    /// Content SHA of the deciding rule, which only changes when that rule
+    /// does; see [`RuleSet::rule_shas`].
+    #[serde(default)]
+    pub rule_content_sha: String,
+    /// [`payload_sha`](crate::payload_sha) of the evaluated payload.
+    #[serde(default)]
+    pub payload_sha: String,
+    /// `version` of the ruleset the decision was made under.
+    #[serde(default)]
+    pub ruleset_version: String,
+    /// The ruleset metadata keys selected with
+    /// [`RuleEngine::set_decision_metadata_keys`]; absent keys are left out.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, serde_json::Value>,
+    /// Values of the rule's `capture` fields, keyed by path; null when absent.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub captured: HashMap<String, serde_json::Value>,
+    /// The rule's tags followed by any added by `add_tag` operations.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// The rule's severity, unless a `set_severity` operation replaced it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<String>,
+    /// The rule's description.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reasons: Vec<Reason>,
+    /// Which of the rule's actions produced the decision.
+    #[serde(default, skip_serializing_if = "Branch::is_then")]
+    pub branch: Branch,
+    /// Capture groups of the rule's `matches` conditions, keyed by group
+    /// name or number. Groups that did not participate are left out.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub captures: HashMap<String, String>,
+    /// Made by a draft rule, so not a production decision.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub draft: bool,
+    /// Made by a rule an LLM wrote, from the prompt with `prompt_sha`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub generated_by_llm: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_sha: Option<String>,
+    /// Hex HMAC-SHA256 of the rest of the decision, when the engine has a
+    /// key from [`RuleEngine::set_decision_signing_key`]; see
+    /// [`crate::verify_decision`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Branch {
+    #[default]
+    Then,
+    Else,
+}
+
+impl Branch {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Branch::Then => "then",
+            Branch::Else => "else",
+        }
+    }
+
+    fn is_then(&self) -> bool {
+        *self == Branch::Then
+    }
+}
+
+impl Decision {
+    /// The decision as [`canonical::canonical_json`], so equal decisions
+    /// always serialize to the same string, however their numbers were
+    /// written. Decision signatures cover these bytes.
+    pub fn to_json(&self) -> Result<String, EngineError> {
+        serde_json::to_value(self)
+            .map(|value| canonical::canonical_json(&value))
+            .map_err(|e| EngineError::Parse(ParseError::new(e.to_string())))
+    }
+
+    /// Indented JSON with object keys sorted, for people to read; numbers
+    /// are written as parsed, unlike [`Decision::to_json`].
+    pub fn to_json_pretty(&self) -> Result<String, EngineError> {
+        serde_json::to_value(self)
+            .and_then(|value| serde_json::to_string_pretty(&value))
+            .map_err(|e| EngineError::Parse(ParseError::new(e.to_string())))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, EngineError> {
+        serde_json::from_str(json)
+            .map_err(|e| EngineError::Parse(ParseError::new(format!("Decision JSON parse error: {}", e))))
+    }
+}
+
+/// Every decision made by [`RuleEngine::evaluate_collect`], merged in rule
+/// order: later outcome keys replace earlier ones, tags are kept once and
+/// reasons are concatenated.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CollectedDecision {
+    /// IDs of the contributing rules, in evaluation order.
+    pub rule_ids: Vec<String>,
+    pub outcome: HashMap<String, serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reasons: Vec<Reason>,
+    /// The terminal rule that ended evaluation, if any.
+    pub stopped_by: Option<String>,
+    /// The individual decisions, in the order of `rule_ids`.
+    pub decisions: Vec<Decision>,
+}
+
+impl CollectedDecision {
+    fn push(&mut self, decision: Decision) {
+        self.rule_ids.push(decision.rule_id.clone());
+        self.outcome.extend(decision.outcome.iter().map(|(key, value)| (key.clone(), value.clone())));
+        for tag in &decision.tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+        self.reasons.extend(decision.reasons.iter().cloned());
+        self.decisions.push(decision);
+    }
+}
+
+/// Result of [`RuleEngine::evaluate_score`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScoreResult {
+    /// Sum of the matched rules' scores.
+    pub total: f64,
+    /// `(rule_id, score)` for every matched rule, in rule order.
+    pub contributions: Vec<(String, f64)>,
+    /// IDs of the matched rules, in rule order.
+    pub matched: Vec<String>,
+    /// The label of the threshold the total reached, if the ruleset
+    /// declares thresholds.
+    pub label: Option<String>,
+}
+
+/// The decisions of one rule group, from [`RuleEngine::evaluate_group`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupResult {
+    pub group: String,
+    pub hit_policy: GroupHitPolicy,
+    /// At most one decision under [`GroupHitPolicy::FirstMatch`]; empty when
+    /// no rule of the group decided.
+    pub decisions: Vec<Decision>,
+}
+
+/// One [`GroupResult`] per declared group, in declaration order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PipelineResult {
+    pub groups: Vec<GroupResult>,
+}
+
+impl PipelineResult {
+    pub fn group(&self, name: &str) -> Option<&GroupResult> {
+        self.groups.iter().find(|result| result.group == name)
+    }
+}
+
+/// Result of [`RuleEngine::evaluate_chain`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChainResult {
+    /// The non-terminal rules that fed their outcomes back, in firing order.
+    pub steps: Vec<ChainStep>,
+    /// The terminal decision that ended the chain, if any.
+    pub decision: Option<Decision>,
+    /// The caller's payload with every step's delta applied.
+    pub payload: HashMap<String, serde_json::Value>,
+    pub passes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainStep {
+    pub rule_id: String,
+    /// Outcome keys that were new or changed the working payload.
+    pub delta: HashMap<String, serde_json::Value>,
+}
+
+/// How every rule fared against one event, from [`RuleEngine::explain`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trace {
+    /// The rule [`RuleEngine::evaluate`] would return a decision from.
+    pub winner: Option<String>,
+    /// Which of the winner's actions would apply.
+    pub branch: Option<Branch>,
+    /// Every rule, in evaluation order.
+    pub rules: Vec<RuleTrace>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleTrace {
+    pub rule_id: String,
+    pub status: RuleStatus,
+    /// The condition tree; absent for disabled rules and rules outside their
+    /// effective window.
+    pub condition: Option<ConditionTrace>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleStatus {
+    /// The condition matched and the rule decided.
+    Matched,
+    /// The condition failed and the rule's else branch decided.
+    Else,
+    NotMatched,
+    NotEffective,
+    /// Switched off with `enabled: false` or `status: disabled`; the
+    /// condition isn't evaluated.
+    Disabled,
+    /// A draft rule, outside [`RuleEngine::evaluate_including_drafts`].
+    Draft,
+    /// An earlier rule already decided. The condition is still evaluated so
+    /// the trace shows whether the rule would have matched.
+    Skipped,
+}
+
+/// One node of a condition tree with the values it was evaluated against.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConditionTrace {
+    #[serde(rename = "type")]
+    pub condition_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// The resolved field value; absent when the field is missing. Wildcard
+    /// paths show every expansion as an array.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    /// The condition's own parameters, such as `value` or `values`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<serde_json::Value>,
+    /// How the value was obtained: aliases tried, defaults, coercion.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+    pub result: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ConditionTrace>,
+}
+
+/// Result of [`RuleEngine::evaluate_detailed`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Evaluation {
+    Matched(Box<Decision>),
+    NoMatch(NoMatch),
+}
+
+/// Why no rule decided: for each rule, the first leaf condition that
+/// failed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoMatch {
+    pub rules: Vec<NearMiss>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NearMiss {
+    pub rule_id: String,
+    pub status: RuleStatus,
+    /// Absent for disabled rules and rules outside their effective window.
+    pub failed: Option<FailedCondition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedCondition {
+    #[serde(rename = "type")]
+    pub condition_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<serde_json::Value>,
+    /// The field value seen; absent when the field is missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual: Option<serde_json::Value>,
+}
+
+impl FailedCondition {
+    /// Describes `condition`, which failed against `scope`, the way
+    /// [`RuleEngine::explain`] traces it.
+    fn new(condition: &Condition, scope: Scope<'_>) -> Self {
+        let (field, expected, actual) = match condition {
+            Condition::Xor { exactly, .. } => (None, Some(serde_json::json!({ "exactly": exactly })), None),
+            Condition::AtLeast { n, .. } => (None, Some(serde_json::json!({ "n": n })), None),
+            Condition::Any { field, .. } | Condition::All { field, .. } => (Some(field.clone()), None, scope.get(field).cloned()),
+            Condition::Not { .. } | Condition::Implies { .. } => (None, None, None),
+            _ => match condition.subject() {
+                Some(Subject { field, .. }) => (Some(field.to_string()), expected_parameters(condition), subject_value(scope, field)),
+                None => (None, expected_parameters(condition), None),
+            },
+        };
+        FailedCondition { condition_type: condition.type_name().to_string(), field, expected, actual }
+    }
+}
+
+/// The value a leaf condition tests for `field`; a wildcard field gives all
+/// of its expansions as an array.
+fn subject_value(scope: Scope<'_>, field: &str) -> Option<serde_json::Value> {
+    if path::has_wildcard(field) {
+        Some(serde_json::Value::Array(scope.get_all(field).into_iter().cloned().collect()))
+    } else {
+        scope.get(field).cloned()
+    }
+}
+
+/// The parameters a leaf condition tests its field against, as traced.
+fn expected_parameters(condition: &Condition) -> Option<serde_json::Value> {
+    let mut parameters = serde_json::to_value(condition).ok()?;
+    let object = parameters.as_object_mut()?;
+    for key in ["type", "field", "default", "apply_on_null"] {
+        object.remove(key);
+    }
+    Some(parameters).filter(|p| p.as_object().is_some_and(|p| !p.is_empty()))
+}
+
+/// Options that influence a single evaluation call.
+#[derive(Debug, Clone, Default)]
+pub struct EvalOptions {
+    /// Reference time for relative-time conditions. Defaults to the current
+    /// time; set it to make evaluation reproducible in tests and replays.
+    pub now: Option<DateTime<Utc>>,
+    /// Overrides the engine's [`SelectionMode`] for this call.
+    pub selection: Option<SelectionMode>,
+    /// Also evaluate draft rules, for shadow testing.
+    pub include_drafts: bool,
+}
+
+/// Decision-table hit policy of a ruleset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HitPolicy {
+    /// The first deciding rule in the engine's selection order.
+    #[default]
+    First,
+    /// The deciding rule with the highest `priority`, which every rule must
+    /// declare. Overrides the engine's selection mode.
+    Priority,
+    /// One decision from every deciding rule, merged as in
+    /// [`CollectedDecision`]: `rule_id` names the first rule and
+    /// `matched_conditions` lists them all.
+    Collect,
+    /// Like `first`, but more than one deciding rule is an error.
+    Unique,
+}
+
+impl HitPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HitPolicy::First => "first",
+            HitPolicy::Priority => "priority",
+            HitPolicy::Collect => "collect",
+            HitPolicy::Unique => "unique",
+        }
+    }
+
+    fn is_first(&self) -> bool {
+        *self == HitPolicy::First
+    }
+}
+
+/// How the engine picks the deciding rule among those that match.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionMode {
+    /// The first matching rule in document order.
+    #[default]
+    FirstMatch,
+    /// The matching rule with the highest `priority`; ties go to the rule
+    /// that comes first in the document (rule IDs are unique, so this is a
+    /// total order).
+    HighestPriority,
+}
+
+impl SelectionMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "first_match" => Some(SelectionMode::FirstMatch),
+            "highest_priority" => Some(SelectionMode::HighestPriority),
+            _ => None,
+        }
+    }
+}
+
+/// An event ready for its ruleset's rules, with when its evaluation started
+/// and its SHA once a decision needs it.
+struct PreparedEvent<'a> {
+    ruleset: &'a RuleSet,
+    view: PayloadView<'a>,
+    sha: std::cell::OnceCell<String>,
+    start_time: Instant,
+}
+
+/// When the evaluation of an event started, and how long the condition of
+/// the rule being decided took.
+#[derive(Clone, Copy)]
+struct DecisionTiming {
+    start_time: Instant,
+    rule_elapsed: Duration,
+    /// The evaluation's reference time, which stamps the decision.
+    now: DateTime<Utc>,
+}
+
+/// Per-call state shared by every condition evaluated for one event.
+struct EvalContext {
+    now: DateTime<Utc>,
+    coerce_numeric_strings: bool,
+}
+
+impl EvalContext {
+    fn new(options: &EvalOptions, coerce_numeric_strings: bool) -> Self {
+        Self {
+            now: options.now.unwrap_or_else(Utc::now),
+            coerce_numeric_strings,
+        }
+    }
+
+    /// Reads a payload value as a number for numeric comparisons. Strings are
+    /// only accepted when coercion is enabled; surrounding whitespace is
+    /// ignored but locale formats such as "1,5" are not.
+    fn number(&self, value: Option<&serde_json::Value>) -> Option<f64> {
+        match value? {
+            serde_json::Value::Number(num) => num.as_f64(),
+            serde_json::Value::String(text) if self.coerce_numeric_strings => {
+                text.trim().parse::<f64>().ok().filter(|n| n.is_finite())
+            },
+            _ => None,
+        }
+    }
+}
+
+/// An event as handed to the engine: a map of top-level fields, or any JSON
+/// value.
+#[derive(Clone, Copy)]
+enum Document<'a> {
+    Fields(&'a HashMap<String, serde_json::Value>),
+    Value(&'a serde_json::Value),
+}
+
+impl Document<'_> {
+    /// [`canonical::payload_sha`] of the document as given, without
+    /// computed fields.
+    fn sha(&self) -> String {
+        match self {
+            Document::Fields(fields) => canonical::fields_sha(fields.iter()),
+            Document::Value(value) => canonical::payload_sha(value),
+        }
+    }
+}
+
+/// The event as rules see it: its own fields overlaid with the ruleset's
+/// computed fields. An object document behaves exactly like a field map;
+/// for an array or scalar document, paths start at the document itself
+/// (`""`, `[0].sku`), and computed fields are only reachable by name.
+struct PayloadView<'a> {
+    document: Document<'a>,
+    computed: HashMap<String, serde_json::Value>,
+    aliases: &'a HashMap<String, Vec<String>>,
+}
+
+impl PayloadView<'_> {
+    fn resolve(&self, field: &str) -> Option<&serde_json::Value> {
+        match self.document {
+            Document::Value(value) if field.is_empty() || !value.is_object() => {
+                path::resolve_in(value, field).or_else(|| path::resolve(field, |key| self.computed.get(key)))
+            },
+            _ => path::resolve(field, |key| self.root(key)),
+        }
+    }
+
+    fn resolve_all(&self, field: &str) -> Vec<&serde_json::Value> {
+        match self.document {
+            Document::Value(value) if !value.is_object() => path::resolve_all_in(value, field),
+            _ => path::resolve_all(field, |key| self.root(key), || self.root_values()),
+        }
+    }
+
+    fn fields(&self) -> Box<dyn Iterator<Item = (&String, &serde_json::Value)> + '_> {
+        match self.document {
+            Document::Fields(fields) => Box::new(fields.iter()),
+            Document::Value(serde_json::Value::Object(fields)) => Box::new(fields.iter()),
+            Document::Value(_) => Box::new(std::iter::empty()),
+        }
+    }
+
+    fn root(&self, key: &str) -> Option<&serde_json::Value> {
+        self.computed.get(key).or_else(|| match self.document {
+            Document::Fields(fields) => fields.get(key),
+            Document::Value(value) => value.as_object()?.get(key),
+        })
+    }
+
+    fn root_values(&self) -> Vec<&serde_json::Value> {
+        self.fields()
+            .filter(|(key, _)| !self.computed.contains_key(*key))
+            .map(|(_, value)| value)
+            .chain(self.computed.values())
+            .collect()
+    }
+}
+
+/// The data a condition is evaluated against: the top-level payload, or a
+/// single array element inside an `any`/`all` quantifier. `Pinned` fixes a
+/// field to one wildcard expansion, or to its default, while a leaf is
+/// evaluated. `Captures` adds regex captures as `$captures.<group>` for
+/// rendering a decision.
+#[derive(Clone, Copy)]
+enum Scope<'a> {
+    Payload(&'a PayloadView<'a>),
+    Element(&'a serde_json::Value),
+    Pinned {
+        base: &'a Scope<'a>,
+        field: &'a str,
+        value: &'a serde_json::Value,
+    },
+    Captures {
+        base: &'a Scope<'a>,
+        captures: &'a HashMap<String, serde_json::Value>,
+    },
+}
+
+impl<'a> Scope<'a> {
+    fn get(&self, field: &str) -> Option<&'a serde_json::Value> {
+        match self {
+            Scope::Payload(view) => view.resolve(field).or_else(|| {
+                view.aliases.get(field)?.iter().find_map(|alias| view.resolve(alias))
+            }),
+            Scope::Element(element) => path::resolve_in(element, field),
+            Scope::Pinned { field: pinned, value, .. } if *pinned == field => Some(value),
+            Scope::Pinned { base, .. } => base.get(field),
+            Scope::Captures { base, captures } => match field.strip_prefix("$captures.") {
+                Some(group) => captures.get(group),
+                None => base.get(field),
+            },
+        }
+    }
+
+    /// Explains where [`Scope::get`] finds `field` when it isn't simply
+    /// present in the payload: a computed field, or the aliases tried in
+    /// order.
+    fn resolution_notes(&self, field: &str) -> Vec<String> {
+        match self {
+            Scope::Payload(view) => {
+                let mut notes = Vec::new();
+                let root = field.split(['.', '[']).next().unwrap_or_default();
+                if view.computed.contains_key(root) {
+                    notes.push(format!("'{}' is a computed field", root));
+                }
+                if let Some(aliases) = view.aliases.get(field).filter(|_| view.resolve(field).is_none()) {
+                    let mut tried = Vec::new();
+                    for alias in aliases {
+                        tried.push(format!("'{}'", alias));
+                        if view.resolve(alias).is_some() {
+                            notes.push(format!("'{}' is missing; resolved through alias '{}' (tried {})", field, alias, tried.join(", ")));
+                            return notes;
+                        }
+                    }
+                    notes.push(format!("'{}' is missing and so are its aliases {}", field, tried.join(", ")));
+                }
+                notes
+            },
+            Scope::Pinned { field: pinned, .. } if *pinned == field => Vec::new(),
+            Scope::Pinned { base, .. } | Scope::Captures { base, .. } => base.resolution_notes(field),
+            Scope::Element(_) => Vec::new(),
+        }
+    }
+
+    /// Every value a wildcard path expands to.
+    fn get_all(&self, field: &str) -> Vec<&'a serde_json::Value> {
+        match self {
+            Scope::Payload(view) => {
+                let values = view.resolve_all(field);
+                match view.aliases.get(field) {
+                    Some(alternatives) if values.is_empty() => alternatives.iter()
+                        .map(|alias| view.resolve_all(alias))
+                        .find(|values| !values.is_empty())
+                        .unwrap_or_default(),
+                    _ => values,
+                }
+            },
+            Scope::Element(element) => path::resolve_all_in(element, field),
+            Scope::Pinned { base, .. } | Scope::Captures { base, .. } => base.get_all(field),
+        }
+    }
+}
+
+/// A previously loaded ruleset, as listed by [`RuleEngine::history`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub sha: String,
+    pub version: String,
+    /// When the ruleset was loaded, in seconds since the Unix epoch.
+    pub loaded_at: u64,
+}
+
+/// Requirements an organisation places on every ruleset it loads, such as a
+/// fixed severity vocabulary. Kept apart from rulesets so that a central
+/// team can maintain it in its own YAML or JSON file and impose it with
+/// [`RuleEngine::set_validation_policy`]. The default policy requires
+/// nothing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ValidationPolicy {
+    /// Allowed rule `severity` values, compared exactly, so `High` and
+    /// `high` are different. Empty allows any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_severities: Vec<String>,
+    /// Every rule needs a `severity`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub require_severity: bool,
+    /// Every rule needs a description that isn't blank.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub require_description: bool,
+    /// The fewest tags a rule may have.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub min_tags: usize,
+    /// Keys every ruleset's `metadata` must have.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_metadata: Vec<String>,
+    /// Metadata key naming the prompt registry, required of rulesets with
+    /// LLM-generated rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_registry_key: Option<String>,
+}
+
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
+impl ValidationPolicy {
+    pub fn from_yaml(yaml: &str) -> Result<Self, EngineError> {
+        serde_yaml::from_str(yaml)
+            .map_err(|e| EngineError::Parse(ParseError::new(format!("Policy YAML parse error: {}", e))))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, EngineError> {
+        serde_json::from_str(json)
+            .map_err(|e| EngineError::Parse(ParseError::new(format!("Policy JSON parse error: {}", e))))
+    }
+
+    fn check_severity(&self, rule: &Rule) -> Result<(), EngineError> {
+        match &rule.severity {
+            None if self.require_severity => Err(EngineError::RuleValidation(format!("Rule {}: severity is required", rule.id))),
+            Some(severity) if !self.allowed_severities.is_empty() && !self.allowed_severities.contains(severity) => {
+                Err(EngineError::RuleValidation(format!(
+                    "Rule {}: severity '{}' is not one of: {}",
+                    rule.id, severity, self.allowed_severities.join(", ")
+                )))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn check_description(&self, rule: &Rule) -> Result<(), EngineError> {
+        if self.require_description && rule.description.as_deref().is_none_or(|text| text.trim().is_empty()) {
+            return Err(EngineError::RuleValidation(format!("Rule {}: description is required", rule.id)));
+        }
+        Ok(())
+    }
+
+    fn check_tags(&self, rule: &Rule) -> Result<(), EngineError> {
+        if rule.tags.len() < self.min_tags {
+            return Err(EngineError::RuleValidation(format!(
+                "Rule {}: needs at least {} tag(s), has {}",
+                rule.id, self.min_tags, rule.tags.len()
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_metadata(&self, ruleset: &RuleSet) -> Result<(), EngineError> {
+        let missing: Vec<&str> = self.required_metadata.iter()
+            .filter(|key| !ruleset.metadata.contains_key(*key))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Err(EngineError::RuleValidation(format!("Missing required metadata: {}", missing.join(", "))));
+        }
+        Ok(())
+    }
+
+    fn check_prompt_registry(&self, ruleset: &RuleSet) -> Result<(), EngineError> {
+        let Some(key) = &self.prompt_registry_key else {
+            return Ok(());
+        };
+        if !ruleset.metadata.contains_key(key) && ruleset.rules.iter().any(|rule| rule.generated_by_llm) {
+            return Err(EngineError::RuleValidation(format!(
+                "Missing required metadata: {} (the ruleset has LLM-generated rules)",
+                key
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Hard caps on the size of a ruleset, for services that load rulesets
+/// they didn't write. Conditions are counted as for
+/// [`SafetyLimits::max_conditions_per_rule`], a definition at each use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EngineLimits {
+    pub max_rules: usize,
+    pub max_total_conditions: usize,
+    /// Size of the ruleset serialized as JSON.
+    pub max_serialized_bytes: usize,
+}
+
+impl Default for EngineLimits {
+    fn default() -> Self {
+        Self {
+            max_rules: 10_000,
+            max_total_conditions: 100_000,
+            max_serialized_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+impl EngineLimits {
+    fn check_rules(&self, ruleset: &RuleSet) -> Result<(), EngineError> {
+        if ruleset.rules.len() > self.max_rules {
+            return Err(EngineError::RuleValidation(format!(
+                "Ruleset has {} rules, over the limit of {}",
+                ruleset.rules.len(), self.max_rules
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_conditions(&self, ruleset: &RuleSet) -> Result<(), EngineError> {
+        let mut total = 0;
+        for rule in &ruleset.rules {
+            let remaining = self.max_total_conditions - total;
+            total += crate::dsl::count_conditions(ruleset, &rule.when, &mut Vec::new(), remaining);
+            if total > self.max_total_conditions {
+                return Err(EngineError::RuleValidation(format!(
+                    "Ruleset has more than {} conditions, the limit",
+                    self.max_total_conditions
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_size(&self, ruleset: &RuleSet) -> Result<(), EngineError> {
+        let size = serde_json::to_vec(ruleset)
+            .map_err(|e| EngineError::RuleValidation(format!("Failed to serialize ruleset: {}", e)))?
+            .len();
+        if size > self.max_serialized_bytes {
+            return Err(EngineError::RuleValidation(format!(
+                "Ruleset is {} bytes serialized, over the limit of {}",
+                size, self.max_serialized_bytes
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct RuleEngine {
+    ruleset: Option<RuleSet>,
+    ruleset_sha: Option<String>,
+    /// [`RuleSet::rule_shas`] of the loaded ruleset.
+    rule_shas: HashMap<String, String>,
+    coerce_numeric_strings: bool,
+    /// Parsed `computed_fields` of the loaded ruleset, in dependency order.
+    computed_fields: Vec<(String, Expr)>,
+    outcome_templates: OutcomeTemplates,
+    template_placeholder: String,
+    selection_mode: SelectionMode,
+    /// Rule indices sorted by descending priority, then document order.
+    priority_order: Vec<usize>,
+    /// Indices of the rules carrying each tag, in document order.
+    rules_by_tag: HashMap<String, Vec<usize>>,
+    decision_metadata_keys: Vec<String>,
+    /// The loaded ruleset's `default_action` as an always-matching rule.
+    default_rule: Option<Rule>,
+    max_chain_passes: usize,
+    require_approval: bool,
+    policy: ValidationPolicy,
+    safety_limits: SafetyLimits,
+    limits: EngineLimits,
+    /// Shared with clones, so a [`crate::SharedRuleEngine`] reload keeps
+    /// the log.
+    audit_log: Option<Arc<AuditLog>>,
+    decision_key: Option<Vec<u8>>,
+    strict_input: bool,
+    accept_sha_prefix: bool,
+    /// When the current ruleset was loaded.
+    loaded_at: u64,
+    /// Rulesets replaced by later loads, most recent first.
+    history: VecDeque<(HistoryEntry, RuleSet)>,
+    history_depth: usize,
+}
+
+impl RuleEngine {
+    pub fn new() -> Self {
+        Self {
+            ruleset: None,
+            ruleset_sha: None,
+            rule_shas: HashMap::new(),
+            coerce_numeric_strings: false,
+            computed_fields: Vec::new(),
+            outcome_templates: OutcomeTemplates::default(),
+            template_placeholder: String::new(),
+            selection_mode: SelectionMode::FirstMatch,
+            priority_order: Vec::new(),
+            rules_by_tag: HashMap::new(),
+            decision_metadata_keys: Vec::new(),
+            default_rule: None,
+            max_chain_passes: 10,
+            require_approval: false,
+            policy: ValidationPolicy::default(),
+            safety_limits: SafetyLimits::default(),
+            limits: EngineLimits::default(),
+            audit_log: None,
+            decision_key: None,
+            strict_input: false,
+            accept_sha_prefix: false,
+            loaded_at: 0,
+            history: VecDeque::new(),
+            history_depth: 10,
+        }
+    }
+
+    /// Strict review policy: rulesets loaded afterwards are rejected unless
+    /// every active rule has `approved_by` and `approved_at`. Off by default.
+    pub fn set_require_approval(&mut self, required: bool) {
+        self.require_approval = required;
+    }
+
+    /// Rulesets loaded afterwards must satisfy `policy`; violations are
+    /// reported with the other validation errors.
+    pub fn set_validation_policy(&mut self, policy: ValidationPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn validation_policy(&self) -> &ValidationPolicy {
+        &self.policy
+    }
+
+    /// Limits on condition size for rulesets loaded afterwards.
+    pub fn set_safety_limits(&mut self, limits: SafetyLimits) {
+        self.safety_limits = limits;
+    }
+
+    pub fn get_safety_limits(&self) -> &SafetyLimits {
+        &self.safety_limits
+    }
+
+    /// Caps on ruleset size for rulesets loaded afterwards.
+    pub fn set_limits(&mut self, limits: EngineLimits) {
+        self.limits = limits;
+    }
+
+    pub fn get_limits(&self) -> &EngineLimits {
+        &self.limits
+    }
+
+    /// HMAC-SHA256 key for signing every decision, or `None` to stop
+    /// signing. Off by default.
+    pub fn set_decision_signing_key(&mut self, key: Option<&[u8]>) {
+        self.decision_key = key.map(<[u8]>::to_vec);
+    }
+
+    /// Records the last `capacity` decisions of [`RuleEngine::evaluate`]
+    /// and its tag, value and batch variants, and with `include_no_match`
+    /// also the payload hash of evaluations no rule decided. Replaces any
+    /// existing log. Off by default.
+    pub fn enable_audit_log(&mut self, capacity: usize, include_no_match: bool) {
+        self.audit_log = Some(Arc::new(AuditLog::new(capacity, include_no_match)));
+    }
+
+    pub fn disable_audit_log(&mut self) {
+        self.audit_log = None;
+    }
+
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit_log.as_deref()
+    }
+
+    /// The last `n` audit records, oldest first; empty when auditing is off.
+    pub fn recent_decisions(&self, n: usize) -> Vec<AuditRecord> {
+        self.audit_log.as_ref().map(|log| log.recent(n)).unwrap_or_default()
+    }
+
+    /// Removes and returns the audit records, oldest first.
+    pub fn drain_audit_log(&self) -> Vec<AuditRecord> {
+        self.audit_log.as_ref().map(|log| log.drain()).unwrap_or_default()
+    }
+
+    /// Writes the audit records as JSON lines; see [`AuditLog::export_jsonl`].
+    pub fn export_audit_jsonl(&self, writer: &mut impl std::io::Write) -> Result<usize, EngineError> {
+        self.audit_log.as_ref().map_or(Ok(0), |log| log.export_jsonl(writer))
+    }
+
+    /// When enabled, evaluation fails for payloads whose values don't fit
+    /// the ruleset's `input_schema`. Fields the payload lacks are not
+    /// violations. Off by default.
+    pub fn set_strict_input(&mut self, enabled: bool) {
+        self.strict_input = enabled;
+    }
+
+    /// When enabled, [`RuleEngine::load_ruleset_verified`] also accepts an
+    /// expected SHA of at least 12 hex digits that the ruleset's SHA starts
+    /// with. Off by default.
+    pub fn set_accept_sha_prefix(&mut self, enabled: bool) {
+        self.accept_sha_prefix = enabled;
+    }
+
+    /// How many replaced rulesets to keep for [`RuleEngine::rollback`]; 10
+    /// by default, 0 to keep none. Lowering it drops the oldest entries.
+    pub fn set_history_depth(&mut self, depth: usize) {
+        self.history_depth = depth;
+        self.history.truncate(depth);
+    }
+
+    /// Ruleset metadata keys (e.g. `policy_name`) copied into every
+    /// [`Decision::metadata`]. None by default.
+    pub fn set_decision_metadata_keys(&mut self, keys: Vec<String>) {
+        self.decision_metadata_keys = keys;
+    }
+
+    /// Upper bound on the passes of [`RuleEngine::evaluate_chain`]; 10 by
+    /// default.
+    pub fn set_max_chain_passes(&mut self, passes: usize) {
+        self.max_chain_passes = passes.max(1);
+    }
+
+    /// Default rule selection for evaluations that don't set
+    /// [`EvalOptions::selection`].
+    pub fn set_selection_mode(&mut self, mode: SelectionMode) {
+        self.selection_mode = mode;
+    }
+
+    /// When enabled, numeric comparisons also accept string payload values
+    /// such as `"42.50"`. Off by default.
+    pub fn set_coerce_numeric_strings(&mut self, enabled: bool) {
+        self.coerce_numeric_strings = enabled;
+    }
+
+    /// Text rendered for missing or null fields in outcome templates. Empty
+    /// by default; `"null"` is a common alternative.
+    pub fn set_template_placeholder(&mut self, placeholder: &str) {
+        self.template_placeholder = placeholder.to_string();
+    }
+
+    pub fn load_ruleset(&mut self, ruleset: RuleSet) -> Result<(), EngineError> {
+        self.load(ruleset, None)
+    }
+
+    /// Loads `ruleset` only if its SHA is `expected_sha`, such as the SHA
+    /// recorded when the ruleset was reviewed. On mismatch the previously
+    /// loaded ruleset stays in place.
+    pub fn load_ruleset_verified(&mut self, ruleset: RuleSet, expected_sha: &str) -> Result<(), EngineError> {
+        self.load(ruleset, Some(expected_sha))
+    }
+
+    pub fn load_ruleset_verified_yaml(&mut self, yaml_content: &str, expected_sha: &str) -> Result<(), EngineError> {
+        self.load_ruleset_verified(crate::dsl::parse_yaml(yaml_content)?, expected_sha)
+    }
+
+    pub fn load_ruleset_verified_json(&mut self, json_content: &str, expected_sha: &str) -> Result<(), EngineError> {
+        self.load_ruleset_verified(crate::dsl::parse_json(json_content)?, expected_sha)
+    }
+
+    /// Reads a `.json` file as JSON and anything else as YAML.
+    pub fn load_ruleset_verified_file(&mut self, path: impl AsRef<std::path::Path>, expected_sha: &str) -> Result<(), EngineError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| EngineError::Parse(ParseError::new(format!("Failed to read {}: {}", path.display(), e))))?;
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json")) {
+            self.load_ruleset_verified_json(&content, expected_sha)
+        } else {
+            self.load_ruleset_verified_yaml(&content, expected_sha)
+        }
+    }
+
+    /// Loads `ruleset` only if `signature` verifies against `public_key`;
+    /// see [`crate::sign_ruleset`]. On failure the previously loaded ruleset
+    /// stays in place.
+    pub fn load_ruleset_signed(&mut self, ruleset: RuleSet, signature: &[u8], public_key: &[u8]) -> Result<(), EngineError> {
+        // The signed bytes come from serializing the ruleset, which only the
+        // depth limit keeps safe; loading runs the full validation.
+        if let Some(issue) = crate::dsl::condition_depth_issues(&ruleset, &self.safety_limits).into_iter().next() {
+            return Err(EngineError::RuleValidation(issue.message));
+        }
+        crate::signing::verify_ruleset(&ruleset, signature, public_key)?;
+        self.load(ruleset, None)
+    }
+
+    fn load(&mut self, ruleset: RuleSet, expected_sha: Option<&str>) -> Result<(), EngineError> {
+        let previous = self.install(ruleset, expected_sha)?;
+        if let Some(previous) = previous {
+            self.history.push_front(previous);
+            self.history.truncate(self.history_depth);
+        }
+        Ok(())
+    }
+
+    /// Validates and installs `ruleset`, returning the one it replaced. On
+    /// error the engine is unchanged.
+    fn install(&mut self, ruleset: RuleSet, expected_sha: Option<&str>) -> Result<Option<(HistoryEntry, RuleSet)>, EngineError> {
+        // Validate ruleset
+        self.validate_ruleset(&ruleset)?;
+        
+        // Calculate SHA
+        let sha = ruleset_sha(&ruleset)?;
+        if let Some(expected) = expected_sha {
+            self.verify_sha(&sha, expected)?;
+        }
+        let rule_shas = ruleset.rule_shas()?;
+        let computed_fields = compile_computed_fields(&ruleset)?;
+        let outcome_templates = OutcomeTemplates::compile(&ruleset)?;
+
+        // Nothing below can fail, so the engine changes all at once.
+        self.rule_shas = rule_shas;
+        self.computed_fields = computed_fields;
+        self.outcome_templates = outcome_templates;
+        self.priority_order = priority_order(&ruleset);
+        self.rules_by_tag = rules_by_tag(&ruleset);
+        self.default_rule = ruleset.default_action.clone().map(default_rule);
+        let loaded_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let previous_sha = self.ruleset_sha.replace(sha);
+        let previous_loaded_at = std::mem::replace(&mut self.loaded_at, loaded_at);
+        Ok(self.ruleset.replace(ruleset).zip(previous_sha).map(|(ruleset, sha)| {
+            let entry = HistoryEntry { sha, version: ruleset.version.clone(), loaded_at: previous_loaded_at };
+            (entry, ruleset)
+        }))
+    }
+
+    /// Replaced rulesets, most recent first. Every successful load, including
+    /// [`RuleEngine::add_rule`] and [`RuleEngine::remove_rule`], adds the
+    /// ruleset it replaced; [`RuleEngine::set_rule_enabled`] does not.
+    pub fn history(&self) -> Vec<&HistoryEntry> {
+        self.history.iter().map(|(entry, _)| entry).collect()
+    }
+
+    fn verify_sha(&self, sha: &str, expected: &str) -> Result<(), EngineError> {
+        let expected_lower = expected.to_ascii_lowercase();
+        let matches = sha == expected_lower
+            || (self.accept_sha_prefix && expected.len() >= 12 && sha.starts_with(&expected_lower));
+        if !matches {
+            return Err(EngineError::RuleValidation(format!(
+                "Ruleset SHA mismatch: expected {}, computed {}",
+                expected, sha
+            )));
+        }
+        Ok(())
+    }
+
+    /// Reloads the most recently replaced ruleset. See
+    /// [`RuleEngine::rollback_to`].
+    pub fn rollback(&mut self) -> Result<(), EngineError> {
+        let sha = self.history.front()
+            .map(|(entry, _)| entry.sha.clone())
+            .ok_or_else(|| EngineError::Execution("No ruleset history to roll back to".to_string()))?;
+        self.rollback_to(&sha)
+    }
+
+    /// Reloads the replaced ruleset with SHA `sha`, validating it again. The
+    /// ruleset rolled back from, and every history entry newer than `sha`,
+    /// are dropped, so repeated rollbacks walk further back. On error the
+    /// engine and its history are unchanged.
+    pub fn rollback_to(&mut self, sha: &str) -> Result<(), EngineError> {
+        let position = self.history.iter()
+            .position(|(entry, _)| entry.sha == sha)
+            .ok_or_else(|| EngineError::Execution(format!("Unknown ruleset SHA in history: {}", sha)))?;
+        self.install(self.history[position].1.clone(), None)?;
+        self.history.drain(..=position);
+        Ok(())
+    }
+
+    /// Switches a loaded rule on or off without reloading. The change is
+    /// part of the ruleset, so the SHA is recomputed.
+    pub fn set_rule_enabled(&mut self, id: &str, enabled: bool) -> Result<(), EngineError> {
+        let ruleset = self.ruleset.as_mut()
+            .ok_or_else(|| EngineError::Execution("No ruleset loaded".to_string()))?;
+        let rule = ruleset.rules.iter_mut()
+            .find(|rule| rule.id == id)
+            .ok_or_else(|| EngineError::Execution(format!("Unknown rule: {}", id)))?;
+        rule.enabled = enabled;
+        self.ruleset_sha = Some(ruleset_sha(ruleset)?);
+        self.rule_shas = ruleset.rule_shas()?;
+        Ok(())
+    }
+
+    pub fn get_ruleset_sha(&self) -> Option<&String> {
+        self.ruleset_sha.as_ref()
+    }
+
+    /// The loaded rule's content SHA; see [`RuleSet::rule_shas`].
+    pub fn get_rule_sha(&self, id: &str) -> Option<&String> {
+        self.rule_shas.get(id)
+    }
+
+    /// `(rule ID, content SHA)` for [`RuleEngine::list_rules`], in the same
+    /// order.
+    pub fn list_rule_shas(&self) -> Vec<(&str, &str)> {
+        self.list_rules().into_iter()
+            .map(|rule| (rule.id.as_str(), self.rule_shas.get(&rule.id).map_or("", String::as_str)))
+            .collect()
+    }
+
+    pub fn get_ruleset(&self) -> Option<&RuleSet> {
+        self.ruleset.as_ref()
+    }
+
+    pub fn get_rule(&self, id: &str) -> Option<&Rule> {
+        self.ruleset.as_ref()?.rules.iter().find(|rule| rule.id == id)
+    }
+
+    /// The loaded rules in evaluation order: document order, or priority
+    /// order under [`SelectionMode::HighestPriority`] or the `priority` hit
+    /// policy. Disabled rules are included.
+    pub fn list_rules(&self) -> Vec<&Rule> {
+        self.ruleset.as_ref()
+            .map(|ruleset| self.rule_order(ruleset, &EvalOptions::default()).map(|(_, rule)| rule).collect())
+            .unwrap_or_default()
+    }
+
+    /// The loaded rules marked `generated_by_llm`, in document order.
+    pub fn llm_generated_rules(&self) -> Vec<&Rule> {
+        self.ruleset.as_ref()
+            .map(|ruleset| ruleset.rules.iter().filter(|rule| rule.generated_by_llm).collect())
+            .unwrap_or_default()
+    }
+
+    /// IDs of [`RuleEngine::list_rules`], in the same order.
+    pub fn list_rule_ids(&self) -> Vec<&str> {
+        self.list_rules().into_iter().map(|rule| rule.id.as_str()).collect()
+    }
+
+    pub fn ruleset_version(&self) -> Option<&str> {
+        self.ruleset.as_ref().map(|ruleset| ruleset.version.as_str())
+    }
+
+    pub fn metadata(&self) -> Option<&HashMap<String, serde_json::Value>> {
+        self.ruleset.as_ref().map(|ruleset| &ruleset.metadata)
+    }
+
+    /// Inserts `rule` at `position` in the loaded ruleset, or appends it, and
+    /// reloads: the result is validated like a loaded ruleset (including DSL
+    /// safety) and gets a new SHA. On error the engine is unchanged.
+    pub fn add_rule(&mut self, rule: Rule, position: Option<usize>) -> Result<(), EngineError> {
+        let rule = serde_json::to_value(&rule).map_err(|e| EngineError::Parse(ParseError::new(e.to_string())))?;
+        self.add_rule_value(rule, position)
+    }
+
+    /// [`RuleEngine::add_rule`] for a rule as written in a ruleset document,
+    /// where values can be `{"$const": name}` references to the loaded
+    /// ruleset's constants.
+    pub fn add_rule_value(&mut self, rule: serde_json::Value, position: Option<usize>) -> Result<(), EngineError> {
+        let ruleset = self.loaded_ruleset()?;
+        let position = position.unwrap_or(ruleset.rules.len());
+        if position > ruleset.rules.len() {
+            return Err(EngineError::RuleValidation(format!(
+                "Rule {}: position {} is past the end of the ruleset ({} rules)",
+                rule["id"].as_str().unwrap_or_default(), position, ruleset.rules.len()
+            )));
+        }
+        let mut source = ruleset.source_value()?;
+        if let Some(rules) = source["rules"].as_array_mut() {
+            rules.insert(position, rule);
+        }
+        self.load_ruleset(crate::dsl::ruleset_from_value(source)?)
+    }
+
+    /// Removes the rule `id` from the loaded ruleset and reloads, returning
+    /// the removed rule.
+    pub fn remove_rule(&mut self, id: &str) -> Result<Rule, EngineError> {
+        let ruleset = self.loaded_ruleset()?;
+        let position = ruleset.rules.iter()
+            .position(|rule| rule.id == id)
+            .ok_or_else(|| EngineError::Execution(format!("Unknown rule: {}", id)))?;
+        let removed = ruleset.rules[position].clone();
+        let mut source = ruleset.source_value()?;
+        if let Some(rules) = source["rules"].as_array_mut() {
+            rules.remove(position);
+        }
+        self.load_ruleset(crate::dsl::ruleset_from_value(source)?)?;
+        Ok(removed)
+    }
+
+    fn loaded_ruleset(&self) -> Result<&RuleSet, EngineError> {
+        self.ruleset.as_ref()
+            .ok_or_else(|| EngineError::Execution("No ruleset loaded".to_string()))
+    }
+
+    fn validate_action(&self, rule_id: &str, action: &Action) -> Result<(), EngineError> {
+        for (key, value) in action.outcome_values() {
+            validate_outcome_value(value).map_err(|e| {
+                EngineError::RuleValidation(format!("Rule {}: outcome '{}' is invalid: {}", rule_id, key, e))
+            })?;
+        }
+        for operation in &action.operations {
+            let (op, text) = match operation {
+                Operation::SetOutcome { key, .. } => ("set_outcome", key),
+                Operation::AddTag { tag } => ("add_tag", tag),
+                Operation::SetSeverity { level } => ("set_severity", level),
+                Operation::AddReason { code, message } => {
+                    if let Some(message) = message {
+                        validate_outcome_value(&serde_json::Value::String(message.clone())).map_err(|e| {
+                            EngineError::RuleValidation(format!("Rule {}: reason '{}' message is invalid: {}", rule_id, code, e))
+                        })?;
+                    }
+                    ("add_reason", code)
+                },
+            };
+            if text.trim().is_empty() {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: {} operation requires a non-empty value", rule_id, op
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every problem with `ruleset`, without loading it: what
+    /// [`RuleEngine::load_ruleset`] would reject, as errors, then as
+    /// warnings fields missing from the input schema and the findings of
+    /// [`crate::dsl::lint_ruleset`]. Rules are checked in order and each
+    /// check reports its first problem per rule or section. A ruleset nested
+    /// beyond the depth limit is only reported for that.
+    pub fn validate(&self, ruleset: &RuleSet) -> Vec<ValidationIssue> {
+        let mut issues = self.validation_issues(ruleset);
+        if crate::dsl::condition_depth_issues(ruleset, &self.safety_limits).is_empty() {
+            issues.extend(self.undeclared_input_fields(ruleset));
+            issues.extend(crate::dsl::lint_ruleset(ruleset));
+        }
+        issues
+    }
+
+    /// Fails with every error-severity issue, joined by `; `.
+    fn validate_ruleset(&self, ruleset: &RuleSet) -> Result<(), EngineError> {
+        let errors: Vec<String> = self.validation_issues(ruleset).into_iter()
+            .filter(|issue| issue.severity == IssueSeverity::Error)
+            .map(|issue| issue.message)
+            .collect();
+        if !errors.is_empty() {
+            return Err(EngineError::RuleValidation(errors.join("; ")));
+        }
+        Ok(())
+    }
+
+    fn validation_issues(&self, ruleset: &RuleSet) -> Vec<ValidationIssue> {
+        // The checks below recurse through conditions.
+        let too_deep = crate::dsl::condition_depth_issues(ruleset, &self.safety_limits);
+        if !too_deep.is_empty() {
+            return too_deep;
+        }
+        // An oversized ruleset is rejected before the per-rule checks run.
+        let oversized: Vec<ValidationIssue> = [
+            ("/rules", self.limits.check_rules(ruleset)),
+            ("/rules", self.limits.check_conditions(ruleset)),
+            ("", self.limits.check_size(ruleset)),
+        ]
+        .into_iter()
+        .filter_map(|(path, result)| Some(ValidationIssue::from_error(None, path.to_string(), result.err()?)))
+        .collect();
+        if !oversized.is_empty() {
+            return oversized;
+        }
+        let mut issues = Vec::new();
+        let mut check = |rule_id: Option<&str>, path: String, result: Result<(), EngineError>| {
+            if let Err(error) = result {
+                issues.push(ValidationIssue::from_error(rule_id, path, error));
+            }
+        };
+
+        let by_priority = priority_order(ruleset);
+        let mut ids = std::collections::HashSet::new();
+        for (index, rule) in ruleset.rules.iter().enumerate() {
+            let (id, path) = (Some(rule.id.as_str()), format!("/rules/{}", index));
+            if !ids.insert(&rule.id) {
+                check(id, format!("{}/id", path), Err(EngineError::RuleValidation(
+                    format!("Duplicate rule ID: {}", rule.id)
+                )));
+            }
+            if rule.id == DEFAULT_RULE_ID {
+                check(id, format!("{}/id", path), Err(EngineError::RuleValidation(format!(
+                    "Rule ID {} is reserved for the ruleset's default_action",
+                    DEFAULT_RULE_ID
+                ))));
+            }
+            check(id, path.clone(), self.validate_effective_window(rule));
+            check(id, format!("{}/approved_at", path), self.validate_approval(rule));
+            check(id, format!("{}/prompt_sha", path), self.validate_provenance(rule));
+            check(id, format!("{}/severity", path), self.policy.check_severity(rule));
+            check(id, format!("{}/description", path), self.policy.check_description(rule));
+            check(id, format!("{}/tags", path), self.policy.check_tags(rule));
+            check(id, format!("{}/when", path), self.validate_condition(ruleset, &rule.id, &rule.when));
+            check(id, format!("{}/when", path), self.validate_input_types(ruleset, &rule.id, &rule.when, "", &mut Vec::new()));
+            check(id, format!("{}/then", path), self.validate_action(&rule.id, &rule.then));
+            if let Some(else_then) = &rule.else_then {
+                check(id, format!("{}/else_then", path), self.validate_action(&rule.id, else_then));
+            }
+            check(id, format!("{}/else_then", path), self.validate_else_branch(ruleset, &by_priority, index));
+        }
+
+        let mut names: Vec<&String> = ruleset.definitions.keys().collect();
+        names.sort();
+        let mut cycle_reported = false;
+        for name in names {
+            let path = format!("/definitions/{}", crate::dsl::escape_pointer_token(name));
+            let context = format!("{} (definition)", name);
+            check(None, path.clone(), self.validate_condition(ruleset, &context, &ruleset.definitions[name]));
+            // Every definition on a cycle would report it again.
+            if !cycle_reported {
+                let cycles = self.check_definition_cycles(ruleset, &ruleset.definitions[name], &mut vec![name.as_str()]);
+                cycle_reported = cycles.is_err();
+                check(None, path, cycles);
+            }
+        }
+        if let Some(default_action) = &ruleset.default_action {
+            check(Some(DEFAULT_RULE_ID), "/default_action".to_string(), self.validate_action(DEFAULT_RULE_ID, default_action));
+        }
+        check(None, "/outcome_schema".to_string(), self.validate_outcome_schema(ruleset));
+        check(None, "/input_schema".to_string(), self.validate_input_schema(ruleset));
+        check(None, "/hit_policy".to_string(), self.validate_hit_policy(ruleset));
+        check(None, "/rules".to_string(), self.validate_approvals(ruleset));
+        check(None, "/metadata".to_string(), self.policy.check_metadata(ruleset));
+        check(None, "/metadata".to_string(), self.policy.check_prompt_registry(ruleset));
+        check(None, "/score_thresholds".to_string(), self.validate_scores(ruleset));
+        check(None, "/groups".to_string(), self.validate_groups(ruleset));
+        check(None, "/aliases".to_string(), self.validate_aliases(ruleset));
+        check(None, "/computed_fields".to_string(), compile_computed_fields(ruleset).map(|_| ()));
+        issues.extend(crate::dsl::dsl_safety_issues(ruleset, &self.safety_limits));
+        issues
+    }
+
+    /// Every alias must belong to exactly one canonical field and must not be
+    /// a canonical field itself, which rules out chains and cycles.
+    fn validate_aliases(&self, ruleset: &RuleSet) -> Result<(), EngineError> {
+        let mut canonicals: Vec<&String> = ruleset.aliases.keys().collect();
+        canonicals.sort();
+        let mut owners: HashMap<&str, &str> = HashMap::new();
+        for canonical in canonicals {
+            for alias in &ruleset.aliases[canonical] {
+                if ruleset.aliases.contains_key(alias) || alias == canonical {
+                    let problem = if alias_reaches(ruleset, alias, canonical) {
+                        "forms an alias cycle"
+                    } else {
+                        "is itself a canonical field"
+                    };
+                    return Err(EngineError::RuleValidation(format!(
+                        "Alias '{}' of '{}' {}",
+                        alias, canonical, problem
+                    )));
+                }
+                if let Some(other) = owners.insert(alias, canonical).filter(|other| other != canonical) {
+                    return Err(EngineError::RuleValidation(format!(
+                        "Alias '{}' is declared for both '{}' and '{}'",
+                        alias, other, canonical
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_definition_cycles<'a>(
+        &self,
+        ruleset: &'a RuleSet,
+        condition: &'a Condition,
+        path: &mut Vec<&'a str>,
+    ) -> Result<(), EngineError> {
+        match condition {
+            Condition::Ref { name } => {
+                if path.contains(&name.as_str()) {
+                    path.push(name);
+                    return Err(EngineError::RuleValidation(format!(
+                        "Circular reference in definitions: {}",
+                        path.join(" -> ")
+                    )));
+                }
+                if let Some(definition) = ruleset.definitions.get(name) {
+                    path.push(name);
+                    self.check_definition_cycles(ruleset, definition, path)?;
+                    path.pop();
+                }
+            },
+            Condition::And { conditions }
+            | Condition::Or { conditions }
+            | Condition::Xor { conditions, .. }
+            | Condition::AtLeast { conditions, .. } => {
+                for cond in conditions {
+                    self.check_definition_cycles(ruleset, cond, path)?;
+                }
+            },
+            Condition::Not { condition }
+            | Condition::Any { condition, .. }
+            | Condition::All { condition, .. } => {
+                self.check_definition_cycles(ruleset, condition, path)?;
+            },
+            Condition::Implies { antecedent, consequent } => {
+                self.check_definition_cycles(ruleset, antecedent, path)?;
+                self.check_definition_cycles(ruleset, consequent, path)?;
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Enum values must fit the declared type, and only arrays have items.
+    fn validate_input_schema(&self, ruleset: &RuleSet) -> Result<(), EngineError> {
+        let mut fields: Vec<(&String, &InputField)> = ruleset.input_schema.iter().collect();
+        fields.sort_by_key(|(field, _)| *field);
+        for (field, declared) in fields {
+            if declared.items.is_some() && declared.value_type != JsonType::Array {
+                return Err(EngineError::RuleValidation(format!(
+                    "Input field '{}': items requires type array, not {}",
+                    field, declared.value_type.as_str()
+                )));
+            }
+            let unrestricted = InputField { allowed: Vec::new(), ..declared.clone() };
+            for value in &declared.allowed {
+                unrestricted.check(value).map_err(|e| {
+                    EngineError::RuleValidation(format!("Input field '{}': enum value {}", field, e))
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the declared fields `condition` tests against the input
+    /// schema: the condition must suit the field's type, and the values it
+    /// compares the field with must fit the field (or, for `contains` on an
+    /// array, its elements). References are followed, so a definition is
+    /// checked in the scope of each rule that uses it.
+    fn validate_input_types<'a>(
+        &self,
+        ruleset: &'a RuleSet,
+        rule_id: &str,
+        condition: &'a Condition,
+        prefix: &str,
+        refs: &mut Vec<&'a str>,
+    ) -> Result<(), EngineError> {
+        let field = match condition {
+            Condition::Ref { name } => {
+                if let Some(definition) = ruleset.definitions.get(name).filter(|_| !refs.contains(&name.as_str())) {
+                    refs.push(name);
+                    self.validate_input_types(ruleset, rule_id, definition, prefix, refs)?;
+                    refs.pop();
+                }
+                return Ok(());
+            },
+            Condition::Any { field, condition } | Condition::All { field, condition } => {
+                let array = qualify_field(prefix, field);
+                let elements = if path::has_wildcard(field) { array.clone() } else { format!("{}[*]", array) };
+                self.validate_input_types(ruleset, rule_id, condition, &elements, refs)?;
+                array
+            },
+            _ => {
+                for child in condition.children() {
+                    self.validate_input_types(ruleset, rule_id, child, prefix, refs)?;
+                }
+                match condition.subject() {
+                    Some(subject) => qualify_field(prefix, subject.field),
+                    None => return Ok(()),
+                }
+            },
+        };
+        let Some(declared) = ruleset.input_schema.get(&field) else {
+            return Ok(());
+        };
+        if let Some(accepted) = condition.input_types() {
+            let coerced = self.coerce_numeric_strings && declared.value_type == JsonType::String && accepted == [JsonType::Number];
+            if !declared.suits(accepted) && !coerced {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: {} can't test field '{}', declared as {}",
+                    rule_id, condition.type_name(), field, declared.value_type.as_str()
+                )));
+            }
+        }
+        let compared: Vec<&serde_json::Value> = match condition {
+            Condition::Equals { value, .. }
+            | Condition::NotEquals { value, .. }
+            | Condition::DeepEquals { value, .. }
+            | Condition::Contains { value, .. } => vec![value],
+            Condition::In { values, .. }
+            | Condition::NotIn { values, .. }
+            | Condition::ContainsAny { values, .. }
+            | Condition::ContainsAll { values, .. } => values.iter().collect(),
+            _ => Vec::new(),
+        };
+        // `contains` compares array elements, or substrings of a string.
+        let contains = matches!(condition, Condition::Contains { .. } | Condition::ContainsAny { .. } | Condition::ContainsAll { .. });
+        let expected = match (contains, declared.value_type, declared.items) {
+            (false, _, _) => Some(declared.clone()),
+            (true, JsonType::Array, items) => items.map(|items| InputField { value_type: items, items: None, allowed: Vec::new(), nullable: true }),
+            (true, _, _) => Some(InputField { value_type: JsonType::String, items: None, allowed: Vec::new(), nullable: false }),
+        };
+        if let Some(expected) = expected {
+            for value in compared {
+                expected.check(value).map_err(|e| EngineError::RuleValidation(format!(
+                    "Rule {}: {} value for field '{}' {}",
+                    rule_id, condition.type_name(), field, e
+                )))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fields rules read that the input schema doesn't declare, once per
+    /// rule and field, as warnings. Computed fields, regex captures and
+    /// rulesets without an input schema are left out.
+    fn undeclared_input_fields(&self, ruleset: &RuleSet) -> Vec<ValidationIssue> {
+        if ruleset.input_schema.is_empty() {
+            return Vec::new();
+        }
+        let mut issues = Vec::new();
+        for (index, rule) in ruleset.rules.iter().enumerate() {
+            let pointer = format!("/rules/{}", index);
+            let mut reads = Vec::new();
+            ruleset.collect_referenced_fields(&rule.when, &format!("{}/when", pointer), "", &mut Vec::new(), &mut reads);
+            for (key, action) in std::iter::once(("then", &rule.then)).chain(rule.else_then.as_ref().map(|action| ("else_then", action))) {
+                action_reads(action, &format!("{}/{}", pointer, key), &mut reads);
+            }
+            let mut reported = std::collections::HashSet::new();
+            for (location, field) in reads {
+                let root = field.split(['.', '[']).next().unwrap_or_default();
+                let declared = ruleset.input_schema.contains_key(&field)
+                    || field.strip_suffix("[*]").is_some_and(|array| ruleset.input_schema.contains_key(array))
+                    || ruleset.computed_fields.contains_key(root);
+                if !declared && reported.insert(field.clone()) {
+                    issues.push(ValidationIssue {
+                        rule_id: Some(rule.id.clone()),
+                        path: location,
+                        message: format!("Rule {}: field '{}' is not declared in input_schema", rule.id, field),
+                        severity: IssueSeverity::Warning,
+                    });
+                }
+            }
+        }
+        issues
+    }
+
+    /// Checks every rule action against the ruleset's outcome schema and
+    /// reports all violations at once.
+    fn validate_outcome_schema(&self, ruleset: &RuleSet) -> Result<(), EngineError> {
+        let Some(schema) = &ruleset.outcome_schema else {
+            return Ok(());
+        };
+        let mut violations = Vec::new();
+        for rule in &ruleset.rules {
+            violations.extend(schema.check(&format!("Rule {}", rule.id), &rule.then));
+            if let Some(else_then) = &rule.else_then {
+                violations.extend(schema.check(&format!("Rule {} (else)", rule.id), else_then));
+            }
+        }
+        if let Some(default_action) = &ruleset.default_action {
+            violations.extend(schema.check("default_action", default_action));
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(EngineError::RuleValidation(format!(
+                "Outcome schema violations: {}",
+                violations.join("; ")
+            )))
+        }
+    }
+
+    fn validate_hit_policy(&self, ruleset: &RuleSet) -> Result<(), EngineError> {
+        if ruleset.hit_policy != HitPolicy::Priority {
+            return Ok(());
+        }
+        let missing: Vec<&str> = ruleset.rules.iter()
+            .filter(|rule| rule.priority.is_none())
+            .map(|rule| rule.id.as_str())
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(EngineError::RuleValidation(format!(
+                "Hit policy priority requires a priority on every rule; missing on: {}",
+                missing.join(", ")
+            )))
+        }
+    }
+
+    fn validate_groups(&self, ruleset: &RuleSet) -> Result<(), EngineError> {
+        let mut names = std::collections::HashSet::new();
+        for group in &ruleset.groups {
+            if !names.insert(group.name.as_str()) {
+                return Err(EngineError::RuleValidation(format!("Duplicate group: {}", group.name)));
+            }
+        }
+        for rule in &ruleset.rules {
+            if let Some(group) = rule.group.as_deref().filter(|group| !names.contains(group)) {
+                return Err(EngineError::RuleValidation(format!("Rule {}: unknown group '{}'", rule.id, group)));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_scores(&self, ruleset: &RuleSet) -> Result<(), EngineError> {
+        if let Some(rule) = ruleset.rules.iter().find(|rule| rule.score.is_some_and(|score| !score.is_finite())) {
+            return Err(EngineError::RuleValidation(format!("Rule {}: score must be a finite number", rule.id)));
+        }
+        let mut previous: Option<&ScoreThreshold> = None;
+        for (index, threshold) in ruleset.score_thresholds.iter().enumerate() {
+            if threshold.label.trim().is_empty() {
+                return Err(EngineError::RuleValidation(format!("Score threshold {} has an empty label", index)));
+            }
+            let min = match threshold.min {
+                None if index == 0 => continue,
+                None => {
+                    return Err(EngineError::RuleValidation(format!(
+                        "Score threshold '{}' needs a min; only the first threshold may omit it",
+                        threshold.label
+                    )));
+                },
+                Some(min) if !min.is_finite() => {
+                    return Err(EngineError::RuleValidation(format!(
+                        "Score threshold '{}' min must be a finite number",
+                        threshold.label
+                    )));
+                },
+                Some(min) => min,
+            };
+            if let Some(previous) = previous.filter(|previous| previous.min.is_some_and(|prev| prev >= min)) {
+                return Err(EngineError::RuleValidation(format!(
+                    "Score thresholds must ascend: '{}' ({}) follows '{}' ({})",
+                    threshold.label, min, previous.label, previous.min.unwrap_or_default()
+                )));
+            }
+            previous = Some(threshold);
+        }
+        Ok(())
+    }
+
+    /// A live rule with an else branch always decides, so under the `first`
+    /// and `priority` hit policies no live rule may follow it in file or
+    /// priority order, and it rules out a `default_action`, unless an
+    /// effective window lets later rules take over.
+    fn validate_else_branch(&self, ruleset: &RuleSet, by_priority: &[usize], index: usize) -> Result<(), EngineError> {
+        let rule = &ruleset.rules[index];
+        let first_match = matches!(ruleset.hit_policy, HitPolicy::First | HitPolicy::Priority);
+        if rule.else_then.is_none() || !rule.is_live(false) || !first_match
+            || rule.effective_from.is_some() || rule.effective_until.is_some()
+        {
+            return Ok(());
+        }
+        let priority_position = by_priority.iter().position(|&other| other == index).unwrap_or_default();
+        let shadowed = ruleset.rules[index + 1..].iter()
+            .chain(by_priority[priority_position + 1..].iter().map(|&other| &ruleset.rules[other]))
+            .find(|other| other.is_live(false));
+        if let Some(shadowed) = shadowed {
+            return Err(EngineError::RuleValidation(format!(
+                "Rule {}: else_then makes rule {} unreachable; a rule with an else branch must be last \
+                 unless it has an effective window",
+                rule.id, shadowed.id
+            )));
+        }
+        if ruleset.default_action.is_some() {
+            return Err(EngineError::RuleValidation(format!(
+                "Rule {}: else_then makes the default_action unreachable; use one or the other",
+                rule.id
+            )));
+        }
+        Ok(())
+    }
+
+    fn validate_approval(&self, rule: &Rule) -> Result<(), EngineError> {
+        match rule.approved_at.as_deref() {
+            Some(value) if temporal::parse_timestamp_str(value).is_none() => Err(EngineError::RuleValidation(format!(
+                "Rule {}: invalid approved_at '{}'",
+                rule.id, value
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// An LLM-generated rule must name the prompt it came from, and any
+    /// `prompt_sha` must be a SHA-256 in hex.
+    fn validate_provenance(&self, rule: &Rule) -> Result<(), EngineError> {
+        match rule.prompt_sha.as_deref() {
+            None if rule.generated_by_llm => Err(EngineError::RuleValidation(format!(
+                "Rule {}: generated_by_llm requires a prompt_sha",
+                rule.id
+            ))),
+            Some(sha) if sha.len() != 64 || !matchers::is_hex(sha) => Err(EngineError::RuleValidation(format!(
+                "Rule {}: prompt_sha '{}' is not a 64-character hex SHA-256",
+                rule.id, sha
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Under [`RuleEngine::set_require_approval`], every active rule must
+    /// record who approved it and when.
+    fn validate_approvals(&self, ruleset: &RuleSet) -> Result<(), EngineError> {
+        if !self.require_approval {
+            return Ok(());
+        }
+        let unapproved: Vec<&str> = ruleset.rules.iter()
+            .filter(|rule| rule.status == RuleLifecycle::Active && (rule.approved_by.is_none() || rule.approved_at.is_none()))
+            .map(|rule| rule.id.as_str())
+            .collect();
+        if !unapproved.is_empty() {
+            return Err(EngineError::RuleValidation(format!(
+                "Active rules require approved_by and approved_at; missing on: {}",
+                unapproved.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    fn validate_effective_window(&self, rule: &Rule) -> Result<(), EngineError> {
+        let parse = |bound: &Option<String>| -> Result<Option<DateTime<Utc>>, EngineError> {
+            bound.as_deref()
+                .map(|value| temporal::parse_timestamp_str(value).ok_or_else(|| {
+                    EngineError::RuleValidation(format!(
+                        "Rule {}: invalid effective date '{}'",
+                        rule.id, value
+                    ))
+                }))
+                .transpose()
+        };
+        if let (Some(from), Some(until)) = (parse(&rule.effective_from)?, parse(&rule.effective_until)?) {
+            if from > until {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: effective_from {} is after effective_until {}",
+                    rule.id, from, until
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_timezone(&self, rule_id: &str, timezone: Option<&str>) -> Result<(), EngineError> {
+        if temporal::parse_timezone(timezone).is_none() {
+            return Err(EngineError::RuleValidation(format!(
+                "Rule {}: unknown timezone '{}'",
+                rule_id, timezone.unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+
+    fn validate_condition(&self, ruleset: &RuleSet, rule_id: &str, condition: &Condition) -> Result<(), EngineError> {
+        if let Some(Subject { field, default: FieldDefault { value: None, apply_on_null: true } }) = condition.subject() {
+            return Err(EngineError::RuleValidation(format!(
+                "Rule {}: apply_on_null on '{}' has no default to apply",
+                rule_id, field
+            )));
+        }
+        if let Some(Subject { field, default: FieldDefault { value: Some(_), .. } }) = condition.subject() {
+            if path::has_wildcard(field) {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: {} on wildcard path '{}' can't take a default",
+                    rule_id, condition.type_name(), field
+                )));
+            }
+            let missing_is_match = match condition {
+                Condition::Exists { .. } | Condition::IsNull { .. } => {
+                    return Err(EngineError::RuleValidation(format!(
+                        "Rule {}: {} on '{}' tests presence and can't take a default",
+                        rule_id, condition.type_name(), field
+                    )));
+                },
+                Condition::NotEquals { missing_is_match, .. }
+                | Condition::IsEmpty { missing_is_match, .. }
+                | Condition::NotIn { missing_is_match, .. } => *missing_is_match,
+                _ => false,
+            };
+            if missing_is_match {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: {} on '{}' can't combine a default with missing_is_match",
+                    rule_id, condition.type_name(), field
+                )));
+            }
+        }
+        match condition {
+            Condition::And { conditions }
+            | Condition::Or { conditions } => {
+                for cond in conditions {
+                    self.validate_condition(ruleset, rule_id, cond)?;
+                }
+            },
+            Condition::Xor { conditions, exactly } => {
+                if *exactly > conditions.len() {
+                    return Err(EngineError::RuleValidation(format!(
+                        "Rule {}: xor requires exactly <= {}, got exactly = {}",
+                        rule_id, conditions.len(), exactly
+                    )));
+                }
+                for cond in conditions {
+                    self.validate_condition(ruleset, rule_id, cond)?;
+                }
+            },
+            Condition::Implies { antecedent, consequent } => {
+                self.validate_condition(ruleset, rule_id, antecedent)?;
+                self.validate_condition(ruleset, rule_id, consequent)?;
+            },
+            Condition::AtLeast { n, conditions } => {
+                if *n == 0 || *n > conditions.len() {
+                    return Err(EngineError::RuleValidation(format!(
+                        "Rule {}: at_least requires 1 <= n <= {}, got n = {}",
+                        rule_id, conditions.len(), n
+                    )));
+                }
+                for cond in conditions {
+                    self.validate_condition(ruleset, rule_id, cond)?;
+                }
+            },
+            Condition::Not { condition }
+            | Condition::Any { condition, .. }
+            | Condition::All { condition, .. } => {
+                self.validate_condition(ruleset, rule_id, condition)?;
+            },
+            Condition::Between { field, min, max, .. } if min > max => {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: between on '{}' has min {} greater than max {}",
+                    rule_id, field, min, max
+                )));
+            },
+            Condition::Ref { name } if !ruleset.definitions.contains_key(name) => {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: reference to unknown definition '{}'",
+                    rule_id, name
+                )));
+            },
+            Condition::DateBefore { field, value, .. } | Condition::DateAfter { field, value, .. }
+                if temporal::parse_timestamp_str(value).is_none() =>
+            {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: date comparison on '{}' has invalid date '{}'",
+                    rule_id, field, value
+                )));
+            },
+            Condition::WithinLast { field, duration, .. } if temporal::parse_duration(duration).is_none() => {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: within_last on '{}' has invalid duration '{}'",
+                    rule_id, field, duration
+                )));
+            },
+            Condition::DayOfWeek { days, timezone, .. } => {
+                if let Some(day) = days.iter().find(|day| temporal::parse_weekday(day).is_none()) {
+                    return Err(EngineError::RuleValidation(format!(
+                        "Rule {}: day_of_week has unknown day '{}'",
+                        rule_id, day
+                    )));
+                }
+                self.validate_timezone(rule_id, timezone.as_deref())?;
+            },
+            Condition::TimeOfDay { start, end, timezone, .. } => {
+                for time in [start, end] {
+                    if temporal::parse_clock_time(time).is_none() {
+                        return Err(EngineError::RuleValidation(format!(
+                            "Rule {}: time_of_day has invalid time '{}', expected HH:MM",
+                            rule_id, time
+                        )));
+                    }
+                }
+                self.validate_timezone(rule_id, timezone.as_deref())?;
+            },
+            Condition::IpInCidr { field, cidrs, networks, .. } => {
+                let mut parsed = Vec::with_capacity(cidrs.len());
+                for cidr in cidrs {
+                    parsed.push(Cidr::parse(cidr).ok_or_else(|| {
+                        EngineError::RuleValidation(format!(
+                            "Rule {}: ip_in_cidr on '{}' has malformed range '{}'",
+                            rule_id, field, cidr
+                        ))
+                    })?);
+                }
+                let _ = networks.set(parsed);
+            },
+            Condition::In { values, compiled, .. } | Condition::NotIn { values, compiled, .. } => {
+                let _ = compiled.set(ValueSet::new(values));
+            },
+            Condition::Semver { field, operator, value, .. } => {
+                if CompareOp::from_name(operator).is_none() {
+                    return Err(EngineError::RuleValidation(format!(
+                        "Rule {}: semver on '{}' has unknown operator '{}'",
+                        rule_id, field, operator
+                    )));
+                }
+                if SemVer::parse(value).is_none() {
+                    return Err(EngineError::RuleValidation(format!(
+                        "Rule {}: semver on '{}' has invalid version '{}'",
+                        rule_id, field, value
+                    )));
+                }
+            },
+            Condition::MatchesGlob { field, pattern, case_insensitive, path_separators, compiled, .. } => {
+                let glob = Glob::compile(pattern, *case_insensitive, *path_separators).map_err(|e| {
+                    EngineError::RuleValidation(format!(
+                        "Rule {}: matches_glob on '{}' has invalid pattern '{}': {}",
+                        rule_id, field, pattern, e
+                    ))
+                })?;
+                let _ = compiled.set(Some(glob));
+            },
+            Condition::Matches { field, pattern, case_insensitive, compiled, .. } => {
+                let regex = compile_regex(pattern, *case_insensitive).map_err(|e| {
+                    EngineError::RuleValidation(format!(
+                        "Rule {}: matches on '{}' has invalid pattern '{}': {}",
+                        rule_id, field, pattern, e
+                    ))
+                })?;
+                let _ = compiled.set(Some(regex));
+            },
+            Condition::Modulo { field, divisor: 0, .. } => {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: modulo on '{}' has a divisor of zero",
+                    rule_id, field
+                )));
+            },
+            Condition::IsFormat { field, format, .. } if StringFormat::from_name(format).is_none() => {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: is_format on '{}' has unknown format '{}'",
+                    rule_id, field, format
+                )));
+            },
+            Condition::Percentage { field, percent, .. } if !(0.0..=100.0).contains(percent) => {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: percentage on '{}' must be between 0 and 100, got {}",
+                    rule_id, field, percent
+                )));
+            },
+            Condition::Bitmask { field, mask, .. } if mask.as_u64().is_none() => {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: bitmask on '{}' has mask {} that does not fit in an unsigned 64-bit integer",
+                    rule_id, field, mask
+                )));
+            },
+            Condition::GeoWithin { field, other_field, lat, lon, max_km, .. } => {
+                let target_ok = match (other_field, lat, lon) {
+                    (Some(_), None, None) => true,
+                    (None, Some(lat), Some(lon)) => matchers::valid_coordinates(*lat, *lon),
+                    _ => false,
+                };
+                if !target_ok || max_km.is_nan() || *max_km < 0.0 {
+                    return Err(EngineError::RuleValidation(format!(
+                        "Rule {}: geo_within on '{}' needs either other_field or a valid lat/lon pair, and a non-negative max_km",
+                        rule_id, field
+                    )));
+                }
+            },
+            Condition::Lookup { table, .. } if !ruleset.metadata.get(table).is_some_and(|t| t.is_object()) => {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: lookup references unknown table '{}'",
+                    rule_id, table
+                )));
+            },
+            Condition::Lookup { table, equals, one_of, .. } if equals.is_some() == one_of.is_some() => {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: lookup in '{}' needs exactly one of equals or in",
+                    rule_id, table
+                )));
+            },
+            Condition::ApproxEquals { field, epsilon, .. } if epsilon.is_nan() || *epsilon < 0.0 => {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: approx_equals on '{}' needs a non-negative epsilon, got {}",
+                    rule_id, field, epsilon
+                )));
+            },
+            Condition::Length { field, operator, .. } if CompareOp::from_name(operator).is_none() => {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: length on '{}' has unknown operator '{}'",
+                    rule_id, field, operator
+                )));
+            },
+            Condition::Aggregate { field, operator, .. } if CompareOp::from_name(operator).is_none() => {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: aggregate on '{}' has unknown operator '{}'",
+                    rule_id, field, operator
+                )));
+            },
+            Condition::StringLength { field, operator, .. } if CompareOp::from_name(operator).is_none() => {
+                return Err(EngineError::RuleValidation(format!(
+                    "Rule {}: string_length on '{}' has unknown operator '{}'",
+                    rule_id, field, operator
+                )));
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn evaluate(&self, payload: &HashMap<String, serde_json::Value>) -> Result<Option<Decision>, EngineError> {
+        self.evaluate_with_options(payload, &EvalOptions::default())
+    }
+
+    pub fn evaluate_with_options(
+        &self,
+        payload: &HashMap<String, serde_json::Value>,
+        options: &EvalOptions,
+    ) -> Result<Option<Decision>, EngineError> {
+        self.evaluate_document(Document::Fields(payload), options, None, None)
+    }
+
+    /// Shadow evaluation: like [`RuleEngine::evaluate`], but draft rules run
+    /// in their place in the order, and their decisions have `draft` set.
+    pub fn evaluate_including_drafts(&self, payload: &HashMap<String, serde_json::Value>) -> Result<Option<Decision>, EngineError> {
+        let options = EvalOptions { include_drafts: true, ..Default::default() };
+        self.evaluate_with_options(payload, &options)
+    }
+
+    /// Evaluates a JSON document directly. Objects behave like the field map
+    /// taken by [`RuleEngine::evaluate`]; arrays and scalars can be tested
+    /// through the empty field (the document itself) or paths such as
+    /// `[0].sku`.
+    pub fn evaluate_value(&self, payload: &serde_json::Value) -> Result<Option<Decision>, EngineError> {
+        self.evaluate_value_with_options(payload, &EvalOptions::default())
+    }
+
+    pub fn evaluate_value_with_options(
+        &self,
+        payload: &serde_json::Value,
+        options: &EvalOptions,
+    ) -> Result<Option<Decision>, EngineError> {
+        self.evaluate_document(Document::Value(payload), options, None, None)
+    }
+
+    fn evaluate_document(
+        &self,
+        document: Document<'_>,
+        options: &EvalOptions,
+        selected: Option<&[bool]>,
+        misses: Option<&mut Vec<NearMiss>>,
+    ) -> Result<Option<Decision>, EngineError> {
+        // Decisions and audit records without one share the reference time.
+        let now = options.now.unwrap_or_else(Utc::now);
+        let options = EvalOptions { now: Some(now), ..options.clone() };
+        let decision = self.select_decision(document, &options, selected, misses)?;
+        if let Some(log) = &self.audit_log {
+            if decision.is_some() || log.includes_no_match() {
+                log.record(AuditRecord {
+                    timestamp: u64::try_from(now.timestamp()).unwrap_or_default(),
+                    ruleset_sha: self.ruleset_sha.clone().unwrap_or_default(),
+                    payload_sha: decision.as_ref().map_or_else(|| document.sha(), |decision| decision.payload_sha.clone()),
+                    decision: decision.clone(),
+                });
+            }
+        }
+        Ok(decision)
+    }
+
+    fn select_decision(
+        &self,
+        document: Document<'_>,
+        options: &EvalOptions,
+        selected: Option<&[bool]>,
+        misses: Option<&mut Vec<NearMiss>>,
+    ) -> Result<Option<Decision>, EngineError> {
+        let hit_policy = self.ruleset.as_ref().map_or(HitPolicy::First, |ruleset| ruleset.hit_policy);
+        let mut decisions = Vec::new();
+        self.run_rules(document, options, selected, misses, |_, decision| {
+            decisions.push(decision);
+            matches!(hit_policy, HitPolicy::Collect | HitPolicy::Unique)
+        })?;
+        match hit_policy {
+            HitPolicy::Unique if decisions.len() > 1 => {
+                let ids: Vec<&str> = decisions.iter().map(|d| d.rule_id.as_str()).collect();
+                Err(EngineError::Execution(format!(
+                    "Hit policy unique violated: rules {} all match",
+                    ids.join(", ")
+                )))
+            },
+            HitPolicy::Collect if decisions.len() > 1 => {
+                let mut collected = CollectedDecision::default();
+                for decision in decisions {
+                    collected.push(decision);
+                }
+                let mut merged = collected.decisions.swap_remove(0);
+                merged.outcome = collected.outcome;
+                merged.tags = collected.tags;
+                merged.reasons = collected.reasons;
+                merged.matched_conditions = collected.rule_ids;
+                self.seal(&mut merged)?;
+                Ok(Some(merged))
+            },
+            _ => Ok(decisions.into_iter().next()),
+        }
+    }
+
+    /// Evaluates every effective rule and returns a decision for each one
+    /// that decides, in selection order, so the first is the decision
+    /// [`RuleEngine::evaluate`] returns.
+    pub fn evaluate_all(&self, payload: &HashMap<String, serde_json::Value>) -> Result<Vec<Decision>, EngineError> {
+        self.evaluate_all_with_options(payload, &EvalOptions::default())
+    }
+
+    pub fn evaluate_all_with_options(
+        &self,
+        payload: &HashMap<String, serde_json::Value>,
+        options: &EvalOptions,
+    ) -> Result<Vec<Decision>, EngineError> {
+        let mut decisions = Vec::new();
+        self.run_rules(Document::Fields(payload), options, None, None, |_, decision| {
+            decisions.push(decision);
+            true
+        })?;
+        Ok(decisions)
+    }
+
+    /// Evaluates every rule in order, collecting the decisions of matching
+    /// rules until a `terminal` one matches. With only terminal rules this
+    /// yields the same single decision as [`RuleEngine::evaluate`].
+    pub fn evaluate_collect(&self, payload: &HashMap<String, serde_json::Value>) -> Result<CollectedDecision, EngineError> {
+        self.evaluate_collect_with_options(payload, &EvalOptions::default())
+    }
+
+    pub fn evaluate_collect_with_options(
+        &self,
+        payload: &HashMap<String, serde_json::Value>,
+        options: &EvalOptions,
+    ) -> Result<CollectedDecision, EngineError> {
+        let mut collected = CollectedDecision::default();
+        self.run_rules(Document::Fields(payload), options, None, None, |rule, decision| {
+            collected.push(decision);
+            if rule.terminal {
+                collected.stopped_by = Some(rule.id.clone());
+            }
+            !rule.terminal
+        })?;
+        Ok(collected)
+    }
+
+    /// Forward chaining over a working copy of the payload. Each pass runs
+    /// the rules in order: a terminal rule ends the chain with its decision,
+    /// while the first non-terminal rule whose outcome adds or changes
+    /// payload fields merges them and starts the next pass. The chain also
+    /// ends once a pass changes nothing, and fails if it is still changing
+    /// after the configured maximum number of passes.
+    pub fn evaluate_chain(&self, payload: &HashMap<String, serde_json::Value>) -> Result<ChainResult, EngineError> {
+        self.evaluate_chain_with_options(payload, &EvalOptions::default())
+    }
+
+    /// Like [`RuleEngine::evaluate_chain`]; every pass runs at the same
+    /// reference time.
+    pub fn evaluate_chain_with_options(
+        &self,
+        payload: &HashMap<String, serde_json::Value>,
+        options: &EvalOptions,
+    ) -> Result<ChainResult, EngineError> {
+        let options = EvalOptions { now: Some(options.now.unwrap_or_else(Utc::now)), ..options.clone() };
+        let mut result = ChainResult { payload: payload.clone(), ..Default::default() };
+        while result.passes < self.max_chain_passes {
+            result.passes += 1;
+            let mut delta = HashMap::new();
+            let working = &result.payload;
+            let mut fired = None;
+            self.run_rules(Document::Fields(working), &options, None, None, |rule, decision| {
+                if rule.terminal {
+                    fired = Some(decision);
+                    return false;
+                }
+                delta = decision.outcome.into_iter()
+                    .filter(|(key, value)| working.get(key) != Some(value))
+                    .collect();
+                if delta.is_empty() {
+                    return true;
+                }
+                result.steps.push(ChainStep { rule_id: rule.id.clone(), delta: delta.clone() });
+                false
+            })?;
+            if fired.is_some() || delta.is_empty() {
+                result.decision = fired;
+                return Ok(result);
+            }
+            result.payload.extend(delta);
+        }
+        let rule_count = self.ruleset.as_ref().map_or(0, |ruleset| ruleset.rules.len());
+        let mut cycling: Vec<&str> = Vec::new();
+        for step in result.steps.iter().rev().take(rule_count.max(1)) {
+            if !cycling.contains(&step.rule_id.as_str()) {
+                cycling.insert(0, &step.rule_id);
+            }
+        }
+        Err(EngineError::Execution(format!(
+            "Forward chaining still changing the payload after {} passes; rules firing: {}",
+            self.max_chain_passes,
+            cycling.join(", ")
+        )))
+    }
+
+    /// Evaluates only the rules of `group`, applying the group's hit policy.
+    pub fn evaluate_group(&self, group: &str, payload: &HashMap<String, serde_json::Value>) -> Result<GroupResult, EngineError> {
+        self.evaluate_group_with_options(group, payload, &EvalOptions::default())
+    }
+
+    pub fn evaluate_group_with_options(
+        &self,
+        group: &str,
+        payload: &HashMap<String, serde_json::Value>,
+        options: &EvalOptions,
+    ) -> Result<GroupResult, EngineError> {
+        let ruleset = self.ruleset.as_ref()
+            .ok_or_else(|| EngineError::Execution("No ruleset loaded".to_string()))?;
+        let group = ruleset.groups.iter()
+            .find(|candidate| candidate.name == group)
+            .ok_or_else(|| EngineError::Execution(format!("Unknown group: {}", group)))?;
+        self.run_group(group, &self.prepare(Document::Fields(payload))?, options)
+    }
+
+    /// Runs every declared group in order. Each group sees the original
+    /// payload, so a group that decides nothing doesn't stop later ones.
+    pub fn evaluate_pipeline(&self, payload: &HashMap<String, serde_json::Value>) -> Result<PipelineResult, EngineError> {
+        self.evaluate_pipeline_with_options(payload, &EvalOptions::default())
+    }
+
+    /// Like [`RuleEngine::evaluate_pipeline`]; every group runs at the same
+    /// reference time.
+    pub fn evaluate_pipeline_with_options(
+        &self,
+        payload: &HashMap<String, serde_json::Value>,
+        options: &EvalOptions,
+    ) -> Result<PipelineResult, EngineError> {
+        let options = EvalOptions { now: Some(options.now.unwrap_or_else(Utc::now)), ..options.clone() };
+        let event = self.prepare(Document::Fields(payload))?;
+        let groups = event.ruleset.groups.iter()
+            .map(|group| self.run_group(group, &event, &options))
+            .collect::<Result<_, _>>()?;
+        Ok(PipelineResult { groups })
+    }
+
+    fn run_group(&self, group: &RuleGroup, event: &PreparedEvent<'_>, options: &EvalOptions) -> Result<GroupResult, EngineError> {
+        let selected: Vec<bool> = event.ruleset.rules.iter().map(|rule| rule.group.as_deref() == Some(group.name.as_str())).collect();
+        let mut decisions = Vec::new();
+        self.run_prepared(event, options, Some(&selected), None, |_, decision| {
+            decisions.push(decision);
+            group.hit_policy == GroupHitPolicy::AllMatches
+        })?;
+        Ok(GroupResult { group: group.name.clone(), hit_policy: group.hit_policy, decisions })
+    }
+
+    /// Evaluates every effective rule and sums the scores of those that
+    /// match. Else branches and `terminal` play no part in scoring.
+    pub fn evaluate_score(&self, payload: &HashMap<String, serde_json::Value>) -> Result<ScoreResult, EngineError> {
+        self.evaluate_score_with_options(payload, &EvalOptions::default())
+    }
+
+    pub fn evaluate_score_with_options(
+        &self,
+        payload: &HashMap<String, serde_json::Value>,
+        options: &EvalOptions,
+    ) -> Result<ScoreResult, EngineError> {
+        let ctx = EvalContext::new(options, self.coerce_numeric_strings);
+        let ruleset = self.ruleset.as_ref()
+            .ok_or_else(|| EngineError::Execution("No ruleset loaded".to_string()))?;
+        let view = self.payload_view(ruleset, Document::Fields(payload));
+        self.check_input(ruleset, &view)?;
+        let mut result = ScoreResult::default();
+        for rule in ruleset.rules.iter().filter(|rule| rule.is_live(options.include_drafts) && rule.is_effective_at(ctx.now)) {
+            if self.evaluate_condition(&rule.when, Scope::Payload(&view), &ctx)? {
+                let score = rule.score.unwrap_or(0.0);
+                result.total += score;
+                result.contributions.push((rule.id.clone(), score));
+                result.matched.push(rule.id.clone());
+            }
+        }
+        result.label = ruleset.score_thresholds.iter()
+            .rev()
+            .find(|threshold| threshold.min.is_none_or(|min| result.total >= min))
+            .map(|threshold| threshold.label.clone());
+        Ok(result)
+    }
+
+    /// Like [`RuleEngine::evaluate`], but when no rule decides, reports the
+    /// first failing condition of every rule instead of `None`. Rules are
+    /// evaluated once, keeping only the failing leaf of each.
+    pub fn evaluate_detailed(&self, payload: &HashMap<String, serde_json::Value>) -> Result<Evaluation, EngineError> {
+        let mut misses = Vec::new();
+        match self.evaluate_document(Document::Fields(payload), &EvalOptions::default(), None, Some(&mut misses))? {
+            Some(decision) => Ok(Evaluation::Matched(Box::new(decision))),
+            None => Ok(Evaluation::NoMatch(NoMatch { rules: misses })),
+        }
+    }
+
+    /// Evaluates every rule against the payload and records each condition
+    /// with the value it saw. Slower than [`RuleEngine::evaluate`]; meant for
+    /// audits and debugging.
+    pub fn explain(&self, payload: &HashMap<String, serde_json::Value>) -> Result<Trace, EngineError> {
+        self.explain_with_options(payload, &EvalOptions::default())
+    }
+
+    pub fn explain_with_options(
+        &self,
+        payload: &HashMap<String, serde_json::Value>,
+        options: &EvalOptions,
+    ) -> Result<Trace, EngineError> {
+        let ctx = EvalContext::new(options, self.coerce_numeric_strings);
+        let ruleset = self.ruleset.as_ref()
+            .ok_or_else(|| EngineError::Execution("No ruleset loaded".to_string()))?;
+        let view = self.payload_view(ruleset, Document::Fields(payload));
+        self.check_input(ruleset, &view)?;
+        let mut trace = Trace { winner: None, branch: None, rules: Vec::new() };
+        for (_, rule) in self.rule_order(ruleset, options) {
+            if !rule.is_live(options.include_drafts) {
+                let status = if rule.enabled && rule.status == RuleLifecycle::Draft { RuleStatus::Draft } else { RuleStatus::Disabled };
+                trace.rules.push(RuleTrace { rule_id: rule.id.clone(), status, condition: None });
+                continue;
+            }
+            if !rule.is_effective_at(ctx.now) {
+                trace.rules.push(RuleTrace { rule_id: rule.id.clone(), status: RuleStatus::NotEffective, condition: None });
+                continue;
+            }
+            let condition = self.explain_condition(&rule.when, Scope::Payload(&view), &ctx)?;
+            let status = match (&trace.winner, condition.result, &rule.else_then) {
+                (Some(_), _, _) => RuleStatus::Skipped,
+                (None, true, _) => RuleStatus::Matched,
+                (None, false, Some(_)) => RuleStatus::Else,
+                (None, false, None) => RuleStatus::NotMatched,
+            };
+            if matches!(status, RuleStatus::Matched | RuleStatus::Else) {
+                trace.winner = Some(rule.id.clone());
+                trace.branch = Some(if status == RuleStatus::Else { Branch::Else } else { Branch::Then });
+            }
+            trace.rules.push(RuleTrace { rule_id: rule.id.clone(), status, condition: Some(condition) });
+        }
+        Ok(trace)
+    }
+
+    fn explain_condition(&self, condition: &Condition, scope: Scope<'_>, ctx: &EvalContext) -> Result<ConditionTrace, EngineError> {
+        let mut trace = ConditionTrace {
+            condition_type: condition.type_name().to_string(),
+            field: None,
+            value: None,
+            expected: None,
+            notes: Vec::new(),
+            result: false,
+            children: Vec::new(),
+        };
+        let explain_all = |conditions: &[Condition]| -> Result<Vec<ConditionTrace>, EngineError> {
+            conditions.iter().map(|cond| self.explain_condition(cond, scope, ctx)).collect()
+        };
+        let hits = |children: &[ConditionTrace]| children.iter().filter(|child| child.result).count();
+        match condition {
+            Condition::And { conditions } => {
+                trace.children = explain_all(conditions)?;
+                trace.result = trace.children.iter().all(|child| child.result);
+            },
+            Condition::Or { conditions } => {
+                trace.children = explain_all(conditions)?;
+                trace.result = trace.children.iter().any(|child| child.result);
+            },
+            Condition::Not { condition } => {
+                trace.children = vec![self.explain_condition(condition, scope, ctx)?];
+                trace.result = !trace.children[0].result;
+            },
+            Condition::Implies { antecedent, consequent } => {
+                trace.children = vec![
+                    self.explain_condition(antecedent, scope, ctx)?,
+                    self.explain_condition(consequent, scope, ctx)?,
+                ];
+                trace.result = !trace.children[0].result || trace.children[1].result;
+            },
+            Condition::Xor { conditions, exactly } => {
+                trace.children = explain_all(conditions)?;
+                trace.result = hits(&trace.children) == *exactly;
+                trace.expected = Some(serde_json::json!({ "exactly": exactly }));
+            },
+            Condition::AtLeast { n, conditions } => {
+                trace.children = explain_all(conditions)?;
+                trace.result = hits(&trace.children) >= *n;
+                trace.expected = Some(serde_json::json!({ "n": n }));
+            },
+            Condition::Ref { name } => {
+                let definition = self.ruleset.as_ref()
+                    .and_then(|ruleset| ruleset.definitions.get(name))
+                    .ok_or_else(|| EngineError::Execution(format!("Unknown definition: {}", name)))?;
+                trace.children = vec![self.explain_condition(definition, scope, ctx)?];
+                trace.result = trace.children[0].result;
+                trace.expected = Some(serde_json::json!({ "name": name }));
+            },
+            Condition::Any { field, condition: inner } | Condition::All { field, condition: inner } => {
+                let elements: Vec<&serde_json::Value> = if path::has_wildcard(field) {
+                    scope.get_all(field)
+                } else {
+                    scope.get(field).and_then(|v| v.as_array()).map(|items| items.iter().collect()).unwrap_or_default()
+                };
+                trace.field = Some(field.clone());
+                trace.value = scope.get(field).cloned();
+                trace.notes = scope.resolution_notes(field);
+                trace.children = elements.into_iter()
+                    .map(|element| self.explain_condition(inner, Scope::Element(element), ctx))
+                    .collect::<Result<_, _>>()?;
+                trace.result = self.evaluate_condition(condition, scope, ctx)?;
+            },
+            _ => {
+                trace.result = self.evaluate_condition(condition, scope, ctx)?;
+                if let Some(Subject { field, default }) = condition.subject() {
+                    let value = subject_value(scope, field);
+                    trace.notes = scope.resolution_notes(field);
+                    match (&value, &default.value) {
+                        (None, Some(fallback)) => trace.notes.push(format!("field is missing; default {} applied", fallback)),
+                        (None, None) => trace.notes.push("field is missing".to_string()),
+                        (Some(serde_json::Value::Null), Some(fallback)) if default.apply_on_null => {
+                            trace.notes.push(format!("field is null; default {} applied", fallback));
+                        },
+                        (Some(serde_json::Value::String(text)), _) if condition.is_numeric_comparison() => {
+                            match ctx.number(value.as_ref()) {
+                                Some(number) => trace.notes.push(format!("string \"{}\" coerced to {}", text, number)),
+                                None => trace.notes.push("string value not compared as a number".to_string()),
+                            }
+                        },
+                        _ => {},
+                    }
+                    trace.field = Some(field.to_string());
+                    trace.value = value;
+                }
+                trace.expected = expected_parameters(condition);
+            },
+        }
+        Ok(trace)
+    }
+
+    /// Rules with their indices, in selection order.
+    fn rule_order<'a>(&'a self, ruleset: &'a RuleSet, options: &EvalOptions) -> Box<dyn Iterator<Item = (usize, &'a Rule)> + 'a> {
+        let mode = match ruleset.hit_policy {
+            HitPolicy::Priority => SelectionMode::HighestPriority,
+            _ => options.selection.unwrap_or(self.selection_mode),
+        };
+        match mode {
+            SelectionMode::FirstMatch => Box::new(ruleset.rules.iter().enumerate()),
+            SelectionMode::HighestPriority => Box::new(self.priority_order.iter().map(|&index| (index, &ruleset.rules[index]))),
+        }
+    }
+
+    /// Passes each decision to `on_decision` in selection order for as long
+    /// as it returns true. `selected`, indexed like the ruleset's rules,
+    /// restricts which rules run; the default action only applies to
+    /// unrestricted runs in which no rule decided. `misses` collects why
+    /// each other rule didn't decide.
+    fn run_rules(
+        &self,
+        document: Document<'_>,
+        options: &EvalOptions,
+        selected: Option<&[bool]>,
+        misses: Option<&mut Vec<NearMiss>>,
+        on_decision: impl FnMut(&Rule, Decision) -> bool,
+    ) -> Result<(), EngineError> {
+        self.run_prepared(&self.prepare(document)?, options, selected, misses, on_decision)
+    }
+
+    /// Computes the event's derived fields and checks it against the input
+    /// schema, once for every rule run over it.
+    fn prepare<'a>(&'a self, document: Document<'a>) -> Result<PreparedEvent<'a>, EngineError> {
+        let start_time = Instant::now();
+        let ruleset = self.loaded_ruleset()?;
+        let view = self.payload_view(ruleset, document);
+        self.check_input(ruleset, &view)?;
+        Ok(PreparedEvent { ruleset, view, sha: std::cell::OnceCell::new(), start_time })
+    }
+
+    /// [`RuleEngine::run_rules`] over an event already prepared.
+    fn run_prepared(
+        &self,
+        event: &PreparedEvent<'_>,
+        options: &EvalOptions,
+        selected: Option<&[bool]>,
+        mut misses: Option<&mut Vec<NearMiss>>,
+        mut on_decision: impl FnMut(&Rule, Decision) -> bool,
+    ) -> Result<(), EngineError> {
+        let ctx = EvalContext::new(options, self.coerce_numeric_strings);
+        let (ruleset, view, start_time) = (event.ruleset, &event.view, event.start_time);
+        let mut decided = false;
+        
+        for (index, rule) in self.rule_order(ruleset, options) {
+            if selected.is_some_and(|selected| !selected[index]) {
+                continue;
+            }
+            let skipped = if !rule.is_live(options.include_drafts) {
+                Some(if rule.enabled && rule.status == RuleLifecycle::Draft { RuleStatus::Draft } else { RuleStatus::Disabled })
+            } else if !rule.is_effective_at(ctx.now) {
+                Some(RuleStatus::NotEffective)
+            } else {
+                None
+            };
+            if let Some(status) = skipped {
+                if let Some(misses) = misses.as_deref_mut() {
+                    misses.push(NearMiss { rule_id: rule.id.clone(), status, failed: None });
+                }
+                continue;
+            }
+            let rule_start = Instant::now();
+            let matched = match misses.as_deref_mut() {
+                None => self.evaluate_condition(&rule.when, Scope::Payload(view), &ctx)?,
+                Some(misses) => {
+                    let mut failed = None;
+                    let matched = self.evaluate_noting_failure(&rule.when, Scope::Payload(view), &ctx, &mut failed)?;
+                    if !matched && rule.else_then.is_none() {
+                        misses.push(NearMiss {
+                            rule_id: rule.id.clone(),
+                            status: RuleStatus::NotMatched,
+                            failed: failed.map(|condition| FailedCondition::new(condition, Scope::Payload(view))),
+                        });
+                    }
+                    matched
+                },
+            };
+            let rule_elapsed = rule_start.elapsed();
+            let branch = if matched {
+                Branch::Then
+            } else if rule.else_then.is_some() {
+                Branch::Else
+            } else {
+                continue;
+            };
+            let payload_sha = event.sha.get_or_init(|| view.document.sha());
+            let timing = DecisionTiming { start_time, rule_elapsed, now: ctx.now };
+            let mut decision = self.decide(ruleset, rule, branch, Scope::Payload(view), payload_sha, timing);
+            self.seal(&mut decision)?;
+            decided = true;
+            if !on_decision(rule, decision) {
+                break;
+            }
+        }
+        
+        if let Some(rule) = self.default_rule.as_ref().filter(|_| !decided && selected.is_none()) {
+            let payload_sha = event.sha.get_or_init(|| view.document.sha());
+            let timing = DecisionTiming { start_time, rule_elapsed: Duration::ZERO, now: ctx.now };
+            let mut decision = self.decide(ruleset, rule, Branch::Then, Scope::Payload(view), payload_sha, timing);
+            decision.matched_conditions.clear();
+            self.seal(&mut decision)?;
+            on_decision(rule, decision);
+        }
+        
+        Ok(())
+    }
+
+    /// Signs `decision` once it is final, if the engine has a key.
+    fn seal(&self, decision: &mut Decision) -> Result<(), EngineError> {
+        if let Some(key) = &self.decision_key {
+            decision.signature = Some(crate::signing::sign_decision(decision, key)?);
+        }
+        Ok(())
+    }
+
+    fn decide(
+        &self,
+        ruleset: &RuleSet,
+        rule: &Rule,
+        branch: Branch,
+        scope: Scope<'_>,
+        payload_sha: &str,
+        timing: DecisionTiming,
+    ) -> Decision {
+        let (action, matched_conditions) = match (branch, &rule.else_then) {
+            (Branch::Else, Some(action)) => (action, Vec::new()),
+            _ => (&rule.then, vec![rule.id.clone()]), // Simplified
+        };
+        let mut captures = HashMap::new();
+        if branch == Branch::Then {
+            self.collect_captures(&rule.when, scope, &mut captures);
+        }
+        let capture_values = captures.iter()
+            .map(|(group, text)| (group.clone(), serde_json::Value::String(text.clone())))
+            .collect();
+        let scope = Scope::Captures { base: &scope, captures: &capture_values };
+        
+        let mut decision = Decision {
+            decision_id: uuid::Uuid::now_v7().to_string(),
+            rule_id: rule.id.clone(),
+            outcome: self.render_outcome(&action.outcome, scope),
+            matched_conditions,
+            elapsed_us: 0,
+            elapsed_ns: timing.rule_elapsed.as_nanos() as u64,
+            total_elapsed_ns: 0,
+            timestamp: u64::try_from(timing.now.timestamp()).unwrap_or_default(),
+            rule_sha: self.ruleset_sha.clone().unwrap_or_default(),
+            rule_content_sha: self.rule_shas.get(&rule.id).cloned().unwrap_or_default(),
+            payload_sha: payload_sha.to_string(),
+            ruleset_version: ruleset.version.clone(),
+            metadata: self.decision_metadata_keys.iter()
+                .filter_map(|key| Some((key.clone(), ruleset.metadata.get(key)?.clone())))
+                .collect(),
+            captured: action.capture.iter()
+                .map(|field| (field.clone(), scope.get(field).cloned().unwrap_or_default()))
+                .collect(),
+            tags: rule.tags.clone(),
+            severity: rule.severity.clone(),
+            description: rule.description.clone(),
+            reasons: Vec::new(),
+            branch,
+            captures,
+            draft: rule.status == RuleLifecycle::Draft,
+            generated_by_llm: rule.generated_by_llm,
+            prompt_sha: rule.prompt_sha.clone(),
+            signature: None,
+        };
+        self.apply_operations(&action.operations, &mut decision, scope);
+        let total = timing.start_time.elapsed();
+        decision.total_elapsed_ns = total.as_nanos() as u64;
+        decision.elapsed_us = total.as_micros() as u64;
+        decision
+    }
+
+    pub fn evaluate_many(&self, events: &[HashMap<String, serde_json::Value>]) -> Result<Vec<Option<Decision>>, EngineError> {
+        events.iter()
+            .map(|event| self.evaluate(event))
+            .collect()
+    }
+
+    /// Evaluates only the rules that share a tag with `include` (any rule
+    /// when it is empty) and none with `exclude`. Tags no rule carries just
+    /// select nothing.
+    pub fn evaluate_with_tags(
+        &self,
+        payload: &HashMap<String, serde_json::Value>,
+        include: &[String],
+        exclude: &[String],
+    ) -> Result<Option<Decision>, EngineError> {
+        let selected = self.select_by_tags(include, exclude);
+        self.evaluate_document(Document::Fields(payload), &EvalOptions::default(), Some(&selected), None)
+    }
+
+    /// [`RuleEngine::evaluate_with_tags`] over a batch; the rule selection
+    /// is computed once for all events.
+    pub fn evaluate_many_with_tags(
+        &self,
+        events: &[HashMap<String, serde_json::Value>],
+        include: &[String],
+        exclude: &[String],
+    ) -> Result<Vec<Option<Decision>>, EngineError> {
+        let selected = self.select_by_tags(include, exclude);
+        events.iter()
+            .map(|event| self.evaluate_document(Document::Fields(event), &EvalOptions::default(), Some(&selected), None))
+            .collect()
+    }
+
+    /// Which rules of the loaded ruleset pass the tag filter, by index,
+    /// looked up in the tag index built at load time.
+    fn select_by_tags(&self, include: &[String], exclude: &[String]) -> Vec<bool> {
+        let rule_count = self.ruleset.as_ref().map_or(0, |ruleset| ruleset.rules.len());
+        let tagged = |tags: &[String]| -> Vec<usize> {
+            tags.iter().filter_map(|tag| self.rules_by_tag.get(tag)).flatten().copied().collect()
+        };
+        let mut selected = vec![include.is_empty(); rule_count];
+        for index in tagged(include) {
+            selected[index] = true;
+        }
+        for index in tagged(exclude) {
+            selected[index] = false;
+        }
+        selected
+    }
+
+    pub fn evaluate_many_values(&self, events: &[serde_json::Value]) -> Result<Vec<Option<Decision>>, EngineError> {
+        events.iter()
+            .map(|event| self.evaluate_value(event))
+            .collect()
+    }
+
+    /// [`RuleEngine::evaluate_condition`] that, when the condition fails,
+    /// leaves in `failed` the node that first made it fail: it descends
+    /// through `and`, `or` and `ref`, and stops at leaves and at combinators
+    /// such as `not` or `xor` whose failure isn't down to a single child.
+    fn evaluate_noting_failure<'a>(
+        &'a self,
+        condition: &'a Condition,
+        scope: Scope<'_>,
+        ctx: &EvalContext,
+        failed: &mut Option<&'a Condition>,
+    ) -> Result<bool, EngineError> {
+        match condition {
+            Condition::And { conditions } => {
+                for cond in conditions {
+                    if !self.evaluate_noting_failure(cond, scope, ctx, failed)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            },
+            Condition::Or { conditions } => {
+                let mut first_failed = None;
+                for (index, cond) in conditions.iter().enumerate() {
+                    let noted = if index == 0 { &mut first_failed } else { &mut None };
+                    if self.evaluate_noting_failure(cond, scope, ctx, noted)? {
+                        return Ok(true);
+                    }
+                }
+                *failed = first_failed.or(Some(condition));
+                Ok(false)
+            },
+            Condition::Ref { name } => {
+                let definition = self.ruleset.as_ref()
+                    .and_then(|ruleset| ruleset.definitions.get(name))
+                    .ok_or_else(|| EngineError::Execution(format!("Unknown definition: {}", name)))?;
+                self.evaluate_noting_failure(definition, scope, ctx, failed)
+            },
+            _ => {
+                let matched = self.evaluate_condition(condition, scope, ctx)?;
+                if !matched {
+                    *failed = Some(condition);
+                }
+                Ok(matched)
+            },
+        }
+    }
+
+    fn evaluate_condition(&self, condition: &Condition, scope: Scope<'_>, ctx: &EvalContext) -> Result<bool, EngineError> {
+        if let Some(Subject { field, default }) = condition.subject() {
+            let pinned = matches!(scope, Scope::Pinned { field: pinned, .. } if pinned == field);
+            // A leaf on a wildcard path matches if any expansion does;
+            // aggregates instead combine the expansions below.
+            if !pinned && path::has_wildcard(field) && !matches!(condition, Condition::Aggregate { .. }) {
+                for value in scope.get_all(field) {
+                    if self.evaluate_condition(condition, Scope::Pinned { base: &scope, field, value }, ctx)? {
+                        return Ok(true);
+                    }
+                }
+                return Ok(false);
+            }
+            if let Some(value) = default.value.as_ref().filter(|_| !pinned && !path::has_wildcard(field)) {
+                let missing = match scope.get(field) {
+                    None => true,
+                    Some(serde_json::Value::Null) => default.apply_on_null,
+                    Some(_) => false,
+                };
+                if missing {
+                    return self.evaluate_condition(condition, Scope::Pinned { base: &scope, field, value }, ctx);
+                }
+            }
+        }
+
+        match condition {
+            Condition::And { conditions } => {
+                for cond in conditions {
+                    if !self.evaluate_condition(cond, scope, ctx)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            },
+            Condition::Or { conditions } => {
+                for cond in conditions {
+                    if self.evaluate_condition(cond, scope, ctx)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            },
+            Condition::Not { condition } => {
+                Ok(!self.evaluate_condition(condition, scope, ctx)?)
+            },
+            Condition::Implies { antecedent, consequent } => {
+                Ok(!self.evaluate_condition(antecedent, scope, ctx)? || self.evaluate_condition(consequent, scope, ctx)?)
+            },
+            Condition::Xor { conditions, exactly } => {
+                let mut hits = 0;
+                for cond in conditions {
+                    if self.evaluate_condition(cond, scope, ctx)? {
+                        hits += 1;
+                        if hits > *exactly {
+                            return Ok(false);
+                        }
+                    }
+                }
+                Ok(hits == *exactly)
+            },
+            Condition::Ref { name } => {
+                let definition = self.ruleset.as_ref()
+                    .and_then(|ruleset| ruleset.definitions.get(name))
+                    .ok_or_else(|| EngineError::Execution(format!("Unknown definition: {}", name)))?;
+                self.evaluate_condition(definition, scope, ctx)
+            },
+            Condition::AtLeast { n, conditions } => {
+                let mut hits = 0;
+                for cond in conditions {
+                    if self.evaluate_condition(cond, scope, ctx)? {
+                        hits += 1;
+                        if hits >= *n {
+                            return Ok(true);
+                        }
+                    }
+                }
+                Ok(false)
+            },
+            Condition::Any { field, condition } if path::has_wildcard(field) => {
+                for item in scope.get_all(field) {
+                    if self.evaluate_condition(condition, Scope::Element(item), ctx)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            },
+            Condition::All { field, condition } if path::has_wildcard(field) => {
+                for item in scope.get_all(field) {
+                    if !self.evaluate_condition(condition, Scope::Element(item), ctx)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            },
+            Condition::Any { field, condition } => {
+                if let Some(serde_json::Value::Array(items)) = scope.get(field) {
+                    for item in items {
+                        if self.evaluate_condition(condition, Scope::Element(item), ctx)? {
+                            return Ok(true);
+                        }
+                    }
+                }
+                Ok(false)
+            },
+            Condition::All { field, condition } => {
+                if let Some(serde_json::Value::Array(items)) = scope.get(field) {
+                    for item in items {
+                        if !self.evaluate_condition(condition, Scope::Element(item), ctx)? {
+                            return Ok(false);
+                        }
+                    }
+                    return Ok(true);
+                }
+                Ok(false)
+            },
+            Condition::Equals { field, value, .. } => {
+                Ok(scope.get(field) == Some(value))
+            },
+            Condition::DeepEquals { field, value, ignore_extra_keys, .. } => {
+                Ok(scope.get(field).is_some_and(|actual| deep_equals(actual, value, *ignore_extra_keys)))
+            },
+            Condition::NotEquals { field, value, missing_is_match, .. } => {
+                match scope.get(field) {
+                    Some(field_value) => Ok(field_value != value),
+                    None => Ok(*missing_is_match),
+                }
+            },
+            Condition::GreaterThan { field, value, .. } => {
+                Ok(ctx.number(scope.get(field)).is_some_and(|num| num > *value))
+            },
+            Condition::LessThan { field, value, .. } => {
+                Ok(ctx.number(scope.get(field)).is_some_and(|num| num < *value))
+            },
+            Condition::GreaterThanOrEqual { field, value, .. } => {
+                Ok(ctx.number(scope.get(field)).is_some_and(|num| num >= *value))
+            },
+            Condition::LessThanOrEqual { field, value, .. } => {
+                Ok(ctx.number(scope.get(field)).is_some_and(|num| num <= *value))
+            },
+            Condition::Between { field, min, max, inclusive_min, inclusive_max, .. } => {
+                if let Some(num) = ctx.number(scope.get(field)) {
+                    let above_min = if *inclusive_min { num >= *min } else { num > *min };
+                    let below_max = if *inclusive_max { num <= *max } else { num < *max };
+                    return Ok(above_min && below_max);
+                }
+                Ok(false)
+            },
+            Condition::ApproxEquals { field, value, epsilon, relative, .. } => {
+                if let Some(num) = ctx.number(scope.get(field)).filter(|n| n.is_finite()) {
+                    let tolerance = if *relative { epsilon * num.abs().max(value.abs()) } else { *epsilon };
+                    return Ok((num - value).abs() <= tolerance);
+                }
+                Ok(false)
+            },
+            Condition::Contains { field, value, .. } => {
+                match (scope.get(field), value) {
+                    (Some(serde_json::Value::String(str_val)), serde_json::Value::String(needle)) => {
+                        Ok(str_val.contains(needle.as_str()))
+                    },
+                    (Some(serde_json::Value::Array(items)), _) => Ok(items.contains(value)),
+                    _ => Ok(false),
+                }
+            },
+            Condition::ContainsAny { field, values, .. } => {
+                Ok(match scope.get(field) {
+                    Some(serde_json::Value::Array(items)) => values.iter().any(|v| items.contains(v)),
+                    Some(serde_json::Value::String(str_val)) => values.iter()
+                        .any(|v| v.as_str().is_some_and(|needle| str_val.contains(needle))),
+                    _ => false,
+                })
+            },
+            Condition::ContainsAll { field, values, .. } => {
+                Ok(match scope.get(field) {
+                    Some(serde_json::Value::Array(items)) => values.iter().all(|v| items.contains(v)),
+                    Some(serde_json::Value::String(str_val)) => values.iter()
+                        .all(|v| v.as_str().is_some_and(|needle| str_val.contains(needle))),
+                    _ => false,
+                })
+            },
+            Condition::StartsWith { field, value, .. } => {
+                if let Some(str_val) = scope.get(field).and_then(|v| v.as_str()) {
+                    return Ok(str_val.starts_with(value.as_str()));
+                }
+                Ok(false)
+            },
+            Condition::EndsWith { field, value, .. } => {
+                if let Some(str_val) = scope.get(field).and_then(|v| v.as_str()) {
+                    return Ok(str_val.ends_with(value.as_str()));
+                }
+                Ok(false)
+            },
+            Condition::Exists { field, .. } => {
+                Ok(scope.get(field).is_some())
+            },
+            Condition::IsNull { field, .. } => {
+                Ok(matches!(scope.get(field), Some(serde_json::Value::Null)))
+            },
+            Condition::IsEmpty { field, trim, missing_is_match, .. } => {
+                match scope.get(field) {
+                    Some(serde_json::Value::String(s)) if *trim => Ok(s.trim().is_empty()),
+                    Some(serde_json::Value::String(s)) => Ok(s.is_empty()),
+                    Some(serde_json::Value::Array(items)) => Ok(items.is_empty()),
+                    Some(serde_json::Value::Object(map)) => Ok(map.is_empty()),
+                    Some(_) => Ok(false),
+                    None => Ok(*missing_is_match),
+                }
+            },
+            Condition::Length { field, operator, value, .. } => {
+                let length = match scope.get(field) {
+                    Some(serde_json::Value::Array(items)) => items.len(),
+                    Some(serde_json::Value::String(s)) => s.chars().count(),
+                    Some(serde_json::Value::Object(map)) => map.len(),
+                    _ => return Ok(false),
+                };
+                Ok(CompareOp::from_name(operator).is_some_and(|op| op.apply(length, *value)))
+            },
+            Condition::Aggregate { field, path, op, operator, value, strict, .. } => {
+                let items: Vec<&serde_json::Value> = if path::has_wildcard(field) {
+                    scope.get_all(field)
+                        .into_iter()
+                        .flat_map(|value| match value {
+                            serde_json::Value::Array(items) => items.iter().collect(),
+                            other => vec![other],
+                        })
+                        .collect()
+                } else {
+                    let Some(serde_json::Value::Array(items)) = scope.get(field) else {
+                        return Ok(false);
+                    };
+                    items.iter().collect()
+                };
+                let mut numbers = Vec::with_capacity(items.len());
+                for item in items {
+                    let element = match path {
+                        Some(path) => Scope::Element(item).get(path),
+                        None => Some(item),
+                    };
+                    match ctx.number(element) {
+                        Some(num) => numbers.push(num),
+                        None if *strict => {
+                            return Err(EngineError::Execution(format!(
+                                "aggregate on '{}' found a non-numeric element",
+                                field
+                            )));
+                        },
+                        None => {},
+                    }
+                }
+                Ok(match (op.apply(&numbers), CompareOp::from_name(operator)) {
+                    (Some(result), Some(op)) => op.apply(result, *value),
+                    _ => false,
+                })
+            },
+            Condition::DateBefore { field, value, .. } => {
+                let actual = scope.get(field).and_then(temporal::parse_timestamp);
+                Ok(match (actual, temporal::parse_timestamp_str(value)) {
+                    (Some(actual), Some(limit)) => actual < limit,
+                    _ => false,
+                })
+            },
+            Condition::DateAfter { field, value, .. } => {
+                let actual = scope.get(field).and_then(temporal::parse_timestamp);
+                Ok(match (actual, temporal::parse_timestamp_str(value)) {
+                    (Some(actual), Some(limit)) => actual > limit,
+                    _ => false,
+                })
+            },
+            Condition::WithinLast { field, duration, .. } => {
+                let actual = scope.get(field).and_then(temporal::parse_timestamp);
+                Ok(match (actual, temporal::parse_duration(duration)) {
+                    (Some(actual), Some(duration)) => actual <= ctx.now && actual >= ctx.now - duration,
+                    _ => false,
+                })
+            },
+            Condition::DayOfWeek { field, days, timezone } => {
+                let Some((weekday, _)) = self.local_time(field.as_deref(), timezone.as_deref(), scope, ctx) else {
+                    return Ok(false);
+                };
+                Ok(days.iter().any(|day| temporal::parse_weekday(day) == Some(weekday)))
+            },
+            Condition::TimeOfDay { field, start, end, timezone } => {
+                let Some((_, time)) = self.local_time(field.as_deref(), timezone.as_deref(), scope, ctx) else {
+                    return Ok(false);
+                };
+                Ok(match (temporal::parse_clock_time(start), temporal::parse_clock_time(end)) {
+                    (Some(start), Some(end)) if start <= end => time >= start && time < end,
+                    (Some(start), Some(end)) => time >= start || time < end,
+                    _ => false,
+                })
+            },
+            Condition::IpInCidr { field, cidrs, networks, .. } => {
+                let Some(ip) = scope.get(field).and_then(|v| v.as_str()).and_then(|s| s.trim().parse().ok()) else {
+                    return Ok(false);
+                };
+                let networks = networks.get_or_init(|| cidrs.iter().filter_map(|c| Cidr::parse(c)).collect());
+                Ok(networks.iter().any(|network| network.contains(ip)))
+            },
+            Condition::Semver { field, operator, value, .. } => {
+                let actual = scope.get(field).and_then(|v| v.as_str()).and_then(SemVer::parse);
+                Ok(match (actual, SemVer::parse(value), CompareOp::from_name(operator)) {
+                    (Some(actual), Some(expected), Some(op)) => op.apply(actual, expected),
+                    _ => false,
+                })
+            },
+            Condition::MatchesGlob { field, pattern, case_insensitive, path_separators, compiled, .. } => {
+                let Some(text) = scope.get(field).and_then(|v| v.as_str()) else {
+                    return Ok(false);
+                };
+                let glob = compiled.get_or_init(|| Glob::compile(pattern, *case_insensitive, *path_separators).ok());
+                Ok(glob.as_ref().is_some_and(|glob| glob.is_match(text)))
+            },
+            Condition::Matches { field, pattern, case_insensitive, compiled, .. } => {
+                let Some(text) = scope.get(field).and_then(|v| v.as_str()) else {
+                    return Ok(false);
+                };
+                let regex = compiled.get_or_init(|| compile_regex(pattern, *case_insensitive).ok());
+                Ok(regex.as_ref().is_some_and(|regex| regex.is_match(text)))
+            },
+            Condition::StringLength { field, operator, value, .. } => {
+                let Some(text) = scope.get(field).and_then(|v| v.as_str()) else {
+                    return Ok(false);
+                };
+                Ok(CompareOp::from_name(operator).is_some_and(|op| op.apply(text.chars().count(), *value)))
+            },
+            Condition::Modulo { field, divisor, remainder, .. } => {
+                let Some(num) = scope.get(field).and_then(|v| v.as_i64()) else {
+                    return Ok(false);
+                };
+                Ok(num.checked_rem_euclid(*divisor) == Some(*remainder))
+            },
+            Condition::TypeIs { field, expected, .. } => {
+                Ok(scope.get(field).is_some_and(|value| expected.matches(value)))
+            },
+            Condition::Checksum { field, algorithm, .. } => {
+                Ok(scope.get(field).and_then(|v| v.as_str()).is_some_and(|s| algorithm.is_valid(s)))
+            },
+            Condition::IsFormat { field, format, .. } => {
+                let Some(text) = scope.get(field).and_then(|v| v.as_str()) else {
+                    return Ok(false);
+                };
+                Ok(StringFormat::from_name(format).is_some_and(|format| format.is_valid(text)))
+            },
+            Condition::Percentage { field, percent, salt, .. } => {
+                let key = match scope.get(field) {
+                    None | Some(serde_json::Value::Null) => return Ok(false),
+                    Some(serde_json::Value::String(s)) => s.clone(),
+                    Some(other) => other.to_string(),
+                };
+                Ok(matchers::rollout_bucket(salt.as_deref().unwrap_or_default(), &key) < *percent)
+            },
+            Condition::Bitmask { field, mask, mode, .. } => {
+                let bits = match scope.get(field) {
+                    Some(serde_json::Value::Number(n)) => n.as_u64().or_else(|| n.as_i64().map(|i| i as u64)),
+                    _ => None,
+                };
+                Ok(match (bits, mask.as_u64()) {
+                    (Some(bits), Some(mask)) => mode.apply(bits, mask),
+                    _ => false,
+                })
+            },
+            Condition::GeoWithin { field, other_field, lat, lon, max_km, .. } => {
+                let origin = scope.get(field).and_then(matchers::geo_point);
+                let target = match (other_field, lat, lon) {
+                    (Some(other), _, _) => scope.get(other).and_then(matchers::geo_point),
+                    (None, Some(lat), Some(lon)) => Some((*lat, *lon)),
+                    _ => None,
+                };
+                Ok(match (origin, target) {
+                    (Some(origin), Some(target)) => matchers::haversine_km(origin, target) <= *max_km,
+                    _ => false,
+                })
+            },
+            Condition::Lookup { table, key_field, equals, one_of, default } => {
+                let key = match scope.get(key_field) {
+                    Some(serde_json::Value::String(key)) => key.clone(),
+                    Some(serde_json::Value::Number(key)) => key.to_string(),
+                    _ => return Ok(false),
+                };
+                let entry = self.ruleset.as_ref()
+                    .and_then(|ruleset| ruleset.metadata.get(table))
+                    .and_then(|table| table.get(&key))
+                    .or(default.as_ref());
+                Ok(match (entry, equals, one_of) {
+                    (Some(entry), Some(expected), _) => entry == expected,
+                    (Some(entry), None, Some(values)) => values.contains(entry),
+                    _ => false,
+                })
+            },
+            Condition::In { field, values, compiled, .. } => {
+                if let Some(field_value) = scope.get(field) {
+                    return Ok(compiled.get_or_init(|| ValueSet::new(values)).contains(field_value));
+                }
+                Ok(false)
+            },
+            Condition::NotIn { field, values, missing_is_match, compiled, .. } => {
+                match scope.get(field) {
+                    Some(field_value) => Ok(!compiled.get_or_init(|| ValueSet::new(values)).contains(field_value)),
+                    None => Ok(*missing_is_match),
+                }
+            },
+        }
+    }
+
+    /// Records the capture groups of every `matches` condition in the rule
+    /// whose pattern matches the event, in document order so that later
+    /// groups override earlier ones with the same name. Conditions under
+    /// `not` or inside `any`/`all` quantifiers are skipped.
+    fn collect_captures(
+        &self,
+        condition: &Condition,
+        scope: Scope<'_>,
+        captures: &mut HashMap<String, String>,
+    ) {
+        match condition {
+            Condition::And { conditions }
+            | Condition::Or { conditions }
+            | Condition::Xor { conditions, .. }
+            | Condition::AtLeast { conditions, .. } => {
+                for cond in conditions {
+                    self.collect_captures(cond, scope, captures);
+                }
+            },
+            Condition::Implies { antecedent, consequent } => {
+                self.collect_captures(antecedent, scope, captures);
+                self.collect_captures(consequent, scope, captures);
+            },
+            Condition::Ref { name } => {
+                if let Some(definition) = self.ruleset.as_ref().and_then(|ruleset| ruleset.definitions.get(name)) {
+                    self.collect_captures(definition, scope, captures);
+                }
+            },
+            Condition::Matches { field, pattern, case_insensitive, compiled, default } => {
+                let Some(regex) = compiled.get_or_init(|| compile_regex(pattern, *case_insensitive).ok()) else {
+                    return;
+                };
+                let mut candidates = if path::has_wildcard(field) {
+                    scope.get_all(field)
+                } else {
+                    scope.get(field).into_iter().collect()
+                };
+                if candidates.iter().all(|value| value.is_null() && default.apply_on_null) {
+                    candidates.extend(&default.value);
+                }
+                let Some(groups) = candidates.iter()
+                    .filter_map(|value| value.as_str())
+                    .find_map(|text| regex.captures(text))
+                else {
+                    return;
+                };
+                for (index, name) in regex.capture_names().enumerate().skip(1) {
+                    if let Some(group) = groups.get(index) {
+                        let key = name.map_or_else(|| index.to_string(), str::to_string);
+                        captures.insert(key, group.as_str().to_string());
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Under [`RuleEngine::set_strict_input`], fails on the first payload
+    /// value, in field order, that doesn't fit the input schema.
+    fn check_input(&self, ruleset: &RuleSet, view: &PayloadView<'_>) -> Result<(), EngineError> {
+        if !self.strict_input {
+            return Ok(());
+        }
+        let mut fields: Vec<(&String, &InputField)> = ruleset.input_schema.iter().collect();
+        fields.sort_by_key(|(field, _)| *field);
+        for (field, declared) in fields {
+            let values = if path::has_wildcard(field) {
+                view.resolve_all(field)
+            } else {
+                Scope::Payload(view).get(field).into_iter().collect()
+            };
+            for value in values {
+                declared.check(value).map_err(|e| {
+                    EngineError::Execution(format!("Input schema violation: field '{}' {}", field, e))
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes the ruleset's derived fields for one event. Each expression
+    /// sees the event and the fields computed before it.
+    fn payload_view<'a>(&self, ruleset: &'a RuleSet, document: Document<'a>) -> PayloadView<'a> {
+        let mut view = PayloadView { document, computed: HashMap::new(), aliases: &ruleset.aliases };
+        for (name, expr) in &self.computed_fields {
+            let scope = Scope::Payload(&view);
+            if let Some(value) = expr.evaluate(&|field| scope.get(field)) {
+                view.computed.insert(name.clone(), value);
+            }
+        }
+        view
+    }
+
+    fn render_outcome(&self, outcome: &HashMap<String, serde_json::Value>, scope: Scope<'_>) -> HashMap<String, serde_json::Value> {
+        outcome.iter()
+            .map(|(key, value)| (key.clone(), self.render_value(value, scope)))
+            .collect()
+    }
+
+    /// Interpolates `{{field}}` templates in strings and evaluates
+    /// `{"$expr": ...}` values, at any depth, using the ones parsed at load
+    /// time. An expression that cannot be computed renders as null.
+    fn render_value(&self, value: &serde_json::Value, scope: Scope<'_>) -> serde_json::Value {
+        if let Some(source) = expr::outcome_expr_source(value) {
+            return source.ok()
+                .and_then(|source| self.outcome_templates.exprs.get(source))
+                .and_then(|expr| expr.evaluate(&|field| scope.get(field)))
+                .unwrap_or_default();
+        }
+        match value {
+            serde_json::Value::String(text) => serde_json::Value::String(self.render_text(text, scope)),
+            serde_json::Value::Array(items) => {
+                serde_json::Value::Array(items.iter().map(|item| self.render_value(item, scope)).collect())
+            },
+            serde_json::Value::Object(map) => serde_json::Value::Object(
+                map.iter().map(|(key, item)| (key.clone(), self.render_value(item, scope))).collect(),
+            ),
+            _ => value.clone(),
+        }
+    }
+
+    fn render_text(&self, text: &str, scope: Scope<'_>) -> String {
+        match self.outcome_templates.templates.get(text) {
+            Some(template) => template.render(|field| scope.get(field), &self.template_placeholder),
+            None => text.to_string(),
+        }
+    }
+
+    fn apply_operations(&self, operations: &[Operation], decision: &mut Decision, scope: Scope<'_>) {
+        for operation in operations {
+            match operation {
+                Operation::SetOutcome { key, value } => {
+                    decision.outcome.insert(key.clone(), self.render_value(value, scope));
+                },
+                Operation::AddTag { tag } => {
+                    if !decision.tags.contains(tag) {
+                        decision.tags.push(tag.clone());
+                    }
+                },
+                Operation::SetSeverity { level } => decision.severity = Some(level.clone()),
+                Operation::AddReason { code, message } => decision.reasons.push(Reason {
+                    code: code.clone(),
+                    message: message.as_deref().map(|message| self.render_text(message, scope)),
+                }),
+            }
+        }
+    }
+
+    /// Local weekday and time of the timestamp at `field`, or of the
+    /// evaluation time when no field is given.
+    fn local_time(
+        &self,
+        field: Option<&str>,
+        timezone: Option<&str>,
+        scope: Scope<'_>,
+        ctx: &EvalContext,
+    ) -> Option<(chrono::Weekday, chrono::NaiveTime)> {
+        let instant = match field {
+            Some(field) => temporal::parse_timestamp(scope.get(field)?)?,
+            None => ctx.now,
+        };
+        let tz = temporal::parse_timezone(timezone)?;
+        Some(temporal::local_weekday_and_time(instant, tz))
+    }
+}
+
+impl Default for RuleEngine {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::parse_yaml;
+    use crate::test_support::payload;
+    use serde_json::json;
+
+    fn engine(yaml: &str) -> RuleEngine {
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(parse_yaml(yaml).unwrap()).unwrap();
+        engine
+    }
+
+    fn matched(engine: &RuleEngine, value: serde_json::Value) -> Option<String> {
+        engine.evaluate(&payload(value)).unwrap().map(|d| d.rule_id)
+    }
+
+    #[test]
+    fn test_gte_and_lte_boundaries() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "big"
+    when: { type: "gte", field: "amount", value: 100 }
+    then: { outcome: { decision: "review" } }
+  - id: "small"
+    when: { type: "lte", field: "amount", value: 10.5 }
+    then: { outcome: { decision: "approve" } }
+"#);
+        assert_eq!(matched(&engine, json!({"amount": 100})).as_deref(), Some("big"));
+        assert_eq!(matched(&engine, json!({"amount": 100.0})).as_deref(), Some("big"));
+        assert_eq!(matched(&engine, json!({"amount": 99.99})), None);
+        assert_eq!(matched(&engine, json!({"amount": 10.5})).as_deref(), Some("small"));
+        assert_eq!(matched(&engine, json!({"amount": 10})).as_deref(), Some("small"));
+        assert_eq!(matched(&engine, json!({"amount": "100"})), None);
+        assert_eq!(matched(&engine, json!({})), None);
+    }
+
+    #[test]
+    fn test_not_equals_missing_field_policy() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "strict"
+    when: { type: "not_equals", field: "status", value: "active" }
+    then: { outcome: {} }
+  - id: "lenient"
+    when: { type: "not_equals", field: "status", value: "active", missing_is_match: true }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"status": "closed"})).as_deref(), Some("strict"));
+        assert_eq!(matched(&engine, json!({"status": "active"})), None);
+        assert_eq!(matched(&engine, json!({})).as_deref(), Some("lenient"));
+        assert_eq!(matched(&engine, json!({"status": null})).as_deref(), Some("strict"));
+    }
+
+    #[test]
+    fn test_between_inclusive_and_exclusive_ends() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "exclusive"
+    when: { type: "between", field: "score", min: 300, max: 700, inclusive_min: false, inclusive_max: false }
+    then: { outcome: {} }
+  - id: "inclusive"
+    when: { type: "between", field: "score", min: 300, max: 700 }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"score": 500})).as_deref(), Some("exclusive"));
+        assert_eq!(matched(&engine, json!({"score": 300})).as_deref(), Some("inclusive"));
+        assert_eq!(matched(&engine, json!({"score": 700.0})).as_deref(), Some("inclusive"));
+        assert_eq!(matched(&engine, json!({"score": 700.5})), None);
+        assert_eq!(matched(&engine, json!({"score": "500"})), None);
+        assert_eq!(matched(&engine, json!({})), None);
+    }
+
+    #[test]
+    fn test_between_rejects_inverted_range() {
+        let ruleset = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "inverted"
+    when: { type: "between", field: "score", min: 700, max: 300 }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(ruleset).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("inverted")));
+    }
+
+    #[test]
+    fn test_starts_with_and_ends_with() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "eu_sku"
+    when: { type: "starts_with", field: "sku", value: "EU-" }
+    then: { outcome: {} }
+  - id: "pdf"
+    when: { type: "ends_with", field: "file", value: ".pdf" }
+    then: { outcome: {} }
+  - id: "any_name"
+    when: { type: "starts_with", field: "name", value: "" }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"sku": "EU-1234"})).as_deref(), Some("eu_sku"));
+        assert_eq!(matched(&engine, json!({"sku": "US-EU-1"})), None);
+        assert_eq!(matched(&engine, json!({"file": "résumé.pdf"})).as_deref(), Some("pdf"));
+        assert_eq!(matched(&engine, json!({"file": "report.PDF"})), None);
+        assert_eq!(matched(&engine, json!({"name": ""})).as_deref(), Some("any_name"));
+        assert_eq!(matched(&engine, json!({"name": 42})), None);
+        assert_eq!(matched(&engine, json!({"sku": ["EU-1"]})), None);
+    }
+
+    #[test]
+    fn test_exists_and_is_null_distinguish_absent_from_null() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "null_doc"
+    when: { type: "is_null", field: "kyc_document" }
+    then: { outcome: {} }
+  - id: "has_doc"
+    when: { type: "exists", field: "kyc_document" }
+    then: { outcome: {} }
+  - id: "missing_doc"
+    when: { type: "not", condition: { type: "exists", field: "kyc_document" } }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({})).as_deref(), Some("missing_doc"));
+        assert_eq!(matched(&engine, json!({"kyc_document": null})).as_deref(), Some("null_doc"));
+        assert_eq!(matched(&engine, json!({"kyc_document": "passport"})).as_deref(), Some("has_doc"));
+    }
+
+    #[test]
+    fn test_is_empty_values_and_trim() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "empty_memo"
+    when: { type: "is_empty", field: "memo" }
+    then: { outcome: {} }
+  - id: "blank_memo"
+    when: { type: "is_empty", field: "memo", trim: true }
+    then: { outcome: {} }
+  - id: "has_attachments"
+    when: { type: "not", condition: { type: "is_empty", field: "attachments" } }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"memo": ""})).as_deref(), Some("empty_memo"));
+        assert_eq!(matched(&engine, json!({"memo": "  \t"})).as_deref(), Some("blank_memo"));
+        assert_eq!(matched(&engine, json!({"memo": [], "attachments": {}})).as_deref(), Some("empty_memo"));
+        assert_eq!(matched(&engine, json!({"memo": "x", "attachments": ["a.pdf"]})).as_deref(), Some("has_attachments"));
+        assert_eq!(matched(&engine, json!({"memo": 0, "attachments": []})), None);
+    }
+
+    #[test]
+    fn test_not_in_values_and_missing_policy() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "unsupported"
+    when: { type: "not_in", field: "country", values: ["DE", "FR", 1] }
+    then: { outcome: {} }
+  - id: "unknown"
+    when: { type: "not_in", field: "country", values: ["DE"], missing_is_match: true }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"country": "US"})).as_deref(), Some("unsupported"));
+        assert_eq!(matched(&engine, json!({"country": "DE"})), None);
+        assert_eq!(matched(&engine, json!({"country": 1})).as_deref(), Some("unknown"));
+        assert_eq!(matched(&engine, json!({})).as_deref(), Some("unknown"));
+    }
+
+    #[test]
+    fn test_contains_on_strings_and_arrays() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "premium"
+    when: { type: "contains", field: "tags", value: "premium" }
+    then: { outcome: {} }
+  - id: "code_7"
+    when: { type: "contains", field: "codes", value: 7 }
+    then: { outcome: {} }
+  - id: "flagged"
+    when: { type: "contains", field: "flags", value: true }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"tags": ["eu", "premium"]})).as_deref(), Some("premium"));
+        assert_eq!(matched(&engine, json!({"tags": "premium-eu"})).as_deref(), Some("premium"));
+        assert_eq!(matched(&engine, json!({"tags": ["premium-eu"]})), None);
+        assert_eq!(matched(&engine, json!({"codes": [1, 7, 9]})).as_deref(), Some("code_7"));
+        assert_eq!(matched(&engine, json!({"codes": ["7"]})), None);
+        assert_eq!(matched(&engine, json!({"flags": ["x", 3, true, null]})).as_deref(), Some("flagged"));
+        assert_eq!(matched(&engine, json!({"codes": "77"})), None);
+    }
+
+    #[test]
+    fn test_any_and_all_over_line_items() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "large_item"
+    when:
+      type: "any"
+      field: "line_items"
+      condition: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: {} }
+  - id: "domestic"
+    when:
+      type: "all"
+      field: "line_items"
+      condition: { type: "equals", field: "country", value: "DE" }
+    then: { outcome: {} }
+  - id: "small_scores"
+    when:
+      type: "all"
+      field: "scores"
+      condition: { type: "less_than", field: "", value: 10 }
+    then: { outcome: {} }
+"#);
+        let items = json!({"line_items": [
+            {"sku": "a", "amount": 20, "country": "DE"},
+            {"sku": "b", "amount": 1500, "country": "FR"}
+        ]});
+        assert_eq!(matched(&engine, items).as_deref(), Some("large_item"));
+        let items = json!({"line_items": [{"amount": 5, "country": "DE"}, {"amount": 7, "country": "DE"}]});
+        assert_eq!(matched(&engine, items).as_deref(), Some("domestic"));
+        assert_eq!(matched(&engine, json!({"line_items": []})).as_deref(), Some("domestic"));
+        assert_eq!(matched(&engine, json!({"line_items": {"amount": 5000}})), None);
+        assert_eq!(matched(&engine, json!({"scores": [1, 2.5, 9]})).as_deref(), Some("small_scores"));
+        assert_eq!(matched(&engine, json!({"scores": [1, 20]})), None);
+    }
+
+    #[test]
+    fn test_length_on_arrays_strings_and_objects() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "many_failures"
+    when: { type: "length", field: "failed_logins", operator: "gt", value: 5 }
+    then: { outcome: {} }
+  - id: "short_code"
+    when: { type: "length", field: "code", operator: "lte", value: 3 }
+    then: { outcome: {} }
+  - id: "two_keys"
+    when: { type: "length", field: "meta", operator: "eq", value: 2 }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"failed_logins": [1, 2, 3, 4, 5, 6]})).as_deref(), Some("many_failures"));
+        assert_eq!(matched(&engine, json!({"failed_logins": [1, 2, 3, 4, 5]})), None);
+        assert_eq!(matched(&engine, json!({"code": "äöü"})).as_deref(), Some("short_code"));
+        assert_eq!(matched(&engine, json!({"meta": {"a": 1, "b": 2}})).as_deref(), Some("two_keys"));
+        assert_eq!(matched(&engine, json!({"code": 12})), None);
+    }
+
+    #[test]
+    fn test_length_rejects_unknown_operator() {
+        let ruleset = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "bad_op"
+    when: { type: "length", field: "items", operator: "ge", value: 1 }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(ruleset).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("bad_op") && msg.contains("'ge'")));
+    }
+
+    #[test]
+    fn test_contains_any_and_contains_all() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "admin"
+    when: { type: "contains_all", field: "permissions", values: ["read", "write", 1] }
+    then: { outcome: {} }
+  - id: "watchlisted"
+    when: { type: "contains_any", field: "parties", values: ["acme", "globex"] }
+    then: { outcome: {} }
+  - id: "nothing_required"
+    when: { type: "contains_all", field: "permissions", values: [] }
+    then: { outcome: {} }
+  - id: "nothing_listed"
+    when: { type: "contains_any", field: "permissions", values: [] }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"permissions": [1, "write", "read"]})).as_deref(), Some("admin"));
+        assert_eq!(matched(&engine, json!({"permissions": ["read"], "parties": ["initech", "globex"]})).as_deref(), Some("watchlisted"));
+        assert_eq!(matched(&engine, json!({"parties": "paid by acme corp"})).as_deref(), Some("watchlisted"));
+        assert_eq!(matched(&engine, json!({"permissions": ["read"]})).as_deref(), Some("nothing_required"));
+        assert_eq!(matched(&engine, json!({})), None);
+    }
+
+    #[test]
+    fn test_contains_any_with_large_watchlist() {
+        let watchlist: Vec<serde_json::Value> = (0..5_000).map(|i| json!(format!("merchant_{}", i))).collect();
+        let ruleset: RuleSet = serde_json::from_value(json!({
+            "version": "1.0",
+            "metadata": {},
+            "rules": [{
+                "id": "watchlist",
+                "when": {"type": "contains_any", "field": "merchants", "values": watchlist},
+                "then": {"outcome": {}}
+            }]
+        })).unwrap();
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(ruleset).unwrap();
+
+        for i in 0..200 {
+            let merchants: Vec<String> = (0..10).map(|j| format!("other_{}_{}", i, j)).collect();
+            assert_eq!(matched(&engine, json!({"merchants": merchants})), None);
+        }
+        assert_eq!(matched(&engine, json!({"merchants": ["x", "merchant_4999"]})).as_deref(), Some("watchlist"));
+    }
+
+    #[test]
+    fn test_aggregate_over_numbers_and_objects() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "big_cart"
+    when: { type: "aggregate", field: "items", path: "price", op: "sum", operator: "gt", value: 500 }
+    then: { outcome: {} }
+  - id: "high_avg"
+    when: { type: "aggregate", field: "scores", op: "avg", operator: "gte", value: 50 }
+    then: { outcome: {} }
+  - id: "no_scores"
+    when: { type: "aggregate", field: "scores", op: "count", operator: "eq", value: 0 }
+    then: { outcome: {} }
+"#);
+        let cart = json!({"items": [{"price": 300}, {"price": 250.5}, {"sku": "gift"}]});
+        assert_eq!(matched(&engine, cart).as_deref(), Some("big_cart"));
+        assert_eq!(matched(&engine, json!({"scores": [40, 60, "n/a"]})).as_deref(), Some("high_avg"));
+        assert_eq!(matched(&engine, json!({"scores": [10, 20]})), None);
+        // avg of nothing has no value, so only the count rule fires
+        assert_eq!(matched(&engine, json!({"scores": []})).as_deref(), Some("no_scores"));
+        assert_eq!(matched(&engine, json!({"scores": ["a", null]})).as_deref(), Some("no_scores"));
+    }
+
+    #[test]
+    fn test_aggregate_strict_rejects_mixed_arrays() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "max_check"
+    when: { type: "aggregate", field: "values", op: "max", operator: "lt", value: 10, strict: true }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"values": [1, 9.5]})).as_deref(), Some("max_check"));
+        assert!(engine.evaluate(&payload(json!({"values": [1, "2"]}))).is_err());
+    }
+
+    #[test]
+    fn test_deep_equals_ignores_key_order() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "exact"
+    when:
+      type: "deep_equals"
+      field: "address"
+      value: { country: "DE", lines: ["Main St 1", { floor: 2 }] }
+    then: { outcome: {} }
+  - id: "subset"
+    when:
+      type: "deep_equals"
+      field: "address"
+      value: { country: "DE", lines: ["Main St 1", { floor: 2 }] }
+      ignore_extra_keys: true
+    then: { outcome: {} }
+"#);
+        let reordered = json!({"address": {"lines": ["Main St 1", {"floor": 2.0}], "country": "DE"}});
+        assert_eq!(matched(&engine, reordered).as_deref(), Some("exact"));
+        let extra = json!({"address": {"country": "DE", "zip": "10115", "lines": ["Main St 1", {"floor": 2, "door": "b"}]}});
+        assert_eq!(matched(&engine, extra).as_deref(), Some("subset"));
+        let swapped = json!({"address": {"country": "DE", "lines": [{"floor": 2}, "Main St 1"]}});
+        assert_eq!(matched(&engine, swapped), None);
+    }
+
+    #[test]
+    fn test_at_least_threshold() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "two_signals"
+    when:
+      type: "at_least"
+      n: 2
+      conditions:
+        - { type: "equals", field: "new_device", value: true }
+        - { type: "greater_than", field: "amount", value: 1000 }
+        - { type: "not_equals", field: "country", value: "DE" }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"new_device": true, "amount": 5000})).as_deref(), Some("two_signals"));
+        assert_eq!(matched(&engine, json!({"new_device": true, "country": "DE", "amount": 10})), None);
+        assert_eq!(matched(&engine, json!({"country": "FR", "amount": 1001})).as_deref(), Some("two_signals"));
+    }
+
+    #[test]
+    fn test_at_least_validates_n() {
+        for n in [0, 3] {
+            let ruleset = parse_yaml(&format!(r#"
+version: "1.0"
+metadata: {{}}
+rules:
+  - id: "threshold"
+    when:
+      type: "at_least"
+      n: {}
+      conditions:
+        - {{ type: "exists", field: "a" }}
+        - {{ type: "exists", field: "b" }}
+    then: {{ outcome: {{}} }}
+"#, n)).unwrap();
+            let err = RuleEngine::new().load_ruleset(ruleset).unwrap_err();
+            assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("threshold")));
+        }
+    }
+
+    #[test]
+    fn test_xor_means_exactly_one() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "one_channel"
+    when:
+      type: "xor"
+      conditions:
+        - { type: "equals", field: "card_present", value: true }
+        - { type: "equals", field: "card_not_present", value: true }
+        - { type: "equals", field: "wallet", value: true }
+    then: { outcome: {} }
+  - id: "two_flags"
+    when:
+      type: "xor"
+      exactly: 2
+      conditions:
+        - { type: "exists", field: "a" }
+        - { type: "exists", field: "b" }
+        - { type: "exists", field: "c" }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"card_present": true})).as_deref(), Some("one_channel"));
+        assert_eq!(matched(&engine, json!({"card_present": true, "card_not_present": true})), None);
+        // three true is an odd count but not exactly one
+        assert_eq!(matched(&engine, json!({"card_present": true, "card_not_present": true, "wallet": true})), None);
+        assert_eq!(matched(&engine, json!({"a": 1, "c": 1})).as_deref(), Some("two_flags"));
+        assert_eq!(matched(&engine, json!({"a": 1, "b": 1, "c": 1})), None);
+    }
+
+    #[test]
+    fn test_xor_validates_exactly() {
+        let source = |exactly: &str| format!(r#"
+version: "1.0"
+metadata: {{}}
+rules:
+  - id: "flags"
+    when:
+      type: "xor"
+      exactly: {}
+      conditions:
+        - {{ type: "exists", field: "a" }}
+        - {{ type: "exists", field: "b" }}
+    then: {{ outcome: {{}} }}
+"#, exactly);
+        let err = RuleEngine::new().load_ruleset(parse_yaml(&source("3")).unwrap()).unwrap_err().to_string();
+        assert_eq!(err, "Rule validation error: Rule flags: xor requires exactly <= 2, got exactly = 3");
+        assert!(matches!(parse_yaml(&source("-1")), Err(EngineError::Parse(_))));
+        assert!(RuleEngine::new().load_ruleset(parse_yaml(&source("2")).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_implies() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "vat_ok"
+    when:
+      type: "implies"
+      if: { type: "equals", field: "country", value: "DE" }
+      then: { type: "exists", field: "vat_id" }
+    then: { outcome: { decision: "approve" } }
+"#);
+        assert_eq!(matched(&engine, json!({"country": "DE", "vat_id": "DE123"})).as_deref(), Some("vat_ok"));
+        assert_eq!(matched(&engine, json!({"country": "FR"})).as_deref(), Some("vat_ok"));
+        assert_eq!(matched(&engine, json!({"country": "DE"})), None);
+    }
+
+    #[test]
+    fn test_definitions_and_nested_refs() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+definitions:
+  is_eu_customer: { type: "in", field: "country", values: ["DE", "FR", "IT"] }
+  large_eu_order:
+    type: "and"
+    conditions:
+      - { type: "ref", name: "is_eu_customer" }
+      - { type: "greater_than", field: "amount", value: 1000 }
+rules:
+  - id: "large_eu"
+    when: { type: "ref", name: "large_eu_order" }
+    then: { outcome: {} }
+  - id: "eu"
+    when: { type: "ref", name: "is_eu_customer" }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"country": "FR", "amount": 5000})).as_deref(), Some("large_eu"));
+        assert_eq!(matched(&engine, json!({"country": "IT", "amount": 10})).as_deref(), Some("eu"));
+        assert_eq!(matched(&engine, json!({"country": "US", "amount": 5000})), None);
+    }
+
+    #[test]
+    fn test_definition_errors() {
+        let unknown = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "dangling"
+    when: { type: "ref", name: "missing" }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(unknown).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("dangling") && msg.contains("missing")));
+
+        let cyclic = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+definitions:
+  a: { type: "not", condition: { type: "ref", name: "b" } }
+  b: { type: "or", conditions: [{ type: "ref", name: "a" }] }
+rules:
+  - id: "loop"
+    when: { type: "ref", name: "a" }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(cyclic).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("a -> b -> a")));
+    }
+
+    #[test]
+    fn test_canonical_ruleset_sha() {
+        let keys = ["region", "owner", "tier", "team", "cost_center", "ticket", "zone", "approver"];
+        let build = |order: &[&str]| -> RuleSet {
+            let mut ruleset = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 1000.0 }
+    then: { outcome: {} }
+"#).unwrap();
+            for key in order {
+                ruleset.metadata.insert(key.to_string(), json!(key.len()));
+                ruleset.rules[0].then.outcome.insert(key.to_string(), json!(key));
+            }
+            ruleset
+        };
+        let reversed: Vec<&str> = keys.iter().rev().copied().collect();
+        let sha = build(&keys).sha().unwrap();
+        // Each build gets freshly seeded maps, so iteration order varies.
+        for _ in 0..20 {
+            assert_eq!(build(&keys).sha().unwrap(), sha);
+            assert_eq!(build(&reversed).sha().unwrap(), sha);
+        }
+        // Stable across processes and releases, not just within this run.
+        assert_eq!(sha, "988d63624e7e03c9b55677362ffd904a457b67ff54e6fd43324c28ad2a33d7e1");
+
+        let canonical = build(&keys).canonical_json().unwrap();
+        assert!(canonical.contains(r#""metadata":{"approver":8,"cost_center":11,"owner":5,"region":6,"team":4,"ticket":6,"tier":4,"zone":4}"#));
+        assert!(canonical.contains(r#""value":1000"#));
+        assert_eq!(crate::canonical::sha256_hex(canonical.as_bytes()), sha);
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(build(&reversed)).unwrap();
+        assert_eq!(engine.get_ruleset_sha(), Some(&sha));
+    }
+
+    #[test]
+    fn test_rule_content_sha() {
+        let source = r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "large"
+    description: "Large amounts"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "review" } }
+  - id: "foreign"
+    when: { type: "not_equals", field: "country", value: "US" }
+    then: { outcome: { action: "flag" } }
+default_action: { outcome: { action: "allow" } }
+"#;
+        let event = payload(json!({"amount": 5000, "country": "US"}));
+        let original = engine(source);
+        let decision = original.evaluate(&event).unwrap().unwrap();
+        assert_eq!(decision.rule_sha, *original.get_ruleset_sha().unwrap());
+        assert_eq!(Some(&decision.rule_content_sha), original.get_rule_sha("large"));
+        assert_ne!(decision.rule_content_sha, decision.rule_sha);
+        assert_eq!(original.list_rule_shas().iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec!["large", "foreign"]);
+
+        // Changing another rule changes the ruleset SHA only.
+        let other = engine(&source.replace("value: \"US\"", "value: \"CA\""));
+        let unrelated = other.evaluate(&event).unwrap().unwrap();
+        assert_ne!(unrelated.rule_sha, decision.rule_sha);
+        assert_eq!(unrelated.rule_content_sha, decision.rule_content_sha);
+        assert_ne!(other.get_rule_sha("foreign"), original.get_rule_sha("foreign"));
+
+        let described = engine(&source.replace("Large amounts", "Amounts over 1000"));
+        assert_ne!(described.evaluate(&event).unwrap().unwrap().rule_content_sha, decision.rule_content_sha);
+        assert_eq!(described.get_rule_sha("foreign"), original.get_rule_sha("foreign"));
+
+        let fallback = original.evaluate(&payload(json!({"amount": 5, "country": "US"}))).unwrap().unwrap();
+        assert_eq!(Some(&fallback.rule_content_sha), original.get_rule_sha(DEFAULT_RULE_ID));
+        assert!(!fallback.rule_content_sha.is_empty());
+    }
+
+    #[test]
+    fn test_rule_content_sha_covers_definitions_and_constants() {
+        let source = r#"
+version: "1.0"
+metadata: {}
+constants: { LIMIT: 1000, REGION: "EU" }
+definitions:
+  large: { type: "greater_than", field: "amount", value: { $const: "LIMIT" } }
+  large_order: { type: "and", conditions: [{ type: "ref", name: "large" }, { type: "exists", field: "order_id" }] }
+  unused: { type: "equals", field: "region", value: { $const: "REGION" } }
+rules:
+  - id: "large_order"
+    when: { type: "ref", name: "large_order" }
+    then: { outcome: { action: "review" } }
+  - id: "foreign"
+    when: { type: "not_equals", field: "country", value: "US" }
+    then: { outcome: { action: "flag" } }
+"#;
+        let original = engine(source);
+        let rule_sha = |edited: &str, id: &str| engine(edited).get_rule_sha(id).cloned();
+
+        // Through the definition the rule references directly, and the one
+        // that references.
+        let redefined = source.replace("field: \"order_id\"", "field: \"order_ref\"");
+        assert_ne!(rule_sha(&redefined, "large_order").as_ref(), original.get_rule_sha("large_order"));
+        let nested = source.replace("field: \"amount\"", "field: \"total\"");
+        assert_ne!(rule_sha(&nested, "large_order").as_ref(), original.get_rule_sha("large_order"));
+        let constant = source.replace("LIMIT: 1000", "LIMIT: 2000");
+        assert_ne!(rule_sha(&constant, "large_order").as_ref(), original.get_rule_sha("large_order"));
+
+        // Definitions and constants the rule doesn't reach leave it alone.
+        for edited in [source.replace("\"EU\"", "\"UK\""), source.replace("field: \"region\"", "field: \"zone\"")] {
+            assert_eq!(rule_sha(&edited, "large_order").as_ref(), original.get_rule_sha("large_order"));
+        }
+        assert_eq!(rule_sha(&constant, "foreign").as_ref(), original.get_rule_sha("foreign"));
+    }
+
+    const APPROVED_RULES: &str = r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "review" } }
+"#;
+
+    fn approved_sha() -> String {
+        parse_yaml(APPROVED_RULES).unwrap().sha().unwrap()
+    }
+
+    #[test]
+    fn test_load_ruleset_verified_on_match() {
+        let sha = approved_sha();
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset_verified_yaml(APPROVED_RULES, &sha).unwrap();
+        assert_eq!(engine.get_ruleset_sha(), Some(&sha));
+        engine.load_ruleset_verified(parse_yaml(APPROVED_RULES).unwrap(), &sha.to_uppercase()).unwrap();
+
+        let path = std::env::temp_dir().join(format!("{}.json", uuid::Uuid::now_v7()));
+        std::fs::write(&path, serde_json::to_string(&parse_yaml(APPROVED_RULES).unwrap()).unwrap()).unwrap();
+        let from_file = engine.load_ruleset_verified_file(&path, &sha);
+        std::fs::remove_file(&path).unwrap();
+        from_file.unwrap();
+        assert!(engine.load_ruleset_verified_file(&path, &sha).unwrap_err().to_string().contains("Failed to read"));
+    }
+
+    #[test]
+    fn test_load_ruleset_verified_mismatch_keeps_the_approved_ruleset() {
+        let sha = approved_sha();
+        let tampered = APPROVED_RULES.replace("1000", "1000000");
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset_verified_yaml(APPROVED_RULES, &sha).unwrap();
+
+        let err = engine.load_ruleset_verified_yaml(&tampered, &sha).unwrap_err();
+        let tampered_sha = parse_yaml(&tampered).unwrap().sha().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("Rule validation error: Ruleset SHA mismatch: expected {}, computed {}", sha, tampered_sha)
+        );
+        // The approved ruleset is still the one deciding.
+        assert_eq!(engine.get_ruleset_sha(), Some(&sha));
+        assert_eq!(engine.evaluate(&payload(json!({"amount": 5000}))).unwrap().unwrap().rule_id, "large");
+    }
+
+    #[test]
+    fn test_load_ruleset_verified_sha_prefix() {
+        // Short SHAs need the prefix flag and at least 12 digits.
+        let sha = approved_sha();
+        let mut engine = RuleEngine::new();
+        assert!(engine.load_ruleset_verified_yaml(APPROVED_RULES, &sha[..12]).is_err());
+        engine.set_accept_sha_prefix(true);
+        engine.load_ruleset_verified_yaml(APPROVED_RULES, &sha[..12]).unwrap();
+        assert!(engine.load_ruleset_verified_yaml(APPROVED_RULES, &sha[..11]).is_err());
+        let tampered = APPROVED_RULES.replace("1000", "1000000");
+        assert!(engine.load_ruleset_verified_yaml(&tampered, &sha[..12]).is_err());
+    }
+
+    #[test]
+    fn test_sha_covers_unresolved_refs() {
+        let yaml = r#"
+version: "1.0"
+metadata: {}
+definitions:
+  adult: { type: "gte", field: "age", value: 18 }
+rules:
+  - id: "adult"
+    when: { type: "ref", name: "adult" }
+    then: { outcome: {} }
+"#;
+        let engine = engine(yaml);
+        let sha = engine.get_ruleset_sha().unwrap().clone();
+        assert_eq!(self::engine(yaml).get_ruleset_sha(), Some(&sha));
+        let changed = self::engine(&yaml.replace("value: 18", "value: 21"));
+        assert_ne!(changed.get_ruleset_sha(), Some(&sha));
+    }
+
+    #[test]
+    fn test_date_before_and_after() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "legacy_signup"
+    when: { type: "date_before", field: "signup_date", value: "2023-01-01" }
+    then: { outcome: {} }
+  - id: "recent_signup"
+    when: { type: "date_after", field: "signup_date", value: "2024-03-01T10:00:00Z" }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"signup_date": "2022-12-31"})).as_deref(), Some("legacy_signup"));
+        assert_eq!(matched(&engine, json!({"signup_date": "2023-01-01T00:30:00+02:00"})).as_deref(), Some("legacy_signup"));
+        assert_eq!(matched(&engine, json!({"signup_date": "2023-01-01T00:00:00Z"})), None);
+        assert_eq!(matched(&engine, json!({"signup_date": "2024-03-01T11:00:00+00:30"})).as_deref(), Some("recent_signup"));
+        assert_eq!(matched(&engine, json!({"signup_date": "2024-03-01T11:00:00+01:00"})), None);
+        assert_eq!(matched(&engine, json!({"signup_date": 1_600_000_000})).as_deref(), Some("legacy_signup"));
+        assert_eq!(matched(&engine, json!({"signup_date": "yesterday"})), None);
+    }
+
+    #[test]
+    fn test_date_literal_validated_at_load() {
+        let ruleset = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "bad_date"
+    when: { type: "date_after", field: "signup_date", value: "2023-13-45" }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(ruleset).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("bad_date")));
+    }
+
+    #[test]
+    fn test_within_last_with_injected_clock() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "active"
+    when: { type: "within_last", field: "last_login", duration: "30d" }
+    then: { outcome: {} }
+"#);
+        let options = EvalOptions {
+            now: Some(temporal::parse_timestamp_str("2024-03-31T12:00:00Z").unwrap()),
+            ..Default::default()
+        };
+        let check = |value: serde_json::Value| {
+            engine.evaluate_with_options(&payload(json!({"last_login": value})), &options)
+                .unwrap()
+                .map(|d| d.rule_id)
+        };
+        assert_eq!(check(json!("2024-03-01T12:00:00Z")).as_deref(), Some("active"));
+        assert_eq!(check(json!("2024-03-01T11:59:59Z")), None);
+        assert_eq!(check(json!("2024-03-31T12:00:00Z")).as_deref(), Some("active"));
+        assert_eq!(check(json!("2024-04-01T00:00:00Z")), None);
+        assert_eq!(check(json!(1_711_800_000)).as_deref(), Some("active"));
+        assert_eq!(check(json!("31/03/2024")), None);
+    }
+
+    #[test]
+    fn test_within_last_rejects_bad_duration() {
+        let ruleset = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "bad_duration"
+    when: { type: "within_last", field: "last_login", duration: "30 days" }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(ruleset).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("bad_duration")));
+    }
+
+    #[test]
+    fn test_day_of_week_and_time_of_day() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "night"
+    when: { type: "time_of_day", field: "created_at", start: "22:00", end: "06:00", timezone: "Europe/Berlin" }
+    then: { outcome: {} }
+  - id: "weekend"
+    when: { type: "day_of_week", field: "booked_at", days: ["sat", "Sunday"], timezone: "America/New_York" }
+    then: { outcome: {} }
+"#);
+        // 21:30 UTC is 22:30 in Berlin (CET)
+        assert_eq!(matched(&engine, json!({"created_at": "2024-01-10T21:30:00Z"})).as_deref(), Some("night"));
+        assert_eq!(matched(&engine, json!({"created_at": "2024-01-10T04:59:00Z"})).as_deref(), Some("night"));
+        assert_eq!(matched(&engine, json!({"created_at": "2024-01-10T05:00:00Z"})), None);
+        // after the spring-forward switch 04:30 UTC is already 06:30 CEST
+        assert_eq!(matched(&engine, json!({"created_at": "2024-03-31T03:30:00Z"})).as_deref(), Some("night"));
+        assert_eq!(matched(&engine, json!({"created_at": "2024-03-31T04:30:00Z"})), None);
+        // Sunday 02:00 UTC is still Saturday evening in New York
+        assert_eq!(matched(&engine, json!({"booked_at": "2024-01-14T02:00:00Z"})).as_deref(), Some("weekend"));
+        assert_eq!(matched(&engine, json!({"booked_at": "2024-01-15T02:00:00Z"})).as_deref(), Some("weekend"));
+        assert_eq!(matched(&engine, json!({"booked_at": "2024-01-16T12:00:00Z"})), None);
+    }
+
+    #[test]
+    fn test_day_of_week_uses_evaluation_clock() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "monday"
+    when: { type: "day_of_week", days: ["mon"] }
+    then: { outcome: {} }
+"#);
+        let at = |ts: &str| EvalOptions { now: temporal::parse_timestamp_str(ts), ..Default::default() };
+        let monday = engine.evaluate_with_options(&HashMap::new(), &at("2024-01-15T10:00:00Z")).unwrap();
+        assert_eq!(monday.map(|d| d.rule_id).as_deref(), Some("monday"));
+        let tuesday = engine.evaluate_with_options(&HashMap::new(), &at("2024-01-16T10:00:00Z")).unwrap();
+        assert!(tuesday.is_none());
+    }
+
+    #[test]
+    fn test_effective_window() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "new_regulation"
+    effective_from: "2024-07-01"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: {} }
+  - id: "old_regulation"
+    effective_until: "2024-07-01T00:00:00Z"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: {} }
+"#);
+        let at = |ts: &str| {
+            let options = EvalOptions { now: temporal::parse_timestamp_str(ts), ..Default::default() };
+            engine.evaluate_with_options(&payload(json!({"amount": 1})), &options).unwrap().map(|d| d.rule_id)
+        };
+        assert_eq!(at("2024-06-30T23:59:59Z").as_deref(), Some("old_regulation"));
+        assert_eq!(at("2024-07-01T00:00:00Z").as_deref(), Some("new_regulation"));
+    }
+
+    #[test]
+    fn test_effective_window_validation() {
+        let ruleset = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "inverted_window"
+    effective_from: "2025-01-01"
+    effective_until: "2024-01-01"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(ruleset).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("inverted_window")));
+    }
+
+    #[test]
+    fn test_ip_in_cidr() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "internal"
+    when: { type: "ip_in_cidr", field: "source_ip", cidrs: ["10.0.0.0/8", "fd00::/8", "192.168.1.7/32"] }
+    then: { outcome: {} }
+  - id: "office"
+    when: { type: "ip_in_cidr", field: "source_ip", cidrs: ["2001:db8::1"] }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"source_ip": "10.200.3.4"})).as_deref(), Some("internal"));
+        assert_eq!(matched(&engine, json!({"source_ip": "11.0.0.1"})), None);
+        assert_eq!(matched(&engine, json!({"source_ip": "192.168.1.7"})).as_deref(), Some("internal"));
+        assert_eq!(matched(&engine, json!({"source_ip": "192.168.1.8"})), None);
+        assert_eq!(matched(&engine, json!({"source_ip": "fd12:3456::1"})).as_deref(), Some("internal"));
+        assert_eq!(matched(&engine, json!({"source_ip": "2001:db8::1"})).as_deref(), Some("office"));
+        assert_eq!(matched(&engine, json!({"source_ip": "2001:db8::2"})), None);
+        assert_eq!(matched(&engine, json!({"source_ip": "not-an-ip"})), None);
+        assert_eq!(matched(&engine, json!({"source_ip": 167772161})), None);
+    }
+
+    #[test]
+    fn test_ip_in_cidr_rejects_malformed_ranges() {
+        for cidr in ["10.0.0.0/33", "10.0.0/8", "fd00::/129"] {
+            let ruleset = parse_yaml(&format!(r#"
+version: "1.0"
+metadata: {{}}
+rules:
+  - id: "bad_range"
+    when: {{ type: "ip_in_cidr", field: "source_ip", cidrs: ["{}"] }}
+    then: {{ outcome: {{}} }}
+"#, cidr)).unwrap();
+            let err = RuleEngine::new().load_ruleset(ruleset).unwrap_err();
+            assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("bad_range")));
+        }
+    }
+
+    #[test]
+    fn test_semver_ordering() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "modern"
+    when: { type: "semver", field: "app_version", operator: "gte", value: "2.3.0" }
+    then: { outcome: {} }
+  - id: "pre_release"
+    when: { type: "semver", field: "app_version", operator: "lt", value: "2.0.0" }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"app_version": "2.10.0"})).as_deref(), Some("modern"));
+        assert_eq!(matched(&engine, json!({"app_version": "2.9.1"})).as_deref(), Some("modern"));
+        assert_eq!(matched(&engine, json!({"app_version": "2.3.0+build.7"})).as_deref(), Some("modern"));
+        assert_eq!(matched(&engine, json!({"app_version": "2.3.0-beta.2"})), None);
+        assert_eq!(matched(&engine, json!({"app_version": "2.0.0-rc.1"})).as_deref(), Some("pre_release"));
+        assert_eq!(matched(&engine, json!({"app_version": "2.0.0"})), None);
+        assert_eq!(matched(&engine, json!({"app_version": "2.3"})), None);
+
+        let ordered = ["1.0.0-alpha", "1.0.0-alpha.1", "1.0.0-alpha.beta", "1.0.0-beta.2", "1.0.0-beta.11", "1.0.0-rc.1", "1.0.0"];
+        for pair in ordered.windows(2) {
+            assert!(SemVer::parse(pair[0]).unwrap() < SemVer::parse(pair[1]).unwrap(), "{:?}", pair);
+        }
+        assert_eq!(SemVer::parse("1.0.0+a").unwrap(), SemVer::parse("1.0.0+b").unwrap());
+    }
+
+    #[test]
+    fn test_semver_rejects_invalid_literal() {
+        let ruleset = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "bad_version"
+    when: { type: "semver", field: "app_version", operator: "gte", value: "2.x" }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(ruleset).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("bad_version")));
+    }
+
+    #[test]
+    fn test_matches_glob() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "internal_host"
+    when: { type: "matches_glob", field: "host", pattern: "*.internal.example.com" }
+    then: { outcome: {} }
+  - id: "error_log"
+    when: { type: "matches_glob", field: "path", pattern: "logs/202[0-4]/*/error.json", path_separators: true }
+    then: { outcome: {} }
+  - id: "any_depth"
+    when: { type: "matches_glob", field: "path", pattern: "logs/**/*.JSON", path_separators: true, case_insensitive: true }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"host": "db1.internal.example.com"})).as_deref(), Some("internal_host"));
+        assert_eq!(matched(&engine, json!({"host": "a.b.internal.example.com"})).as_deref(), Some("internal_host"));
+        assert_eq!(matched(&engine, json!({"host": "DB1.INTERNAL.example.com"})), None);
+        assert_eq!(matched(&engine, json!({"path": "logs/2024/03/error.json"})).as_deref(), Some("error_log"));
+        assert_eq!(matched(&engine, json!({"path": "logs/2024/03/01/error.json"})).as_deref(), Some("any_depth"));
+        assert_eq!(matched(&engine, json!({"path": "logs/trace.json"})).as_deref(), Some("any_depth"));
+        assert_eq!(matched(&engine, json!({"path": "logs/2025/03/error.txt"})), None);
+        assert_eq!(matched(&engine, json!({"host": 42})), None);
+    }
+
+    #[test]
+    fn test_matches_glob_rejects_bad_pattern() {
+        let ruleset = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "bad_glob"
+    when: { type: "matches_glob", field: "path", pattern: "logs/[0-9" }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(ruleset).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("bad_glob")));
+    }
+
+    #[test]
+    fn test_string_length_counts_chars() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "long_memo"
+    when: { type: "string_length", field: "memo", operator: "gt", value: 5 }
+    then: { outcome: {} }
+  - id: "exact_id"
+    when: { type: "string_length", field: "id", operator: "eq", value: 4 }
+    then: { outcome: {} }
+"#);
+        // "héllo" is 5 chars but 6 bytes
+        assert_eq!(matched(&engine, json!({"memo": "héllo"})), None);
+        assert_eq!(matched(&engine, json!({"memo": "héllo!"})).as_deref(), Some("long_memo"));
+        assert_eq!(matched(&engine, json!({"id": "日本語!"})).as_deref(), Some("exact_id"));
+        assert_eq!(matched(&engine, json!({"id": "🦀🦀🦀🦀"})).as_deref(), Some("exact_id"));
+        assert_eq!(matched(&engine, json!({"id": 1234})), None);
+        assert_eq!(matched(&engine, json!({"memo": ["a", "b", "c", "d", "e", "f"]})), None);
+    }
+
+    #[test]
+    fn test_modulo_buckets() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "canary"
+    when: { type: "modulo", field: "user_id", divisor: 10, remainder: 3 }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"user_id": 1243})).as_deref(), Some("canary"));
+        assert_eq!(matched(&engine, json!({"user_id": 1244})), None);
+        // Euclidean remainder: -7 = -1 * 10 + 3
+        assert_eq!(matched(&engine, json!({"user_id": -7})).as_deref(), Some("canary"));
+        assert_eq!(matched(&engine, json!({"user_id": -3})), None);
+        assert_eq!(matched(&engine, json!({"user_id": 13.0})), None);
+        assert_eq!(matched(&engine, json!({"user_id": "13"})), None);
+
+        let zero = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "zero_divisor"
+    when: { type: "modulo", field: "user_id", divisor: 0, remainder: 0 }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(zero).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("zero_divisor")));
+    }
+
+    #[test]
+    fn test_type_is_for_every_json_type() {
+        let cases = [
+            ("string", json!("42"), true),
+            ("string", json!(42), false),
+            ("number", json!(42), true),
+            ("number", json!(4.2), true),
+            ("number", json!("4.2"), false),
+            ("integer", json!(-42), true),
+            ("integer", json!(u64::MAX), true),
+            ("integer", json!(42.0), false),
+            ("boolean", json!(false), true),
+            ("boolean", json!(0), false),
+            ("array", json!([]), true),
+            ("array", json!({}), false),
+            ("object", json!({"a": 1}), true),
+            ("object", json!(null), false),
+            ("null", json!(null), true),
+            ("null", json!(""), false),
+        ];
+        for (expected, value, should_match) in cases {
+            let engine = engine(&format!(r#"
+version: "1.0"
+metadata: {{}}
+rules:
+  - id: "typed"
+    when: {{ type: "type_is", field: "amount", expected: "{}" }}
+    then: {{ outcome: {{}} }}
+"#, expected));
+            let result = matched(&engine, json!({"amount": value.clone()}));
+            assert_eq!(result.is_some(), should_match, "{} vs {}", expected, value);
+            assert_eq!(matched(&engine, json!({})), None, "missing field for {}", expected);
+        }
+    }
+
+    #[test]
+    fn test_checksum_algorithms() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "card"
+    when: { type: "checksum", field: "pan", algorithm: "luhn" }
+    then: { outcome: {} }
+  - id: "iban"
+    when: { type: "checksum", field: "account", algorithm: "iban_mod97" }
+    then: { outcome: {} }
+"#);
+        for good in ["4111111111111111", "4111 1111 1111 1111", "5500-0000-0000-0004", "79927398713"] {
+            assert_eq!(matched(&engine, json!({"pan": good})).as_deref(), Some("card"), "{}", good);
+        }
+        for bad in ["4111111111111112", "79927398710", "", "4111x11111111111"] {
+            assert_eq!(matched(&engine, json!({"pan": bad})), None, "{}", bad);
+        }
+        assert_eq!(matched(&engine, json!({"pan": 4111111111111111u64})), None);
+
+        for good in ["DE89370400440532013000", "GB82 WEST 1234 5698 7654 32", "gb82west12345698765432"] {
+            assert_eq!(matched(&engine, json!({"account": good})).as_deref(), Some("iban"), "{}", good);
+        }
+        for bad in ["DE89370400440532013001", "GB82WEST1234569876543", "", "1289370400440532013000"] {
+            assert_eq!(matched(&engine, json!({"account": bad})), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_checksum_rejects_unknown_algorithm() {
+        let result = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "unknown_algo"
+    when: { type: "checksum", field: "pan", algorithm: "crc32" }
+    then: { outcome: {} }
+"#);
+        assert!(matches!(result, Err(EngineError::Parse(ref error)) if error.message.contains("crc32")));
+    }
+
+    #[test]
+    fn test_is_format_validators() {
+        let cases = [
+            ("email", "jane.doe+billing@example.co.uk", true),
+            ("email", "a@b.io", true),
+            ("email", "jane..doe@example.com", false),
+            ("email", "jane@localhost", false),
+            ("email", "jane@-example.com", false),
+            ("email", "@example.com", false),
+            ("url", "https://example.com/path?q=1#frag", true),
+            ("url", "http://127.0.0.1:8080", true),
+            ("url", "ftp://user@files.example.com", true),
+            ("url", "example.com/path", false),
+            ("url", "https://exa mple.com", false),
+            ("url", "https://example.com:99999", false),
+            ("url", "http://[::1]:8080/", true),
+            ("uuid", "123e4567-e89b-12d3-a456-426614174000", true),
+            ("uuid", "123E4567-E89B-12D3-A456-426614174000", true),
+            ("uuid", "123e4567e89b12d3a456426614174000", false),
+            ("uuid", "123e4567-e89b-12d3-a456-42661417400g", false),
+            ("hex", "deadBEEF01", true),
+            ("hex", "0xdead", false),
+            ("base64", "aGVsbG8=", true),
+            ("base64", "aGVsbG8", false),
+            ("base64", "a===", false),
+        ];
+        for (format, value, should_match) in cases {
+            let engine = engine(&format!(r#"
+version: "1.0"
+metadata: {{}}
+rules:
+  - id: "formatted"
+    when: {{ type: "is_format", field: "value", format: "{}" }}
+    then: {{ outcome: {{}} }}
+"#, format));
+            assert_eq!(matched(&engine, json!({"value": value})).is_some(), should_match, "{} {}", format, value);
+        }
+    }
+
+    #[test]
+    fn test_is_format_rejects_unknown_format() {
+        let ruleset = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "phone_check"
+    when: { type: "is_format", field: "phone", format: "phone" }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(ruleset).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("phone_check")));
+    }
+
+    #[test]
+    fn test_percentage_rollout_is_stable() {
+        assert_eq!(format!("{:.4}", matchers::rollout_bucket("", "user-1")), "77.6406");
+        assert_eq!(format!("{:.4}", matchers::rollout_bucket("", "user-2")), "84.8319");
+        assert_eq!(format!("{:.4}", matchers::rollout_bucket("checkout-v2", "user-1")), "38.4009");
+
+        let rollout = |percent: f64, salt: &str| engine(&format!(r#"
+version: "1.0"
+metadata: {{}}
+rules:
+  - id: "rollout"
+    when: {{ type: "percentage", field: "user_id", percent: {}, salt: "{}" }}
+    then: {{ outcome: {{}} }}
+"#, percent, salt));
+        let ten_percent = rollout(10.0, "checkout-v2");
+        let users: Vec<String> = (0..2_000).map(|i| format!("user-{}", i)).collect();
+        let enabled: Vec<&String> = users.iter()
+            .filter(|user| matched(&ten_percent, json!({"user_id": user})).is_some())
+            .collect();
+        assert!((150..250).contains(&enabled.len()), "got {}", enabled.len());
+        // same inputs land in the same bucket on a fresh engine
+        let again = rollout(10.0, "checkout-v2");
+        assert!(enabled.iter().all(|user| matched(&again, json!({"user_id": user})).is_some()));
+        // a different salt reshuffles assignment
+        let resalted = rollout(10.0, "checkout-v3");
+        assert!(!enabled.iter().all(|user| matched(&resalted, json!({"user_id": user})).is_some()));
+
+        assert_eq!(matched(&rollout(100.0, ""), json!({"user_id": 42})).as_deref(), Some("rollout"));
+        assert_eq!(matched(&rollout(0.0, ""), json!({"user_id": 42})), None);
+        assert_eq!(matched(&rollout(100.0, ""), json!({"user_id": null})), None);
+    }
+
+    #[test]
+    fn test_bitmask_modes() {
+        let engine = |mask: u64, mode: &str| engine(&format!(r#"
+version: "1.0"
+metadata: {{}}
+rules:
+  - id: "flags"
+    when: {{ type: "bitmask", field: "account_flags", mask: {}, mode: "{}" }}
+    then: {{ outcome: {{}} }}
+"#, mask, mode));
+        let check = |engine: &RuleEngine, flags: serde_json::Value| matched(engine, json!({"account_flags": flags})).is_some();
+
+        let any = engine(0x4, "any_set");
+        assert!(check(&any, json!(0b0110)));
+        assert!(!check(&any, json!(0b1011)));
+        let all = engine(0x30, "all_set");
+        assert!(check(&all, json!(0x31)));
+        assert!(!check(&all, json!(0x10)));
+        let none = engine(0x30, "none_set");
+        assert!(check(&none, json!(0x0f)));
+        assert!(!check(&none, json!(0x20)));
+        // negative values are two's complement: -1 has every bit set, -2 all but the lowest
+        assert!(check(&all, json!(-1)));
+        assert!(!check(&engine(0x1, "any_set"), json!(-2)));
+        assert!(!check(&any, json!(4.0)));
+        assert!(!check(&any, json!("4")));
+
+        let too_big = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "huge_mask"
+    when: { type: "bitmask", field: "account_flags", mask: -4, mode: "any_set" }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(too_big).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("huge_mask")));
+    }
+
+    #[test]
+    fn test_geo_within() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "near_home"
+    when: { type: "geo_within", field: "location", other_field: "home", max_km: 50 }
+    then: { outcome: {} }
+  - id: "near_berlin"
+    when: { type: "geo_within", field: "location", lat: 52.52, lon: 13.405, max_km: 900 }
+    then: { outcome: {} }
+"#);
+        let paris = json!({"lat": 48.8566, "lon": 2.3522});
+        let berlin = json!([52.52, 13.405]);
+        // Berlin to Paris is roughly 878 km
+        let distance = matchers::haversine_km((52.52, 13.405), (48.8566, 2.3522));
+        assert!((distance - 878.0).abs() < 5.0, "{}", distance);
+        assert_eq!(matched(&engine, json!({"location": paris, "home": berlin})).as_deref(), Some("near_berlin"));
+        assert_eq!(matched(&engine, json!({"location": [52.6, 13.3], "home": berlin})).as_deref(), Some("near_home"));
+        assert_eq!(matched(&engine, json!({"location": [40.7128, -74.006]})), None);
+        // points either side of the antimeridian are ~32 km apart, not ~40,000
+        let fiji = json!({"location": [-17.0, 179.85], "home": [-17.0, -179.85]});
+        assert_eq!(matched(&engine, fiji).as_deref(), Some("near_home"));
+        assert_eq!(matched(&engine, json!({"location": [91.0, 13.4], "home": [52.52, 13.405]})), None);
+        assert_eq!(matched(&engine, json!({"location": {"lat": 52.52}, "home": berlin})), None);
+    }
+
+    #[test]
+    fn test_approx_equals() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "vat_rate"
+    when: { type: "approx_equals", field: "rate", value: 0.07, epsilon: 0.000001 }
+    then: { outcome: {} }
+  - id: "about_a_million"
+    when: { type: "approx_equals", field: "total", value: 1000000, epsilon: 0.01, relative: true }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"rate": 0.07000000001})).as_deref(), Some("vat_rate"));
+        assert_eq!(matched(&engine, json!({"rate": 0.070001})).as_deref(), Some("vat_rate"));
+        assert_eq!(matched(&engine, json!({"rate": 0.0701})), None);
+        assert_eq!(matched(&engine, json!({"rate": "0.07"})), None);
+        assert_eq!(matched(&engine, json!({"rate": null})), None);
+        assert_eq!(matched(&engine, json!({"total": 990_001})).as_deref(), Some("about_a_million"));
+        assert_eq!(matched(&engine, json!({"total": 1_010_000.0})).as_deref(), Some("about_a_million"));
+        assert_eq!(matched(&engine, json!({"total": 1_020_000})), None);
+
+        let negative = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "negative_epsilon"
+    when: { type: "approx_equals", field: "rate", value: 0.07, epsilon: -0.1 }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(negative).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("negative_epsilon")));
+    }
+
+    #[test]
+    fn test_coerce_numeric_strings() {
+        let mut engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "large_amount"
+    when: { type: "greater_than", field: "amount", value: 40 }
+    then: { outcome: {} }
+  - id: "mid_score"
+    when: { type: "between", field: "score", min: 1, max: 2 }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"amount": "42.50"})), None);
+        assert_eq!(matched(&engine, json!({"amount": 42.5})).as_deref(), Some("large_amount"));
+
+        engine.set_coerce_numeric_strings(true);
+        assert_eq!(matched(&engine, json!({"amount": "42.50"})).as_deref(), Some("large_amount"));
+        assert_eq!(matched(&engine, json!({"amount": "  42.50\n"})).as_deref(), Some("large_amount"));
+        assert_eq!(matched(&engine, json!({"amount": "39"})), None);
+        assert_eq!(matched(&engine, json!({"amount": "42,50"})), None);
+        assert_eq!(matched(&engine, json!({"amount": "1,042"})), None);
+        assert_eq!(matched(&engine, json!({"amount": "lots"})), None);
+        assert_eq!(matched(&engine, json!({"amount": "inf"})), None);
+        assert_eq!(matched(&engine, json!({"score": "1.5"})).as_deref(), Some("mid_score"));
+        assert_eq!(matched(&engine, json!({"score": "1,5"})), None);
+    }
+
+    #[test]
+    fn test_lookup_table() {
+        let tiers: serde_json::Map<String, serde_json::Value> = (0..200)
+            .map(|i| (format!("C{:03}", i), json!(if i % 10 == 0 { "high" } else { "low" })))
+            .chain([("US".to_string(), json!("medium")), ("840".to_string(), json!("medium"))])
+            .collect();
+        let ruleset: RuleSet = serde_json::from_value(json!({
+            "version": "1.0",
+            "metadata": { "owner": "risk", "country_tier": tiers },
+            "rules": [
+                {
+                    "id": "high_risk_country",
+                    "when": { "type": "lookup", "table": "country_tier", "key_field": "country", "equals": "high" },
+                    "then": { "outcome": {} }
+                },
+                {
+                    "id": "reviewable_country",
+                    "when": {
+                        "type": "lookup", "table": "country_tier", "key_field": "country",
+                        "in": ["medium", "unrated"], "default": "unrated"
+                    },
+                    "then": { "outcome": {} }
+                }
+            ]
+        })).unwrap();
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(ruleset.clone()).unwrap();
+
+        assert_eq!(matched(&engine, json!({"country": "C010"})).as_deref(), Some("high_risk_country"));
+        assert_eq!(matched(&engine, json!({"country": "C011"})), None);
+        assert_eq!(matched(&engine, json!({"country": "US"})).as_deref(), Some("reviewable_country"));
+        assert_eq!(matched(&engine, json!({"country": 840})).as_deref(), Some("reviewable_country"));
+        assert_eq!(matched(&engine, json!({"country": "ZZ"})).as_deref(), Some("reviewable_country"));
+        assert_eq!(matched(&engine, json!({"country": null})), None);
+        assert_eq!(matched(&engine, json!({})), None);
+
+        let original_sha = engine.get_ruleset_sha().cloned();
+        let mut retiered = ruleset.clone();
+        retiered.metadata.get_mut("country_tier").unwrap()["C011"] = json!("high");
+        engine.load_ruleset(retiered).unwrap();
+        assert_ne!(engine.get_ruleset_sha().cloned(), original_sha);
+        assert_eq!(matched(&engine, json!({"country": "C011"})).as_deref(), Some("high_risk_country"));
+
+        let mut missing = ruleset;
+        missing.metadata.remove("country_tier");
+        let err = RuleEngine::new().load_ruleset(missing).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("country_tier")));
+    }
+
+    #[test]
+    fn test_in_mixed_value_types() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "listed"
+    when: { type: "in", field: "v", values: ["a", 1, 2.5, true, null, [1, 2], {"k": "v"}] }
+    then: { outcome: {} }
+  - id: "unlisted"
+    when: { type: "not_in", field: "w", values: ["a", 1, false] }
+    then: { outcome: {} }
+"#);
+        for hit in [json!("a"), json!(1), json!(2.5), json!(true), json!(null), json!([1, 2]), json!({"k": "v"})] {
+            assert_eq!(matched(&engine, json!({"v": hit})).as_deref(), Some("listed"), "{}", hit);
+        }
+        for miss in [json!("A"), json!(1.0), json!(-1), json!(false), json!([2, 1]), json!("1")] {
+            assert_eq!(matched(&engine, json!({"v": miss})), None, "{}", miss);
+        }
+        assert_eq!(matched(&engine, json!({"w": "b"})).as_deref(), Some("unlisted"));
+        assert_eq!(matched(&engine, json!({"w": 1.0})).as_deref(), Some("unlisted"));
+        assert_eq!(matched(&engine, json!({"w": true})).as_deref(), Some("unlisted"));
+        assert_eq!(matched(&engine, json!({"w": 1})), None);
+        assert_eq!(matched(&engine, json!({"w": false})), None);
+    }
+
+    #[test]
+    fn test_in_large_list_uses_compiled_set() {
+        let merchants: Vec<serde_json::Value> = (0..5_000).map(|i| json!(format!("merchant-{:05}", i))).collect();
+        let ruleset: RuleSet = serde_json::from_value(json!({
+            "version": "1.0",
+            "metadata": {},
+            "rules": [{
+                "id": "blocked_merchant",
+                "when": { "type": "in", "field": "merchant_id", "values": merchants },
+                "then": { "outcome": {} }
+            }]
+        })).unwrap();
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(ruleset).unwrap();
+
+        // The set is built while loading, not on the first lookup.
+        let Condition::In { compiled, .. } = &engine.ruleset.as_ref().unwrap().rules[0].when else {
+            panic!("expected an in condition");
+        };
+        let set = compiled.get().expect("in list compiled at load time");
+        assert!(set.contains(&json!("merchant-04999")));
+        assert!(!set.contains(&json!("unknown-1")));
+
+        let events: Vec<HashMap<String, serde_json::Value>> = (0..2_000)
+            .map(|i| payload(json!({"merchant_id": format!("unknown-{}", i)})))
+            .collect();
+        assert!(engine.evaluate_many(&events).unwrap().iter().all(Option::is_none));
+        assert_eq!(matched(&engine, json!({"merchant_id": "merchant-04999"})).as_deref(), Some("blocked_merchant"));
+    }
+
+    #[test]
+    fn test_dot_path_fields() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "german_customer"
+    when: { type: "equals", field: "customer.address.country", value: "DE" }
+    then: { outcome: {} }
+  - id: "deep_amount"
+    when: { type: "greater_than", field: "order.totals.gross.amount", value: 100 }
+    then: { outcome: {} }
+  - id: "expensive_line"
+    when:
+      type: "any"
+      field: "order.lines"
+      condition: { type: "gte", field: "price.amount", value: 50 }
+    then: { outcome: {} }
+"#);
+        assert_eq!(
+            matched(&engine, json!({"customer": {"address": {"country": "DE", "city": "Berlin"}}})).as_deref(),
+            Some("german_customer")
+        );
+        assert_eq!(matched(&engine, json!({"customer": {"address": {"country": "FR"}}})), None);
+        assert_eq!(
+            matched(&engine, json!({"order": {"totals": {"gross": {"amount": 150}}}})).as_deref(),
+            Some("deep_amount")
+        );
+        assert_eq!(
+            matched(&engine, json!({"order": {"lines": [{"price": {"amount": 10}}, {"price": {"amount": 75}}]}})).as_deref(),
+            Some("expensive_line")
+        );
+
+        // Missing or scalar intermediate nodes simply don't match.
+        assert_eq!(matched(&engine, json!({"customer": {}})), None);
+        assert_eq!(matched(&engine, json!({"customer": {"address": "Berlin, DE"}})), None);
+        assert_eq!(matched(&engine, json!({"customer": null})), None);
+        assert_eq!(matched(&engine, json!({"order": {"totals": [150]}})), None);
+        assert_eq!(matched(&engine, json!({"customer.address.country": "DE"})), None);
+    }
+
+    #[test]
+    fn test_json_pointer_fields() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "old_app"
+    when: { type: "semver", field: "/app.version", operator: "lt", value: "2.0.0" }
+    then: { outcome: {} }
+  - id: "escaped_keys"
+    when: { type: "equals", field: "/paths/a~1b/m~0n", value: 1 }
+    then: { outcome: {} }
+  - id: "first_sku"
+    when: { type: "equals", field: "/items/0/sku", value: "ABC" }
+    then: { outcome: {} }
+  - id: "sixth_sku"
+    when: { type: "exists", field: "/items/5/sku" }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"app.version": "1.4.0"})).as_deref(), Some("old_app"));
+        assert_eq!(matched(&engine, json!({"app": {"version": "1.4.0"}})), None);
+        assert_eq!(matched(&engine, json!({"paths": {"a/b": {"m~n": 1}}})).as_deref(), Some("escaped_keys"));
+        assert_eq!(matched(&engine, json!({"paths": {"a~1b": {"m~0n": 1}}})), None);
+        assert_eq!(matched(&engine, json!({"items": [{"sku": "ABC"}, {"sku": "XYZ"}]})).as_deref(), Some("first_sku"));
+        assert_eq!(matched(&engine, json!({"items": [{"sku": "XYZ"}, {"sku": "ABC"}]})), None);
+        assert_eq!(matched(&engine, json!({"items": [{"sku": "XYZ"}]})), None);
+        assert_eq!(matched(&engine, json!({"items": {"0": {"sku": "ABC"}}})).as_deref(), Some("first_sku"));
+    }
+
+    #[test]
+    fn test_indexed_field_paths() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "last_event_failed"
+    when: { type: "equals", field: "events[-1].status", value: "failed" }
+    then: { outcome: {} }
+  - id: "first_line_gift"
+    when: { type: "equals", field: "order.lines[0].gift", value: true }
+    then: { outcome: {} }
+  - id: "grid_corner"
+    when: { type: "equals", field: "grid[1][-1]", value: 9 }
+    then: { outcome: {} }
+  - id: "primary_tag_vip"
+    when:
+      type: "any"
+      field: "accounts"
+      condition: { type: "equals", field: "tags[0]", value: "vip" }
+    then: { outcome: {} }
+"#);
+        let history = |statuses: &[&str]| json!({"events": statuses.iter().map(|s| json!({"status": s})).collect::<Vec<_>>()});
+        assert_eq!(matched(&engine, history(&["ok", "failed"])).as_deref(), Some("last_event_failed"));
+        assert_eq!(matched(&engine, history(&["failed", "ok"])), None);
+        assert_eq!(matched(&engine, history(&[])), None);
+        assert_eq!(matched(&engine, json!({"events": {"status": "failed"}})), None);
+
+        assert_eq!(matched(&engine, json!({"order": {"lines": [{"gift": true}, {"gift": false}]}})).as_deref(), Some("first_line_gift"));
+        assert_eq!(matched(&engine, json!({"order": {"lines": [{"gift": false}, {"gift": true}]}})), None);
+        assert_eq!(matched(&engine, json!({"order": {"lines": []}})), None);
+
+        assert_eq!(matched(&engine, json!({"grid": [[1, 2, 3], [7, 8, 9]]})).as_deref(), Some("grid_corner"));
+        assert_eq!(matched(&engine, json!({"grid": [[1, 2, 9]]})), None);
+
+        assert_eq!(
+            matched(&engine, json!({"accounts": [{"tags": ["std"]}, {"tags": ["vip", "beta"]}]})).as_deref(),
+            Some("primary_tag_vip")
+        );
+        assert_eq!(matched(&engine, json!({"accounts": [{"tags": []}, {"tags": ["beta", "vip"]}]})), None);
+    }
+
+    #[test]
+    fn test_wildcard_field_paths() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "overdrawn"
+    when: { type: "less_than", field: "accounts.*.balance", value: 0 }
+    then: { outcome: {} }
+  - id: "blocked_card"
+    when: { type: "equals", field: "regions.*.cards[*].status", value: "blocked" }
+    then: { outcome: {} }
+  - id: "all_verified"
+    when:
+      type: "and"
+      conditions:
+        - { type: "exists", field: "owners" }
+        - type: "all"
+          field: "owners.*"
+          condition: { type: "equals", field: "verified", value: true }
+    then: { outcome: {} }
+"#);
+        assert_eq!(
+            matched(&engine, json!({"accounts": {"acc1": {"balance": 10}, "acc2": {"balance": -5}}})).as_deref(),
+            Some("overdrawn")
+        );
+        assert_eq!(matched(&engine, json!({"accounts": {"acc1": {"balance": 10}, "acc2": {"currency": "EUR"}}})), None);
+        assert_eq!(matched(&engine, json!({"accounts": [{"balance": -1}]})).as_deref(), Some("overdrawn"));
+        assert_eq!(matched(&engine, json!({"accounts": {}})), None);
+        assert_eq!(matched(&engine, json!({"accounts": 5})), None);
+
+        let cards = json!({"regions": {"eu": {"cards": [{"status": "active"}]}, "us": {"cards": [{"status": "active"}, {"status": "blocked"}]}}});
+        assert_eq!(matched(&engine, cards).as_deref(), Some("blocked_card"));
+        assert_eq!(matched(&engine, json!({"regions": {"eu": {"cards": [{"status": "active"}]}}})), None);
+
+        assert_eq!(
+            matched(&engine, json!({"owners": {"a": {"verified": true}, "b": {"verified": true}}})).as_deref(),
+            Some("all_verified")
+        );
+        assert_eq!(matched(&engine, json!({"owners": {"a": {"verified": true}, "b": {"verified": false}}})), None);
+        // `all` over a wildcard that expands to nothing is vacuously true.
+        assert_eq!(matched(&engine, json!({"owners": {}})).as_deref(), Some("all_verified"));
+    }
+
+    #[test]
+    fn test_aggregate_over_wildcard_expansions() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "net_negative"
+    when: { type: "aggregate", field: "accounts.*.balance", op: "sum", operator: "lt", value: 0 }
+    then: { outcome: {} }
+  - id: "many_items"
+    when: { type: "aggregate", field: "orders.*.items", path: "qty", op: "sum", operator: "gte", value: 10 }
+    then: { outcome: {} }
+"#);
+        // Each balance is summed once, not compared on its own.
+        assert_eq!(
+            matched(&engine, json!({"accounts": {"acc1": {"balance": 10}, "acc2": {"balance": -15}}})).as_deref(),
+            Some("net_negative")
+        );
+        assert_eq!(matched(&engine, json!({"accounts": {"acc1": {"balance": 10}, "acc2": {"balance": -5}}})), None);
+        assert_eq!(matched(&engine, json!({"accounts": {}})), None);
+        // Array expansions contribute their elements.
+        let orders = json!({"orders": {"a": {"items": [{"qty": 4}, {"qty": 3}]}, "b": {"items": [{"qty": 3}]}}});
+        assert_eq!(matched(&engine, orders).as_deref(), Some("many_items"));
+        assert_eq!(matched(&engine, json!({"orders": {"a": {"items": [{"qty": 4}]}, "b": {"items": [{"qty": 5}]}}})), None);
+    }
+
+    #[test]
+    fn test_condition_type_name_is_the_type_tag() {
+        let conditions = json!([
+            {"type": "and", "conditions": []},
+            {"type": "implies", "if": {"type": "exists", "field": "a"}, "then": {"type": "exists", "field": "b"}},
+            {"type": "greater_than_or_equal", "field": "amount", "value": 1},
+            {"type": "not_in", "field": "country", "values": ["US"]},
+            {"type": "lookup", "table": "tiers", "key_field": "tier", "equals": "gold"},
+            {"type": "day_of_week", "days": ["mon"]},
+        ]);
+        for condition in serde_json::from_value::<Vec<Condition>>(conditions).unwrap() {
+            assert_eq!(serde_json::to_value(&condition).unwrap()["type"], json!(condition.type_name()));
+        }
+    }
+
+    #[test]
+    fn test_condition_default_for_missing_field() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "domestic"
+    when: { type: "equals", field: "country", value: "US", default: "US" }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({})).as_deref(), Some("domestic"));
+        assert_eq!(matched(&engine, json!({"country": "US"})).as_deref(), Some("domestic"));
+        assert_eq!(matched(&engine, json!({"country": "DE"})), None);
+        // Null is not absent unless apply_on_null is set.
+        assert_eq!(matched(&engine, json!({"country": null})), None);
+    }
+
+    #[test]
+    fn test_condition_default_under_not_with_apply_on_null() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "not_priority"
+    when:
+      type: "not"
+      condition: { type: "equals", field: "tier", value: "priority", default: "priority", apply_on_null: true }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({})), None);
+        assert_eq!(matched(&engine, json!({"tier": null})), None);
+        assert_eq!(matched(&engine, json!({"tier": "basic"})).as_deref(), Some("not_priority"));
+    }
+
+    #[test]
+    fn test_condition_default_serialization() {
+        let ruleset = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "plain"
+    when: { type: "equals", field: "country", value: "US" }
+    then: { outcome: {} }
+  - id: "defaulted"
+    when: { type: "greater_than", field: "amount", value: 10, default: 0, apply_on_null: true }
+    then: { outcome: {} }
+"#).unwrap();
+        // Rules without defaults serialize exactly as before.
+        let plain = serde_json::to_value(&ruleset.rules[0].when).unwrap();
+        assert_eq!(plain, json!({"type": "equals", "field": "country", "value": "US"}));
+        let defaulted = serde_json::to_value(&ruleset.rules[1].when).unwrap();
+        assert_eq!(
+            defaulted,
+            json!({"type": "greater_than", "field": "amount", "value": 10.0, "default": 0, "apply_on_null": true})
+        );
+        let Condition::GreaterThan { default, .. } = serde_json::from_value(defaulted).unwrap() else {
+            panic!("expected greater_than");
+        };
+        assert_eq!(default, FieldDefault { value: Some(json!(0)), apply_on_null: true });
+    }
+
+    #[test]
+    fn test_condition_default_only_on_leaf_conditions() {
+        let err = serde_json::from_value::<Condition>(json!({"type": "and", "conditions": [], "default": true})).unwrap_err();
+        assert!(err.to_string().starts_with("unknown field `default`, expected `conditions`"), "{}", err);
+    }
+
+    #[test]
+    fn test_condition_errors_keep_their_position() {
+        let yaml = r#"version: "1.0"
+metadata: {}
+rules:
+  - id: "foreign"
+    when:
+      type: "and"
+      conditions:
+        - { type: "exists", field: "amount" }
+        - { type: "equals", field: ["country"], value: "US", default: "US" }
+    then: { outcome: {} }
+"#;
+        let position = |source: &str| {
+            let location = serde_yaml::from_str::<RuleSet>(source).unwrap_err().location().unwrap();
+            (location.line(), location.column())
+        };
+        assert_eq!(position(yaml), (9, 11));
+        let yaml = yaml.replace("field: [\"country\"]", "field: \"country\"").replace("\"US\" }", "\"US\", apply_on_null: 1 }");
+        assert_eq!(position(&yaml), (9, 90));
+    }
+
+    #[test]
+    fn test_condition_default_validation() {
+        let load = |when: &str| {
+            let source = format!("version: \"1.0\"\nmetadata: {{}}\nrules:\n  - id: \"r\"\n    when: {}\n    then: {{ outcome: {{}} }}\n", when);
+            RuleEngine::new().load_ruleset(parse_yaml(&source).unwrap()).map(|_| ()).map_err(|e| e.to_string())
+        };
+        assert_eq!(
+            load(r#"{ type: "equals", field: "country", value: "US", apply_on_null: true }"#),
+            Err("Rule validation error: Rule r: apply_on_null on 'country' has no default to apply".to_string())
+        );
+        assert_eq!(
+            load(r#"{ type: "exists", field: "country", default: "US" }"#),
+            Err("Rule validation error: Rule r: exists on 'country' tests presence and can't take a default".to_string())
+        );
+        assert_eq!(
+            load(r#"{ type: "is_null", field: "country", default: "US" }"#),
+            Err("Rule validation error: Rule r: is_null on 'country' tests presence and can't take a default".to_string())
+        );
+        assert_eq!(
+            load(r#"{ type: "not_in", field: "country", values: ["US"], missing_is_match: true, default: "US" }"#),
+            Err("Rule validation error: Rule r: not_in on 'country' can't combine a default with missing_is_match".to_string())
+        );
+        assert_eq!(load(r#"{ type: "not_in", field: "country", values: ["US"], default: "US" }"#), Ok(()));
+        assert_eq!(
+            load(r#"{ type: "equals", field: "items.*.kind", value: "x", default: "x" }"#),
+            Err("Rule validation error: Rule r: equals on wildcard path 'items.*.kind' can't take a default".to_string())
+        );
+        let aggregate = load(r#"{ type: "aggregate", field: "items.*", op: "count", operator: "gte", value: 1, default: [1] }"#);
+        assert!(aggregate.unwrap_err().ends_with("aggregate on wildcard path 'items.*' can't take a default"));
+    }
+
+    #[test]
+    fn test_field_aliases() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+aliases:
+  amount: ["txn_amount", "value"]
+  country: ["billing.country"]
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 100 }
+    then: { outcome: {} }
+  - id: "foreign"
+    when: { type: "not_equals", field: "country", value: "US" }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"amount": 150})).as_deref(), Some("large"));
+        assert_eq!(matched(&engine, json!({"txn_amount": 150})).as_deref(), Some("large"));
+        assert_eq!(matched(&engine, json!({"value": 150})).as_deref(), Some("large"));
+        // The canonical field wins over aliases, and earlier aliases over later ones.
+        assert_eq!(matched(&engine, json!({"amount": 50, "txn_amount": 150})), None);
+        assert_eq!(matched(&engine, json!({"txn_amount": 50, "value": 150})), None);
+        assert_eq!(matched(&engine, json!({"value": 150, "txn_amount": 50})), None);
+        assert_eq!(matched(&engine, json!({"billing": {"country": "DE"}})).as_deref(), Some("foreign"));
+        assert_eq!(matched(&engine, json!({"country": "US", "billing": {"country": "DE"}})), None);
+
+        let load = |aliases: serde_json::Value| {
+            let ruleset: RuleSet = serde_json::from_value(json!({
+                "version": "1.0", "metadata": {}, "rules": [], "aliases": aliases
+            })).unwrap();
+            RuleEngine::new().load_ruleset(ruleset)
+        };
+        let cycle = load(json!({"amount": ["value"], "value": ["amount"]})).unwrap_err();
+        assert!(matches!(cycle, EngineError::RuleValidation(ref msg) if msg.contains("cycle")));
+        let chain = load(json!({"amount": ["value"], "value": ["raw_value"]})).unwrap_err();
+        assert!(matches!(chain, EngineError::RuleValidation(ref msg) if msg.contains("canonical")));
+        let shared = load(json!({"amount": ["value"], "price": ["value"]})).unwrap_err();
+        assert!(matches!(shared, EngineError::RuleValidation(ref msg) if msg.contains("both 'amount' and 'price'")));
+    }
+
+    #[test]
+    fn test_computed_fields() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+computed_fields:
+  amount_per_item: "amount / quantity"
+  full_name: 'coalesce(nickname, first) + " " + last'
+rules:
+  - id: "pricey_item"
+    when: { type: "greater_than", field: "amount_per_item", value: 100 }
+    then: { outcome: {} }
+  - id: "known_fraudster"
+    when: { type: "equals", field: "full_name", value: "Bobby Tables" }
+    then: { outcome: {} }
+  - id: "no_per_item_amount"
+    when: { type: "not", condition: { type: "exists", field: "amount_per_item" } }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"amount": 500, "quantity": 2})).as_deref(), Some("pricey_item"));
+        assert_eq!(matched(&engine, json!({"amount": 500, "quantity": 10, "first": "x", "last": "y"})), None);
+        assert_eq!(
+            matched(&engine, json!({"amount": 10, "quantity": 1, "first": "Robert", "nickname": "Bobby", "last": "Tables"})).as_deref(),
+            Some("known_fraudster")
+        );
+        assert_eq!(
+            matched(&engine, json!({"amount": 10, "quantity": 1, "first": "Bobby", "nickname": null, "last": "Tables"})).as_deref(),
+            Some("known_fraudster")
+        );
+        // Division by zero and missing inputs leave the field missing.
+        assert_eq!(matched(&engine, json!({"amount": 500, "quantity": 0})).as_deref(), Some("no_per_item_amount"));
+        assert_eq!(matched(&engine, json!({"amount": 500})).as_deref(), Some("no_per_item_amount"));
+        assert_eq!(matched(&engine, json!({"amount": "500", "quantity": 2})).as_deref(), Some("no_per_item_amount"));
+
+        let load = |computed: serde_json::Value| {
+            let ruleset: RuleSet = serde_json::from_value(json!({
+                "version": "1.0", "metadata": {}, "rules": [], "computed_fields": computed
+            })).unwrap();
+            RuleEngine::new().load_ruleset(ruleset)
+        };
+        assert!(load(json!({"total": "price * (1 + tax_rate)", "with_fee": "total + 2.5"})).is_ok());
+        let syntax = load(json!({"ratio": "amount / "})).unwrap_err();
+        assert!(matches!(syntax, EngineError::RuleValidation(ref msg) if msg.contains("ratio")));
+        let unknown = load(json!({"middle": "median(a, b)"})).unwrap_err();
+        assert!(matches!(unknown, EngineError::RuleValidation(ref msg) if msg.contains("unknown function")));
+        let cycle = load(json!({"a": "b + 1", "b": "a * 2"})).unwrap_err();
+        assert!(matches!(cycle, EngineError::RuleValidation(ref msg) if msg.contains("a -> b -> a")));
+    }
+
+    #[test]
+    fn test_computed_fields_build_on_each_other() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+computed_fields:
+  with_fee: "total + 2.5"
+  total: "price * (1 + tax_rate)"
+  negated: "-total"
+rules:
+  - id: "int_math"
+    when: { type: "equals", field: "negated", value: -300 }
+    then: { outcome: {} }
+  - id: "over_limit"
+    when: { type: "gte", field: "with_fee", value: 122.5 }
+    then: { outcome: {} }
+"#);
+        assert_eq!(matched(&engine, json!({"price": 100, "tax_rate": 0.2})).as_deref(), Some("over_limit"));
+        assert_eq!(matched(&engine, json!({"price": 100, "tax_rate": 0.1})), None);
+        assert_eq!(matched(&engine, json!({"price": 100, "tax_rate": 2})).as_deref(), Some("int_math"));
+        assert_eq!(matched(&engine, json!({"price": 100.0, "tax_rate": 2})).as_deref(), Some("over_limit"));
+    }
+
+    #[test]
+    fn test_escaped_field_paths() {
+        let yaml = r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "escaped_dot"
+    when: { type: "equals", field: 'user\.agent.browser', value: "firefox" }
+    then: { outcome: {} }
+  - id: "quoted_segment"
+    when: { type: "equals", field: '["app.version"].major', value: 2 }
+    then: { outcome: {} }
+  - id: "brackets_and_backslashes"
+    when: { type: "equals", field: 'raw.a\[0\]\\b', value: true }
+    then: { outcome: {} }
+  - id: "quoted_escapes"
+    when: { type: "equals", field: 'labels["say \"hi\"\\now"]', value: 1 }
+    then: { outcome: {} }
+  - id: "literal_star"
+    when: { type: "equals", field: 'ratings.\*', value: 5 }
+    then: { outcome: {} }
+"#;
+        let ruleset = parse_yaml(yaml).unwrap();
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(ruleset.clone()).unwrap();
+
+        assert_eq!(matched(&engine, json!({"user.agent": {"browser": "firefox"}})).as_deref(), Some("escaped_dot"));
+        assert_eq!(matched(&engine, json!({"user": {"agent": {"browser": "firefox"}}})), None);
+        assert_eq!(matched(&engine, json!({"app.version": {"major": 2}})).as_deref(), Some("quoted_segment"));
+        assert_eq!(matched(&engine, json!({"app": {"version": {"major": 2}}})), None);
+        assert_eq!(matched(&engine, json!({"raw": {"a[0]\\b": true}})).as_deref(), Some("brackets_and_backslashes"));
+        assert_eq!(matched(&engine, json!({"raw": {"a": [{"b": true}]}})), None);
+        assert_eq!(matched(&engine, json!({"labels": {"say \"hi\"\\now": 1}})).as_deref(), Some("quoted_escapes"));
+        assert_eq!(matched(&engine, json!({"ratings": {"*": 5}})).as_deref(), Some("literal_star"));
+        assert_eq!(matched(&engine, json!({"ratings": {"food": 5}})), None);
+
+        // Field strings survive a YAML -> JSON -> YAML round trip untouched.
+        let json = serde_json::to_string(&ruleset).unwrap();
+        let from_json = crate::dsl::parse_json(&json).unwrap();
+        let from_yaml = parse_yaml(&serde_yaml::to_string(&from_json).unwrap()).unwrap();
+        for (original, round_tripped) in ruleset.rules.iter().zip(&from_yaml.rules) {
+            assert_eq!(
+                serde_json::to_value(&original.when).unwrap(),
+                serde_json::to_value(&round_tripped.when).unwrap()
+            );
+        }
+        let mut reloaded = RuleEngine::new();
+        reloaded.load_ruleset(from_yaml).unwrap();
+        assert_eq!(matched(&reloaded, json!({"user.agent": {"browser": "firefox"}})).as_deref(), Some("escaped_dot"));
+    }
+
+    #[test]
+    fn test_evaluate_value_matches_map_api() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+computed_fields:
+  per_item: "amount / quantity"
+rules:
+  - id: "pricey"
+    when: { type: "greater_than", field: "per_item", value: 100 }
+    then: { outcome: { action: "review" } }
+  - id: "nested_country"
+    when: { type: "in", field: "customer.address.country", values: ["KP", "IR"] }
+    then: { outcome: { action: "block" } }
+  - id: "first_sku"
+    when: { type: "equals", field: "[0].sku", value: "GIFT" }
+    then: { outcome: { action: "gift" } }
+  - id: "bare_number"
+    when: { type: "greater_than", field: "", value: 1000 }
+    then: { outcome: { action: "big" } }
+"#);
+        let documents = vec![
+            json!({"amount": 500, "quantity": 2}),
+            json!({"amount": 500, "quantity": 10, "customer": {"address": {"country": "IR"}}}),
+            json!({"amount": 5, "quantity": 1}),
+        ];
+        let maps: Vec<_> = documents.iter().map(|doc| payload(doc.clone())).collect();
+        let ids = |decisions: Vec<Option<Decision>>| -> Vec<Option<String>> {
+            decisions.into_iter().map(|d| d.map(|d| d.rule_id)).collect()
+        };
+        let from_values = ids(engine.evaluate_many_values(&documents).unwrap());
+        assert_eq!(from_values, ids(engine.evaluate_many(&maps).unwrap()));
+        assert_eq!(from_values, vec![Some("pricey".to_string()), Some("nested_country".to_string()), None]);
+        let single = engine.evaluate_value(&documents[1]).unwrap().unwrap();
+        assert_eq!(single.outcome["action"], json!("block"));
+
+        // Top-level arrays and scalars are addressed from the root.
+        assert_eq!(engine.evaluate_value(&json!([{"sku": "GIFT"}, {"sku": "X"}])).unwrap().unwrap().rule_id, "first_sku");
+        assert!(engine.evaluate_value(&json!([{"sku": "X"}])).unwrap().is_none());
+        assert_eq!(engine.evaluate_value(&json!(5000)).unwrap().unwrap().rule_id, "bare_number");
+        assert!(engine.evaluate_value(&json!("5000")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_referenced_fields() {
+        let ruleset = parse_yaml(r#"
+version: "1.0"
+metadata:
+  tiers: { US: "low" }
+definitions:
+  is_vip: { type: "equals", field: "customer.tier", value: "vip" }
+aliases:
+  amount: ["txn_amount"]
+computed_fields:
+  per_item: "amount / quantity"
+  full_name: 'first + " " + last'
+rules:
+  - id: "combinators"
+    when:
+      type: "and"
+      conditions:
+        - { type: "or", conditions: [{ type: "equals", field: "a", value: 1 }, { type: "not_equals", field: "b", value: 1 }] }
+        - { type: "not", condition: { type: "ref", name: "is_vip" } }
+        - { type: "implies", if: { type: "exists", field: "c" }, then: { type: "is_null", field: "d" } }
+        - { type: "xor", conditions: [{ type: "is_empty", field: "e" }, { type: "deep_equals", field: "f", value: {} }] }
+        - { type: "at_least", n: 1, conditions: [{ type: "contains", field: "g", value: "x" }] }
+    then: { outcome: {} }
+  - id: "quantifiers"
+    when:
+      type: "and"
+      conditions:
+        - { type: "any", field: "items", condition: { type: "greater_than", field: "price", value: 1 } }
+        - { type: "all", field: "owners.*", condition: { type: "equals", field: "verified", value: true } }
+        - { type: "aggregate", field: "lines", path: "qty", op: "sum", operator: "gt", value: 1 }
+    then: { outcome: {} }
+  - id: "numbers"
+    when:
+      type: "or"
+      conditions:
+        - { type: "greater_than", field: "n1", value: 1 }
+        - { type: "less_than", field: "n2", value: 1 }
+        - { type: "gte", field: "n3", value: 1 }
+        - { type: "lte", field: "n4", value: 1 }
+        - { type: "between", field: "n5", min: 1, max: 2 }
+        - { type: "approx_equals", field: "n6", value: 1, epsilon: 0.1 }
+        - { type: "modulo", field: "n7", divisor: 2, remainder: 0 }
+        - { type: "bitmask", field: "n8", mask: 4, mode: "any_set" }
+        - { type: "percentage", field: "n9", percent: 10 }
+    then: { outcome: {} }
+  - id: "strings"
+    when:
+      type: "or"
+      conditions:
+        - { type: "contains_any", field: "s1", values: ["x"] }
+        - { type: "contains_all", field: "s2", values: ["x"] }
+        - { type: "starts_with", field: "s3", value: "x" }
+        - { type: "ends_with", field: "s4", value: "x" }
+        - { type: "length", field: "s5", operator: "gt", value: 1 }
+        - { type: "string_length", field: "s6", operator: "gt", value: 1 }
+        - { type: "matches_glob", field: "s7", pattern: "*.txt" }
+        - { type: "is_format", field: "s8", format: "email" }
+        - { type: "checksum", field: "s9", algorithm: "luhn" }
+        - { type: "semver", field: "s10", operator: "gt", value: "1.0.0" }
+        - { type: "type_is", field: "s11", expected: "string" }
+        - { type: "in", field: "s12", values: ["x"] }
+        - { type: "not_in", field: "s13", values: ["x"] }
+    then: { outcome: {} }
+  - id: "time_and_place"
+    when:
+      type: "or"
+      conditions:
+        - { type: "date_before", field: "t1", value: "2024-01-01" }
+        - { type: "date_after", field: "t2", value: "2024-01-01" }
+        - { type: "within_last", field: "t3", duration: "1d" }
+        - { type: "day_of_week", field: "t4", days: ["monday"] }
+        - { type: "time_of_day", start: "09:00", end: "17:00" }
+        - { type: "ip_in_cidr", field: "ip", cidrs: ["10.0.0.0/8"] }
+        - { type: "geo_within", field: "home", other_field: "location", max_km: 10 }
+        - { type: "lookup", table: "tiers", key_field: "country", equals: "low" }
+    then: { outcome: {} }
+  - id: "derived"
+    when:
+      type: "and"
+      conditions:
+        - { type: "greater_than", field: "per_item", value: 1 }
+        - { type: "greater_than", field: "amount", value: 1 }
+    then: { outcome: {} }
+"#).unwrap();
+        RuleEngine::new().load_ruleset(ruleset.clone()).unwrap();
+
+        let by_rule = ruleset.referenced_fields_by_rule();
+        let fields = |id: &str| by_rule[id].iter().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(fields("combinators"), ["a", "b", "c", "customer.tier", "d", "e", "f", "g"]);
+        assert_eq!(
+            fields("quantifiers"),
+            ["items", "items[*].price", "lines", "lines[*].qty", "owners.*", "owners.*.verified"]
+        );
+        assert_eq!(fields("numbers"), ["n1", "n2", "n3", "n4", "n5", "n6", "n7", "n8", "n9"]);
+        assert_eq!(
+            fields("strings"),
+            ["s1", "s10", "s11", "s12", "s13", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9"]
+        );
+        assert_eq!(fields("time_and_place"), ["country", "home", "ip", "location", "t1", "t2", "t3", "t4"]);
+        assert_eq!(fields("derived"), ["amount", "quantity", "txn_amount"]);
+
+        let all = ruleset.referenced_fields();
+        assert!(all.contains("first") && all.contains("last"));
+        assert!(!all.contains("per_item") && !all.contains("full_name"));
+        assert_eq!(all.len(), by_rule.values().flatten().collect::<BTreeSet<_>>().len() + 2);
+    }
+
+    #[test]
+    fn test_outcome_templates() {
+        let mut engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "block_transfer"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then:
+      outcome:
+        action: "block"
+        reason: "Blocked transfer of {{amount}} for {{ customer.id }}"
+        details: { ratio: "{{ratio}}", flags: ["{{flags}}", "{{ customer.vip }}"], note: "{{missing.field}}" }
+        literal: 'Use \{{amount}} for the raw amount, {single} braces stay'
+        count: 3
+"#);
+        let decision = engine.evaluate(&payload(json!({
+            "amount": 1500,
+            "ratio": 0.25,
+            "flags": ["a", "b"],
+            "customer": {"id": "C-42", "vip": true}
+        }))).unwrap().unwrap();
+        assert_eq!(decision.outcome["action"], json!("block"));
+        assert_eq!(decision.outcome["reason"], json!("Blocked transfer of 1500 for C-42"));
+        assert_eq!(
+            decision.outcome["details"],
+            json!({"ratio": "0.25", "flags": ["[\"a\",\"b\"]", "true"], "note": ""})
+        );
+        assert_eq!(decision.outcome["literal"], json!("Use {{amount}} for the raw amount, {single} braces stay"));
+        assert_eq!(decision.outcome["count"], json!(3));
+
+        engine.set_template_placeholder("null");
+        let decision = engine.evaluate(&payload(json!({"amount": 2000.5, "customer": {"id": null}}))).unwrap().unwrap();
+        assert_eq!(decision.outcome["reason"], json!("Blocked transfer of 2000.5 for null"));
+
+        // The ruleset keeps the raw templates, so the SHA doesn't depend on payloads.
+        let raw = &engine.get_ruleset().unwrap().rules[0].then.outcome["reason"];
+        assert_eq!(raw, &json!("Blocked transfer of {{amount}} for {{ customer.id }}"));
+
+        let broken = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "typo"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { reason: "Amount {{amount was too high" } }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(broken).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("typo") && msg.contains("reason")));
+
+        let fields = engine.get_ruleset().unwrap().referenced_fields();
+        assert_eq!(
+            fields.iter().map(String::as_str).collect::<Vec<_>>(),
+            ["amount", "customer.id", "customer.vip", "flags", "missing.field", "ratio"]
+        );
+    }
+
+    #[test]
+    fn test_outcome_templates_compiled_at_load() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "review"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then:
+      outcome: { fee: { $expr: "amount * 0.01" } }
+      operations: [{ op: "add_reason", code: "large", message: "{{amount}} is large" }]
+    else_then:
+      outcome: { note: "{{amount}} is fine" }
+"#);
+        let compiled = &engine.outcome_templates;
+        let mut templates: Vec<&str> = compiled.templates.keys().map(String::as_str).collect();
+        templates.sort();
+        assert_eq!(templates, ["{{amount}} is fine", "{{amount}} is large"]);
+        assert_eq!(compiled.exprs.keys().collect::<Vec<_>>(), ["amount * 0.01"]);
+
+        let decision = engine.evaluate(&payload(json!({"amount": 2000}))).unwrap().unwrap();
+        assert_eq!(decision.outcome["fee"], json!(20.0));
+        assert_eq!(decision.reasons[0].message.as_deref(), Some("2000 is large"));
+        let decision = engine.evaluate(&payload(json!({"amount": 5}))).unwrap().unwrap();
+        assert_eq!(decision.outcome["note"], json!("5 is fine"));
+
+        let broken = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+default_action:
+  outcome: { note: "no rule for {{id" }
+rules: []
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(broken).unwrap_err().to_string();
+        assert!(err.contains("Rule __default__: outcome 'note' is invalid"), "{}", err);
+    }
+
+    #[test]
+    fn test_capture_fields() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "large_transfer"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then:
+      outcome: { action: "review" }
+      capture: ["transaction_id", "amount", "customer.address.country", "lines[-1].sku", "customer.phone"]
+  - id: "small_transfer"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { action: "allow" } }
+"#);
+        let decision = engine.evaluate(&payload(json!({
+            "transaction_id": "T-1",
+            "amount": 1500,
+            "customer": {"address": {"country": "NL"}},
+            "lines": [{"sku": "A"}, {"sku": "B"}]
+        }))).unwrap().unwrap();
+        assert_eq!(decision.outcome, HashMap::from([("action".to_string(), json!("review"))]));
+        assert_eq!(decision.captured, HashMap::from([
+            ("transaction_id".to_string(), json!("T-1")),
+            ("amount".to_string(), json!(1500)),
+            ("customer.address.country".to_string(), json!("NL")),
+            ("lines[-1].sku".to_string(), json!("B")),
+            ("customer.phone".to_string(), json!(null)),
+        ]));
+
+        let decision = engine.evaluate(&payload(json!({"amount": 5}))).unwrap().unwrap();
+        assert!(decision.captured.is_empty());
+        assert!(!serde_json::to_value(&decision).unwrap().as_object().unwrap().contains_key("captured"));
+    }
+
+    #[test]
+    fn test_outcome_expressions() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "card_fee"
+    when: { type: "exists", field: "amount" }
+    then:
+      outcome:
+        action: "charge"
+        fee: { $expr: "amount * 0.029 + 0.30" }
+        fee_rounded: { $expr: "round(amount * 0.029 + 0.30, 2)" }
+        discount: { $expr: "min(amount * 0.1, 50)" }
+        floor: { $expr: "max(amount - credit, 0)" }
+        per_item: { $expr: "amount / quantity" }
+        whole: { $expr: "round(amount / 3)" }
+        labels: ["static", { $expr: "amount * 2" }]
+"#);
+        let decision = engine.evaluate(&payload(json!({"amount": 100, "credit": 30, "quantity": 0}))).unwrap().unwrap();
+        let outcome = &decision.outcome;
+        assert_eq!(outcome["action"], json!("charge"));
+        assert!((outcome["fee"].as_f64().unwrap() - 3.2).abs() < 1e-9);
+        assert_eq!(outcome["fee_rounded"], json!(3.2));
+        assert_eq!(outcome["discount"], json!(10.0));
+        assert_eq!(outcome["floor"], json!(70));
+        assert_eq!(outcome["per_item"], json!(null));
+        assert_eq!(outcome["whole"], json!(33));
+        assert_eq!(outcome["labels"], json!(["static", 200]));
+
+        let decision = engine.evaluate(&payload(json!({"amount": 1000.555}))).unwrap().unwrap();
+        assert_eq!(decision.outcome["fee_rounded"], json!(29.32));
+        assert_eq!(decision.outcome["discount"], json!(50));
+        assert_eq!(decision.outcome["floor"], json!(null));
+
+        // Static outcome parts and the stored ruleset are untouched.
+        let raw = &engine.get_ruleset().unwrap().rules[0].then.outcome;
+        assert_eq!(raw["fee"], json!({"$expr": "amount * 0.029 + 0.30"}));
+        assert_eq!(raw["action"], json!("charge"));
+
+        let invalid = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "bad_fee"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { fee: { $expr: "amount * " } } }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(invalid).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("bad_fee") && msg.contains("fee")));
+    }
+
+    #[test]
+    fn test_action_operations() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "sanctioned"
+    when: { type: "equals", field: "country", value: "KP" }
+    then:
+      outcome: { decision: "review" }
+      operations:
+        - { op: "add_tag", tag: "sanctions" }
+        - { op: "set_outcome", key: "decision", value: "decline" }
+        - { op: "set_severity", level: "critical" }
+        - { op: "add_reason", code: "R001", message: "Country {{country}} is sanctioned" }
+        - { op: "add_tag", tag: "sanctions" }
+        - { op: "add_tag", tag: "manual" }
+        - { op: "add_reason", code: "R002" }
+        - { op: "set_outcome", key: "limit", value: { $expr: "amount * 2" } }
+  - id: "legacy"
+    when: { type: "exists", field: "amount" }
+    then:
+      outcome: { decision: "approve" }
+"#);
+        let decision = engine.evaluate(&payload(json!({"country": "KP", "amount": 10}))).unwrap().unwrap();
+        assert_eq!(decision.outcome, HashMap::from([
+            ("decision".to_string(), json!("decline")),
+            ("limit".to_string(), json!(20)),
+        ]));
+        assert_eq!(decision.tags, vec!["sanctions", "manual"]);
+        assert_eq!(decision.severity.as_deref(), Some("critical"));
+        assert_eq!(decision.reasons, vec![
+            Reason { code: "R001".to_string(), message: Some("Country KP is sanctioned".to_string()) },
+            Reason { code: "R002".to_string(), message: None },
+        ]);
+
+        // Old-format rules produce the same decision as before.
+        let decision = engine.evaluate(&payload(json!({"country": "FR", "amount": 10}))).unwrap().unwrap();
+        assert_eq!(decision.rule_id, "legacy");
+        assert_eq!(decision.outcome["decision"], json!("approve"));
+        assert!(decision.tags.is_empty() && decision.severity.is_none() && decision.reasons.is_empty());
+        let serialized = serde_json::to_value(&decision).unwrap();
+        for key in ["tags", "severity", "description", "reasons"] {
+            assert!(!serialized.as_object().unwrap().contains_key(key));
+        }
+        let legacy = serde_json::to_value(&engine.get_ruleset().unwrap().rules[1].then).unwrap();
+        assert_eq!(legacy, json!({"outcome": {"decision": "approve"}}));
+
+        let invalid = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "blank_tag"
+    when: { type: "exists", field: "amount" }
+    then: { operations: [{ op: "add_tag", tag: " " }] }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(invalid).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("blank_tag") && msg.contains("add_tag")));
+    }
+
+    #[test]
+    fn test_else_branch() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "vip"
+    when: { type: "equals", field: "tier", value: "vip" }
+    then: { outcome: { decision: "approve", path: "vip" } }
+  - id: "risk"
+    when: { type: "greater_than", field: "risk_score", value: 80 }
+    then: { outcome: { decision: "decline" } }
+    else_then:
+      outcome: { decision: "approve" }
+      operations: [{ op: "add_reason", code: "LOW_RISK", message: "score {{risk_score}}" }]
+"#);
+        let decision = engine.evaluate(&payload(json!({"risk_score": 95}))).unwrap().unwrap();
+        assert_eq!((decision.rule_id.as_str(), decision.branch), ("risk", Branch::Then));
+        assert_eq!(decision.outcome["decision"], json!("decline"));
+        assert!(!serde_json::to_value(&decision).unwrap().as_object().unwrap().contains_key("branch"));
+
+        let decision = engine.evaluate(&payload(json!({"risk_score": 20}))).unwrap().unwrap();
+        assert_eq!((decision.rule_id.as_str(), decision.branch), ("risk", Branch::Else));
+        assert_eq!(decision.outcome["decision"], json!("approve"));
+        assert_eq!(decision.reasons[0].message.as_deref(), Some("score 20"));
+        assert!(decision.matched_conditions.is_empty());
+        assert_eq!(serde_json::to_value(&decision).unwrap()["branch"], json!("else"));
+
+        // Earlier rules still win; the else branch also covers missing fields.
+        let decision = engine.evaluate(&payload(json!({"tier": "vip", "risk_score": 95}))).unwrap().unwrap();
+        assert_eq!(decision.rule_id, "vip");
+        let decision = engine.evaluate(&payload(json!({}))).unwrap().unwrap();
+        assert_eq!(decision.branch, Branch::Else);
+
+        let unreachable = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "risk"
+    when: { type: "greater_than", field: "risk_score", value: 80 }
+    then: { outcome: { decision: "decline" } }
+    else_then: { outcome: { decision: "approve" } }
+  - id: "never"
+    when: { type: "exists", field: "risk_score" }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(unreachable.clone()).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("risk") && msg.contains("never")));
+        let issues = RuleEngine::new().validate(&unreachable);
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].rule_id.as_deref(), issues[0].path.as_str()), (Some("risk"), "/rules/0/else_then"));
+
+        // A windowed else rule lets later rules take over outside the window.
+        let mut windowed = unreachable;
+        windowed.rules[0].effective_until = Some("2020-01-01T00:00:00Z".to_string());
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(windowed).unwrap();
+        let decision = engine.evaluate(&payload(json!({"risk_score": 10}))).unwrap().unwrap();
+        assert_eq!(decision.rule_id, "never");
+    }
+
+    const ELSE_THEN_FIRST: &str = r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "a"
+    when: { type: "greater_than", field: "risk_score", value: 80 }
+    then: { outcome: { decision: "decline" } }
+    else_then: { outcome: { decision: "approve" } }
+  - id: "b"
+    when: { type: "exists", field: "risk_score" }
+    then: { outcome: { decision: "review" } }
+"#;
+
+    #[test]
+    fn test_else_branch_off_rule_shadows_nothing() {
+        let mut disabled = parse_yaml(ELSE_THEN_FIRST).unwrap();
+        disabled.rules[0].enabled = false;
+        assert!(RuleEngine::new().load_ruleset(disabled).is_ok());
+
+        let mut draft = parse_yaml(ELSE_THEN_FIRST).unwrap();
+        draft.rules[0].status = RuleLifecycle::Draft;
+        assert!(RuleEngine::new().load_ruleset(draft).is_ok());
+    }
+
+    #[test]
+    fn test_else_branch_shadows_only_live_rules() {
+        let mut disabled = parse_yaml(ELSE_THEN_FIRST).unwrap();
+        disabled.rules[1].enabled = false;
+        assert!(RuleEngine::new().load_ruleset(disabled.clone()).is_ok());
+
+        // A live rule further on is still unreachable, and named.
+        let mut live = disabled.rules[1].clone();
+        live.id = "c".to_string();
+        live.enabled = true;
+        disabled.rules.push(live);
+        let err = RuleEngine::new().load_ruleset(disabled).unwrap_err();
+        assert!(err.to_string().contains("else_then makes rule c unreachable"), "{}", err);
+    }
+
+    #[test]
+    fn test_else_branch_under_collect() {
+        let mut collect = parse_yaml(ELSE_THEN_FIRST).unwrap();
+        collect.hit_policy = HitPolicy::Collect;
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(collect).unwrap();
+        let decision = engine.evaluate(&payload(json!({"risk_score": 10}))).unwrap().unwrap();
+        assert_eq!(decision.matched_conditions, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn test_outcome_schema() {
+        let schema = r#"
+version: "1.0"
+metadata: {}
+outcome_schema:
+  required: ["decision"]
+  properties:
+    decision: { type: "string", enum: ["approve", "decline", "review"] }
+    fee: { type: "number" }
+    note: { type: "string", enum: ["manual review", "auto approve"] }
+rules:
+"#;
+        let load = |rules: &str| RuleEngine::new().load_ruleset(parse_yaml(&format!("{}{}", schema, rules)).unwrap());
+
+        assert!(load(r#"
+  - id: "ok"
+    when: { type: "exists", field: "amount" }
+    then:
+      outcome: { decision: "review", fee: { $expr: "amount * 0.01" }, note: "{{kind}} review" }
+    else_then:
+      operations: [{ op: "set_outcome", key: "decision", value: "approve" }]
+"#).is_ok());
+
+        let err = load(r#"
+  - id: "typo"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { decision: "aprove", fee: "1.00" } }
+  - id: "missing"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { note: "{{kind}} approval" } }
+    else_then: { outcome: { decision: "review" } }
+"#).unwrap_err();
+        let EngineError::RuleValidation(msg) = err else { panic!("unexpected error: {:?}", err) };
+        assert!(msg.contains(r#"Rule typo: outcome 'decision' must be one of ["approve", "decline", "review"], got "aprove""#), "{}", msg);
+        assert!(msg.contains(r#"Rule typo: outcome 'fee' must be of type number, got "1.00""#), "{}", msg);
+        assert!(msg.contains("Rule missing: missing required outcome key 'decision'"), "{}", msg);
+        assert!(msg.contains("Rule missing: outcome 'note' must be one of"), "{}", msg);
+        assert!(!msg.contains("(else)"), "{}", msg);
+
+        // Without a schema any outcome shape loads.
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "typo"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { decision: "aprove" } }
+"#).unwrap()).unwrap();
+        assert!(engine.get_ruleset().unwrap().outcome_schema.is_none());
+    }
+
+    #[test]
+    fn test_regex_captures() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+definitions:
+  eu_locale: { type: "matches", field: "locale", pattern: "^[a-z]{2}[-_](?P<country>DE|FR|IT)$" }
+rules:
+  - id: "card"
+    when:
+      type: "and"
+      conditions:
+        - { type: "matches", field: "card", pattern: "^(?P<bin>\\d{6})\\d{6}(\\d{4})$" }
+        - { type: "matches", field: "locale", pattern: "^(?P<lang>[a-z]{2})[-_](?P<country>[A-Z])" }
+        - { type: "ref", name: "eu_locale" }
+        - { type: "not", condition: { type: "matches", field: "card", pattern: "^(?P<bin>0+)" } }
+    then:
+      outcome: { message: "BIN {{$captures.bin}} in {{$captures.country}}" }
+  - id: "language_only"
+    when: { type: "matches", field: "locale", pattern: "^(?P<lang>[a-z]{2})(?:[-_](?P<country>[A-Z]{2}))?$", case_insensitive: true }
+    then: { outcome: {} }
+"#);
+        let decision = engine.evaluate(&payload(json!({"card": "4111111111111234", "locale": "de-DE"}))).unwrap().unwrap();
+        assert_eq!(decision.rule_id, "card");
+        assert_eq!(decision.captures, HashMap::from([
+            ("bin".to_string(), "411111".to_string()),
+            ("2".to_string(), "1234".to_string()),
+            ("lang".to_string(), "de".to_string()),
+            ("country".to_string(), "DE".to_string()),
+        ]));
+        assert_eq!(decision.outcome["message"], json!("BIN 411111 in DE"));
+        assert!(!engine.get_ruleset().unwrap().referenced_fields().iter().any(|field| field.starts_with('$')));
+
+        // The optional country group did not participate, so it is absent.
+        let decision = engine.evaluate(&payload(json!({"locale": "EN"}))).unwrap().unwrap();
+        assert_eq!(decision.rule_id, "language_only");
+        assert_eq!(decision.captures, HashMap::from([("lang".to_string(), "EN".to_string())]));
+
+        assert!(engine.evaluate(&payload(json!({"locale": "english"}))).unwrap().is_none());
+
+        let invalid = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "bad_regex"
+    when: { type: "matches", field: "card", pattern: "(\\d+" }
+    then: { outcome: {} }
+"#).unwrap();
+        let err = RuleEngine::new().load_ruleset(invalid).unwrap_err();
+        assert!(matches!(err, EngineError::RuleValidation(ref msg) if msg.contains("bad_regex") && msg.contains("invalid pattern")));
+    }
+
+    #[test]
+    fn test_highest_priority_selection() {
+        let mut engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "generic"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { decision: "approve" } }
+  - id: "large_b"
+    priority: 10
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { decision: "review" } }
+  - id: "large_a"
+    priority: 10
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { decision: "review" } }
+  - id: "blocked"
+    priority: 100
+    when: { type: "equals", field: "blocked", value: true }
+    then: { outcome: { decision: "decline" } }
+  - id: "negative"
+    priority: -5
+    when: { type: "less_than", field: "amount", value: 0 }
+    then: { outcome: { decision: "decline" } }
+"#);
+        let event = payload(json!({"amount": 5000, "blocked": true}));
+        assert_eq!(engine.evaluate(&event).unwrap().unwrap().rule_id, "generic");
+
+        let options = EvalOptions { selection: Some(SelectionMode::HighestPriority), ..Default::default() };
+        let decide = |engine: &RuleEngine, event: serde_json::Value| {
+            engine.evaluate_with_options(&payload(event), &options).unwrap().unwrap().rule_id
+        };
+        assert_eq!(decide(&engine, json!({"amount": 5000, "blocked": true})), "blocked");
+        // Equal priorities fall back to document order, every time.
+        for _ in 0..10 {
+            assert_eq!(decide(&engine, json!({"amount": 5000})), "large_b");
+        }
+        // A missing priority ranks above negative ones.
+        assert_eq!(decide(&engine, json!({"amount": -1})), "generic");
+
+        engine.set_selection_mode(SelectionMode::HighestPriority);
+        assert_eq!(engine.evaluate(&event).unwrap().unwrap().rule_id, "blocked");
+        let first_match = EvalOptions { selection: Some(SelectionMode::FirstMatch), ..Default::default() };
+        assert_eq!(engine.evaluate_with_options(&event, &first_match).unwrap().unwrap().rule_id, "generic");
+        assert_eq!(SelectionMode::from_name("highest_priority"), Some(SelectionMode::HighestPriority));
+        assert_eq!(SelectionMode::from_name("priority"), None);
+    }
+
+    #[test]
+    fn test_evaluate_collect() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "new_account"
+    terminal: false
+    when: { type: "less_than", field: "account_age_days", value: 30 }
+    then:
+      outcome: { decision: "review", risk: "elevated" }
+      operations: [{ op: "add_tag", tag: "new_account" }, { op: "add_reason", code: "NEW" }]
+  - id: "foreign"
+    terminal: false
+    when: { type: "not_equals", field: "country", value: "US" }
+    then:
+      outcome: { risk: "high" }
+      operations: [{ op: "add_tag", tag: "foreign" }, { op: "add_tag", tag: "new_account" }]
+  - id: "blocked"
+    when: { type: "equals", field: "blocked", value: true }
+    then: { outcome: { decision: "decline" } }
+  - id: "fallback_note"
+    terminal: false
+    when: { type: "exists", field: "country" }
+    then: { outcome: { note: "checked" } }
+"#);
+        let collected = engine.evaluate_collect(&payload(json!({
+            "account_age_days": 3, "country": "FR", "blocked": true
+        }))).unwrap();
+        assert_eq!(collected.rule_ids, vec!["new_account", "foreign", "blocked"]);
+        assert_eq!(collected.stopped_by.as_deref(), Some("blocked"));
+        assert_eq!(collected.outcome, HashMap::from([
+            ("decision".to_string(), json!("decline")),
+            ("risk".to_string(), json!("high")),
+        ]));
+        assert_eq!(collected.tags, vec!["new_account", "foreign"]);
+        assert_eq!(collected.reasons.len(), 1);
+        let outcomes: Vec<_> = collected.decisions.iter().map(|d| d.outcome.get("risk").cloned()).collect();
+        assert_eq!(outcomes, vec![Some(json!("elevated")), Some(json!("high")), None]);
+
+        // Without a terminal match every matching rule contributes.
+        let collected = engine.evaluate_collect(&payload(json!({"account_age_days": 90, "country": "CA"}))).unwrap();
+        assert_eq!(collected.rule_ids, vec!["foreign", "fallback_note"]);
+        assert_eq!(collected.stopped_by, None);
+
+        // The terminal flag doesn't change single-decision evaluation.
+        let decision = engine.evaluate(&payload(json!({"account_age_days": 3, "country": "US"}))).unwrap().unwrap();
+        assert_eq!(decision.rule_id, "new_account");
+        assert!(!serde_json::to_string(&engine.get_ruleset().unwrap().rules[2]).unwrap().contains("terminal"));
+    }
+
+    #[test]
+    fn test_decision_carries_rule_metadata() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "sanctioned"
+    description: "Transfers to sanctioned countries"
+    severity: "critical"
+    tags: ["sanctions", "aml"]
+    when: { type: "equals", field: "country", value: "KP" }
+    then:
+      outcome: { decision: "decline" }
+      operations: [{ op: "add_tag", tag: "manual" }, { op: "add_tag", tag: "aml" }]
+  - id: "large"
+    severity: "low"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then:
+      outcome: { decision: "review" }
+      operations: [{ op: "set_severity", level: "high" }]
+"#);
+        let event = payload(json!({"country": "KP", "amount": 5000}));
+        let decision = engine.evaluate(&event).unwrap().unwrap();
+        assert_eq!(decision.description.as_deref(), Some("Transfers to sanctioned countries"));
+        assert_eq!(decision.severity.as_deref(), Some("critical"));
+        assert_eq!(decision.tags, vec!["sanctions", "aml", "manual"]);
+        let serialized = serde_json::to_value(&decision).unwrap();
+        assert_eq!(serialized["severity"], json!("critical"));
+        assert_eq!(serialized["tags"], json!(["sanctions", "aml", "manual"]));
+        assert_eq!(serialized["description"], json!("Transfers to sanctioned countries"));
+
+        // Every evaluation path fills them the same way.
+        let many = engine.evaluate_many(std::slice::from_ref(&event)).unwrap();
+        let collected = engine.evaluate_collect(&event).unwrap();
+        for other in [many[0].as_ref().unwrap(), &collected.decisions[0]] {
+            assert_eq!((&other.severity, &other.tags, &other.description), (&decision.severity, &decision.tags, &decision.description));
+        }
+
+        let decision = engine.evaluate(&payload(json!({"amount": 5000}))).unwrap().unwrap();
+        assert_eq!((decision.severity.as_deref(), decision.description), (Some("high"), None));
+        assert!(decision.tags.is_empty());
+    }
+
+    #[test]
+    fn test_explain_trace() {
+        let mut engine = engine(r#"
+version: "1.0"
+metadata: {}
+aliases:
+  amount: ["amt", "total"]
+definitions:
+  trusted: { type: "equals", field: "tier", value: "gold" }
+rules:
+  - id: "nested"
+    when:
+      type: "and"
+      conditions:
+        - { type: "greater_than", field: "amount", value: 100 }
+        - type: "or"
+          conditions:
+            - { type: "equals", field: "country", value: "FR" }
+            - type: "not"
+              condition: { type: "ref", name: "trusted" }
+        - { type: "exists", field: "device_id" }
+    then: { outcome: {} }
+  - id: "big"
+    when: { type: "greater_than", field: "amount", value: 50 }
+    then: { outcome: { decision: "review" } }
+  - id: "also_big"
+    when: { type: "greater_than", field: "amount", value: 10, default: 0 }
+    then: { outcome: { decision: "review" } }
+"#);
+        engine.set_coerce_numeric_strings(true);
+        let trace = engine.explain(&payload(json!({"total": "250", "tier": "gold", "country": "DE"}))).unwrap();
+        assert_eq!(trace.winner.as_deref(), Some("big"));
+        assert_eq!(trace.branch, Some(Branch::Then));
+        let statuses: Vec<_> = trace.rules.iter().map(|rule| (rule.rule_id.as_str(), rule.status)).collect();
+        assert_eq!(statuses, vec![
+            ("nested", RuleStatus::NotMatched),
+            ("big", RuleStatus::Matched),
+            ("also_big", RuleStatus::Skipped),
+        ]);
+
+        // Deeply nested: and -> or -> not -> ref -> equals.
+        let nested = trace.rules[0].condition.as_ref().unwrap();
+        assert_eq!((nested.condition_type.as_str(), nested.result), ("and", false));
+        let amount = &nested.children[0];
+        assert_eq!((amount.value.clone(), amount.expected.clone(), amount.result), (Some(json!("250")), Some(json!({"value": 100.0})), true));
+        assert_eq!(amount.notes, vec![
+            "'amount' is missing; resolved through alias 'total' (tried 'amt', 'total')",
+            "string \"250\" coerced to 250",
+        ]);
+        let tier = &nested.children[1].children[1].children[0].children[0];
+        assert_eq!((tier.field.as_deref(), tier.value.clone(), tier.result), (Some("tier"), Some(json!("gold")), true));
+        assert!(!nested.children[1].result);
+        // Missing field.
+        let device = &nested.children[2];
+        assert_eq!((device.value.clone(), device.result), (None, false));
+        assert_eq!(device.notes, vec!["field is missing"]);
+
+        // The skipped rule still reports whether it would have matched.
+        assert!(trace.rules[2].condition.as_ref().unwrap().result);
+        let trace = engine.explain(&payload(json!({}))).unwrap();
+        assert_eq!(trace.winner, None);
+        assert_eq!(trace.rules[2].status, RuleStatus::NotMatched);
+        assert_eq!(trace.rules[2].condition.as_ref().unwrap().notes, vec![
+            "'amount' is missing and so are its aliases 'amt', 'total'",
+            "field is missing; default 0 applied",
+        ]);
+
+        let serialized = serde_json::to_value(engine.explain(&payload(json!({"amount": 60}))).unwrap()).unwrap();
+        assert_eq!(serialized["winner"], json!("big"));
+        assert_eq!(serialized["rules"][1]["status"], json!("matched"));
+        assert_eq!(serialized["rules"][0]["condition"]["children"][0]["type"], json!("greater_than"));
+    }
+
+    #[test]
+    fn test_decision_ids_are_unique() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "any"
+    when: { type: "exists", field: "n" }
+    then: { outcome: {} }
+"#);
+        let events: Vec<_> = (0..5000).map(|n| payload(json!({"n": n}))).collect();
+        let mut ids: Vec<String> = engine.evaluate_many(&events).unwrap()
+            .into_iter()
+            .map(|decision| decision.unwrap().decision_id)
+            .collect();
+        let batch = ids.clone();
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| engine.evaluate_many(&events[..1000]).unwrap()))
+                .collect();
+            for worker in workers {
+                ids.extend(worker.join().unwrap().into_iter().map(|decision| decision.unwrap().decision_id));
+            }
+        });
+        let unique: std::collections::HashSet<_> = ids.iter().collect();
+        assert_eq!(unique.len(), ids.len());
+        // Ids within a batch sort in evaluation order.
+        let mut sorted = batch.clone();
+        sorted.sort();
+        assert_eq!(sorted, batch);
+
+        let decision = engine.evaluate(&payload(json!({"n": 1}))).unwrap().unwrap();
+        let serialized = serde_json::to_value(&decision).unwrap();
+        assert_eq!(serialized["decision_id"], json!(decision.decision_id));
+        assert_eq!(decision.decision_id.len(), 36);
+    }
+
+    #[test]
+    fn test_decision_timing() {
+        let mut rules = String::from("version: \"1.0\"\nmetadata: {}\nrules:\n");
+        for i in 0..200 {
+            rules.push_str(&format!(
+                "  - id: \"r{}\"\n    when: {{ type: \"equals\", field: \"n\", value: {} }}\n    then: {{ outcome: {{}} }}\n",
+                i, i
+            ));
+        }
+        let engine = engine(&rules);
+        for n in [0, 199] {
+            let decision = engine.evaluate(&payload(json!({"n": n}))).unwrap().unwrap();
+            assert!(decision.elapsed_ns > 0);
+            assert!(decision.elapsed_ns <= decision.total_elapsed_ns);
+            assert_eq!(decision.elapsed_us, decision.total_elapsed_ns / 1000);
+        }
+        let collected = engine.evaluate_collect(&payload(json!({"n": 5}))).unwrap();
+        let decision = &collected.decisions[0];
+        assert!(decision.elapsed_ns > 0 && decision.elapsed_ns <= decision.total_elapsed_ns);
+    }
+
+    #[test]
+    fn test_decision_json_round_trip() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "nested"
+    severity: "high"
+    when: { type: "exists", field: "amount" }
+    then:
+      outcome:
+        decision: "review"
+        limits: { daily: 500, tiers: [{ name: "a", rate: 0.5 }, { name: "b", rate: null }] }
+        fee: { $expr: "amount * 0.1" }
+      capture: ["amount"]
+      operations: [{ op: "add_reason", code: "R1", message: "amount {{amount}}" }]
+"#);
+        let decision = engine.evaluate(&payload(json!({"amount": 42}))).unwrap().unwrap();
+        let json = decision.to_json().unwrap();
+        assert_eq!(Decision::from_json(&json).unwrap(), decision);
+        assert_eq!(Decision::from_json(&decision.to_json_pretty().unwrap()).unwrap(), decision);
+        // Key order is stable, so the same decision always serializes the same way.
+        assert_eq!(Decision::from_json(&json).unwrap().to_json().unwrap(), json);
+        assert!(json.starts_with(r#"{"captured":{"amount":42},"decision_id":"#), "{}", json);
+        assert!(json.contains(r#""limits":{"daily":500,"tiers":[{"name":"a","rate":0.5},{"name":"b","rate":null}]}"#));
+
+        // Decisions serialized before newer fields existed still load.
+        let legacy = Decision::from_json(r#"{
+            "rule_id": "r", "outcome": {}, "matched_conditions": ["r"],
+            "elapsed_us": 3, "timestamp": 1700000000, "rule_sha": "abc"
+        }"#).unwrap();
+        assert_eq!((legacy.branch, legacy.elapsed_ns, legacy.decision_id.as_str()), (Branch::Then, 0, ""));
+        assert!(matches!(Decision::from_json("{}"), Err(EngineError::Parse(_))));
+    }
+
+    #[test]
+    fn test_decision_ruleset_version_and_metadata() {
+        let ruleset = |version: &str, policy: &str| parse_yaml(&format!(r#"
+version: "{}"
+metadata: {{ policy_name: "{}", owner: "risk-team" }}
+rules:
+  - id: "any"
+    when: {{ type: "exists", field: "amount" }}
+    then: {{ outcome: {{}} }}
+"#, version, policy)).unwrap();
+        let mut engine = RuleEngine::new();
+        engine.set_decision_metadata_keys(vec!["policy_name".to_string(), "region".to_string()]);
+        engine.load_ruleset(ruleset("2024.3", "cards")).unwrap();
+        let event = payload(json!({"amount": 1}));
+        let decision = engine.evaluate(&event).unwrap().unwrap();
+        assert_eq!(decision.ruleset_version, "2024.3");
+        assert_eq!(decision.metadata, HashMap::from([("policy_name".to_string(), json!("cards"))]));
+
+        engine.load_ruleset(ruleset("2024.4", "cards-v2")).unwrap();
+        let decision = engine.evaluate(&event).unwrap().unwrap();
+        assert_eq!(decision.ruleset_version, "2024.4");
+        assert_eq!(decision.metadata["policy_name"], json!("cards-v2"));
+        assert_eq!(serde_json::to_value(&decision).unwrap()["ruleset_version"], json!("2024.4"));
+
+        engine.set_decision_metadata_keys(Vec::new());
+        let decision = engine.evaluate(&event).unwrap().unwrap();
+        assert!(decision.metadata.is_empty());
+        assert!(!decision.to_json().unwrap().contains("\"metadata\""));
+    }
+
+    #[test]
+    fn test_payload_sha() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "any"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: {} }
+"#);
+        let first: serde_json::Value = serde_json::from_str(
+            r#"{"amount": 10.0, "customer": {"id": "c1", "tags": ["a", "b"], "address": {"zip": "75001", "city": "Paris"}}}"#,
+        ).unwrap();
+        let reordered: serde_json::Value = serde_json::from_str(
+            r#"{"customer": {"address": {"city": "Paris", "zip": "75001"}, "tags": ["a", "b"], "id": "c1"}, "amount": 10}"#,
+        ).unwrap();
+        let mut map = HashMap::new();
+        for key in ["customer", "amount"] {
+            map.insert(key.to_string(), reordered[key].clone());
+        }
+        let sha = engine.evaluate_value(&first).unwrap().unwrap().payload_sha;
+        assert_eq!(sha.len(), 64);
+        assert_eq!(engine.evaluate_value(&reordered).unwrap().unwrap().payload_sha, sha);
+        assert_eq!(engine.evaluate(&map).unwrap().unwrap().payload_sha, sha);
+        assert_eq!(crate::payload_sha(&first), sha);
+        assert_eq!(
+            crate::canonical_json(&first),
+            r#"{"amount":10,"customer":{"address":{"city":"Paris","zip":"75001"},"id":"c1","tags":["a","b"]}}"#
+        );
+
+        // Nested values and array order still matter.
+        let changed = json!({"amount": 10, "customer": {"id": "c1", "tags": ["b", "a"], "address": {"zip": "75001", "city": "Paris"}}});
+        assert_ne!(engine.evaluate_value(&changed).unwrap().unwrap().payload_sha, sha);
+        let changed = json!({"amount": 10.5, "customer": {"id": "c1", "tags": ["a", "b"], "address": {"zip": "75001", "city": "Paris"}}});
+        assert_ne!(engine.evaluate_value(&changed).unwrap().unwrap().payload_sha, sha);
+    }
+
+    #[test]
+    fn test_evaluate_detailed_no_match() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+definitions:
+  verified: { type: "equals", field: "kyc", value: "verified" }
+rules:
+  - id: "large_foreign"
+    when:
+      type: "and"
+      conditions:
+        - { type: "greater_than", field: "amount", value: 1000 }
+        - { type: "not_equals", field: "country", value: "US" }
+    then: { outcome: { action: "review" } }
+  - id: "unverified"
+    when:
+      type: "or"
+      conditions:
+        - { type: "not", condition: { type: "ref", name: "verified" } }
+        - { type: "exists", field: "chargeback_id" }
+    then: { outcome: { action: "block" } }
+  - id: "night"
+    when:
+      type: "and"
+      conditions:
+        - { type: "ref", name: "verified" }
+        - { type: "in", field: "hour", values: [0, 1, 2] }
+    then: { outcome: { action: "hold" } }
+  - id: "expired"
+    effective_until: "2020-01-01T00:00:00Z"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { action: "legacy" } }
+"#);
+        let event = payload(json!({"amount": 5000, "country": "US", "kyc": "verified", "hour": 14}));
+        let Evaluation::NoMatch(no_match) = engine.evaluate_detailed(&event).unwrap() else {
+            panic!("expected no match");
+        };
+        let failed: Vec<_> = no_match.rules.iter()
+            .map(|miss| miss.failed.as_ref().map(|f| (f.condition_type.as_str(), f.field.as_deref(), f.actual.clone())))
+            .collect();
+        assert_eq!(failed, vec![
+            Some(("not_equals", Some("country"), Some(json!("US")))),
+            Some(("not", None, None)),
+            Some(("in", Some("hour"), Some(json!(14)))),
+            None,
+        ]);
+        assert_eq!(no_match.rules[0].failed.as_ref().unwrap().expected.as_ref().unwrap()["value"], json!("US"));
+        assert_eq!(no_match.rules[3].status, RuleStatus::NotEffective);
+
+        // A missing field is reported without an actual value.
+        let Evaluation::NoMatch(no_match) = engine.evaluate_detailed(&payload(json!({"kyc": "verified"}))).unwrap() else {
+            panic!("expected no match");
+        };
+        let first = no_match.rules[0].failed.as_ref().unwrap();
+        assert_eq!((first.field.as_deref(), first.actual.clone()), (Some("amount"), None));
+
+        let event = payload(json!({"amount": 5000, "country": "FR"}));
+        assert!(matches!(engine.evaluate_detailed(&event).unwrap(), Evaluation::Matched(d) if d.rule_id == "large_foreign"));
+        let serialized = serde_json::to_value(engine.evaluate_detailed(&payload(json!({"kyc": "verified"}))).unwrap()).unwrap();
+        assert_eq!(serialized["result"], json!("no_match"));
+        assert_eq!(serialized["rules"][2]["failed"]["field"], json!("hour"));
+    }
+
+    #[test]
+    fn test_evaluate_score() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+score_thresholds:
+  - { label: "approve" }
+  - { min: 50, label: "review" }
+  - { min: 80, label: "decline" }
+rules:
+  - id: "new_account"
+    score: 30
+    when: { type: "less_than", field: "account_age_days", value: 30 }
+    then: { outcome: {} }
+  - id: "large_amount"
+    score: 45.5
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: {} }
+  - id: "trusted_device"
+    score: -20
+    when: { type: "equals", field: "device_trusted", value: true }
+    then: { outcome: {} }
+  - id: "foreign"
+    score: 10
+    when: { type: "not_equals", field: "country", value: "US" }
+    then: { outcome: {} }
+  - id: "unscored"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: {} }
+"#);
+        let result = engine.evaluate_score(&payload(json!({
+            "account_age_days": 3, "amount": 5000, "device_trusted": true, "country": "US"
+        }))).unwrap();
+        assert_eq!(result.total, 55.5);
+        assert_eq!(result.matched, vec!["new_account", "large_amount", "trusted_device", "unscored"]);
+        assert_eq!(result.contributions[2], ("trusted_device".to_string(), -20.0));
+        assert_eq!(result.contributions[3], ("unscored".to_string(), 0.0));
+        assert_eq!(result.label.as_deref(), Some("review"));
+
+        let result = engine.evaluate_score(&payload(json!({
+            "account_age_days": 3, "amount": 5000, "country": "FR"
+        }))).unwrap();
+        assert_eq!((result.total, result.label.as_deref()), (85.5, Some("decline")));
+
+        // Negative totals fall through to the unbounded first threshold.
+        let result = engine.evaluate_score(&payload(json!({"device_trusted": true, "country": "US"}))).unwrap();
+        assert_eq!((result.total, result.label.as_deref()), (-20.0, Some("approve")));
+
+        let result = engine.evaluate_score(&payload(json!({"country": "US", "account_age_days": 400}))).unwrap();
+        assert_eq!(result, ScoreResult { label: Some("approve".to_string()), ..Default::default() });
+
+        let mut engine = RuleEngine::new();
+        let unordered = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+score_thresholds: [{ min: 80, label: "decline" }, { min: 50, label: "review" }]
+rules: []
+"#).unwrap();
+        let err = engine.load_ruleset(unordered).unwrap_err().to_string();
+        assert!(err.contains("'review' (50) follows 'decline' (80)"), "{}", err);
+    }
+
+    #[test]
+    fn test_evaluate_all() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "review" } }
+  - id: "expired"
+    effective_until: "2020-01-01T00:00:00Z"
+    when: { type: "greater_than", field: "amount", value: 0 }
+    then: { outcome: { action: "legacy" } }
+  - id: "very_large"
+    when: { type: "greater_than", field: "amount", value: 10000 }
+    then: { outcome: { action: "block" } }
+  - id: "foreign"
+    when: { type: "not_equals", field: "country", value: "US" }
+    then: { outcome: { action: "flag" } }
+"#);
+        let event = payload(json!({"amount": 50000, "country": "FR"}));
+        let decisions = engine.evaluate_all(&event).unwrap();
+        let ids: Vec<_> = decisions.iter().map(|d| d.rule_id.as_str()).collect();
+        assert_eq!(ids, vec!["large", "very_large", "foreign"]);
+        assert_eq!(decisions[1].outcome["action"], json!("block"));
+        assert_eq!(decisions[2].matched_conditions, vec!["foreign"]);
+        assert!(decisions.iter().all(|d| d.payload_sha == decisions[0].payload_sha));
+        assert_eq!(engine.evaluate(&event).unwrap().unwrap().rule_id, decisions[0].rule_id);
+
+        let event = payload(json!({"amount": 5000, "country": "US"}));
+        let decisions = engine.evaluate_all(&event).unwrap();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(engine.evaluate(&event).unwrap().unwrap().rule_id, decisions[0].rule_id);
+
+        assert!(engine.evaluate_all(&payload(json!({"amount": 5, "country": "US"}))).unwrap().is_empty());
+    }
+
+    const TAGGED_RULES: &str = r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "sanctions"
+    tags: ["kyc", "compliance"]
+    when: { type: "exists", field: "name" }
+    then: { outcome: { action: "screen" } }
+  - id: "velocity"
+    tags: ["fraud"]
+    when: { type: "exists", field: "name" }
+    then: { outcome: { action: "throttle" } }
+  - id: "document_check"
+    tags: ["kyc"]
+    when: { type: "exists", field: "name" }
+    then: { outcome: { action: "verify" } }
+  - id: "untagged"
+    when: { type: "exists", field: "name" }
+    then: { outcome: { action: "log" } }
+"#;
+
+    fn tags(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    /// The rule of [`TAGGED_RULES`] deciding an event under a tag filter.
+    fn tag_winner(include: &[&str], exclude: &[&str]) -> Option<String> {
+        engine(TAGGED_RULES)
+            .evaluate_with_tags(&payload(json!({"name": "Ada"})), &tags(include), &tags(exclude))
+            .unwrap()
+            .map(|decision| decision.rule_id)
+    }
+
+    #[test]
+    fn test_evaluate_with_tags_include() {
+        assert_eq!(tag_winner(&[], &[]).as_deref(), Some("sanctions"));
+        assert_eq!(tag_winner(&["fraud"], &[]).as_deref(), Some("velocity"));
+        assert_eq!(tag_winner(&["fraud", "kyc"], &[]).as_deref(), Some("sanctions"));
+    }
+
+    #[test]
+    fn test_evaluate_with_tags_exclude_wins_over_include() {
+        assert_eq!(tag_winner(&["kyc"], &["compliance"]).as_deref(), Some("document_check"));
+        assert_eq!(tag_winner(&["kyc"], &["kyc"]), None);
+    }
+
+    #[test]
+    fn test_evaluate_with_tags_untagged_rules() {
+        assert_eq!(tag_winner(&[], &["kyc", "fraud"]).as_deref(), Some("untagged"));
+        assert_eq!(tag_winner(&["kyc", "fraud", "compliance"], &["kyc", "fraud"]), None);
+    }
+
+    #[test]
+    fn test_evaluate_with_unknown_tags() {
+        assert_eq!(tag_winner(&["aml"], &[]), None);
+        assert_eq!(tag_winner(&[], &["aml"]).as_deref(), Some("sanctions"));
+    }
+
+    #[test]
+    fn test_evaluate_many_with_tags() {
+        let event = payload(json!({"name": "Ada"}));
+        let events = vec![event.clone(), payload(json!({})), event];
+        let batch = engine(TAGGED_RULES).evaluate_many_with_tags(&events, &tags(&["fraud", "kyc"]), &tags(&["compliance"])).unwrap();
+        let ids: Vec<_> = batch.iter().map(|d| d.as_ref().map(|d| d.rule_id.as_str())).collect();
+        assert_eq!(ids, vec![Some("velocity"), None, Some("velocity")]);
+    }
+
+    #[test]
+    fn test_evaluate_groups_with_options() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+groups:
+  - { name: "promotions", hit_policy: "all_matches" }
+rules:
+  - id: "spring_sale"
+    group: "promotions"
+    effective_until: "2024-01-01T00:00:00Z"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { discount: 0.1 } }
+  - id: "trial_offer"
+    group: "promotions"
+    status: "draft"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { discount: 0.2 } }
+"#);
+        let event = payload(json!({"amount": 50}));
+        assert!(engine.evaluate_group("promotions", &event).unwrap().decisions.is_empty());
+
+        let options = EvalOptions {
+            now: temporal::parse_timestamp_str("2023-06-01T00:00:00Z"),
+            include_drafts: true,
+            ..Default::default()
+        };
+        let group = engine.evaluate_group_with_options("promotions", &event, &options).unwrap();
+        let ids: Vec<&str> = group.decisions.iter().map(|decision| decision.rule_id.as_str()).collect();
+        assert_eq!(ids, vec!["spring_sale", "trial_offer"]);
+        let pipeline = engine.evaluate_pipeline_with_options(&event, &options).unwrap();
+        let decided: Vec<(&str, u64)> = pipeline.groups[0].decisions.iter()
+            .map(|decision| (decision.rule_id.as_str(), decision.timestamp))
+            .collect();
+        assert_eq!(decided, vec![("spring_sale", 1_685_577_600), ("trial_offer", 1_685_577_600)]);
+    }
+
+    #[test]
+    fn test_evaluate_pipeline() {
+        let engine = engine(r#"
+version: "1.0"
+metadata: {}
+groups:
+  - { name: "eligibility" }
+  - { name: "pricing", hit_policy: "all_matches" }
+  - { name: "compliance" }
+rules:
+  - id: "underage"
+    group: "eligibility"
+    when: { type: "less_than", field: "age", value: 18 }
+    then: { outcome: { eligible: false } }
+  - id: "loyalty_discount"
+    group: "pricing"
+    when: { type: "greater_than", field: "orders", value: 10 }
+    then: { outcome: { discount: 0.1 } }
+  - id: "bulk_discount"
+    group: "pricing"
+    when: { type: "greater_than", field: "quantity", value: 100 }
+    then: { outcome: { discount: 0.05 } }
+  - id: "export_control"
+    group: "compliance"
+    when: { type: "in", field: "country", values: ["KP", "IR"] }
+    then: { outcome: { blocked: true } }
+  - id: "sanctions_screen"
+    group: "compliance"
+    when: { type: "exists", field: "country" }
+    then: { outcome: { screened: true } }
+  - id: "ungrouped"
+    when: { type: "exists", field: "age" }
+    then: { outcome: {} }
+"#);
+        let event = payload(json!({"age": 30, "orders": 25, "quantity": 500, "country": "FR"}));
+        let pipeline = engine.evaluate_pipeline(&event).unwrap();
+        let stages: Vec<(&str, Vec<&str>)> = pipeline.groups.iter()
+            .map(|result| (result.group.as_str(), result.decisions.iter().map(|d| d.rule_id.as_str()).collect()))
+            .collect();
+        assert_eq!(stages, vec![
+            ("eligibility", vec![]),
+            ("pricing", vec!["loyalty_discount", "bulk_discount"]),
+            ("compliance", vec!["sanctions_screen"]),
+        ]);
+        assert_eq!(pipeline.group("compliance").unwrap().decisions[0].outcome["screened"], json!(true));
+
+        let compliance = engine.evaluate_group("compliance", &payload(json!({"country": "KP"}))).unwrap();
+        assert_eq!(compliance.decisions.len(), 1);
+        assert_eq!(compliance.decisions[0].rule_id, "export_control");
+        assert!(engine.evaluate_group("billing", &event).unwrap_err().to_string().contains("Unknown group: billing"));
+
+        let mut engine = RuleEngine::new();
+        let err = engine.load_ruleset(parse_yaml(r#"
+version: "1.0"
+metadata: {}
+groups: [{ name: "pricing" }]
+rules:
+  - id: "fee"
+    group: "prcing"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: {} }
+"#).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("Rule fee: unknown group 'prcing'"), "{}", err);
+    }
+
+    const DEFAULT_ACTION_RULES: &str = r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "review" } }
+"#;
+
+    fn with_default_action() -> String {
+        format!("{}default_action: {{ outcome: {{ action: \"approve\" }}, operations: [{{ op: \"add_reason\", code: \"DEFAULT\" }}] }}\n", DEFAULT_ACTION_RULES)
+    }
+
+    #[test]
+    fn test_default_action_applies_when_no_rule_decides() {
+        assert_eq!(engine(DEFAULT_ACTION_RULES).evaluate(&payload(json!({"amount": 5}))).unwrap(), None);
+        let decision = engine(&with_default_action()).evaluate(&payload(json!({"amount": 5}))).unwrap().unwrap();
+        assert_eq!(decision.rule_id, DEFAULT_RULE_ID);
+        assert_eq!(decision.outcome["action"], json!("approve"));
+        assert_eq!(decision.reasons[0].code, "DEFAULT");
+        assert!(decision.matched_conditions.is_empty());
+    }
+
+    #[test]
+    fn test_default_action_yields_to_matching_rules() {
+        let engine = engine(&with_default_action());
+        let decision = engine.evaluate(&payload(json!({"amount": 5000}))).unwrap().unwrap();
+        assert_eq!(decision.rule_id, "large");
+        let all = engine.evaluate_all(&payload(json!({"amount": 5000}))).unwrap();
+        assert_eq!(all.len(), 1);
+    }
+
+    #[test]
+    fn test_default_action_sha() {
+        // Rulesets without a default are hashed exactly as before.
+        assert!(!serde_json::to_string(&parse_yaml(DEFAULT_ACTION_RULES).unwrap()).unwrap().contains("default_action"));
+        let plain = engine(DEFAULT_ACTION_RULES);
+        assert_eq!(plain.get_ruleset_sha(), engine(DEFAULT_ACTION_RULES).get_ruleset_sha());
+        assert_ne!(plain.get_ruleset_sha(), engine(&with_default_action()).get_ruleset_sha());
+    }
+
+    #[test]
+    fn test_default_action_validation() {
+        let mut engine = RuleEngine::new();
+        let reserved = DEFAULT_ACTION_RULES.replace("\"large\"", "\"__default__\"");
+        assert!(engine.load_ruleset(parse_yaml(&reserved).unwrap()).unwrap_err().to_string().contains("reserved"));
+        let shadowed = format!("{}    else_then: {{ outcome: {{ action: \"approve\" }} }}\n{}", DEFAULT_ACTION_RULES,
+            "default_action: { outcome: { action: \"approve\" } }\n");
+        let err = engine.load_ruleset(parse_yaml(&shadowed).unwrap()).unwrap_err().to_string();
+        assert!(err.contains("else_then makes the default_action unreachable"), "{}", err);
+    }
+
+    const CHAIN_RULES: &str = r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "gold_tier"
+    terminal: false
+    when: { type: "greater_than", field: "total_spend", value: 1000 }
+    then: { outcome: { customer_tier: "gold" } }
+  - id: "gold_discount"
+    when: { type: "equals", field: "customer_tier", value: "gold" }
+    then: { outcome: { discount: 0.2 } }
+  - id: "mark_reviewed"
+    terminal: false
+    when: { type: "exists", field: "total_spend" }
+    then: { outcome: { reviewed: true } }
+"#;
+
+    #[test]
+    fn test_evaluate_chain_two_steps() {
+        let event = payload(json!({"total_spend": 5000}));
+        let chain = engine(CHAIN_RULES).evaluate_chain(&event).unwrap();
+        let steps: Vec<_> = chain.steps.iter().map(|step| (step.rule_id.as_str(), step.delta.clone())).collect();
+        assert_eq!(steps, vec![("gold_tier", payload(json!({"customer_tier": "gold"})))]);
+        assert_eq!(chain.decision.unwrap().rule_id, "gold_discount");
+        assert_eq!(chain.passes, 2);
+        assert_eq!(chain.payload["customer_tier"], json!("gold"));
+        assert_eq!(event, payload(json!({"total_spend": 5000})));
+    }
+
+    #[test]
+    fn test_evaluate_chain_settles_without_a_terminal_match() {
+        let chain = engine(CHAIN_RULES).evaluate_chain(&payload(json!({"total_spend": 10}))).unwrap();
+        assert_eq!(chain.steps.len(), 1);
+        assert_eq!(chain.steps[0].rule_id, "mark_reviewed");
+        assert!(chain.decision.is_none());
+        assert_eq!(chain.passes, 2);
+    }
+
+    #[test]
+    fn test_evaluate_chain_with_options() {
+        let windowed = CHAIN_RULES.replace(
+            "  - id: \"gold_discount\"\n",
+            "  - id: \"gold_discount\"\n    effective_until: \"2024-01-01T00:00:00Z\"\n",
+        );
+        let engine = engine(&windowed);
+        let event = payload(json!({"total_spend": 5000}));
+        assert!(engine.evaluate_chain(&event).unwrap().decision.is_none());
+
+        let options = EvalOptions { now: temporal::parse_timestamp_str("2023-06-01T00:00:00Z"), ..Default::default() };
+        let decision = engine.evaluate_chain_with_options(&event, &options).unwrap().decision.unwrap();
+        assert_eq!((decision.rule_id.as_str(), decision.timestamp), ("gold_discount", 1_685_577_600));
+    }
+
+    #[test]
+    fn test_evaluate_chain_pass_limit() {
+        let mut engine = engine(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "enable"
+    terminal: false
+    when: { type: "equals", field: "flag", value: false }
+    then: { outcome: { flag: true } }
+  - id: "disable"
+    terminal: false
+    when: { type: "equals", field: "flag", value: true }
+    then: { outcome: { flag: false } }
+"#);
+        engine.set_max_chain_passes(5);
+        let event = payload(json!({"flag": true}));
+        let err = engine.evaluate_chain(&event).unwrap_err().to_string();
+        assert!(err.contains("after 5 passes; rules firing: enable, disable"), "{}", err);
+        assert_eq!(event, payload(json!({"flag": true})));
+    }
+
+    fn hit_policy_table(policy: &str, priorities: [i32; 3]) -> String {
+        format!(r#"
+version: "1.0"
+metadata: {{}}
+hit_policy: "{}"
+rules:
+  - id: "adult"
+    priority: {}
+    tags: ["age"]
+    when: {{ type: "greater_than_or_equal", field: "age", value: 18 }}
+    then: {{ outcome: {{ adult: true, band: "adult" }} }}
+  - id: "senior"
+    priority: {}
+    tags: ["age", "discount"]
+    when: {{ type: "greater_than_or_equal", field: "age", value: 65 }}
+    then: {{ outcome: {{ band: "senior", discount: 0.3 }} }}
+  - id: "minor"
+    priority: {}
+    when: {{ type: "less_than", field: "age", value: 18 }}
+    then: {{ outcome: {{ band: "minor" }} }}
+"#, policy, priorities[0], priorities[1], priorities[2])
+    }
+
+    fn hit_policy_winner(yaml: &str, age: Option<i64>) -> Result<Option<String>, EngineError> {
+        let event = payload(age.map_or(json!({}), |age| json!({"age": age})));
+        engine(yaml).evaluate(&event).map(|d| d.map(|d| d.rule_id))
+    }
+
+    #[test]
+    fn test_hit_policy_first() {
+        assert_eq!(hit_policy_winner(&hit_policy_table("first", [0, 5, 0]), Some(70)).unwrap().as_deref(), Some("adult"));
+    }
+
+    #[test]
+    fn test_hit_policy_priority() {
+        assert_eq!(hit_policy_winner(&hit_policy_table("priority", [0, 5, 0]), Some(70)).unwrap().as_deref(), Some("senior"));
+
+        let unprioritized = hit_policy_table("priority", [1, 2, 3]).replace("    priority: 3\n", "");
+        let err = RuleEngine::new().load_ruleset(parse_yaml(&unprioritized).unwrap()).unwrap_err().to_string();
+        assert!(err.contains("requires a priority on every rule; missing on: minor"), "{}", err);
+    }
+
+    #[test]
+    fn test_hit_policy_collect() {
+        let decision = engine(&hit_policy_table("collect", [0, 0, 0])).evaluate(&payload(json!({"age": 70}))).unwrap().unwrap();
+        assert_eq!(decision.rule_id, "adult");
+        assert_eq!(decision.matched_conditions, vec!["adult", "senior"]);
+        assert_eq!(decision.outcome, payload(json!({"adult": true, "band": "senior", "discount": 0.3})));
+        assert_eq!(decision.tags, vec!["age", "discount"]);
+    }
+
+    #[test]
+    fn test_hit_policy_unique() {
+        let unique = hit_policy_table("unique", [0, 0, 0]);
+        assert_eq!(hit_policy_winner(&unique, Some(30)).unwrap().as_deref(), Some("adult"));
+        assert_eq!(hit_policy_winner(&unique, None).unwrap(), None);
+        let err = hit_policy_winner(&unique, Some(70)).unwrap_err().to_string();
+        assert_eq!(err, "Execution error: Hit policy unique violated: rules adult, senior all match");
+    }
+
+    #[test]
+    fn test_hit_policy_sha() {
+        // The policy is part of the ruleset SHA; the default is omitted.
+        let sha = |policy: &str| {
+            let yaml = format!("version: \"1.0\"\nmetadata: {{}}\n{}rules: []\n", policy);
+            engine(&yaml).get_ruleset_sha().cloned()
+        };
+        assert_ne!(sha("hit_policy: \"first\"\n"), sha("hit_policy: \"collect\"\n"));
+        assert_eq!(sha("hit_policy: \"first\"\n"), sha(""));
+    }
+
+    #[test]
+    fn test_add_and_remove_rule() {
+        let mut engine = engine(r#"
+version: "1.0"
+metadata: {}
+constants:
+  LIMIT: 1000
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: { $const: "LIMIT" } }
+    then: { outcome: { action: "review" } }
+"#);
+        let event = payload(json!({"amount": 5000, "country": "KP"}));
+        let original_sha = engine.get_ruleset_sha().cloned();
+        let block: Rule = serde_json::from_value(json!({
+            "id": "emergency_block",
+            "when": { "type": "equals", "field": "country", "value": "KP" },
+            "then": { "outcome": { "action": "block" } }
+        })).unwrap();
+
+        engine.add_rule(block.clone(), None).unwrap();
+        assert_eq!(engine.evaluate(&event).unwrap().unwrap().rule_id, "large");
+        let appended_sha = engine.get_ruleset_sha().cloned();
+        assert_ne!(appended_sha, original_sha);
+
+        engine.remove_rule("emergency_block").unwrap();
+        assert_eq!(engine.get_ruleset_sha().cloned(), original_sha);
+        engine.add_rule(block.clone(), Some(0)).unwrap();
+        assert_eq!(engine.evaluate(&event).unwrap().unwrap().rule_id, "emergency_block");
+        assert_ne!(engine.get_ruleset_sha().cloned(), appended_sha);
+
+        // Constant references move with their rule, so the SHA still hashes
+        // `{"$const": "LIMIT"}` in the right place.
+        let removed = engine.remove_rule("emergency_block").unwrap();
+        assert_eq!(removed.id, "emergency_block");
+        assert_eq!(engine.get_ruleset_sha().cloned(), original_sha);
+
+        let err = engine.add_rule(engine.get_ruleset().unwrap().rules[0].clone(), None).unwrap_err();
+        assert_eq!(err.to_string(), "Rule validation error: Duplicate rule ID: large");
+        assert!(engine.add_rule(block, Some(5)).unwrap_err().to_string().contains("position 5 is past the end"));
+        assert_eq!(engine.remove_rule("missing").unwrap_err().to_string(), "Execution error: Unknown rule: missing");
+        assert_eq!(engine.get_ruleset_sha().cloned(), original_sha);
+        assert!(RuleEngine::new().remove_rule("large").is_err());
+    }
+
+    #[test]
+    fn test_add_rule_with_constants() {
+        let source = r#"
+version: "1.0"
+metadata: {}
+constants:
+  LIMIT: 1000
+  BLOCKED: ["KP", "IR"]
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: { $const: "LIMIT" } }
+    then: { outcome: { action: "review" } }
+"#;
+        // The SHA is the one of the same document loaded directly.
+        let direct = source.replace("rules:\n", r#"rules:
+  - id: "blocked"
+    when: { type: "in", field: "country", values: { $const: "BLOCKED" } }
+    then: { outcome: { action: "block" } }
+"#);
+        let direct_sha = engine(&direct).get_ruleset_sha().cloned();
+
+        let mut engine = engine(source);
+        engine.add_rule_value(json!({
+            "id": "blocked",
+            "when": { "type": "in", "field": "country", "values": { "$const": "BLOCKED" } },
+            "then": { "outcome": { "action": "block" } }
+        }), Some(0)).unwrap();
+        assert_eq!(engine.evaluate(&payload(json!({"amount": 5, "country": "IR"}))).unwrap().unwrap().rule_id, "blocked");
+        assert_eq!(engine.get_ruleset_sha().cloned(), direct_sha);
+
+        let err = engine.add_rule_value(json!({
+            "id": "small",
+            "when": { "type": "less_than", "field": "amount", "value": { "$const": "MINIMUM" } },
+            "then": { "outcome": {} }
+        }), None).unwrap_err();
+        assert_eq!(err.to_string(), "Rule validation error: Rule small: unknown constant 'MINIMUM' at /when/value");
+    }
+
+    #[test]
+    fn test_rule_enabled() {
+        let source = r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "noisy"
+    enabled: false
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { action: "block" } }
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "review" } }
+"#;
+        let enabled_sha = engine(&source.replace("    enabled: false\n", "")).get_ruleset_sha().cloned();
+        let mut engine = engine(source);
+        let event = payload(json!({"amount": 5000}));
+        assert_eq!(engine.evaluate(&event).unwrap().unwrap().rule_id, "large");
+        let trace = engine.explain(&event).unwrap();
+        assert_eq!(trace.rules[0].status, RuleStatus::Disabled);
+        assert!(trace.rules[0].condition.is_none());
+        assert_eq!(serde_json::to_value(&trace).unwrap()["rules"][0]["status"], json!("disabled"));
+
+        // Disabled rules are still hashed and validated.
+        assert_ne!(engine.get_ruleset_sha().cloned(), enabled_sha);
+        let invalid = source.replace("type: \"exists\", field: \"amount\"", "type: \"matches\", field: \"name\", pattern: \"(\"");
+        assert!(RuleEngine::new().load_ruleset(parse_yaml(&invalid).unwrap()).is_err());
+
+        engine.set_rule_enabled("noisy", true).unwrap();
+        assert_eq!(engine.evaluate(&event).unwrap().unwrap().rule_id, "noisy");
+        assert_eq!(engine.get_ruleset_sha().cloned(), enabled_sha);
+        engine.set_rule_enabled("large", false).unwrap();
+        engine.set_rule_enabled("noisy", false).unwrap();
+        assert!(engine.evaluate_all(&event).unwrap().is_empty());
+        assert_eq!(engine.evaluate_score(&event).unwrap().matched.len(), 0);
+        assert!(!engine.get_ruleset().unwrap().rules[1].enabled);
+        let err = engine.set_rule_enabled("missing", true).unwrap_err();
+        assert_eq!(err.to_string(), "Execution error: Unknown rule: missing");
+    }
+
+    #[test]
+    fn test_rule_introspection() {
+        let mut engine = RuleEngine::new();
+        assert!(engine.list_rules().is_empty());
+        assert_eq!((engine.ruleset_version(), engine.metadata()), (None, None));
+
+        let source = r#"
+version: "2.3"
+metadata: { owner: "risk" }
+rules:
+  - id: "low"
+    priority: 1
+    description: "Low priority"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { action: "log" } }
+  - id: "high"
+    priority: 9
+    tags: ["fraud"]
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "block" } }
+"#;
+        let loaded = parse_yaml(source).unwrap();
+        engine.load_ruleset(loaded.clone()).unwrap();
+        assert_eq!(engine.list_rule_ids(), vec!["low", "high"]);
+        assert_eq!(engine.ruleset_version(), Some("2.3"));
+        assert_eq!(engine.metadata().unwrap()["owner"], json!("risk"));
+        let high = engine.get_rule("high").unwrap();
+        assert_eq!(serde_json::to_value(high).unwrap(), serde_json::to_value(&loaded.rules[1]).unwrap());
+        assert!(engine.get_rule("missing").is_none());
+
+        engine.set_selection_mode(SelectionMode::HighestPriority);
+        assert_eq!(engine.list_rule_ids(), vec!["high", "low"]);
+
+        engine.set_rule_enabled("low", false).unwrap();
+        assert!(!engine.get_rule("low").unwrap().enabled);
+        let mut medium = loaded.rules[0].clone();
+        medium.id = "medium".to_string();
+        engine.add_rule(medium, None).unwrap();
+        engine.remove_rule("low").unwrap();
+        assert_eq!(engine.list_rule_ids(), vec!["high", "medium"]);
+        assert_eq!(engine.get_rule("medium").unwrap().description.as_deref(), Some("Low priority"));
+    }
+
+    fn merge_base() -> RuleSet {
+        parse_yaml(r#"
+version: "1.4"
+metadata: { owner: "risk", region: "global" }
+definitions:
+  large: { type: "greater_than", field: "amount", value: 1000 }
+rules:
+  - id: "sanctions"
+    when: { type: "in", field: "country", values: ["KP", "IR"] }
+    then: { outcome: { action: "block" } }
+  - id: "large"
+    when: { type: "ref", name: "large" }
+    then: { outcome: { action: "review" } }
+"#).unwrap()
+    }
+
+    /// Collides with [`merge_base`] on rule `large` and metadata `region`.
+    fn merge_overlay() -> RuleSet {
+        parse_yaml(r#"
+version: "eu.2"
+metadata: { region: "eu", team: "payments" }
+rules:
+  - id: "vat_missing"
+    when: { type: "not", condition: { type: "exists", field: "vat_id" } }
+    then: { outcome: { action: "hold" } }
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 500 }
+    then: { outcome: { action: "review" } }
+"#).unwrap()
+    }
+
+    const OVERLAY_WINS: MergePolicy = MergePolicy {
+        rules: ConflictPolicy::OverlayWins,
+        metadata: ConflictPolicy::OverlayWins,
+        version: VersionPolicy::Overlay,
+    };
+
+    fn rule_ids(ruleset: &RuleSet) -> Vec<String> {
+        ruleset.rules.iter().map(|rule| rule.id.clone()).collect()
+    }
+
+    fn when_value(rule: &Rule) -> serde_json::Value {
+        serde_json::to_value(&rule.when).unwrap()
+    }
+
+    #[test]
+    fn test_merge_conflicts_fail_by_default() {
+        let err = RuleSet::merge(&merge_base(), &merge_overlay(), MergePolicy::default()).unwrap_err().to_string();
+        assert!(err.ends_with("Merge conflicts: metadata 'region', rule 'large'"), "{}", err);
+    }
+
+    #[test]
+    fn test_merge_overlay_wins() {
+        let merged = RuleSet::merge(&merge_base(), &merge_overlay(), OVERLAY_WINS).unwrap();
+        assert_eq!(rule_ids(&merged), vec!["sanctions", "large", "vat_missing"]);
+        assert_eq!(when_value(&merged.rules[1]), when_value(&merge_overlay().rules[1]));
+        assert_eq!(merged.version, "eu.2");
+        assert_eq!(merged.metadata, payload(json!({"owner": "risk", "region": "eu", "team": "payments"})));
+    }
+
+    #[test]
+    fn test_merge_base_wins() {
+        let policy = MergePolicy {
+            rules: ConflictPolicy::BaseWins,
+            metadata: ConflictPolicy::BaseWins,
+            version: VersionPolicy::Combined,
+        };
+        let base = merge_base();
+        let merged = RuleSet::merge(&base, &merge_overlay(), policy).unwrap();
+        assert_eq!(rule_ids(&merged), vec!["sanctions", "large", "vat_missing"]);
+        assert_eq!(when_value(&merged.rules[1]), when_value(&base.rules[1]));
+        assert_eq!(merged.version, "1.4+eu.2");
+        assert_eq!(merged.metadata["region"], json!("global"));
+
+        // The merged ruleset is loadable and hashed on its own.
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(merged.clone()).unwrap();
+        assert_eq!(engine.get_ruleset_sha().cloned(), Some(merged.sha().unwrap()));
+        assert_ne!(merged.sha().unwrap(), base.sha().unwrap());
+    }
+
+    #[test]
+    fn test_merge_validates_overlay_rules() {
+        // Overlay rules are validated against the merged definitions.
+        let mut dangling = merge_overlay();
+        dangling.rules[0].when = Condition::Ref { name: "missing".to_string() };
+        assert!(RuleSet::merge(&merge_base(), &dangling, OVERLAY_WINS).is_err());
+    }
+
+    #[test]
+    fn test_merge_keeps_constant_references() {
+        let base = r#"
+version: "1.0"
+metadata: {}
+constants:
+  LIMIT: 1000
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: { $const: "LIMIT" } }
+    then: { outcome: { action: "review", limit: { $const: "LIMIT" } } }
+"#;
+        let overlay = r#"
+version: "1.0"
+metadata: {}
+constants:
+  LIMIT: 500
+  BLOCKED: ["KP"]
+rules:
+  - id: "blocked"
+    when: { type: "in", field: "country", values: { $const: "BLOCKED" } }
+    then: { outcome: { action: "block" } }
+"#;
+        let policy = MergePolicy { rules: ConflictPolicy::OverlayWins, ..Default::default() };
+        let merged = RuleSet::merge(&parse_yaml(base).unwrap(), &parse_yaml(overlay).unwrap(), policy).unwrap();
+        let direct = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+constants:
+  LIMIT: 500
+  BLOCKED: ["KP"]
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: { $const: "LIMIT" } }
+    then: { outcome: { action: "review", limit: { $const: "LIMIT" } } }
+  - id: "blocked"
+    when: { type: "in", field: "country", values: { $const: "BLOCKED" } }
+    then: { outcome: { action: "block" } }
+"#).unwrap();
+        assert_eq!(merged.sha().unwrap(), direct.sha().unwrap());
+        assert_eq!(merged.constant_refs.len(), 3);
+
+        // Base rules see the overlay's value of a constant they reference.
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(merged).unwrap();
+        let decision = engine.evaluate(&payload(json!({"amount": 700}))).unwrap().unwrap();
+        assert_eq!(decision.outcome["limit"], json!(500));
+    }
+
+    #[test]
+    fn test_history_and_rollback() {
+        let limit = |limit: i64| parse_yaml(&format!(r#"
+version: "v{}"
+metadata: {{}}
+rules:
+  - id: "large"
+    when: {{ type: "greater_than", field: "amount", value: {} }}
+    then: {{ outcome: {{ action: "review" }} }}
+"#, limit, limit)).unwrap();
+        let mut engine = RuleEngine::new();
+        assert!(engine.rollback().is_err());
+        engine.set_history_depth(3);
+        let mut shas = Vec::new();
+        for value in [100, 200, 300, 400, 500] {
+            engine.load_ruleset(limit(value)).unwrap();
+            shas.push(engine.get_ruleset_sha().cloned().unwrap());
+        }
+
+        // Capped at three, most recent first; the current ruleset isn't listed.
+        let versions: Vec<_> = engine.history().iter().map(|entry| entry.version.as_str()).collect();
+        assert_eq!(versions, vec!["v400", "v300", "v200"]);
+        assert_eq!(engine.history()[0].sha, shas[3]);
+
+        let event = payload(json!({"amount": 350}));
+        engine.rollback().unwrap();
+        assert_eq!(engine.ruleset_version(), Some("v400"));
+        assert!(engine.evaluate(&event).unwrap().is_none());
+        engine.rollback().unwrap();
+        assert_eq!(engine.evaluate(&event).unwrap().unwrap().rule_sha, shas[2]);
+        assert_eq!(engine.history().len(), 1);
+
+        engine.load_ruleset(limit(600)).unwrap();
+        engine.load_ruleset(limit(700)).unwrap();
+        engine.rollback_to(&shas[2]).unwrap();
+        assert_eq!(engine.get_ruleset_sha(), Some(&shas[2]));
+        let versions: Vec<_> = engine.history().iter().map(|entry| entry.version.as_str()).collect();
+        assert_eq!(versions, vec!["v200"]);
+
+        let err = engine.rollback_to(&shas[4]).unwrap_err();
+        assert_eq!(err.to_string(), format!("Execution error: Unknown ruleset SHA in history: {}", shas[4]));
+        engine.set_history_depth(0);
+        assert!(engine.history().is_empty());
+        engine.load_ruleset(limit(800)).unwrap();
+        assert!(engine.rollback().is_err());
+    }
+
+    #[test]
+    fn test_draft_rules() {
+        let source = r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "llm_velocity"
+    status: "draft"
+    generated_by_llm: true
+    prompt_sha: "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+    when: { type: "greater_than", field: "amount", value: 100 }
+    then: { outcome: { action: "decline" } }
+  - id: "large"
+    approved_by: "alice"
+    approved_at: "2024-05-01T09:30:00Z"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "review" } }
+  - id: "legacy"
+    status: "disabled"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { action: "hold" } }
+"#;
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(parse_yaml(source).unwrap()).unwrap();
+        let event = payload(json!({"amount": 5000}));
+
+        let decision = engine.evaluate(&event).unwrap().unwrap();
+        assert_eq!((decision.rule_id.as_str(), decision.draft), ("large", false));
+        assert!(engine.evaluate(&payload(json!({"amount": 500}))).unwrap().is_none());
+        let statuses: Vec<_> = engine.explain(&event).unwrap().rules.iter().map(|rule| rule.status).collect();
+        assert_eq!(statuses, vec![RuleStatus::Draft, RuleStatus::Matched, RuleStatus::Disabled]);
+
+        let shadow = engine.evaluate_including_drafts(&event).unwrap().unwrap();
+        assert_eq!((shadow.rule_id.as_str(), shadow.draft), ("llm_velocity", true));
+        assert!(shadow.to_json().unwrap().contains("\"draft\":true"));
+
+        // Strict policy: drafts and disabled rules are exempt.
+        let mut strict = RuleEngine::new();
+        strict.set_require_approval(true);
+        strict.load_ruleset(parse_yaml(source).unwrap()).unwrap();
+        let mut unapproved = parse_yaml(source).unwrap();
+        unapproved.rules[0].status = RuleLifecycle::Active;
+        unapproved.rules[1].approved_at = None;
+        let err = strict.load_ruleset(unapproved).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Rule validation error: Active rules require approved_by and approved_at; missing on: llm_velocity, large"
+        );
+
+        let err = engine.load_ruleset(parse_yaml(&source.replace("2024-05-01T09:30:00Z", "last tuesday")).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("Rule large: invalid approved_at 'last tuesday'"));
+    }
+
+    const LLM_RULES: &str = r#"
+version: "1.0"
+metadata: { prompt_registry: "s3://prompts/fraud" }
+rules:
+  - id: "llm_velocity"
+    generated_by_llm: true
+    prompt_sha: "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08"
+    when: { type: "greater_than", field: "amount", value: 100 }
+    then: { outcome: { action: "review" } }
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 10 }
+    then: { outcome: { action: "hold" } }
+"#;
+
+    #[test]
+    fn test_llm_provenance_reaches_decisions() {
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(parse_yaml(LLM_RULES).unwrap()).unwrap();
+        let ids: Vec<&str> = engine.llm_generated_rules().iter().map(|rule| rule.id.as_str()).collect();
+        assert_eq!(ids, vec!["llm_velocity"]);
+
+        let decision = engine.evaluate(&payload(json!({"amount": 500}))).unwrap().unwrap();
+        assert!(decision.generated_by_llm);
+        assert_eq!(decision.prompt_sha.as_deref(), Some("9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08"));
+        assert!(decision.to_json().unwrap().contains("\"generated_by_llm\":true"));
+        let manual = engine.evaluate(&payload(json!({"amount": 50}))).unwrap().unwrap();
+        assert_eq!((manual.generated_by_llm, manual.prompt_sha.as_deref()), (false, None));
+        assert!(!manual.to_json().unwrap().contains("prompt_sha"));
+    }
+
+    #[test]
+    fn test_llm_rule_without_prompt_sha() {
+        let mut engine = RuleEngine::new();
+        let mut missing = parse_yaml(LLM_RULES).unwrap();
+        missing.rules[0].prompt_sha = None;
+        let issues = engine.validate(&missing);
+        assert_eq!(issues[0].path, "/rules/0/prompt_sha");
+        assert_eq!(issues[0].message, "Rule llm_velocity: generated_by_llm requires a prompt_sha");
+        assert!(engine.load_ruleset(missing).is_err());
+    }
+
+    #[test]
+    fn test_malformed_prompt_sha() {
+        let mut malformed = parse_yaml(LLM_RULES).unwrap();
+        malformed.rules[1].prompt_sha = Some("sha256:9f86d081".to_string());
+        let err = RuleEngine::new().load_ruleset(malformed).unwrap_err();
+        assert_eq!(err.to_string(), "Rule validation error: Rule large: prompt_sha 'sha256:9f86d081' is not a 64-character hex SHA-256");
+    }
+
+    #[test]
+    fn test_llm_rules_require_the_prompt_registry() {
+        let mut engine = RuleEngine::new();
+        engine.set_validation_policy(ValidationPolicy::from_yaml("prompt_registry_key: prompt_registry").unwrap());
+        assert!(engine.load_ruleset(parse_yaml(LLM_RULES).unwrap()).is_ok());
+        let mut unregistered = parse_yaml(LLM_RULES).unwrap();
+        unregistered.metadata.clear();
+        let err = engine.load_ruleset(unregistered.clone()).unwrap_err();
+        assert!(err.to_string().ends_with("Missing required metadata: prompt_registry (the ruleset has LLM-generated rules)"));
+        unregistered.rules.remove(0);
+        assert!(engine.load_ruleset(unregistered).is_ok());
+    }
+
+    /// `rules` rules, each an `and` of `conditions_each` equals conditions.
+    fn generated_rules(rules: usize, conditions_each: usize) -> RuleSet {
+        let mut ruleset = parse_yaml("version: \"1.0\"\nmetadata: {}\nrules: []").unwrap();
+        for index in 0..rules {
+            let conditions: Vec<_> = (0..conditions_each)
+                .map(|field| json!({"type": "equals", "field": format!("f{}", field), "value": index}))
+                .collect();
+            ruleset.rules.push(serde_json::from_value(json!({
+                "id": format!("r{}", index),
+                "when": {"type": "and", "conditions": conditions},
+                "then": {"outcome": {}},
+            })).unwrap());
+        }
+        ruleset
+    }
+
+    fn limited_engine(limits: EngineLimits) -> RuleEngine {
+        let mut engine = RuleEngine::new();
+        assert_eq!(*engine.get_limits(), EngineLimits::default());
+        engine.set_limits(limits);
+        engine
+    }
+
+    #[test]
+    fn test_rule_count_limit() {
+        let mut engine = limited_engine(EngineLimits { max_rules: 5, ..EngineLimits::default() });
+        assert!(engine.load_ruleset(generated_rules(5, 1)).is_ok());
+        let err = engine.load_ruleset(generated_rules(6, 1)).unwrap_err();
+        assert_eq!(err.to_string(), "Rule validation error: Ruleset has 6 rules, over the limit of 5");
+    }
+
+    #[test]
+    fn test_condition_count_limit() {
+        // Each rule is an `and` plus its children.
+        let mut engine = limited_engine(EngineLimits { max_total_conditions: 12, ..EngineLimits::default() });
+        assert!(engine.load_ruleset(generated_rules(4, 2)).is_ok());
+        let err = engine.load_ruleset(generated_rules(5, 2)).unwrap_err();
+        assert_eq!(err.to_string(), "Rule validation error: Ruleset has more than 12 conditions, the limit");
+    }
+
+    #[test]
+    fn test_serialized_size_limit() {
+        let size = serde_json::to_vec(&generated_rules(3, 3)).unwrap().len();
+        let mut engine = limited_engine(EngineLimits { max_serialized_bytes: size, ..EngineLimits::default() });
+        assert!(engine.load_ruleset(generated_rules(3, 3)).is_ok());
+        let err = engine.load_ruleset(generated_rules(3, 4)).unwrap_err();
+        assert!(err.to_string().ends_with(&format!("bytes serialized, over the limit of {}", size)), "{}", err);
+        assert_eq!(engine.list_rules().len(), 3);
+    }
+
+    #[test]
+    fn test_oversized_rulesets_are_reported_alone() {
+        let engine = limited_engine(EngineLimits { max_rules: 1, ..EngineLimits::default() });
+        let mut duplicated = generated_rules(2, 1);
+        duplicated.rules[1].id = "r0".to_string();
+        let issues = engine.validate(&duplicated);
+        assert_eq!(issues.iter().filter(|issue| issue.severity == IssueSeverity::Error).count(), 1);
+        assert_eq!(issues[0].path, "/rules");
+    }
+
+    #[test]
+    fn test_ruleset_summary() {
+        let ruleset = parse_yaml(r#"
+version: "3.1"
+metadata: {}
+definitions:
+  risky_country: { type: "or", conditions: [
+    { type: "equals", field: "country", value: "KP" },
+    { type: "not", condition: { type: "exists", field: "country" } } ] }
+rules:
+  - id: "large_risky"
+    severity: "high"
+    tags: ["fraud", "aml"]
+    generated_by_llm: true
+    when: { type: "and", conditions: [
+      { type: "greater_than", field: "amount", value: 1000 },
+      { type: "ref", name: "risky_country" } ] }
+    then: { outcome: { action: "block" } }
+  - id: "any_gift_card"
+    severity: "high"
+    tags: ["fraud"]
+    when: { type: "any", field: "items", condition: { type: "equals", field: "kind", value: "gift_card" } }
+    then: { outcome: { action: "review" } }
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 5000 }
+    then: { outcome: { action: "review" } }
+"#).unwrap();
+        let summary = ruleset.summary().unwrap();
+        assert_eq!((summary.version.as_str(), summary.total_rules, summary.llm_generated), ("3.1", 3, 1));
+        assert_eq!(summary.sha, ruleset.sha().unwrap());
+        assert_eq!(summary.by_tag, BTreeMap::from([("aml".to_string(), 1), ("fraud".to_string(), 2)]));
+        assert_eq!(summary.by_severity, BTreeMap::from([("high".to_string(), 2)]));
+        let types: Vec<(&str, usize)> = summary.condition_types.iter().map(|(name, count)| (name.as_str(), *count)).collect();
+        assert_eq!(types, vec![
+            ("and", 1), ("any", 1), ("equals", 2), ("exists", 1), ("greater_than", 2), ("not", 1), ("or", 1), ("ref", 1),
+        ]);
+        // and -> (ref) or -> not -> exists
+        assert_eq!(summary.max_condition_depth, 4);
+        assert_eq!(ruleset.condition_depth(&ruleset.rules[1].when), 2);
+        assert_eq!(ruleset.condition_depth(&ruleset.rules[2].when), 1);
+    }
+
+    #[test]
+    fn test_find_rules() {
+        let ruleset = parse_yaml(r#"
+version: "1.0"
+metadata:
+  tiers: { KP: "high" }
+definitions:
+  domestic: { type: "equals", field: "customer.country", value: "US" }
+default_action:
+  outcome: { route_to: "auto", note: "checked {{customer.country}}" }
+rules:
+  - id: "sanctioned"
+    when: { type: "in", field: "customer.country", values: ["KP", "IR"] }
+    then:
+      outcome: { action: "block", route_to: "compliance" }
+      capture: ["customer.country"]
+  - id: "high_tier"
+    when: { type: "and", conditions: [
+      { type: "exists", field: "amount" },
+      { type: "lookup", table: "tiers", key_field: "customer.country", equals: "high" } ] }
+    then:
+      outcome: { action: "review" }
+      operations: [{ op: "add_reason", code: "TIER", message: "tier of {{customer.country}}" }]
+  - id: "foreign_shipping"
+    when: { type: "not", condition: { type: "ref", name: "domestic" } }
+    then:
+      outcome: { action: "review" }
+      operations: [{ op: "set_outcome", key: "route_to", value: "intl" }]
+  - id: "foreign_party"
+    when: { type: "any", field: "parties", condition: { type: "not_equals", field: "country", value: "US" } }
+    then: { outcome: { action: "hold", customer_id: "{{customer.id}}" } }
+"#).unwrap();
+        let found = |references: Vec<RuleReference>| -> Vec<(String, Vec<String>)> {
+            references.into_iter().map(|reference| (reference.rule_id, reference.locations)).collect()
+        };
+        let expected = |pairs: &[(&str, &[&str])]| -> Vec<(String, Vec<String>)> {
+            pairs.iter().map(|(id, locations)| (id.to_string(), locations.iter().map(|l| l.to_string()).collect())).collect()
+        };
+
+        assert_eq!(found(ruleset.find_rules_by_field("customer.country")), expected(&[
+            ("sanctioned", &["/rules/0/when/field", "/rules/0/then/capture/0"]),
+            ("high_tier", &["/rules/1/when/conditions/1/key_field", "/rules/1/then/operations/0/message"]),
+            ("foreign_shipping", &["/definitions/domestic/field"]),
+            ("__default__", &["/default_action/outcome/note"]),
+        ]));
+        let below_customer: Vec<String> = ruleset.find_rules_by_field("customer.").into_iter().map(|r| r.rule_id).collect();
+        assert_eq!(below_customer, vec!["sanctioned", "high_tier", "foreign_shipping", "foreign_party", "__default__"]);
+        assert_eq!(found(ruleset.find_rules_by_field("parties[*].country")), expected(&[
+            ("foreign_party", &["/rules/3/when/condition/field"]),
+        ]));
+        assert!(ruleset.find_rules_by_field("customer").is_empty());
+
+        assert_eq!(found(ruleset.find_rules_by_outcome_key("route_to")), expected(&[
+            ("sanctioned", &["/rules/0/then/outcome/route_to"]),
+            ("foreign_shipping", &["/rules/2/then/operations/0"]),
+            ("__default__", &["/default_action/outcome/route_to"]),
+        ]));
+    }
+
+    #[test]
+    fn test_validate_reports_every_issue() {
+        let ruleset = parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "review", fee: { $expr: "amount % 7" } } }
+  - id: "large"
+    when: { type: "ref", name: "missing" }
+    then: { outcome: { action: "review" } }
+  - id: "late"
+    effective_from: "2025-01-01"
+    effective_until: "2024-01-01"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { action: "hold" } }
+"#).unwrap();
+        let engine = RuleEngine::new();
+        let issues = engine.validate(&ruleset);
+        let summary: Vec<(Option<&str>, &str, IssueSeverity)> = issues.iter()
+            .map(|issue| (issue.rule_id.as_deref(), issue.path.as_str(), issue.severity))
+            .collect();
+        assert_eq!(summary, vec![
+            (Some("large"), "/rules/1/id", IssueSeverity::Error),
+            (Some("large"), "/rules/1/when", IssueSeverity::Error),
+            (Some("late"), "/rules/2", IssueSeverity::Error),
+            (Some("large"), "/rules/0/then", IssueSeverity::Error),
+        ]);
+        assert_eq!(issues[0].message, "Duplicate rule ID: large");
+        assert!(issues[1].message.contains("unknown definition 'missing'"));
+        assert!(issues[2].message.contains("effective_from"));
+
+        // Loading reports every error at once; warnings don't block it.
+        let err = RuleEngine::new().load_ruleset(ruleset.clone()).unwrap_err().to_string();
+        assert!(err.starts_with("Rule validation error: Duplicate rule ID: large; Rule large: reference to unknown"), "{}", err);
+        assert_eq!(err.matches("; ").count(), 3);
+        let mut fixable = ruleset;
+        fixable.rules.truncate(1);
+        fixable.rules[0].then.outcome.insert("fee".to_string(), json!(7));
+        assert!(engine.validate(&fixable).is_empty());
+        assert!(RuleEngine::new().load_ruleset(fixable).is_ok());
+    }
+
+    fn input_schema_ruleset(when: &str) -> RuleSet {
+        parse_yaml(&format!(r#"
+version: "1.0"
+metadata: {{}}
+input_schema:
+  amount: {{ type: "number" }}
+  count: {{ type: "integer" }}
+  status: {{ type: "string", enum: ["active", "closed"] }}
+  tags: {{ type: "array", items: "string" }}
+  created: {{ type: "string" }}
+  customer: {{ type: "object" }}
+  customer.country: {{ type: "string", nullable: true }}
+  items: {{ type: "array" }}
+  items[*].price: {{ type: "number" }}
+definitions:
+  pricey: {{ type: "greater_than", field: "price", value: 100 }}
+rules:
+  - id: "r"
+    when: {}
+    then: {{ outcome: {{ action: "review" }} }}
+"#, when)).unwrap()
+    }
+
+    /// Asserts that `when` loads against [`input_schema_ruleset`] with no
+    /// errors, or with exactly one starting with `Rule r: <expected>`.
+    fn assert_input_schema_check(when: &str, expected: Option<&str>) {
+        let errors: Vec<String> = RuleEngine::new().validate(&input_schema_ruleset(when)).into_iter()
+            .filter(|issue| issue.severity == IssueSeverity::Error)
+            .map(|issue| issue.message)
+            .collect();
+        match expected {
+            None => assert!(errors.is_empty(), "{}: {:?}", when, errors),
+            Some(message) => assert!(errors.len() == 1 && errors[0].starts_with(&format!("Rule r: {}", message)), "{}: {:?}", when, errors),
+        }
+    }
+
+    #[test]
+    fn test_input_schema_accepts_conditions_that_fit() {
+        let conditions = [
+            r#"{ type: "greater_than", field: "amount", value: 10 }"#,
+            r#"{ type: "between", field: "count", min: 1, max: 5 }"#,
+            r#"{ type: "modulo", field: "amount", divisor: 2, remainder: 0 }"#,
+            r#"{ type: "equals", field: "status", value: "active" }"#,
+            r#"{ type: "not_equals", field: "customer.country", value: null }"#,
+            r#"{ type: "contains_any", field: "tags", values: ["vip"] }"#,
+            r#"{ type: "matches", field: "customer.country", pattern: "^D" }"#,
+            r#"{ type: "is_empty", field: "tags" }"#,
+            r#"{ type: "date_before", field: "created", value: "2024-01-01" }"#,
+            r#"{ type: "exists", field: "tags" }"#,
+            r#"{ type: "type_is", field: "amount", expected: "string" }"#,
+        ];
+        for when in conditions {
+            assert_input_schema_check(when, None);
+        }
+    }
+
+    #[test]
+    fn test_input_schema_rejects_conditions_on_the_wrong_type() {
+        let cases = [
+            (r#"{ type: "greater_than", field: "status", value: 10 }"#, "greater_than can't test field 'status', declared as string"),
+            (r#"{ type: "lte", field: "tags", value: 10 }"#, "lte can't test field 'tags', declared as array"),
+            (r#"{ type: "contains", field: "amount", value: 1 }"#, "contains can't test field 'amount', declared as number"),
+            (r#"{ type: "starts_with", field: "count", value: "1" }"#, "starts_with can't test field 'count', declared as integer"),
+            (r#"{ type: "length", field: "amount", operator: "gt", value: 1 }"#, "length can't test field 'amount', declared as number"),
+            (r#"{ type: "within_last", field: "tags", duration: "1d" }"#, "within_last can't test field 'tags', declared as array"),
+            (r#"{ type: "and", conditions: [{ type: "exists", field: "amount" }, { type: "ends_with", field: "amount", value: "0" }] }"#, "ends_with can't test field 'amount'"),
+        ];
+        for (when, expected) in cases {
+            assert_input_schema_check(when, Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_input_schema_checks_condition_values() {
+        let cases = [
+            (r#"{ type: "equals", field: "status", value: "actve" }"#, r#"equals value for field 'status' must be one of ["active", "closed"], got "actve""#),
+            (r#"{ type: "in", field: "status", values: ["closed", 3] }"#, "in value for field 'status' must be of type string, got 3"),
+            (r#"{ type: "contains", field: "tags", value: 1 }"#, "contains value for field 'tags' must be of type string, got 1"),
+        ];
+        for (when, expected) in cases {
+            assert_input_schema_check(when, Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_input_schema_inside_quantifiers() {
+        assert_input_schema_check(
+            r#"{ type: "any", field: "items", condition: { type: "greater_than", field: "price", value: 1 } }"#,
+            None,
+        );
+        assert_input_schema_check(
+            r#"{ type: "not", condition: { type: "any", field: "items", condition: { type: "ref", name: "pricey" } } }"#,
+            None,
+        );
+        assert_input_schema_check(
+            r#"{ type: "any", field: "customer", condition: { type: "exists", field: "" } }"#,
+            Some("any can't test field 'customer', declared as object"),
+        );
+        assert_input_schema_check(
+            r#"{ type: "all", field: "items", condition: { type: "equals", field: "price", value: "free" } }"#,
+            Some("equals value for field 'items[*].price' must be of type number, got \"free\""),
+        );
+    }
+
+    #[test]
+    fn test_input_schema_warns_about_undeclared_fields() {
+        let undeclared = input_schema_ruleset(r#"{ type: "any", field: "items", condition: { type: "exists", field: "sku" } }"#);
+        let warnings: Vec<_> = RuleEngine::new().validate(&undeclared).into_iter().map(|issue| (issue.path, issue.message)).collect();
+        assert_eq!(warnings, vec![(
+            "/rules/0/when/condition/field".to_string(),
+            "Rule r: field 'items[*].sku' is not declared in input_schema".to_string(),
+        )]);
+    }
+
+    #[test]
+    fn test_strict_input() {
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(input_schema_ruleset(r#"{ type: "greater_than", field: "amount", value: 10 }"#)).unwrap();
+        let odd = payload(json!({"amount": 50, "status": "pending", "items": [{"price": "12"}]}));
+        assert!(engine.evaluate(&odd).unwrap().is_some());
+        engine.set_strict_input(true);
+        let err = engine.evaluate(&odd).unwrap_err().to_string();
+        assert_eq!(err, "Execution error: Input schema violation: field 'items[*].price' must be of type number, got \"12\"");
+        let err = engine.evaluate(&payload(json!({"amount": 50, "tags": ["a", 1]}))).unwrap_err().to_string();
+        assert!(err.ends_with("field 'tags' must only hold string elements, got 1"), "{}", err);
+        assert!(engine.evaluate(&payload(json!({"amount": 50, "customer": {"country": null}}))).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_input_schema_is_validated() {
+        let mut invalid = input_schema_ruleset(r#"{ type: "exists", field: "amount" }"#);
+        invalid.input_schema.get_mut("status").unwrap().allowed.push(json!(1));
+        let err = RuleEngine::new().load_ruleset(invalid).unwrap_err().to_string();
+        assert!(err.contains("Input field 'status': enum value must be of type string, got 1"), "{}", err);
+    }
+
+    /// Paths and messages of the issues `policy` finds in a ruleset with the
+    /// given metadata and rules.
+    fn policy_issues(policy: &str, metadata: &str, rules: &str) -> Vec<(String, String)> {
+        let mut engine = RuleEngine::new();
+        engine.set_validation_policy(ValidationPolicy::from_yaml(policy).unwrap());
+        let ruleset = parse_yaml(&format!("version: \"1.0\"\nmetadata: {}\nrules:\n{}", metadata, rules)).unwrap();
+        engine.validate(&ruleset).into_iter().map(|issue| (issue.path, issue.message)).collect()
+    }
+
+    fn policy_issue(path: &str, message: &str) -> (String, String) {
+        (path.to_string(), message.to_string())
+    }
+
+    const POLICY_RULES: &str = r#"
+  - id: "ok"
+    description: "Large amounts"
+    severity: "high"
+    tags: ["fraud"]
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "review" } }
+  - id: "shouty"
+    description: "  "
+    severity: "HIGH"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { action: "review" } }
+  - id: "bare"
+    when: { type: "exists", field: "merchant" }
+    then: { outcome: { action: "review" } }
+"#;
+
+    #[test]
+    fn test_validation_policy_allowed_severities() {
+        let issues = policy_issues(r#"allowed_severities: ["low", "medium", "high", "critical"]"#, "{}", POLICY_RULES);
+        assert_eq!(issues, [policy_issue("/rules/1/severity", "Rule shouty: severity 'HIGH' is not one of: low, medium, high, critical")]);
+    }
+
+    #[test]
+    fn test_validation_policy_require_severity() {
+        let issues = policy_issues("require_severity: true", "{}", POLICY_RULES);
+        assert_eq!(issues, [policy_issue("/rules/2/severity", "Rule bare: severity is required")]);
+    }
+
+    #[test]
+    fn test_validation_policy_require_description() {
+        let issues = policy_issues("require_description: true", "{}", POLICY_RULES);
+        assert_eq!(issues, [
+            policy_issue("/rules/1/description", "Rule shouty: description is required"),
+            policy_issue("/rules/2/description", "Rule bare: description is required"),
+        ]);
+    }
+
+    #[test]
+    fn test_validation_policy_min_tags() {
+        let issues = policy_issues("min_tags: 1", "{}", POLICY_RULES);
+        assert_eq!(issues, [
+            policy_issue("/rules/1/tags", "Rule shouty: needs at least 1 tag(s), has 0"),
+            policy_issue("/rules/2/tags", "Rule bare: needs at least 1 tag(s), has 0"),
+        ]);
+    }
+
+    #[test]
+    fn test_validation_policy_required_metadata() {
+        let policy = r#"required_metadata: ["owner", "team"]"#;
+        let issues = policy_issues(policy, r#"{ owner: "risk" }"#, POLICY_RULES);
+        assert_eq!(issues, [policy_issue("/metadata", "Missing required metadata: team")]);
+        assert!(policy_issues(policy, r#"{ owner: "risk", team: "payments" }"#, POLICY_RULES).is_empty());
+    }
+
+    #[test]
+    fn test_validation_policy_applies_on_load() {
+        let ruleset = |rules: &str| parse_yaml(&format!("version: \"1.0\"\nmetadata: {{}}\nrules:\n{}", rules)).unwrap();
+        let policy = ValidationPolicy::from_yaml("require_severity: true").unwrap();
+        let mut engine = RuleEngine::new();
+        assert!(engine.validate(&ruleset(POLICY_RULES)).is_empty());
+        engine.set_validation_policy(policy.clone());
+        assert_eq!(engine.validation_policy(), &policy);
+        let err = engine.load_ruleset(ruleset(POLICY_RULES)).unwrap_err().to_string();
+        assert!(err.contains("Rule bare: severity is required"), "{}", err);
+
+        let compliant = POLICY_RULES.split("  - id: \"bare\"").next().unwrap();
+        assert!(engine.load_ruleset(ruleset(compliant)).is_ok());
+    }
+
+    #[test]
+    fn test_validation_policy_serialization() {
+        let policy = ValidationPolicy::from_yaml(r#"
+allowed_severities: ["low", "medium", "high", "critical"]
+require_severity: true
+require_description: true
+min_tags: 1
+required_metadata: ["owner", "team"]
+"#).unwrap();
+        let json = serde_json::to_string(&policy).unwrap();
+        assert_eq!(ValidationPolicy::from_json(&json).unwrap(), policy);
+        assert!(ValidationPolicy::from_yaml("min_tag: 1").unwrap_err().to_string().contains("unknown field `min_tag`"));
+    }
+}
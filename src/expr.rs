@@ -0,0 +1,368 @@
+//! A small expression language for values derived from the payload:
+//! arithmetic (`+ - * / %`), string concatenation with `+`, parentheses,
+//! `coalesce(a, b, ...)`, `min(a, b, ...)`, `max(a, b, ...)` and
+//! `round(x)` / `round(x, digits)`. Operands are numbers, quoted strings, `true`,
+//! `false`, `null` and field paths such as `order.lines[0].price`.
+//!
+//! Evaluation never fails: a missing or mistyped input, division by zero or
+//! an overflowing result yields no value.
+
+use serde_json::{Number, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Field(String),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Coalesce(Vec<Expr>),
+    Min(Vec<Expr>),
+    Max(Vec<Expr>),
+    /// Rounds half away from zero to the given number of decimal places;
+    /// with zero places the result is an integer.
+    Round(Box<Expr>, u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.sum()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected {} in '{}'", token.describe(), source)),
+        }
+    }
+
+    /// Field paths the expression reads, in source order.
+    pub fn fields(&self) -> Vec<&str> {
+        let mut fields = Vec::new();
+        self.collect_fields(&mut fields);
+        fields
+    }
+
+    fn collect_fields<'a>(&'a self, fields: &mut Vec<&'a str>) {
+        match self {
+            Expr::Literal(_) => {},
+            Expr::Field(field) => fields.push(field),
+            Expr::Neg(inner) => inner.collect_fields(fields),
+            Expr::Binary(_, lhs, rhs) => {
+                lhs.collect_fields(fields);
+                rhs.collect_fields(fields);
+            },
+            Expr::Coalesce(args) | Expr::Min(args) | Expr::Max(args) => {
+                args.iter().for_each(|arg| arg.collect_fields(fields))
+            },
+            Expr::Round(inner, _) => inner.collect_fields(fields),
+        }
+    }
+
+    pub fn evaluate<'a>(&self, lookup: &impl Fn(&str) -> Option<&'a Value>) -> Option<Value> {
+        match self {
+            Expr::Literal(value) => Some(value.clone()),
+            Expr::Field(field) => lookup(field).cloned(),
+            Expr::Neg(inner) => match inner.evaluate(lookup)? {
+                Value::Number(n) => match n.as_i64() {
+                    Some(i) => i.checked_neg().map(Value::from),
+                    None => float(-n.as_f64()?),
+                },
+                _ => None,
+            },
+            Expr::Binary(op, lhs, rhs) => match (lhs.evaluate(lookup)?, rhs.evaluate(lookup)?) {
+                (Value::Number(a), Value::Number(b)) => arithmetic(*op, &a, &b),
+                (Value::String(a), Value::String(b)) if *op == BinaryOp::Add => Some(Value::String(a + &b)),
+                _ => None,
+            },
+            Expr::Coalesce(args) => args.iter()
+                .filter_map(|arg| arg.evaluate(lookup))
+                .find(|value| !value.is_null()),
+            Expr::Min(args) | Expr::Max(args) => {
+                let mut best: Option<Number> = None;
+                for arg in args {
+                    let Value::Number(n) = arg.evaluate(lookup)? else {
+                        return None;
+                    };
+                    let better = best.as_ref().is_none_or(|b| match self {
+                        Expr::Min(_) => n.as_f64() < b.as_f64(),
+                        _ => n.as_f64() > b.as_f64(),
+                    });
+                    if better {
+                        best = Some(n);
+                    }
+                }
+                best.map(Value::Number)
+            },
+            Expr::Round(inner, digits) => {
+                let Value::Number(n) = inner.evaluate(lookup)? else {
+                    return None;
+                };
+                if n.is_i64() || n.is_u64() {
+                    return Some(Value::Number(n));
+                }
+                let x = n.as_f64()?;
+                if *digits == 0 {
+                    let rounded = x.round();
+                    return if rounded.abs() < i64::MAX as f64 { Some(Value::from(rounded as i64)) } else { float(rounded) };
+                }
+                let scale = 10f64.powi(*digits as i32);
+                float((x * scale).round() / scale)
+            },
+        }
+    }
+}
+
+/// Recognises an outcome value of the form `{"$expr": "<expression>"}` and
+/// parses its expression.
+pub fn outcome_expr(value: &Value) -> Option<Result<Expr, String>> {
+    outcome_expr_source(value).map(|source| source.and_then(Expr::parse))
+}
+
+/// The expression of an outcome value of the form `{"$expr": "<expression>"}`,
+/// unparsed.
+pub fn outcome_expr_source(value: &Value) -> Option<Result<&str, String>> {
+    let map = value.as_object().filter(|map| map.len() == 1)?;
+    let source = map.get("$expr")?;
+    Some(source.as_str().ok_or_else(|| "$expr must be a string".to_string()))
+}
+
+/// Integer arithmetic when both sides are integers (except division, which
+/// is always floating point), falling back to f64 otherwise.
+fn arithmetic(op: BinaryOp, a: &Number, b: &Number) -> Option<Value> {
+    if let (Some(a), Some(b), false) = (a.as_i64(), b.as_i64(), op == BinaryOp::Div) {
+        let result = match op {
+            BinaryOp::Add => a.checked_add(b),
+            BinaryOp::Sub => a.checked_sub(b),
+            BinaryOp::Mul => a.checked_mul(b),
+            BinaryOp::Rem => a.checked_rem(b),
+            BinaryOp::Div => None,
+        };
+        return result.map(Value::from);
+    }
+    let (a, b) = (a.as_f64()?, b.as_f64()?);
+    if matches!(op, BinaryOp::Div | BinaryOp::Rem) && b == 0.0 {
+        return None;
+    }
+    float(match op {
+        BinaryOp::Add => a + b,
+        BinaryOp::Sub => a - b,
+        BinaryOp::Mul => a * b,
+        BinaryOp::Div => a / b,
+        BinaryOp::Rem => a % b,
+    })
+}
+
+fn float(value: f64) -> Option<Value> {
+    Number::from_f64(value).map(Value::Number)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(Value),
+    Ident(String),
+    Op(char),
+    Open,
+    Close,
+    Comma,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Literal(value) => format!("literal {}", value),
+            Token::Ident(name) => format!("'{}'", name),
+            Token::Op(op) => format!("'{}'", op),
+            Token::Open => "'('".to_string(),
+            Token::Close => "')'".to_string(),
+            Token::Comma => "','".to_string(),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '+' | '-' | '*' | '/' | '%' => {
+                tokens.push(Token::Op(c));
+                i += 1;
+            },
+            '(' => {
+                tokens.push(Token::Open);
+                i += 1;
+            },
+            ')' => {
+                tokens.push(Token::Close);
+                i += 1;
+            },
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            },
+            '"' | '\'' => {
+                let end = chars[i + 1..].iter().position(|&q| q == c)
+                    .ok_or_else(|| format!("unterminated string in '{}'", source))?;
+                tokens.push(Token::Literal(Value::String(chars[i + 1..i + 1 + end].iter().collect())));
+                i += end + 2;
+            },
+            _ if c.is_ascii_digit() => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number = match text.parse::<i64>() {
+                    Ok(int) => Value::from(int),
+                    Err(_) => text.parse::<f64>().ok().and_then(float)
+                        .ok_or_else(|| format!("invalid number '{}' in '{}'", text, source))?,
+                };
+                tokens.push(Token::Literal(number));
+            },
+            _ if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() {
+                    match chars[i] {
+                        '[' => {
+                            let mut quoted = false;
+                            loop {
+                                i += 1;
+                                match chars.get(i) {
+                                    None => return Err(format!("unterminated '[' in '{}'", source)),
+                                    Some('\\') => i += 1,
+                                    Some('"') => quoted = !quoted,
+                                    Some(']') if !quoted => break,
+                                    Some(_) => {},
+                                }
+                            }
+                            i += 1;
+                        },
+                        '\\' if i + 1 < chars.len() => i += 2,
+                        ch if ch.is_alphanumeric() || ch == '_' || ch == '.' => i += 1,
+                        _ => break,
+                    }
+                }
+                let name: String = chars[start..i].iter().collect();
+                tokens.push(match name.as_str() {
+                    "true" => Token::Literal(Value::Bool(true)),
+                    "false" => Token::Literal(Value::Bool(false)),
+                    "null" => Token::Literal(Value::Null),
+                    _ => Token::Ident(name),
+                });
+            },
+            _ => return Err(format!("unexpected character '{}' in '{}'", c, source)),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_op(&self, ops: &[char]) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(op) => Some(*op),
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+        while let Some(op) = self.peek_op(&['+', '-']) {
+            self.pos += 1;
+            let op = if op == '+' { BinaryOp::Add } else { BinaryOp::Sub };
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.product()?));
+        }
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while let Some(op) = self.peek_op(&['*', '/', '%']) {
+            self.pos += 1;
+            let op = match op {
+                '*' => BinaryOp::Mul,
+                '/' => BinaryOp::Div,
+                _ => BinaryOp::Rem,
+            };
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek_op(&['-']).is_some() {
+            self.pos += 1;
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Literal(value)) => Ok(Expr::Literal(value)),
+            Some(Token::Ident(name)) if self.tokens.get(self.pos) == Some(&Token::Open) => {
+                self.pos += 1;
+                let mut args = Vec::new();
+                if self.tokens.get(self.pos) == Some(&Token::Close) {
+                    self.pos += 1;
+                } else {
+                    loop {
+                        args.push(self.sum()?);
+                        match self.next() {
+                            Some(Token::Comma) => continue,
+                            Some(Token::Close) => break,
+                            _ => return Err(format!("expected ',' or ')' in arguments to {}", name)),
+                        }
+                    }
+                }
+                match (name.as_str(), args.len()) {
+                    ("coalesce" | "min" | "max", 0) => Err(format!("{} needs at least one argument", name)),
+                    ("coalesce", _) => Ok(Expr::Coalesce(args)),
+                    ("min", _) => Ok(Expr::Min(args)),
+                    ("max", _) => Ok(Expr::Max(args)),
+                    ("round", 1) => Ok(Expr::Round(Box::new(args.remove(0)), 0)),
+                    ("round", 2) => match &args[1] {
+                        Expr::Literal(Value::Number(n)) if n.as_u64().is_some_and(|d| d <= 15) => {
+                            let digits = n.as_u64().unwrap_or_default() as u32;
+                            Ok(Expr::Round(Box::new(args.remove(0)), digits))
+                        },
+                        _ => Err("round digits must be an integer literal between 0 and 15".to_string()),
+                    },
+                    ("round", _) => Err("round takes a value and optional digits".to_string()),
+                    _ => Err(format!("unknown function '{}'", name)),
+                }
+            },
+            Some(Token::Ident(name)) => Ok(Expr::Field(name)),
+            Some(Token::Open) => {
+                let expr = self.sum()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("missing ')'".to_string()),
+                }
+            },
+            Some(token) => Err(format!("unexpected {}", token.describe())),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
@@ -1,17 +1,60 @@
 use pyo3::prelude::*;
 
+mod audit;
+mod canonical;
+mod diff;
 mod engine;
 mod dsl;
+mod expr;
+mod lock;
+mod matchers;
+mod path;
+mod pipeline;
 mod python_bindings;
+mod replay;
+mod shared;
+mod signing;
+mod template;
+mod tenant;
+mod temporal;
+#[cfg(test)]
+mod test_support;
 
+pub use audit::{AuditLog, AuditRecord};
+pub use canonical::{canonical_json, payload_sha};
+pub use diff::{diff_rulesets, FieldChange, RuleChange, RuleSetDiff};
 pub use engine::*;
 pub use dsl::*;
+pub use pipeline::{PipelinePolicy, RulesetPipeline, StageDecision};
+pub use replay::{
+    parse_replay_jsonl, replay, replay_ruleset, Divergence, RecordedEvent, ReplayFailure, ReplayReport, UNCOMPARED_FIELDS,
+};
+pub use shared::SharedRuleEngine;
+pub use signing::{
+    derive_public_key, embed_signature, embedded_signature, sign_decision, sign_ruleset, verify_decision, verify_ruleset,
+    SIGNATURE_METADATA_KEY,
+};
+pub use tenant::TenantRuleEngine;
 
 /// Python module for LogicBridge rule engine
 #[pymodule]
 fn logicbridge_core(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<python_bindings::PyRuleEngine>()?;
     m.add_class::<python_bindings::PyDecision>()?;
+    m.add_class::<python_bindings::PyCollectedDecision>()?;
+    m.add_class::<python_bindings::PyScoreResult>()?;
+    m.add_class::<python_bindings::PyGroupResult>()?;
+    m.add_class::<python_bindings::PyChainResult>()?;
     m.add_class::<python_bindings::PyRuleSet>()?;
+    m.add_class::<python_bindings::PyRulesetPipeline>()?;
+    m.add_class::<python_bindings::PyStageDecision>()?;
+    m.add_class::<python_bindings::PyTenantRuleEngine>()?;
+    m.add_class::<python_bindings::PySharedRuleEngine>()?;
+    m.add_function(wrap_pyfunction!(python_bindings::diff_rulesets, m)?)?;
+    m.add_function(wrap_pyfunction!(python_bindings::render_ruleset_diff, m)?)?;
+    m.add_function(wrap_pyfunction!(python_bindings::merge_rulesets, m)?)?;
+    m.add_function(wrap_pyfunction!(python_bindings::sign_ruleset, m)?)?;
+    m.add_function(wrap_pyfunction!(python_bindings::derive_public_key, m)?)?;
+    m.add_function(wrap_pyfunction!(python_bindings::verify_decision, m)?)?;
     Ok(())
 }
@@ -0,0 +1,446 @@
+//! Value-level predicates used by leaf conditions that need more than a
+//! plain comparison.
+
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+/// An IPv4 or IPv6 network in CIDR notation. A bare address is treated as a
+/// single-host network (/32 or /128).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (addr, prefix) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s.trim(), None),
+        };
+        let network: IpAddr = addr.parse().ok()?;
+        let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse().ok().filter(|p| *p <= max_prefix)?,
+            None => max_prefix,
+        };
+        Some(Self { network, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix)).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            },
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix)).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            },
+            _ => false,
+        }
+    }
+}
+
+/// A semantic version (`MAJOR.MINOR.PATCH[-PRERELEASE][+BUILD]`), ordered by
+/// semver precedence. Build metadata is parsed but ignored for ordering and
+/// equality; a leading `v` is accepted.
+#[derive(Debug, Clone)]
+pub struct SemVer {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre: Vec<String>,
+}
+
+impl SemVer {
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let s = s.strip_prefix('v').unwrap_or(s);
+        let s = s.split_once('+').map_or(s, |(version, _build)| version);
+        let (core, pre) = match s.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (s, None),
+        };
+        let mut parts = core.split('.').map(parse_numeric_identifier);
+        let (major, minor, patch) = (parts.next()??, parts.next()??, parts.next()??);
+        if parts.next().is_some() {
+            return None;
+        }
+        let pre = match pre {
+            Some(pre) => {
+                let identifiers: Vec<String> = pre.split('.').map(str::to_string).collect();
+                let valid = identifiers.iter().all(|id| {
+                    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                });
+                if !valid {
+                    return None;
+                }
+                identifiers
+            },
+            None => Vec::new(),
+        };
+        Some(Self { major, minor, patch, pre })
+    }
+}
+
+fn parse_numeric_identifier(s: &str) -> Option<u64> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) || (s.len() > 1 && s.starts_with('0')) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn compare_pre_identifiers(a: &str, b: &str) -> Ordering {
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+impl Ord for SemVer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                // a pre-release sorts before the release it precedes
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => {
+                    for (a, b) in self.pre.iter().zip(&other.pre) {
+                        let ordering = compare_pre_identifiers(a, b);
+                        if ordering != Ordering::Equal {
+                            return ordering;
+                        }
+                    }
+                    self.pre.len().cmp(&other.pre.len())
+                },
+            })
+    }
+}
+
+impl PartialOrd for SemVer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SemVer {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SemVer {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum GlobToken {
+    Literal(char),
+    AnyChar,
+    Star,
+    DoubleStar,
+    Class { negated: bool, ranges: Vec<(char, char)> },
+}
+
+/// A compiled glob pattern supporting `*`, `?`, `**`, `[abc]`, `[a-z]`,
+/// `[!x]` and backslash escapes.
+///
+/// By default `*` and `?` match any character. With `path_separators`, they
+/// stop at `/` and only `**` spans directories (`a/**/b` also matches `a/b`).
+#[derive(Debug, Clone)]
+pub struct Glob {
+    tokens: Vec<GlobToken>,
+    case_insensitive: bool,
+    path_separators: bool,
+}
+
+impl Glob {
+    pub fn compile(pattern: &str, case_insensitive: bool, path_separators: bool) -> Result<Self, String> {
+        let normalize = |c: char| if case_insensitive { c.to_lowercase().next().unwrap_or(c) } else { c };
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    GlobToken::DoubleStar
+                },
+                '*' => GlobToken::Star,
+                '?' => GlobToken::AnyChar,
+                '\\' => GlobToken::Literal(normalize(chars.next().ok_or("dangling escape at end of pattern")?)),
+                '[' => {
+                    let negated = matches!(chars.peek(), Some('!') | Some('^'));
+                    if negated {
+                        chars.next();
+                    }
+                    let mut ranges = Vec::new();
+                    let mut closed = false;
+                    while let Some(c) = chars.next() {
+                        if c == ']' && !ranges.is_empty() {
+                            closed = true;
+                            break;
+                        }
+                        let start = normalize(c);
+                        let mut lookahead = chars.clone();
+                        if lookahead.next() == Some('-') && lookahead.peek().is_some_and(|c| *c != ']') {
+                            chars.next();
+                            let end = normalize(chars.next().unwrap_or(c));
+                            if end < start {
+                                return Err(format!("invalid range {}-{} in character class", start, end));
+                            }
+                            ranges.push((start, end));
+                        } else {
+                            ranges.push((start, start));
+                        }
+                    }
+                    if !closed {
+                        return Err("unclosed character class".to_string());
+                    }
+                    GlobToken::Class { negated, ranges }
+                },
+                c => GlobToken::Literal(normalize(c)),
+            };
+            tokens.push(token);
+        }
+        Ok(Self { tokens, case_insensitive, path_separators })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = if self.case_insensitive {
+            text.chars().flat_map(char::to_lowercase).collect()
+        } else {
+            text.chars().collect()
+        };
+        let crosses = |c: char| !(self.path_separators && c == '/');
+        // matches[i][j]: tokens[i..] match text[j..]
+        let (n, m) = (self.tokens.len(), text.len());
+        let mut matches = vec![vec![false; m + 1]; n + 1];
+        matches[n][m] = true;
+        for i in (0..n).rev() {
+            for j in (0..=m).rev() {
+                let next_char = text.get(j).copied();
+                matches[i][j] = match &self.tokens[i] {
+                    GlobToken::Literal(c) => next_char == Some(*c) && matches[i + 1][j + 1],
+                    GlobToken::AnyChar => next_char.is_some_and(crosses) && matches[i + 1][j + 1],
+                    GlobToken::Star => {
+                        matches[i + 1][j] || (next_char.is_some_and(crosses) && matches[i][j + 1])
+                    },
+                    GlobToken::DoubleStar => {
+                        let skips_separator = self.path_separators
+                            && self.tokens.get(i + 1) == Some(&GlobToken::Literal('/'))
+                            && matches[i + 2][j];
+                        matches[i + 1][j] || skips_separator || (next_char.is_some() && matches[i][j + 1])
+                    },
+                    GlobToken::Class { negated, ranges } => next_char.is_some_and(|c| {
+                        crosses(c) && ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != *negated
+                    }) && matches[i + 1][j + 1],
+                };
+            }
+        }
+        matches[0][0]
+    }
+}
+
+/// Membership set for `in`/`not_in` value lists. Strings and numbers are
+/// hashed; anything else (booleans, null, arrays, objects) falls back to a
+/// linear scan. Lookups agree with `serde_json::Value` equality, so `1` and
+/// `1.0` remain distinct.
+#[derive(Debug, Clone, Default)]
+pub struct ValueSet {
+    strings: HashSet<String>,
+    numbers: HashSet<serde_json::Number>,
+    others: Vec<serde_json::Value>,
+}
+
+impl ValueSet {
+    pub fn new(values: &[serde_json::Value]) -> Self {
+        let mut set = Self::default();
+        for value in values {
+            match value {
+                serde_json::Value::String(s) => {
+                    set.strings.insert(s.clone());
+                },
+                serde_json::Value::Number(n) => {
+                    set.numbers.insert(n.clone());
+                },
+                other => set.others.push(other.clone()),
+            }
+        }
+        set
+    }
+
+    pub fn contains(&self, value: &serde_json::Value) -> bool {
+        match value {
+            serde_json::Value::String(s) => self.strings.contains(s),
+            serde_json::Value::Number(n) => self.numbers.contains(n),
+            other => self.others.contains(other),
+        }
+    }
+}
+
+/// Luhn check over a digit string; spaces and hyphens are ignored.
+pub fn luhn_valid(s: &str) -> bool {
+    let digits: Option<Vec<u32>> = s.chars()
+        .filter(|c| *c != ' ' && *c != '-')
+        .map(|c| c.to_digit(10))
+        .collect();
+    let Some(digits) = digits else {
+        return false;
+    };
+    if digits.len() < 2 {
+        return false;
+    }
+    let sum: u32 = digits.iter().rev().enumerate().map(|(i, d)| {
+        if i % 2 == 1 {
+            let doubled = d * 2;
+            if doubled > 9 { doubled - 9 } else { doubled }
+        } else {
+            *d
+        }
+    }).sum();
+    sum % 10 == 0
+}
+
+/// ISO 13616 IBAN check (mod 97 == 1); spaces are ignored and letters may be
+/// in either case.
+pub fn iban_valid(s: &str) -> bool {
+    let iban: Vec<char> = s.chars().filter(|c| *c != ' ').map(|c| c.to_ascii_uppercase()).collect();
+    if !(15..=34).contains(&iban.len())
+        || !iban[..2].iter().all(char::is_ascii_uppercase)
+        || !iban[2..4].iter().all(char::is_ascii_digit)
+        || !iban.iter().all(char::is_ascii_alphanumeric)
+    {
+        return false;
+    }
+    let mut remainder = 0u32;
+    for c in iban[4..].iter().chain(&iban[..4]) {
+        // digits stay as-is, letters expand to two digits (A = 10 .. Z = 35)
+        let value = c.to_digit(36).unwrap_or(0);
+        remainder = if value >= 10 {
+            (remainder * 100 + value) % 97
+        } else {
+            (remainder * 10 + value) % 97
+        };
+    }
+    remainder == 1
+}
+
+/// Conservative syntactic check for `local@domain` addresses: dot-atom local
+/// parts (plus addressing included) and a dotted domain with an alphabetic
+/// top-level label. Quoted local parts and IP-literal domains are rejected.
+pub fn is_email(s: &str) -> bool {
+    let Some((local, domain)) = s.rsplit_once('@') else {
+        return false;
+    };
+    let local_ok = !local.is_empty()
+        && local.len() <= 64
+        && !local.starts_with('.')
+        && !local.ends_with('.')
+        && !local.contains("..")
+        && local.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+/=?^_`{|}~.-".contains(c));
+    s.len() <= 254 && local_ok && is_hostname(domain, true)
+}
+
+fn is_hostname(host: &str, require_tld: bool) -> bool {
+    let labels: Vec<&str> = host.split('.').collect();
+    let labels_ok = labels.iter().all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    });
+    let tld_ok = !require_tld
+        || (labels.len() >= 2 && labels.last().is_some_and(|tld| tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_alphabetic())));
+    host.len() <= 253 && labels_ok && tld_ok
+}
+
+/// Absolute URL check: a scheme, `://`, and a hostname or IP address,
+/// optionally followed by a port and a path, query or fragment. Whitespace
+/// anywhere is rejected.
+pub fn is_url(s: &str) -> bool {
+    let Some((scheme, rest)) = s.split_once("://") else {
+        return false;
+    };
+    let scheme_ok = scheme.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+    if !scheme_ok || s.chars().any(char::is_whitespace) {
+        return false;
+    }
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let authority = authority.rsplit_once('@').map_or(authority, |(_userinfo, host)| host);
+    let (host_ok, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+        match bracketed.split_once(']') {
+            Some((ipv6, port)) => (ipv6.parse::<std::net::Ipv6Addr>().is_ok(), port),
+            None => return false,
+        }
+    } else {
+        let (host, port) = authority.split_once(':').unwrap_or((authority, ""));
+        (host.parse::<std::net::Ipv4Addr>().is_ok() || is_hostname(host, false), port)
+    };
+    let port = port.strip_prefix(':').unwrap_or(port);
+    host_ok && (port.is_empty() || port.parse::<u16>().is_ok())
+}
+
+/// Canonical 8-4-4-4-12 hex UUID, in either case.
+pub fn is_uuid(s: &str) -> bool {
+    let groups: Vec<&str> = s.split('-').collect();
+    groups.len() == 5
+        && groups.iter().zip([8, 4, 4, 4, 12]).all(|(group, len)| {
+            group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit())
+        })
+}
+
+/// Non-empty string of hex digits, in either case.
+pub fn is_hex(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Standard (RFC 4648) padded base64.
+pub fn is_base64(s: &str) -> bool {
+    if s.is_empty() || s.len() % 4 != 0 {
+        return false;
+    }
+    let data = s.trim_end_matches('=');
+    s.len() - data.len() <= 2 && data.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/')
+}
+
+/// Stable rollout bucket in `[0, 100)` for `key`: the first eight bytes of
+/// SHA-256(`salt` + `key`) read as a big-endian integer and scaled down.
+/// Other implementations can reproduce assignments from this definition.
+pub fn rollout_bucket(salt: &str, key: &str) -> f64 {
+    let digest = Sha256::new().chain_update(salt).chain_update(key).finalize();
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(prefix) as f64 / 18_446_744_073_709_551_616.0 * 100.0
+}
+
+/// Reads a coordinate from `{"lat": .., "lon": ..}` or `[lat, lon]`,
+/// rejecting values outside [-90, 90] / [-180, 180].
+pub fn geo_point(value: &serde_json::Value) -> Option<(f64, f64)> {
+    let (lat, lon) = match value {
+        serde_json::Value::Object(obj) => (obj.get("lat")?.as_f64()?, obj.get("lon")?.as_f64()?),
+        serde_json::Value::Array(pair) if pair.len() == 2 => (pair[0].as_f64()?, pair[1].as_f64()?),
+        _ => return None,
+    };
+    valid_coordinates(lat, lon).then_some((lat, lon))
+}
+
+pub fn valid_coordinates(lat: f64, lon: f64) -> bool {
+    (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon)
+}
+
+/// Great-circle distance in kilometres (haversine, mean Earth radius).
+pub fn haversine_km((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    const EARTH_RADIUS_KM: f64 = 6371.0088;
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lon2 - lon1).to_radians();
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+}
@@ -0,0 +1,219 @@
+//! Resolution of condition `field` strings against payload values.
+
+use serde_json::Value;
+
+/// Resolves a field reference. Fields starting with `/` are RFC 6901 JSON
+/// Pointers; anything else is a dotted path such as
+/// `customer.address.country`, where any key may be followed by bracketed
+/// array indices (`events[-1].status`, negative counting from the end). See
+/// [`parse_dotted`] for escaping keys that contain dots or brackets.
+/// `root` resolves the first key against the top-level scope; a missing key,
+/// an out-of-range index or a value of the wrong shape along the way
+/// resolves to `None`, as does a malformed path. Paths with `*` segments
+/// resolve to several values and go through [`resolve_all`] instead.
+pub fn resolve<'a>(field: &str, root: impl FnOnce(&str) -> Option<&'a Value>) -> Option<&'a Value> {
+    if let Some(pointer) = field.strip_prefix('/') {
+        let mut tokens = pointer.split('/').map(unescape_pointer_token);
+        let mut current = root(&tokens.next()?)?;
+        for token in tokens {
+            current = pointer_child(current, &token)?;
+        }
+        return Some(current);
+    }
+    if !field.contains(['.', '[', '\\']) {
+        return root(field);
+    }
+
+    let mut segments = parse_dotted(field)?.into_iter();
+    let mut current = match segments.next()? {
+        Segment::Key(key) => root(&key)?,
+        Segment::Index(_) | Segment::Wildcard => return None,
+    };
+    for segment in segments {
+        current = child(current, &segment)?;
+    }
+    Some(current)
+}
+
+/// Resolves `field` inside `value` itself rather than against a top-level
+/// scope: the empty field is `value`, and a leading index or wildcard
+/// applies to `value` when it is an array.
+pub fn resolve_in<'a>(value: &'a Value, field: &str) -> Option<&'a Value> {
+    if field.is_empty() {
+        return Some(value);
+    }
+    if let Some(pointer) = field.strip_prefix('/') {
+        return pointer.split('/')
+            .try_fold(value, |current, token| pointer_child(current, &unescape_pointer_token(token)));
+    }
+    if !field.contains(['.', '[', '\\']) {
+        return value.as_object()?.get(field);
+    }
+    parse_dotted(field)?.iter().try_fold(value, |current, segment| child(current, segment))
+}
+
+/// [`resolve_all`] inside `value` itself; see [`resolve_in`].
+pub fn resolve_all_in<'a>(value: &'a Value, field: &str) -> Vec<&'a Value> {
+    let Some(segments) = parse_dotted(field) else {
+        return Vec::new();
+    };
+    segments.iter().fold(vec![value], |current, segment| match segment {
+        Segment::Wildcard => current.into_iter().flat_map(children).collect(),
+        _ => current.into_iter().filter_map(|value| child(value, segment)).collect(),
+    })
+}
+
+/// Whether `field` is a dotted path with at least one `*` segment.
+pub fn has_wildcard(field: &str) -> bool {
+    field.contains('*')
+        && !field.starts_with('/')
+        && parse_dotted(field).is_some_and(|segments| segments.iter().any(|s| matches!(s, Segment::Wildcard)))
+}
+
+/// Resolves a path that may contain `*` segments, which fan out over every
+/// value of an object or element of an array. `root_values` supplies the
+/// top-level values for a leading `*`. Expansions that hit a missing key or
+/// a scalar are dropped, so the result may be empty.
+pub fn resolve_all<'a>(
+    field: &str,
+    root: impl FnOnce(&str) -> Option<&'a Value>,
+    root_values: impl FnOnce() -> Vec<&'a Value>,
+) -> Vec<&'a Value> {
+    let Some(segments) = parse_dotted(field) else {
+        return Vec::new();
+    };
+    let mut segments = segments.into_iter();
+    let mut current: Vec<&'a Value> = match segments.next() {
+        Some(Segment::Key(key)) => root(&key).into_iter().collect(),
+        Some(Segment::Wildcard) => root_values(),
+        _ => return Vec::new(),
+    };
+    for segment in segments {
+        current = match segment {
+            Segment::Wildcard => current.into_iter().flat_map(children).collect(),
+            _ => current.into_iter().filter_map(|value| child(value, &segment)).collect(),
+        };
+    }
+    current
+}
+
+enum Segment {
+    Key(String),
+    Index(i64),
+    Wildcard,
+}
+
+fn child<'a>(value: &'a Value, segment: &Segment) -> Option<&'a Value> {
+    match segment {
+        Segment::Key(key) => value.as_object()?.get(key),
+        Segment::Index(index) => {
+            let items = value.as_array()?;
+            let position = if *index < 0 {
+                items.len().checked_sub(index.unsigned_abs() as usize)?
+            } else {
+                *index as usize
+            };
+            items.get(position)
+        },
+        Segment::Wildcard => None,
+    }
+}
+
+fn children(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Object(map) => map.values().collect(),
+        Value::Array(items) => items.iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Splits a dotted path into keys, indices and wildcards: `a.b[0][-1].c`,
+/// `accounts.*`, `lines[*]`. A backslash makes the next character part of
+/// the key (`user\.agent`, `a\[b`, `\*`, `\\`), and a quoted bracket segment
+/// is a literal key, as in `["user.agent"].browser`, with `\"` and `\\` as
+/// its only escapes.
+fn parse_dotted(field: &str) -> Option<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut key = String::new();
+    // Set once the key contains an escape, so that `\*` is not a wildcard.
+    let mut literal = false;
+    let mut after_bracket = false;
+    let mut chars = field.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            _ if after_bracket && c != '.' && c != '[' => return None,
+            '\\' => {
+                key.push(chars.next()?);
+                literal = true;
+            },
+            '.' => {
+                if !after_bracket {
+                    push_key(&mut segments, &mut key, &mut literal);
+                }
+                after_bracket = false;
+            },
+            '[' => {
+                let leading = segments.is_empty() && key.is_empty() && !literal;
+                if !after_bracket && !leading {
+                    push_key(&mut segments, &mut key, &mut literal);
+                }
+                if chars.next_if_eq(&'"').is_some() {
+                    let mut quoted = String::new();
+                    loop {
+                        match chars.next()? {
+                            '"' => break,
+                            '\\' => quoted.push(chars.next()?),
+                            q => quoted.push(q),
+                        }
+                    }
+                    chars.next_if_eq(&']')?;
+                    segments.push(Segment::Key(quoted));
+                } else {
+                    let mut digits = String::new();
+                    loop {
+                        match chars.next()? {
+                            ']' => break,
+                            d => digits.push(d),
+                        }
+                    }
+                    segments.push(match digits.as_str() {
+                        "*" => Segment::Wildcard,
+                        _ => Segment::Index(digits.parse().ok()?),
+                    });
+                }
+                after_bracket = true;
+            },
+            _ => key.push(c),
+        }
+    }
+    if !after_bracket {
+        push_key(&mut segments, &mut key, &mut literal);
+    }
+    Some(segments)
+}
+
+fn push_key(segments: &mut Vec<Segment>, key: &mut String, literal: &mut bool) {
+    let key = std::mem::take(key);
+    segments.push(if key == "*" && !*literal { Segment::Wildcard } else { Segment::Key(key) });
+    *literal = false;
+}
+
+fn unescape_pointer_token(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+/// One pointer step: a key into an object, or a decimal index (no leading
+/// zeros) into an array.
+fn pointer_child<'a>(value: &'a Value, token: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(map) => map.get(token),
+        Value::Array(items) => {
+            let canonical = token == "0" || (!token.starts_with('0') && !token.is_empty());
+            if !canonical || !token.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            items.get(token.parse::<usize>().ok()?)
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,156 @@
+//! Several independently loaded rulesets evaluated in sequence, such as a
+//! shared sanity ruleset followed by per-country rulesets owned by other
+//! teams.
+
+use crate::engine::{Decision, EngineError, RuleEngine, RuleSet};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// What a [`RulesetPipeline`] does once a stage decides.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelinePolicy {
+    /// Return the first stage decision; later stages don't run.
+    #[default]
+    FirstDecision,
+    /// Run every stage, keeping at most one decision per stage.
+    EachStage,
+}
+
+impl PipelinePolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "first_decision" => Some(PipelinePolicy::FirstDecision),
+            "each_stage" => Some(PipelinePolicy::EachStage),
+            _ => None,
+        }
+    }
+}
+
+/// A decision and the stage whose ruleset made it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageDecision {
+    pub stage: String,
+    pub ruleset_sha: String,
+    pub decision: Decision,
+}
+
+struct Stage {
+    name: String,
+    engine: RuleEngine,
+}
+
+/// Named stages, each with its own engine and ruleset SHA, run in the
+/// order they were first loaded.
+#[derive(Default)]
+pub struct RulesetPipeline {
+    stages: Vec<Stage>,
+    policy: PipelinePolicy,
+}
+
+impl RulesetPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_policy(&mut self, policy: PipelinePolicy) {
+        self.policy = policy;
+    }
+
+    /// Loads `ruleset` into the stage `name`, appending the stage if it is
+    /// new. Replacing a stage keeps its position and engine settings; a
+    /// ruleset that fails validation leaves the stage as it was.
+    pub fn load_stage(&mut self, name: &str, ruleset: RuleSet) -> Result<(), EngineError> {
+        match self.stages.iter_mut().find(|stage| stage.name == name) {
+            Some(stage) => stage.engine.load_ruleset(ruleset),
+            None => {
+                let mut engine = RuleEngine::new();
+                engine.load_ruleset(ruleset)?;
+                self.stages.push(Stage { name: name.to_string(), engine });
+                Ok(())
+            },
+        }
+    }
+
+    /// Removes the stage `name`, returning whether it existed.
+    pub fn remove_stage(&mut self, name: &str) -> bool {
+        let before = self.stages.len();
+        self.stages.retain(|stage| stage.name != name);
+        self.stages.len() != before
+    }
+
+    pub fn stage_names(&self) -> Vec<&str> {
+        self.stages.iter().map(|stage| stage.name.as_str()).collect()
+    }
+
+    /// The engine behind a stage, for per-stage settings such as the
+    /// selection mode.
+    pub fn stage_mut(&mut self, name: &str) -> Option<&mut RuleEngine> {
+        self.stages.iter_mut().find(|stage| stage.name == name).map(|stage| &mut stage.engine)
+    }
+
+    pub fn stage_sha(&self, name: &str) -> Option<&String> {
+        self.stages.iter().find(|stage| stage.name == name).and_then(|stage| stage.engine.get_ruleset_sha())
+    }
+
+    /// Evaluates the stages in order under the pipeline's policy. Stages
+    /// that decide nothing contribute nothing.
+    pub fn evaluate_pipeline(&self, payload: &HashMap<String, serde_json::Value>) -> Result<Vec<StageDecision>, EngineError> {
+        let mut decisions = Vec::new();
+        for stage in &self.stages {
+            let Some(decision) = stage.engine.evaluate(payload)? else {
+                continue;
+            };
+            decisions.push(StageDecision {
+                stage: stage.name.clone(),
+                ruleset_sha: stage.engine.get_ruleset_sha().cloned().unwrap_or_default(),
+                decision,
+            });
+            if self.policy == PipelinePolicy::FirstDecision {
+                break;
+            }
+        }
+        Ok(decisions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{exists_ruleset, payload};
+    use serde_json::json;
+
+    #[test]
+    fn test_ruleset_pipeline() {
+        let mut pipeline = RulesetPipeline::new();
+        pipeline.load_stage("global", exists_ruleset("missing_amount", "error")).unwrap();
+        pipeline.load_stage("fr", exists_ruleset("fr_vat", "vat_id")).unwrap();
+        pipeline.load_stage("de", exists_ruleset("de_ust", "vat_id")).unwrap();
+        let event = payload(json!({"vat_id": "FR123"}));
+
+        let decisions = pipeline.evaluate_pipeline(&event).unwrap();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!((decisions[0].stage.as_str(), decisions[0].decision.rule_id.as_str()), ("fr", "fr_vat"));
+        assert_eq!(Some(&decisions[0].ruleset_sha), pipeline.stage_sha("fr"));
+
+        pipeline.set_policy(PipelinePolicy::EachStage);
+        let stages: Vec<_> = pipeline.evaluate_pipeline(&event).unwrap().into_iter().map(|d| d.stage).collect();
+        assert_eq!(stages, vec!["fr", "de"]);
+
+        // Replacing a stage keeps its place and leaves the others alone.
+        let (global_sha, de_sha) = (pipeline.stage_sha("global").cloned(), pipeline.stage_sha("de").cloned());
+        pipeline.load_stage("fr", exists_ruleset("fr_siren", "vat_id")).unwrap();
+        assert_eq!(pipeline.stage_names(), vec!["global", "fr", "de"]);
+        assert_eq!((pipeline.stage_sha("global").cloned(), pipeline.stage_sha("de").cloned()), (global_sha, de_sha));
+        assert_eq!(pipeline.evaluate_pipeline(&event).unwrap()[0].decision.rule_id, "fr_siren");
+
+        let mut invalid = exists_ruleset("fr_dup", "vat_id");
+        invalid.rules.push(invalid.rules[0].clone());
+        assert!(pipeline.load_stage("fr", invalid).is_err());
+        assert_eq!(pipeline.evaluate_pipeline(&event).unwrap()[0].decision.rule_id, "fr_siren");
+
+        assert!(pipeline.remove_stage("de"));
+        assert!(!pipeline.remove_stage("de"));
+        assert_eq!(pipeline.stage_names(), vec!["global", "fr"]);
+    }
+}
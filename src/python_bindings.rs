@@ -1,8 +1,17 @@
 use pyo3::prelude::*;
-use pyo3::types::PyDict;
+use pyo3::types::{PyDict, PyList};
 use std::collections::HashMap;
-use crate::engine::{RuleEngine, RuleSet, Decision, EngineError};
+use crate::engine::{
+    RuleEngine, RuleSet, Decision, CollectedDecision, ChainResult, ChainStep, EngineError, Evaluation, GroupResult,
+    ScoreResult, SelectionMode, ConflictPolicy, MergePolicy, VersionPolicy, ValidationPolicy, EngineLimits,
+};
+use crate::pipeline::{PipelinePolicy, RulesetPipeline, StageDecision};
+use crate::shared::SharedRuleEngine;
+use crate::tenant::TenantRuleEngine;
+use crate::diff;
 use crate::dsl;
+use crate::replay;
+use crate::signing;
 
 #[pyclass]
 pub struct PyRuleEngine {
@@ -13,17 +22,133 @@ pub struct PyRuleEngine {
 #[derive(Clone)]
 pub struct PyDecision {
     #[pyo3(get)]
-    pub rule_id: String,
+    pub decision_id: String,
     #[pyo3(get)]
-    pub outcome: HashMap<String, serde_json::Value>,
+    pub rule_id: String,
     #[pyo3(get)]
     pub matched_conditions: Vec<String>,
     #[pyo3(get)]
     pub elapsed_us: u64,
     #[pyo3(get)]
+    pub elapsed_ns: u64,
+    #[pyo3(get)]
+    pub total_elapsed_ns: u64,
+    #[pyo3(get)]
     pub timestamp: u64,
     #[pyo3(get)]
     pub rule_sha: String,
+    #[pyo3(get)]
+    pub rule_content_sha: String,
+    #[pyo3(get)]
+    pub payload_sha: String,
+    #[pyo3(get)]
+    pub ruleset_version: String,
+    #[pyo3(get)]
+    pub tags: Vec<String>,
+    #[pyo3(get)]
+    pub severity: Option<String>,
+    #[pyo3(get)]
+    pub description: Option<String>,
+    /// `(code, message)` pairs in the order the rule added them.
+    #[pyo3(get)]
+    pub reasons: Vec<(String, Option<String>)>,
+    /// `"then"`, or `"else"` when the rule's else branch decided.
+    #[pyo3(get)]
+    pub branch: String,
+    #[pyo3(get)]
+    pub captures: HashMap<String, String>,
+    #[pyo3(get)]
+    pub draft: bool,
+    #[pyo3(get)]
+    pub generated_by_llm: bool,
+    #[pyo3(get)]
+    pub prompt_sha: Option<String>,
+    /// Hex HMAC-SHA256, when the engine signs decisions.
+    #[pyo3(get)]
+    pub signature: Option<String>,
+    /// Source of the JSON-valued getters and of `to_dict`/`to_json`.
+    decision: Decision,
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct PyCollectedDecision {
+    #[pyo3(get)]
+    pub rule_ids: Vec<String>,
+    pub outcome: HashMap<String, serde_json::Value>,
+    #[pyo3(get)]
+    pub tags: Vec<String>,
+    #[pyo3(get)]
+    pub reasons: Vec<(String, Option<String>)>,
+    #[pyo3(get)]
+    pub stopped_by: Option<String>,
+    #[pyo3(get)]
+    pub decisions: Vec<PyDecision>,
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct PyScoreResult {
+    #[pyo3(get)]
+    pub total: f64,
+    /// `(rule_id, score)` pairs in rule order.
+    #[pyo3(get)]
+    pub contributions: Vec<(String, f64)>,
+    #[pyo3(get)]
+    pub matched: Vec<String>,
+    #[pyo3(get)]
+    pub label: Option<String>,
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct PyGroupResult {
+    #[pyo3(get)]
+    pub group: String,
+    /// `"first_match"` or `"all_matches"`.
+    #[pyo3(get)]
+    pub hit_policy: String,
+    #[pyo3(get)]
+    pub decisions: Vec<PyDecision>,
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct PyChainResult {
+    #[pyo3(get)]
+    pub decision: Option<PyDecision>,
+    #[pyo3(get)]
+    pub passes: usize,
+    steps: Vec<ChainStep>,
+    payload: HashMap<String, serde_json::Value>,
+}
+
+#[pyclass]
+pub struct PyRulesetPipeline {
+    pipeline: RulesetPipeline,
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct PyStageDecision {
+    #[pyo3(get)]
+    pub stage: String,
+    #[pyo3(get)]
+    pub ruleset_sha: String,
+    #[pyo3(get)]
+    pub decision: PyDecision,
+}
+
+#[pyclass]
+pub struct PyTenantRuleEngine {
+    tenants: TenantRuleEngine,
+}
+
+/// An engine that can be reloaded from any thread while others evaluate;
+/// every method takes a shared borrow.
+#[pyclass]
+pub struct PySharedRuleEngine {
+    shared: SharedRuleEngine,
 }
 
 #[pyclass]
@@ -34,12 +159,124 @@ pub struct PyRuleSet {
 impl From<Decision> for PyDecision {
     fn from(decision: Decision) -> Self {
         PyDecision {
-            rule_id: decision.rule_id,
-            outcome: decision.outcome,
-            matched_conditions: decision.matched_conditions,
+            decision_id: decision.decision_id.clone(),
+            rule_id: decision.rule_id.clone(),
+            matched_conditions: decision.matched_conditions.clone(),
             elapsed_us: decision.elapsed_us,
+            elapsed_ns: decision.elapsed_ns,
+            total_elapsed_ns: decision.total_elapsed_ns,
             timestamp: decision.timestamp,
-            rule_sha: decision.rule_sha,
+            rule_sha: decision.rule_sha.clone(),
+            rule_content_sha: decision.rule_content_sha.clone(),
+            payload_sha: decision.payload_sha.clone(),
+            ruleset_version: decision.ruleset_version.clone(),
+            tags: decision.tags.clone(),
+            severity: decision.severity.clone(),
+            description: decision.description.clone(),
+            reasons: decision.reasons.iter().map(|reason| (reason.code.clone(), reason.message.clone())).collect(),
+            branch: decision.branch.as_str().to_string(),
+            captures: decision.captures.clone(),
+            draft: decision.draft,
+            generated_by_llm: decision.generated_by_llm,
+            prompt_sha: decision.prompt_sha.clone(),
+            signature: decision.signature.clone(),
+            decision,
+        }
+    }
+}
+
+#[pymethods]
+impl PyDecision {
+    #[getter]
+    pub fn outcome(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.decision.outcome)
+    }
+
+    #[getter]
+    pub fn captured(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.decision.captured)
+    }
+
+    #[getter]
+    pub fn metadata(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.decision.metadata)
+    }
+
+    /// The decision as a dict of native Python values, with the same keys
+    /// as `to_json`.
+    pub fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.decision)
+    }
+
+    pub fn to_json(&self) -> PyResult<String> {
+        self.decision.to_json()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+}
+
+#[pymethods]
+impl PyCollectedDecision {
+    #[getter]
+    pub fn outcome(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.outcome)
+    }
+}
+
+impl From<CollectedDecision> for PyCollectedDecision {
+    fn from(collected: CollectedDecision) -> Self {
+        PyCollectedDecision {
+            rule_ids: collected.rule_ids,
+            outcome: collected.outcome,
+            tags: collected.tags,
+            reasons: collected.reasons.into_iter().map(|reason| (reason.code, reason.message)).collect(),
+            stopped_by: collected.stopped_by,
+            decisions: collected.decisions.into_iter().map(PyDecision::from).collect(),
+        }
+    }
+}
+
+impl From<ScoreResult> for PyScoreResult {
+    fn from(result: ScoreResult) -> Self {
+        PyScoreResult {
+            total: result.total,
+            contributions: result.contributions,
+            matched: result.matched,
+            label: result.label,
+        }
+    }
+}
+
+impl From<GroupResult> for PyGroupResult {
+    fn from(result: GroupResult) -> Self {
+        PyGroupResult {
+            group: result.group,
+            hit_policy: result.hit_policy.as_str().to_string(),
+            decisions: result.decisions.into_iter().map(PyDecision::from).collect(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyChainResult {
+    /// `{"rule_id": ..., "delta": {...}}` for each rule that fed the payload.
+    #[getter]
+    pub fn steps(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.steps)
+    }
+
+    #[getter]
+    pub fn payload(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.payload)
+    }
+}
+
+impl From<ChainResult> for PyChainResult {
+    fn from(result: ChainResult) -> Self {
+        PyChainResult {
+            decision: result.decision.map(PyDecision::from),
+            passes: result.passes,
+            steps: result.steps,
+            payload: result.payload,
         }
     }
 }
@@ -53,9 +290,12 @@ impl PyRuleEngine {
         }
     }
 
-    pub fn load_ruleset_from_yaml(&mut self, yaml_content: &str) -> PyResult<()> {
-        let ruleset = dsl::parse_yaml(yaml_content)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    /// Unknown keys are errors unless `lenient` is set, which drops them.
+    #[pyo3(signature = (yaml_content, lenient = false))]
+    pub fn load_ruleset_from_yaml(&mut self, yaml_content: &str, lenient: bool) -> PyResult<()> {
+        let options = dsl::ParseOptions { allow_unknown_fields: lenient };
+        let ruleset = dsl::parse_yaml_with_options(yaml_content, &options)
+            .map_err(parse_error)?;
         
         self.engine.load_ruleset(ruleset)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
@@ -63,9 +303,98 @@ impl PyRuleEngine {
         Ok(())
     }
 
-    pub fn load_ruleset_from_json(&mut self, json_content: &str) -> PyResult<()> {
-        let ruleset = dsl::parse_json(json_content)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    /// Loads the YAML ruleset only if its SHA is `expected_sha`; otherwise
+    /// raises and keeps the loaded ruleset.
+    pub fn load_ruleset_verified_yaml(&mut self, yaml_content: &str, expected_sha: &str) -> PyResult<()> {
+        let ruleset = dsl::parse_yaml(yaml_content).map_err(parse_error)?;
+        self.engine.load_ruleset_verified(ruleset, expected_sha)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    pub fn load_ruleset_verified_json(&mut self, json_content: &str, expected_sha: &str) -> PyResult<()> {
+        let ruleset = dsl::parse_json(json_content).map_err(parse_error)?;
+        self.engine.load_ruleset_verified(ruleset, expected_sha)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Reads a `.json` file as JSON and anything else as YAML.
+    pub fn load_ruleset_verified_file(&mut self, path: &str, expected_sha: &str) -> PyResult<()> {
+        self.engine.load_ruleset_verified_file(path, expected_sha).map_err(|e| match e {
+            EngineError::Parse(_) => parse_error(e),
+            other => PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(other.to_string()),
+        })
+    }
+
+    /// Loads the YAML ruleset only if `signature` verifies against
+    /// `public_key`, each given as bytes or base64 `str`; otherwise raises
+    /// and keeps the loaded ruleset.
+    pub fn load_ruleset_signed_yaml(&mut self, yaml_content: &str, signature: &PyAny, public_key: &PyAny) -> PyResult<()> {
+        let ruleset = dsl::parse_yaml(yaml_content).map_err(parse_error)?;
+        self.engine.load_ruleset_signed(ruleset, &key_bytes(signature)?, &key_bytes(public_key)?)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Keeps the last `capacity` decisions, and with `include_no_match`
+    /// the payload hash of evaluations no rule decided.
+    #[pyo3(signature = (capacity, include_no_match = false))]
+    pub fn enable_audit_log(&mut self, capacity: usize, include_no_match: bool) {
+        self.engine.enable_audit_log(capacity, include_no_match);
+    }
+
+    pub fn disable_audit_log(&mut self) {
+        self.engine.disable_audit_log();
+    }
+
+    /// The last `n` audit records as dicts, oldest first.
+    pub fn recent_decisions(&self, py: Python<'_>, n: usize) -> PyResult<PyObject> {
+        to_python(py, &self.engine.recent_decisions(n))
+    }
+
+    /// Removes and returns the audit records as dicts, oldest first.
+    pub fn drain_audit_log(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.engine.drain_audit_log())
+    }
+
+    /// The audit records as JSON lines.
+    pub fn export_audit_jsonl(&self) -> PyResult<String> {
+        let mut out = Vec::new();
+        self.engine.export_audit_jsonl(&mut out)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        Ok(String::from_utf8_lossy(&out).into_owned())
+    }
+
+    /// Signs every decision with HMAC-SHA256 under `key` (bytes, or a str
+    /// taken as UTF-8); None stops signing.
+    #[pyo3(signature = (key = None))]
+    pub fn set_decision_signing_key(&mut self, key: Option<&PyAny>) -> PyResult<()> {
+        let key = key.map(key_bytes).transpose()?;
+        self.engine.set_decision_signing_key(key.as_deref());
+        Ok(())
+    }
+
+    /// Re-evaluates recorded events, one `{"payload": ..., "decision": ...}`
+    /// object per JSONL line, and returns the replay report as a dict.
+    pub fn replay_jsonl(&self, py: Python<'_>, jsonl: &str) -> PyResult<PyObject> {
+        let events = replay::parse_replay_jsonl(jsonl).map_err(parse_error)?;
+        to_python(py, &replay::replay(&self.engine, events))
+    }
+
+    /// `replay_jsonl` over the contents of the file at `path`.
+    pub fn replay_jsonl_file(&self, py: Python<'_>, path: &str) -> PyResult<PyObject> {
+        let jsonl = std::fs::read_to_string(path)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(format!("Failed to read {}: {}", path, e)))?;
+        self.replay_jsonl(py, &jsonl)
+    }
+
+    pub fn set_accept_sha_prefix(&mut self, enabled: bool) {
+        self.engine.set_accept_sha_prefix(enabled);
+    }
+
+    #[pyo3(signature = (json_content, lenient = false))]
+    pub fn load_ruleset_from_json(&mut self, json_content: &str, lenient: bool) -> PyResult<()> {
+        let options = dsl::ParseOptions { allow_unknown_fields: lenient };
+        let ruleset = dsl::parse_json_with_options(json_content, &options)
+            .map_err(parse_error)?;
         
         self.engine.load_ruleset(ruleset)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
@@ -73,6 +402,14 @@ impl PyRuleEngine {
         Ok(())
     }
 
+    /// Every problem with a YAML ruleset, without loading it, as dicts with
+    /// `rule_id`, `path`, `message` and `severity` ("error" or "warning").
+    pub fn validate(&self, py: Python<'_>, yaml_content: &str) -> PyResult<PyObject> {
+        let ruleset = dsl::parse_yaml(yaml_content)
+            .map_err(parse_error)?;
+        to_python(py, &self.engine.validate(&ruleset))
+    }
+
     pub fn evaluate(&self, payload: &PyDict) -> PyResult<Option<PyDecision>> {
         let payload_map = python_dict_to_hashmap(payload)?;
         
@@ -82,6 +419,17 @@ impl PyRuleEngine {
         Ok(decision.map(PyDecision::from))
     }
 
+    /// Shadow evaluation with draft rules included; their decisions have
+    /// `draft` set.
+    pub fn evaluate_including_drafts(&self, payload: &PyDict) -> PyResult<Option<PyDecision>> {
+        let payload_map = python_dict_to_hashmap(payload)?;
+        
+        let decision = self.engine.evaluate_including_drafts(&payload_map)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(decision.map(PyDecision::from))
+    }
+
     pub fn evaluate_many(&self, events: Vec<&PyDict>) -> PyResult<Vec<Option<PyDecision>>> {
         let mut payload_maps = Vec::new();
         for event in events {
@@ -94,9 +442,617 @@ impl PyRuleEngine {
         Ok(decisions.into_iter().map(|d| d.map(PyDecision::from)).collect())
     }
 
+    #[pyo3(signature = (payload, include = Vec::new(), exclude = Vec::new()))]
+    pub fn evaluate_with_tags(&self, payload: &PyDict, include: Vec<String>, exclude: Vec<String>) -> PyResult<Option<PyDecision>> {
+        let payload_map = python_dict_to_hashmap(payload)?;
+        
+        let decision = self.engine.evaluate_with_tags(&payload_map, &include, &exclude)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(decision.map(PyDecision::from))
+    }
+
+    #[pyo3(signature = (events, include = Vec::new(), exclude = Vec::new()))]
+    pub fn evaluate_many_with_tags(
+        &self,
+        events: Vec<&PyDict>,
+        include: Vec<String>,
+        exclude: Vec<String>,
+    ) -> PyResult<Vec<Option<PyDecision>>> {
+        let mut payload_maps = Vec::new();
+        for event in events {
+            payload_maps.push(python_dict_to_hashmap(event)?);
+        }
+        
+        let decisions = self.engine.evaluate_many_with_tags(&payload_maps, &include, &exclude)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(decisions.into_iter().map(|d| d.map(PyDecision::from)).collect())
+    }
+
+    pub fn evaluate_all(&self, payload: &PyDict) -> PyResult<Vec<PyDecision>> {
+        let payload_map = python_dict_to_hashmap(payload)?;
+        
+        let decisions = self.engine.evaluate_all(&payload_map)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(decisions.into_iter().map(PyDecision::from).collect())
+    }
+
+    pub fn evaluate_collect(&self, payload: &PyDict) -> PyResult<PyCollectedDecision> {
+        let payload_map = python_dict_to_hashmap(payload)?;
+        
+        let collected = self.engine.evaluate_collect(&payload_map)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(PyCollectedDecision::from(collected))
+    }
+
+    pub fn set_max_chain_passes(&mut self, passes: usize) {
+        self.engine.set_max_chain_passes(passes);
+    }
+
+    /// Rejects later loads unless every active rule is approved.
+    pub fn set_require_approval(&mut self, required: bool) {
+        self.engine.set_require_approval(required);
+    }
+
+    /// Imposes a validation policy, given as YAML or JSON, on later loads.
+    pub fn set_validation_policy(&mut self, policy: &str) -> PyResult<()> {
+        let policy = ValidationPolicy::from_yaml(policy).map_err(parse_error)?;
+        self.engine.set_validation_policy(policy);
+        Ok(())
+    }
+
+    /// Overrides the given limits on condition size; the others keep their
+    /// current values.
+    #[pyo3(signature = (max_condition_depth = None, max_conditions_per_rule = None, max_in_list = None, max_pattern_length = None))]
+    pub fn set_safety_limits(
+        &mut self,
+        max_condition_depth: Option<usize>,
+        max_conditions_per_rule: Option<usize>,
+        max_in_list: Option<usize>,
+        max_pattern_length: Option<usize>,
+    ) {
+        let current = *self.engine.get_safety_limits();
+        self.engine.set_safety_limits(dsl::SafetyLimits {
+            max_condition_depth: max_condition_depth.unwrap_or(current.max_condition_depth),
+            max_conditions_per_rule: max_conditions_per_rule.unwrap_or(current.max_conditions_per_rule),
+            max_in_list: max_in_list.unwrap_or(current.max_in_list),
+            max_pattern_length: max_pattern_length.unwrap_or(current.max_pattern_length),
+        });
+    }
+
+    /// The limits on condition size as a dict.
+    pub fn get_safety_limits(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, self.engine.get_safety_limits())
+    }
+
+    pub fn evaluate_chain(&self, payload: &PyDict) -> PyResult<PyChainResult> {
+        let payload_map = python_dict_to_hashmap(payload)?;
+        
+        let result = self.engine.evaluate_chain(&payload_map)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(PyChainResult::from(result))
+    }
+
+    pub fn evaluate_group(&self, group: &str, payload: &PyDict) -> PyResult<PyGroupResult> {
+        let payload_map = python_dict_to_hashmap(payload)?;
+        
+        let result = self.engine.evaluate_group(group, &payload_map)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(PyGroupResult::from(result))
+    }
+
+    /// One result per declared group, in pipeline order.
+    pub fn evaluate_pipeline(&self, payload: &PyDict) -> PyResult<Vec<PyGroupResult>> {
+        let payload_map = python_dict_to_hashmap(payload)?;
+        
+        let pipeline = self.engine.evaluate_pipeline(&payload_map)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(pipeline.groups.into_iter().map(PyGroupResult::from).collect())
+    }
+
+    pub fn evaluate_score(&self, payload: &PyDict) -> PyResult<PyScoreResult> {
+        let payload_map = python_dict_to_hashmap(payload)?;
+        
+        let result = self.engine.evaluate_score(&payload_map)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(PyScoreResult::from(result))
+    }
+
+    /// The matching decision, or, when no rule decides, a dict listing each
+    /// rule's first failing condition.
+    pub fn evaluate_detailed(&self, py: Python<'_>, payload: &PyDict) -> PyResult<PyObject> {
+        let payload_map = python_dict_to_hashmap(payload)?;
+        
+        let evaluation = self.engine.evaluate_detailed(&payload_map)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        match evaluation {
+            Evaluation::Matched(decision) => Ok(PyDecision::from(*decision).into_py(py)),
+            Evaluation::NoMatch(no_match) => to_python(py, &no_match),
+        }
+    }
+
+    /// The evaluation trace of every rule, as nested dicts and lists.
+    pub fn explain(&self, py: Python<'_>, payload: &PyDict) -> PyResult<PyObject> {
+        let payload_map = python_dict_to_hashmap(payload)?;
+        
+        let trace = self.engine.explain(&payload_map)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        to_python(py, &trace)
+    }
+
     pub fn get_ruleset_sha(&self) -> Option<String> {
         self.engine.get_ruleset_sha().cloned()
     }
+
+    /// The rule as a dict, or None if no loaded rule has this ID.
+    pub fn get_rule(&self, py: Python<'_>, rule_id: &str) -> PyResult<Option<PyObject>> {
+        self.engine.get_rule(rule_id).map(|rule| to_python(py, rule)).transpose()
+    }
+
+    /// Every loaded rule as a dict, in evaluation order, with its content
+    /// SHA under `"sha"`.
+    pub fn list_rules(&self, py: Python<'_>) -> PyResult<Vec<PyObject>> {
+        self.engine.list_rules().into_iter().map(|rule| {
+            let mut value = serde_json::to_value(rule)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+            value["sha"] = self.engine.get_rule_sha(&rule.id).cloned().unwrap_or_default().into();
+            to_python(py, &value)
+        }).collect()
+    }
+
+    /// The loaded rule's content SHA, which only changes when the rule does.
+    pub fn get_rule_sha(&self, rule_id: &str) -> Option<String> {
+        self.engine.get_rule_sha(rule_id).cloned()
+    }
+
+    /// Overrides the given caps on ruleset size; the others keep their
+    /// current values.
+    #[pyo3(signature = (max_rules = None, max_total_conditions = None, max_serialized_bytes = None))]
+    pub fn set_limits(&mut self, max_rules: Option<usize>, max_total_conditions: Option<usize>, max_serialized_bytes: Option<usize>) {
+        let current = *self.engine.get_limits();
+        self.engine.set_limits(EngineLimits {
+            max_rules: max_rules.unwrap_or(current.max_rules),
+            max_total_conditions: max_total_conditions.unwrap_or(current.max_total_conditions),
+            max_serialized_bytes: max_serialized_bytes.unwrap_or(current.max_serialized_bytes),
+        });
+    }
+
+    /// The caps on ruleset size as a dict.
+    pub fn get_limits(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, self.engine.get_limits())
+    }
+
+    /// The loaded LLM-generated rules as dicts, in document order.
+    pub fn llm_generated_rules(&self, py: Python<'_>) -> PyResult<Vec<PyObject>> {
+        self.engine.llm_generated_rules().into_iter().map(|rule| to_python(py, rule)).collect()
+    }
+
+    pub fn list_rule_ids(&self) -> Vec<String> {
+        self.engine.list_rule_ids().into_iter().map(str::to_string).collect()
+    }
+
+    pub fn ruleset_version(&self) -> Option<String> {
+        self.engine.ruleset_version().map(str::to_string)
+    }
+
+    pub fn metadata(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        self.engine.metadata().map(|metadata| to_python(py, metadata)).transpose()
+    }
+
+    /// Inserts a rule, given as a dict or a JSON string, at `position` or
+    /// at the end.
+    #[pyo3(signature = (rule, position = None))]
+    pub fn add_rule(&mut self, py: Python<'_>, rule: &PyAny, position: Option<usize>) -> PyResult<()> {
+        let json: String = match rule.extract() {
+            Ok(json) => json,
+            Err(_) => py.import("json")?.call_method1("dumps", (rule,))?.extract()?,
+        };
+        let rule = serde_json::from_str(&json)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid rule: {}", e)))?;
+        
+        self.engine.add_rule_value(rule, position)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    pub fn set_rule_enabled(&mut self, rule_id: &str, enabled: bool) -> PyResult<()> {
+        self.engine.set_rule_enabled(rule_id, enabled)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    pub fn set_history_depth(&mut self, depth: usize) {
+        self.engine.set_history_depth(depth);
+    }
+
+    /// Replaced rulesets as dicts with `sha`, `version` and `loaded_at`,
+    /// most recent first.
+    pub fn history(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.engine.history())
+    }
+
+    pub fn rollback(&mut self) -> PyResult<()> {
+        self.engine.rollback()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    pub fn rollback_to(&mut self, sha: &str) -> PyResult<()> {
+        self.engine.rollback_to(sha)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// Removes the rule and returns it as a dict.
+    pub fn remove_rule(&mut self, py: Python<'_>, rule_id: &str) -> PyResult<PyObject> {
+        let removed = self.engine.remove_rule(rule_id)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        to_python(py, &removed)
+    }
+
+    /// The loaded ruleset's hit policy: `"first"`, `"priority"`,
+    /// `"collect"` or `"unique"`.
+    pub fn get_hit_policy(&self) -> PyResult<String> {
+        Ok(self.loaded_ruleset()?.hit_policy.as_str().to_string())
+    }
+
+    pub fn set_coerce_numeric_strings(&mut self, enabled: bool) {
+        self.engine.set_coerce_numeric_strings(enabled);
+    }
+
+    /// Rejects payloads that don't fit the ruleset's input_schema.
+    pub fn set_strict_input(&mut self, enabled: bool) {
+        self.engine.set_strict_input(enabled);
+    }
+
+    /// Rule counts by tag and severity, condition-type usage, the deepest
+    /// condition and more, as a dict.
+    pub fn summary(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let summary = self.loaded_ruleset()?.summary()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        to_python(py, &summary)
+    }
+
+    /// Rules reading `path` as a list of `{"rule_id", "locations"}` dicts;
+    /// a path ending in "." matches every field below it.
+    pub fn find_rules_by_field(&self, py: Python<'_>, path: &str) -> PyResult<PyObject> {
+        to_python(py, &self.loaded_ruleset()?.find_rules_by_field(path))
+    }
+
+    /// Rules writing the outcome key, as in `find_rules_by_field`.
+    pub fn find_rules_by_outcome_key(&self, py: Python<'_>, key: &str) -> PyResult<PyObject> {
+        to_python(py, &self.loaded_ruleset()?.find_rules_by_outcome_key(key))
+    }
+
+    pub fn set_template_placeholder(&mut self, placeholder: &str) {
+        self.engine.set_template_placeholder(placeholder);
+    }
+
+    pub fn set_decision_metadata_keys(&mut self, keys: Vec<String>) {
+        self.engine.set_decision_metadata_keys(keys);
+    }
+
+    /// `"first_match"` (the default) or `"highest_priority"`.
+    pub fn set_selection_mode(&mut self, mode: &str) -> PyResult<()> {
+        let mode = SelectionMode::from_name(mode).ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown selection mode: {}", mode))
+        })?;
+        self.engine.set_selection_mode(mode);
+        Ok(())
+    }
+
+    pub fn referenced_fields(&self) -> PyResult<Vec<String>> {
+        Ok(self.loaded_ruleset()?.referenced_fields().into_iter().collect())
+    }
+
+    pub fn referenced_fields_by_rule(&self) -> PyResult<HashMap<String, Vec<String>>> {
+        Ok(self.loaded_ruleset()?
+            .referenced_fields_by_rule()
+            .into_iter()
+            .map(|(rule_id, fields)| (rule_id, fields.into_iter().collect()))
+            .collect())
+    }
+}
+
+impl From<StageDecision> for PyStageDecision {
+    fn from(stage: StageDecision) -> Self {
+        PyStageDecision {
+            stage: stage.stage,
+            ruleset_sha: stage.ruleset_sha,
+            decision: PyDecision::from(stage.decision),
+        }
+    }
+}
+
+#[pymethods]
+impl PyRulesetPipeline {
+    #[new]
+    pub fn new() -> Self {
+        PyRulesetPipeline {
+            pipeline: RulesetPipeline::new(),
+        }
+    }
+
+    /// `"first_decision"` (the default) or `"each_stage"`.
+    pub fn set_policy(&mut self, policy: &str) -> PyResult<()> {
+        let policy = PipelinePolicy::from_name(policy).ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown pipeline policy: {}", policy))
+        })?;
+        self.pipeline.set_policy(policy);
+        Ok(())
+    }
+
+    pub fn load_ruleset_stage(&mut self, name: &str, yaml_content: &str) -> PyResult<()> {
+        let ruleset = dsl::parse_yaml(yaml_content)
+            .map_err(parse_error)?;
+        
+        self.pipeline.load_stage(name, ruleset)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(())
+    }
+
+    pub fn remove_stage(&mut self, name: &str) -> bool {
+        self.pipeline.remove_stage(name)
+    }
+
+    pub fn stage_names(&self) -> Vec<String> {
+        self.pipeline.stage_names().into_iter().map(str::to_string).collect()
+    }
+
+    pub fn stage_sha(&self, name: &str) -> Option<String> {
+        self.pipeline.stage_sha(name).cloned()
+    }
+
+    pub fn evaluate_pipeline(&self, payload: &PyDict) -> PyResult<Vec<PyStageDecision>> {
+        let payload_map = python_dict_to_hashmap(payload)?;
+        
+        let decisions = self.pipeline.evaluate_pipeline(&payload_map)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(decisions.into_iter().map(PyStageDecision::from).collect())
+    }
+}
+
+#[pymethods]
+impl PyTenantRuleEngine {
+    #[new]
+    pub fn new() -> Self {
+        PyTenantRuleEngine {
+            tenants: TenantRuleEngine::new(),
+        }
+    }
+
+    pub fn load_ruleset_for(&self, tenant_id: &str, yaml_content: &str) -> PyResult<()> {
+        let ruleset = dsl::parse_yaml(yaml_content)
+            .map_err(parse_error)?;
+        
+        self.tenants.load_ruleset_for(tenant_id, ruleset)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(())
+    }
+
+    pub fn evaluate_for(&self, py: Python<'_>, tenant_id: &str, payload: &PyDict) -> PyResult<Option<PyDecision>> {
+        let payload_map = python_dict_to_hashmap(payload)?;
+        
+        let decision = py.allow_threads(|| self.tenants.evaluate_for(tenant_id, &payload_map))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(decision.map(PyDecision::from))
+    }
+
+    pub fn list_tenants(&self) -> Vec<String> {
+        self.tenants.list_tenants()
+    }
+
+    pub fn get_ruleset_sha_for(&self, tenant_id: &str) -> Option<String> {
+        self.tenants.ruleset_sha_for(tenant_id)
+    }
+
+    pub fn remove_tenant(&self, tenant_id: &str) -> bool {
+        self.tenants.remove_tenant(tenant_id)
+    }
+}
+
+#[pymethods]
+impl PySharedRuleEngine {
+    #[new]
+    pub fn new() -> Self {
+        PySharedRuleEngine {
+            shared: SharedRuleEngine::default(),
+        }
+    }
+
+    /// Validates the ruleset and swaps it in; evaluations already running
+    /// finish against the previous one.
+    pub fn reload_from_yaml(&self, py: Python<'_>, yaml_content: &str) -> PyResult<()> {
+        let ruleset = dsl::parse_yaml(yaml_content)
+            .map_err(parse_error)?;
+        
+        py.allow_threads(|| self.shared.reload(ruleset))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    pub fn reload_from_json(&self, py: Python<'_>, json_content: &str) -> PyResult<()> {
+        let ruleset = dsl::parse_json(json_content)
+            .map_err(parse_error)?;
+        
+        py.allow_threads(|| self.shared.reload(ruleset))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    pub fn evaluate(&self, py: Python<'_>, payload: &PyDict) -> PyResult<Option<PyDecision>> {
+        let payload_map = python_dict_to_hashmap(payload)?;
+        
+        let decision = py.allow_threads(|| self.shared.evaluate(&payload_map))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(decision.map(PyDecision::from))
+    }
+
+    pub fn evaluate_many(&self, py: Python<'_>, events: Vec<&PyDict>) -> PyResult<Vec<Option<PyDecision>>> {
+        let mut payload_maps = Vec::new();
+        for event in events {
+            payload_maps.push(python_dict_to_hashmap(event)?);
+        }
+        
+        let decisions = py.allow_threads(|| self.shared.evaluate_many(&payload_maps))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        
+        Ok(decisions.into_iter().map(|d| d.map(PyDecision::from)).collect())
+    }
+
+    pub fn get_ruleset_sha(&self) -> Option<String> {
+        self.shared.get_ruleset_sha()
+    }
+}
+
+impl PyRuleEngine {
+    fn loaded_ruleset(&self) -> PyResult<&RuleSet> {
+        self.engine.get_ruleset()
+            .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("No ruleset loaded"))
+    }
+}
+
+#[pymethods]
+impl PyRuleSet {
+    #[staticmethod]
+    pub fn from_yaml(yaml_content: &str) -> PyResult<Self> {
+        let ruleset = dsl::parse_yaml(yaml_content)
+            .map_err(parse_error)?;
+        Ok(PyRuleSet { ruleset })
+    }
+
+    #[staticmethod]
+    pub fn from_json(json_content: &str) -> PyResult<Self> {
+        let ruleset = dsl::parse_json(json_content)
+            .map_err(parse_error)?;
+        Ok(PyRuleSet { ruleset })
+    }
+
+    /// See `RuleEngine.summary`.
+    pub fn summary(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let summary = self.ruleset.summary()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        to_python(py, &summary)
+    }
+
+    /// The SHA the engine records when this ruleset is loaded.
+    pub fn sha(&self) -> PyResult<String> {
+        self.ruleset.sha()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    /// The canonical JSON the SHA is computed over.
+    pub fn canonical_json(&self) -> PyResult<String> {
+        self.ruleset.canonical_json()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
+    }
+
+    pub fn find_rules_by_field(&self, py: Python<'_>, path: &str) -> PyResult<PyObject> {
+        to_python(py, &self.ruleset.find_rules_by_field(path))
+    }
+
+    pub fn find_rules_by_outcome_key(&self, py: Python<'_>, key: &str) -> PyResult<PyObject> {
+        to_python(py, &self.ruleset.find_rules_by_outcome_key(key))
+    }
+
+    /// Conditions that can never match and rules shadowed by an earlier
+    /// rule, as a list of issue dicts.
+    pub fn lint(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &dsl::lint_ruleset(&self.ruleset))
+    }
+}
+
+/// What changed between two YAML rulesets, as nested dicts.
+#[pyfunction]
+pub fn diff_rulesets(py: Python<'_>, old_yaml: &str, new_yaml: &str) -> PyResult<PyObject> {
+    to_python(py, &yaml_diff(old_yaml, new_yaml)?)
+}
+
+/// What changed between two YAML rulesets, one change per line.
+#[pyfunction]
+pub fn render_ruleset_diff(old_yaml: &str, new_yaml: &str) -> PyResult<String> {
+    Ok(yaml_diff(old_yaml, new_yaml)?.render())
+}
+
+/// Merges two YAML rulesets and returns the result as a dict. The policies
+/// are "error", "overlay_wins" or "base_wins" for `rules` and `metadata`,
+/// and "overlay", "base" or "combined" for `version`.
+#[pyfunction]
+#[pyo3(signature = (base_yaml, overlay_yaml, rules = "error", metadata = "error", version = "overlay"))]
+pub fn merge_rulesets(
+    py: Python<'_>,
+    base_yaml: &str,
+    overlay_yaml: &str,
+    rules: &str,
+    metadata: &str,
+    version: &str,
+) -> PyResult<PyObject> {
+    let value_error = |message: String| PyErr::new::<pyo3::exceptions::PyValueError, _>(message);
+    let conflict = |name: &str| ConflictPolicy::from_name(name).ok_or_else(|| value_error(format!("Unknown conflict policy: {}", name)));
+    let policy = MergePolicy {
+        rules: conflict(rules)?,
+        metadata: conflict(metadata)?,
+        version: VersionPolicy::from_name(version).ok_or_else(|| value_error(format!("Unknown version policy: {}", version)))?,
+    };
+    let parse = |yaml| dsl::parse_yaml(yaml).map_err(parse_error);
+    let merged = RuleSet::merge(&parse(base_yaml)?, &parse(overlay_yaml)?, policy).map_err(|e| value_error(e.to_string()))?;
+    to_python(py, &merged)
+}
+
+/// Base64 ed25519 signature of the YAML ruleset. `signing_key` is 32 raw
+/// bytes or base64 text.
+#[pyfunction]
+pub fn sign_ruleset(yaml_content: &str, signing_key: &PyAny) -> PyResult<String> {
+    let ruleset = dsl::parse_yaml(yaml_content).map_err(parse_error)?;
+    signing::sign_ruleset(&ruleset, &key_bytes(signing_key)?)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Base64 public key for a signing key given as for `sign_ruleset`.
+#[pyfunction]
+pub fn derive_public_key(signing_key: &PyAny) -> PyResult<String> {
+    signing::derive_public_key(&key_bytes(signing_key)?)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Whether the decision's signature verifies under `key`, given as for
+/// `RuleEngine.set_decision_signing_key`.
+#[pyfunction]
+pub fn verify_decision(decision: &PyDecision, key: &PyAny) -> PyResult<bool> {
+    Ok(signing::verify_decision(&decision.decision, &key_bytes(key)?).is_ok())
+}
+
+/// Key or signature material from `bytes`, or a `str` as its UTF-8 bytes;
+/// the signing functions decode base64 text where they expect raw bytes.
+fn key_bytes(value: &PyAny) -> PyResult<Vec<u8>> {
+    match value.extract::<&str>() {
+        Ok(text) => Ok(text.as_bytes().to_vec()),
+        Err(_) => value.extract::<&[u8]>().map(<[u8]>::to_vec),
+    }
+}
+
+fn yaml_diff(old_yaml: &str, new_yaml: &str) -> PyResult<diff::RuleSetDiff> {
+    let parse = |yaml| dsl::parse_yaml(yaml).map_err(parse_error);
+    Ok(diff::diff_rulesets(&parse(old_yaml)?, &parse(new_yaml)?))
+}
+
+/// A `ValueError` for a ruleset that didn't parse, showing the offending
+/// lines of the source.
+fn parse_error(error: EngineError) -> PyErr {
+    let message = match &error {
+        EngineError::Parse(parse) => format!("Parse error: {}", parse.render()),
+        other => other.to_string(),
+    };
+    PyErr::new::<pyo3::exceptions::PyValueError, _>(message)
 }
 
 fn python_dict_to_hashmap(py_dict: &PyDict) -> PyResult<HashMap<String, serde_json::Value>> {
@@ -109,6 +1065,36 @@ fn python_dict_to_hashmap(py_dict: &PyDict) -> PyResult<HashMap<String, serde_js
     Ok(map)
 }
 
+fn to_python(py: Python<'_>, value: &impl serde::Serialize) -> PyResult<PyObject> {
+    let value = serde_json::to_value(value)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    json_to_python(py, &value)
+}
+
+fn json_to_python(py: Python<'_>, value: &serde_json::Value) -> PyResult<PyObject> {
+    Ok(match value {
+        serde_json::Value::Null => py.None(),
+        serde_json::Value::Bool(b) => b.into_py(py),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_py(py),
+            (None, Some(u)) => u.into_py(py),
+            (None, None) => n.as_f64().unwrap_or_default().into_py(py),
+        },
+        serde_json::Value::String(s) => s.into_py(py),
+        serde_json::Value::Array(items) => {
+            let items = items.iter().map(|item| json_to_python(py, item)).collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, items).into_py(py)
+        },
+        serde_json::Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, item) in map {
+                dict.set_item(key, json_to_python(py, item)?)?;
+            }
+            dict.into_py(py)
+        },
+    })
+}
+
 fn python_value_to_json(value: &PyAny) -> PyResult<serde_json::Value> {
     if value.is_none() {
         Ok(serde_json::Value::Null)
@@ -117,7 +1103,8 @@ fn python_value_to_json(value: &PyAny) -> PyResult<serde_json::Value> {
     } else if let Ok(i) = value.extract::<i64>() {
         Ok(serde_json::Value::Number(serde_json::Number::from(i)))
     } else if let Ok(f) = value.extract::<f64>() {
-        Ok(serde_json::Value::Number(serde_json::Number::from_f64(f).unwrap()))
+        // JSON has no NaN or infinity; they arrive as null so conditions don't match
+        Ok(serde_json::Number::from_f64(f).map_or(serde_json::Value::Null, serde_json::Value::Number))
     } else if let Ok(s) = value.extract::<String>() {
         Ok(serde_json::Value::String(s))
     } else {
@@ -126,3 +1113,101 @@ fn python_value_to_json(value: &PyAny) -> PyResult<serde_json::Value> {
         Ok(serde_json::Value::String(s))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decision_metadata_survives_conversion() {
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(dsl::parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "sanctioned"
+    description: "Transfers to sanctioned countries"
+    severity: "critical"
+    tags: ["sanctions"]
+    when: { type: "equals", field: "country", value: "KP" }
+    then: { outcome: { decision: "decline" } }
+"#).unwrap()).unwrap();
+        let payload = HashMap::from([("country".to_string(), serde_json::json!("KP"))]);
+        let decision = PyDecision::from(engine.evaluate(&payload).unwrap().unwrap());
+        assert_eq!(decision.severity.as_deref(), Some("critical"));
+        assert_eq!(decision.tags, vec!["sanctions"]);
+        assert_eq!(decision.description.as_deref(), Some("Transfers to sanctioned countries"));
+
+        let collected = PyCollectedDecision::from(engine.evaluate_collect(&payload).unwrap());
+        assert_eq!(collected.decisions[0].severity.as_deref(), Some("critical"));
+    }
+
+    #[test]
+    fn test_decision_to_dict_uses_native_types() {
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(dsl::parse_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "nested"
+    when: { type: "exists", field: "amount" }
+    then:
+      outcome: { decision: "review", limits: { daily: 500, rates: [0.5, null], strict: true } }
+"#).unwrap()).unwrap();
+        let payload = HashMap::from([("amount".to_string(), serde_json::json!(42))]);
+        let decision = PyDecision::from(engine.evaluate(&payload).unwrap().unwrap());
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let dict = decision.to_dict(py).unwrap();
+            let dict: &PyDict = dict.downcast(py).unwrap();
+            let limits: &PyDict = dict.get_item("outcome").unwrap().unwrap()
+                .get_item("limits").unwrap()
+                .downcast().unwrap();
+            assert_eq!(limits.get_item("daily").unwrap().unwrap().extract::<i64>().unwrap(), 500);
+            assert!(limits.get_item("strict").unwrap().unwrap().extract::<bool>().unwrap());
+            let rates: &PyList = limits.get_item("rates").unwrap().unwrap().downcast().unwrap();
+            assert_eq!(rates.get_item(0).unwrap().extract::<f64>().unwrap(), 0.5);
+            assert!(rates.get_item(1).unwrap().is_none());
+            assert_eq!(dict.get_item("rule_id").unwrap().unwrap().extract::<String>().unwrap(), "nested");
+
+            let outcome = decision.outcome(py).unwrap();
+            assert_eq!(outcome.as_ref(py).get_item("decision").unwrap().extract::<String>().unwrap(), "review");
+        });
+        let round_trip = Decision::from_json(&decision.to_json().unwrap()).unwrap();
+        assert_eq!(round_trip, decision.decision);
+    }
+
+    #[test]
+    fn test_add_rule_accepts_dict_and_json() {
+        let mut engine = PyRuleEngine::new();
+        engine.load_ruleset_from_yaml(r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "review" } }
+"#, false).unwrap();
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let rule = py.eval(
+                r#"{"id": "block", "when": {"type": "equals", "field": "country", "value": "KP"}, "then": {"outcome": {"action": "block"}}}"#,
+                None,
+                None,
+            ).unwrap();
+            engine.add_rule(py, rule, Some(0)).unwrap();
+            let json = r#"{"id": "log", "when": {"type": "exists", "field": "amount"}, "then": {"outcome": {}}}"#;
+            engine.add_rule(py, pyo3::types::PyString::new(py, json), None).unwrap();
+            assert!(engine.add_rule(py, pyo3::types::PyString::new(py, "{\"id\": 1}"), None).is_err());
+
+            let ids: Vec<_> = engine.engine.get_ruleset().unwrap().rules.iter().map(|rule| rule.id.clone()).collect();
+            assert_eq!(ids, vec!["block", "large", "log"]);
+
+            let removed = engine.remove_rule(py, "block").unwrap();
+            let removed: &PyDict = removed.downcast(py).unwrap();
+            assert_eq!(removed.get_item("id").unwrap().unwrap().extract::<String>().unwrap(), "block");
+        });
+    }
+}
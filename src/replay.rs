@@ -0,0 +1,305 @@
+//! Re-running recorded events against a ruleset to show that it still
+//! decides them the way it did, for compliance reviews and for checking
+//! what a ruleset change would have done to past traffic.
+
+use crate::diff::FieldChange;
+use crate::engine::{Decision, EngineError, EvalOptions, ParseError, RuleEngine, RuleSet};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Decision fields that differ on every run, or that identify the ruleset
+/// rather than what it decided, and so are not compared.
+pub const UNCOMPARED_FIELDS: &[&str] = &[
+    "decision_id",
+    "elapsed_us",
+    "elapsed_ns",
+    "total_elapsed_ns",
+    "timestamp",
+    "rule_sha",
+    "rule_content_sha",
+    "signature",
+];
+
+/// One recorded event, as read from a line of replay JSONL.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub payload: Value,
+    /// `None` when no rule decided the event.
+    #[serde(default)]
+    pub decision: Option<Decision>,
+    /// Unix seconds the event was evaluated at, for events without a
+    /// decision; otherwise the decision's `timestamp` is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+}
+
+impl RecordedEvent {
+    /// When the event was evaluated, if recorded.
+    fn evaluated_at(&self) -> Option<DateTime<Utc>> {
+        let seconds = self.decision.as_ref().map(|decision| decision.timestamp).or(self.timestamp)?;
+        DateTime::from_timestamp(i64::try_from(seconds).ok()?, 0)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplayReport {
+    /// SHA of the ruleset the events were replayed against.
+    pub ruleset_sha: String,
+    pub total: usize,
+    /// Events whose decision was reproduced.
+    pub reproduced: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub divergences: Vec<Divergence>,
+    /// Events whose evaluation now fails.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ReplayFailure>,
+}
+
+impl ReplayReport {
+    /// Every event was reproduced.
+    pub fn is_reproducible(&self) -> bool {
+        self.reproduced == self.total
+    }
+}
+
+/// An event decided differently. `changes` lists outcome keys as
+/// `outcome.<key>` and other decision fields by name, in field order;
+/// when no rule decides any more, or a rule decides an event none did, it
+/// only has `rule_id`, with no `after` or no `before`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Divergence {
+    /// Position of the event in the input, from 0.
+    pub index: usize,
+    pub payload_sha: String,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayFailure {
+    pub index: usize,
+    pub error: String,
+}
+
+/// Re-evaluates each payload with `engine` at the time it was recorded, so
+/// time-dependent conditions and effective windows see the same clock, and
+/// compares the result with the recorded decision, leaving out
+/// [`UNCOMPARED_FIELDS`]. Events recorded without a time are evaluated now.
+pub fn replay(engine: &RuleEngine, events: impl IntoIterator<Item = RecordedEvent>) -> ReplayReport {
+    let mut report = ReplayReport {
+        ruleset_sha: engine.get_ruleset_sha().cloned().unwrap_or_default(),
+        ..Default::default()
+    };
+    for (index, event) in events.into_iter().enumerate() {
+        report.total += 1;
+        let options = EvalOptions { now: event.evaluated_at(), ..Default::default() };
+        let replayed = match engine.evaluate_value_with_options(&event.payload, &options) {
+            Ok(replayed) => replayed,
+            Err(error) => {
+                report.errors.push(ReplayFailure { index, error: error.to_string() });
+                continue;
+            },
+        };
+        let changes = decision_changes(event.decision.as_ref(), replayed.as_ref());
+        if changes.is_empty() {
+            report.reproduced += 1;
+        } else {
+            report.divergences.push(Divergence { index, payload_sha: crate::payload_sha(&event.payload), changes });
+        }
+    }
+    report
+}
+
+/// [`replay`] against `ruleset` loaded into a default engine.
+pub fn replay_ruleset(ruleset: RuleSet, events: impl IntoIterator<Item = RecordedEvent>) -> Result<ReplayReport, EngineError> {
+    let mut engine = RuleEngine::new();
+    engine.load_ruleset(ruleset)?;
+    Ok(replay(&engine, events))
+}
+
+/// Recorded events from JSONL, one `{"payload": ..., "decision": ...}`
+/// object per line, where `decision` may be missing or `null`. Blank lines
+/// are skipped.
+pub fn parse_replay_jsonl(jsonl: &str) -> Result<Vec<RecordedEvent>, EngineError> {
+    jsonl.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                let message = e.to_string();
+                let position = format!(" at line {} column {}", e.line(), e.column());
+                EngineError::Parse(ParseError {
+                    message: format!("Replay JSONL parse error: {}", message.strip_suffix(&position).unwrap_or(&message)),
+                    line: Some(index + 1),
+                    column: Some(e.column()),
+                    ..Default::default()
+                })
+            })
+        })
+        .collect()
+}
+
+fn decision_changes(recorded: Option<&Decision>, replayed: Option<&Decision>) -> Vec<FieldChange> {
+    let (recorded, replayed) = match (recorded, replayed) {
+        (Some(recorded), Some(replayed)) => (recorded, replayed),
+        (None, None) => return Vec::new(),
+        (recorded, replayed) => {
+            let rule_id = |decision: Option<&Decision>| decision.map(|decision| Value::String(decision.rule_id.clone()));
+            return vec![FieldChange { field: "rule_id".to_string(), before: rule_id(recorded), after: rule_id(replayed) }];
+        },
+    };
+    let (mut before, mut after) = (compared_fields(recorded), compared_fields(replayed));
+    let outcome = |fields: &mut Map<String, Value>| match fields.remove("outcome") {
+        Some(Value::Object(outcome)) => outcome,
+        _ => Map::new(),
+    };
+    let (before_outcome, after_outcome) = (outcome(&mut before), outcome(&mut after));
+    let mut changes = crate::diff::object_changes(&before, &after);
+    let outcome_changes = crate::diff::object_changes(&before_outcome, &after_outcome).into_iter().map(|change| FieldChange {
+        field: format!("outcome.{}", change.field),
+        ..change
+    });
+    let position = changes.partition_point(|change| change.field.as_str() < "outcome");
+    changes.splice(position..position, outcome_changes);
+    changes
+}
+
+fn compared_fields(decision: &Decision) -> Map<String, Value> {
+    let mut fields = match serde_json::to_value(decision) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    };
+    for field in UNCOMPARED_FIELDS {
+        fields.remove(*field);
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::parse_yaml;
+    use serde_json::json;
+
+    const SOURCE: &str = r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "review", queue: "risk" } }
+  - id: "foreign"
+    when: { type: "not_equals", field: "country", value: "US" }
+    then: { outcome: { action: "review", queue: "intl" } }
+default_action: { outcome: { action: "allow" } }
+"#;
+
+    fn recorded() -> String {
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(parse_yaml(SOURCE).unwrap()).unwrap();
+        let payloads = [
+            json!({"amount": 500, "country": "US"}),
+            json!({"amount": 1500, "country": "US"}),
+            json!({"amount": 1500, "country": "FR"}),
+            json!({"amount": 5000, "country": "US"}),
+        ];
+        payloads.into_iter()
+            .map(|payload| {
+                let decision = engine.evaluate_value(&payload).unwrap().unwrap();
+                serde_json::to_string(&json!({"payload": payload, "decision": decision})).unwrap() + "\n"
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_replay_reproduces_the_recorded_ruleset() {
+        let events = parse_replay_jsonl(&recorded()).unwrap();
+        let report = replay_ruleset(parse_yaml(SOURCE).unwrap(), events).unwrap();
+        assert!(report.is_reproducible(), "{:?}", report);
+        assert_eq!((report.total, report.reproduced), (4, 4));
+        assert_eq!(report.ruleset_sha, parse_yaml(SOURCE).unwrap().sha().unwrap());
+    }
+
+    #[test]
+    fn test_replay_reports_divergences() {
+        let events = parse_replay_jsonl(&recorded()).unwrap();
+        let raised = parse_yaml(&SOURCE.replace("value: 1000", "value: 2000")).unwrap();
+        let report = replay_ruleset(raised, events.clone()).unwrap();
+        assert_eq!((report.total, report.reproduced), (4, 2));
+        let summary: Vec<(usize, Vec<&str>)> = report.divergences.iter()
+            .map(|divergence| (divergence.index, divergence.changes.iter().map(|change| change.field.as_str()).collect()))
+            .collect();
+        assert_eq!(summary, vec![
+            (1, vec!["matched_conditions", "outcome.action", "outcome.queue", "rule_id"]),
+            (2, vec!["matched_conditions", "outcome.queue", "rule_id"]),
+        ]);
+        let rule_change = report.divergences[1].changes.last().unwrap();
+        assert_eq!((rule_change.before.clone(), rule_change.after.clone()), (Some(json!("large")), Some(json!("foreign"))));
+        assert_eq!(report.divergences[0].changes[2].after, None);
+        assert_eq!(report.divergences[0].payload_sha, crate::payload_sha(&events[1].payload));
+    }
+
+    #[test]
+    fn test_replay_reports_lost_decisions_and_errors() {
+        let mut strict = RuleEngine::new();
+        strict.set_strict_input(true);
+        let mut narrowed = parse_yaml(&SOURCE.replace("default_action", "input_schema: { country: { type: string, enum: [US] } }\ndefault_action")).unwrap();
+        narrowed.default_action = None;
+        strict.load_ruleset(narrowed).unwrap();
+        let report = replay(&strict, parse_replay_jsonl(&recorded()).unwrap());
+        assert_eq!(report.reproduced, 2);
+        assert_eq!(report.divergences.len(), 1);
+        assert_eq!(report.divergences[0].changes, vec![FieldChange { field: "rule_id".to_string(), before: Some(json!("__default__")), after: None }]);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].index, 2);
+    }
+
+    #[test]
+    fn test_replay_jsonl_parse_error() {
+        let err = parse_replay_jsonl(&(recorded() + "\n{\"decision\": null}\n")).unwrap_err().to_string();
+        assert_eq!(err, "Parse error: Replay JSONL parse error: missing field `payload` (line 6, column 18)");
+    }
+
+    #[test]
+    fn test_replay_at_the_recorded_time() {
+        let source = r#"
+version: "1.0"
+metadata: {}
+rules:
+  - id: "promo"
+    effective_until: "2024-01-01T00:00:00Z"
+    when: { type: "exists", field: "amount" }
+    then: { outcome: { action: "discount" } }
+"#;
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(parse_yaml(source).unwrap()).unwrap();
+        let payload = json!({"amount": 500});
+        let options = EvalOptions { now: crate::temporal::parse_timestamp_str("2023-06-01T00:00:00Z"), ..Default::default() };
+        let decision = engine.evaluate_value_with_options(&payload, &options).unwrap();
+        assert_eq!(decision.as_ref().unwrap().timestamp, 1_685_577_600);
+        assert_eq!(engine.evaluate_value(&payload).unwrap(), None);
+
+        let event = RecordedEvent { payload, decision, timestamp: None };
+        assert!(replay(&engine, [event]).is_reproducible());
+    }
+
+    #[test]
+    fn test_replay_events_no_rule_decided() {
+        let events = parse_replay_jsonl(concat!(
+            r#"{"payload": {"amount": 500, "country": "US"}, "timestamp": 1700000000}"#,
+            "\n",
+            r#"{"payload": {"amount": 600, "country": "US"}, "decision": null}"#,
+        )).unwrap();
+        assert!(events.iter().all(|event| event.decision.is_none()));
+
+        let mut undecided = parse_yaml(SOURCE).unwrap();
+        undecided.default_action = None;
+        assert!(replay_ruleset(undecided, events.clone()).unwrap().is_reproducible());
+
+        // A rule now deciding an event none did is a divergence.
+        let report = replay_ruleset(parse_yaml(SOURCE).unwrap(), events).unwrap();
+        assert_eq!(report.reproduced, 0);
+        let change = FieldChange { field: "rule_id".to_string(), before: None, after: Some(json!("__default__")) };
+        assert_eq!(report.divergences[0].changes, vec![change]);
+    }
+}
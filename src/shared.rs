@@ -0,0 +1,104 @@
+//! A rule engine that can be reloaded while other threads evaluate.
+
+use crate::engine::{Decision, EngineError, RuleEngine, RuleSet};
+use crate::lock;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// An engine behind an atomically swapped handle. Evaluation works on a
+/// snapshot of the current engine, so every evaluation sees either the old
+/// or the new ruleset of a concurrent reload, never a mix, and a reload
+/// never waits for evaluations to finish.
+pub struct SharedRuleEngine {
+    current: RwLock<Arc<RuleEngine>>,
+}
+
+impl SharedRuleEngine {
+    /// Shares `engine`, keeping its settings for later reloads.
+    pub fn new(engine: RuleEngine) -> Self {
+        Self { current: RwLock::new(Arc::new(engine)) }
+    }
+
+    /// Validates `ruleset` against a copy of the current engine and swaps
+    /// it in. On error the current ruleset stays in place.
+    pub fn reload(&self, ruleset: RuleSet) -> Result<(), EngineError> {
+        let mut next = RuleEngine::clone(&self.snapshot());
+        next.load_ruleset(ruleset)?;
+        *lock::write(&self.current) = Arc::new(next);
+        Ok(())
+    }
+
+    /// The engine as of now; later reloads don't affect it.
+    pub fn snapshot(&self) -> Arc<RuleEngine> {
+        Arc::clone(&lock::read(&self.current))
+    }
+
+    pub fn evaluate(&self, payload: &HashMap<String, serde_json::Value>) -> Result<Option<Decision>, EngineError> {
+        self.snapshot().evaluate(payload)
+    }
+
+    /// Evaluates the whole batch against one snapshot.
+    pub fn evaluate_many(&self, events: &[HashMap<String, serde_json::Value>]) -> Result<Vec<Option<Decision>>, EngineError> {
+        self.snapshot().evaluate_many(events)
+    }
+
+    pub fn get_ruleset_sha(&self) -> Option<String> {
+        self.snapshot().get_ruleset_sha().cloned()
+    }
+}
+
+impl Default for SharedRuleEngine {
+    fn default() -> Self {
+        Self::new(RuleEngine::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{exists_ruleset, payload};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn test_reload_during_evaluation() {
+        let shared = SharedRuleEngine::default();
+        shared.reload(exists_ruleset("old", "amount")).unwrap();
+        let old_sha = shared.get_ruleset_sha().unwrap();
+        let new_sha = {
+            let mut engine = RuleEngine::new();
+            engine.load_ruleset(exists_ruleset("new", "amount")).unwrap();
+            engine.get_ruleset_sha().cloned().unwrap()
+        };
+        let event = payload(json!({"amount": 1}));
+        let done = AtomicBool::new(false);
+
+        std::thread::scope(|scope| {
+            let evaluators: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| {
+                    let mut seen = 0;
+                    while !done.load(Ordering::Relaxed) || seen == 0 {
+                        let decision = shared.evaluate(&event).unwrap().unwrap();
+                        let expected_sha = if decision.rule_id == "old" { &old_sha } else { &new_sha };
+                        assert_eq!(&decision.rule_sha, expected_sha);
+                        assert_eq!(decision.outcome["rule"], json!(decision.rule_id));
+                        seen += 1;
+                    }
+                    seen
+                }))
+                .collect();
+            for round in 0..200 {
+                shared.reload(exists_ruleset(if round % 2 == 0 { "new" } else { "old" }, "amount")).unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+            for evaluator in evaluators {
+                assert!(evaluator.join().unwrap() > 0);
+            }
+        });
+
+        let mut invalid = exists_ruleset("broken", "amount");
+        invalid.rules.push(invalid.rules[0].clone());
+        assert!(shared.reload(invalid).is_err());
+        assert_eq!(shared.get_ruleset_sha(), Some(old_sha));
+    }
+}
@@ -0,0 +1,231 @@
+//! Detached ed25519 signatures over a ruleset's canonical JSON, so a
+//! deployment can check that a ruleset came from whoever holds the signing
+//! key and not just that it hashes to a known value, and HMAC signatures on
+//! decisions, so services downstream can tell a decision wasn't altered.
+//!
+//! Ruleset keys and signatures are accepted as raw bytes or as base64 text,
+//! and signatures are produced as base64. A signature can travel as a
+//! sidecar string or in the ruleset's `metadata` under
+//! [`SIGNATURE_METADATA_KEY`], which is left out of the signed bytes.
+
+use crate::engine::{Decision, EngineError, RuleSet};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Metadata key for a signature carried inside the ruleset.
+pub const SIGNATURE_METADATA_KEY: &str = "logicbridge_signature";
+
+/// Base64 ed25519 signature of `ruleset` under the 32-byte `signing_key`.
+pub fn sign_ruleset(ruleset: &RuleSet, signing_key: &[u8]) -> Result<String, EngineError> {
+    let key = SigningKey::from_bytes(&decode_fixed::<32>("signing key", signing_key)?);
+    Ok(STANDARD.encode(key.sign(signed_bytes(ruleset)?.as_bytes()).to_bytes()))
+}
+
+/// Base64 public key matching the 32-byte `signing_key`.
+pub fn derive_public_key(signing_key: &[u8]) -> Result<String, EngineError> {
+    let key = SigningKey::from_bytes(&decode_fixed::<32>("signing key", signing_key)?);
+    Ok(STANDARD.encode(key.verifying_key().to_bytes()))
+}
+
+/// Checks `signature` over `ruleset` against `public_key`.
+pub fn verify_ruleset(ruleset: &RuleSet, signature: &[u8], public_key: &[u8]) -> Result<(), EngineError> {
+    let key = VerifyingKey::from_bytes(&decode_fixed::<32>("public key", public_key)?)
+        .map_err(|e| EngineError::Signature(format!("Invalid public key: {}", e)))?;
+    let signature = Signature::from_bytes(&decode_fixed::<64>("signature", signature)?);
+    key.verify_strict(signed_bytes(ruleset)?.as_bytes(), &signature)
+        .map_err(|_| EngineError::Signature("Ruleset signature does not match".to_string()))
+}
+
+/// Stores `signature` in the ruleset's metadata.
+pub fn embed_signature(ruleset: &mut RuleSet, signature: &str) {
+    ruleset.metadata.insert(SIGNATURE_METADATA_KEY.to_string(), serde_json::Value::String(signature.to_string()));
+}
+
+/// The signature stored in the ruleset's metadata, if any.
+pub fn embedded_signature(ruleset: &RuleSet) -> Option<&str> {
+    ruleset.metadata.get(SIGNATURE_METADATA_KEY)?.as_str()
+}
+
+fn signed_bytes(ruleset: &RuleSet) -> Result<String, EngineError> {
+    if !ruleset.metadata.contains_key(SIGNATURE_METADATA_KEY) {
+        return ruleset.canonical_json();
+    }
+    let mut unsigned = ruleset.clone();
+    unsigned.metadata.remove(SIGNATURE_METADATA_KEY);
+    unsigned.canonical_json()
+}
+
+/// Hex HMAC-SHA256 under `key` of [`Decision::to_json`], canonical JSON,
+/// with the `signature` field left out.
+pub fn sign_decision(decision: &Decision, key: &[u8]) -> Result<String, EngineError> {
+    let mac = decision_mac(decision, key)?;
+    Ok(mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Checks the decision's `signature` against `key`, in constant time.
+pub fn verify_decision(decision: &Decision, key: &[u8]) -> Result<(), EngineError> {
+    let signature = decision.signature.as_deref()
+        .ok_or_else(|| EngineError::Signature("Decision is not signed".to_string()))?;
+    let bytes = decode_hex(signature)
+        .ok_or_else(|| EngineError::Signature("Decision signature is not hex".to_string()))?;
+    decision_mac(decision, key)?
+        .verify_slice(&bytes)
+        .map_err(|_| EngineError::Signature("Decision signature does not match".to_string()))
+}
+
+fn decision_mac(decision: &Decision, key: &[u8]) -> Result<Hmac<Sha256>, EngineError> {
+    let unsigned = Decision { signature: None, ..decision.clone() };
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)
+        .map_err(|e| EngineError::Signature(format!("Invalid HMAC key: {}", e)))?;
+    mac.update(unsigned.to_json()?.as_bytes());
+    Ok(mac)
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok().filter(|pair| pair.len() == 2)?, 16).ok())
+        .collect()
+}
+
+/// `N` raw bytes, or base64 text decoding to `N` bytes.
+fn decode_fixed<const N: usize>(what: &str, input: &[u8]) -> Result<[u8; N], EngineError> {
+    if let Ok(bytes) = <[u8; N]>::try_from(input) {
+        return Ok(bytes);
+    }
+    let text = std::str::from_utf8(input).map(str::trim).unwrap_or_default();
+    STANDARD.decode(text).ok()
+        .and_then(|bytes| <[u8; N]>::try_from(bytes).ok())
+        .ok_or_else(|| EngineError::Signature(format!("Invalid {}: expected {} bytes, raw or base64", what, N)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::parse_yaml;
+    use crate::engine::RuleEngine;
+
+    const SOURCE: &str = r#"
+version: "1.0"
+metadata: { owner: "policy" }
+rules:
+  - id: "large"
+    when: { type: "greater_than", field: "amount", value: 1000 }
+    then: { outcome: { action: "review" } }
+"#;
+
+    #[test]
+    fn test_signed_rulesets() {
+        let signing_key = [7u8; 32];
+        let public = derive_public_key(&signing_key).unwrap();
+        let signature = sign_ruleset(&parse_yaml(SOURCE).unwrap(), STANDARD.encode(signing_key).as_bytes()).unwrap();
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset_signed(parse_yaml(SOURCE).unwrap(), signature.as_bytes(), public.as_bytes()).unwrap();
+        let sha = engine.get_ruleset_sha().cloned();
+
+        // Raw bytes work as well as base64, and the signature can ride along in metadata.
+        let raw_public = STANDARD.decode(&public).unwrap();
+        let raw_signature = STANDARD.decode(&signature).unwrap();
+        assert!(verify_ruleset(&parse_yaml(SOURCE).unwrap(), &raw_signature, &raw_public).is_ok());
+        let mut carried = parse_yaml(SOURCE).unwrap();
+        embed_signature(&mut carried, &signature);
+        assert!(carried.metadata.contains_key("logicbridge_signature"));
+        let embedded = embedded_signature(&carried).unwrap().to_string();
+        assert!(verify_ruleset(&carried, embedded.as_bytes(), public.as_bytes()).is_ok());
+
+        let tampered = parse_yaml(&SOURCE.replace("1000", "1000000")).unwrap();
+        let err = engine.load_ruleset_signed(tampered, signature.as_bytes(), public.as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "Signature error: Ruleset signature does not match");
+        assert!(matches!(err, EngineError::Signature(_)));
+
+        let wrong_key = derive_public_key(&[8u8; 32]).unwrap();
+        let err = engine.load_ruleset_signed(parse_yaml(SOURCE).unwrap(), signature.as_bytes(), wrong_key.as_bytes()).unwrap_err();
+        assert!(matches!(err, EngineError::Signature(_)));
+        assert_eq!(engine.get_ruleset_sha().cloned(), sha);
+        assert!(engine.history().is_empty());
+
+        // A signed load replaces the ruleset the way any load does.
+        engine.load_ruleset_signed(parse_yaml(SOURCE).unwrap(), signature.as_bytes(), public.as_bytes()).unwrap();
+        assert_eq!(engine.history().len(), 1);
+
+        let err = verify_ruleset(&carried, b"not a signature", public.as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "Signature error: Invalid signature: expected 64 bytes, raw or base64");
+    }
+
+    fn large_event() -> std::collections::HashMap<String, serde_json::Value> {
+        serde_json::from_value(serde_json::json!({"amount": 5000})).unwrap()
+    }
+
+    fn signed_decision() -> Decision {
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(parse_yaml(SOURCE).unwrap()).unwrap();
+        engine.set_decision_signing_key(Some(b"audit-key"));
+        engine.evaluate(&large_event()).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_signed_decision_verifies() {
+        let decision = signed_decision();
+        let signature = decision.signature.clone().unwrap();
+        assert_eq!(signature.len(), 64);
+        assert!(verify_decision(&decision, b"audit-key").is_ok());
+        assert_eq!(sign_decision(&decision, b"audit-key").unwrap(), signature);
+        // Survives the JSON round trip that carries it between services.
+        assert!(verify_decision(&Decision::from_json(&decision.to_json().unwrap()).unwrap(), b"audit-key").is_ok());
+    }
+
+    #[test]
+    fn test_signed_decision_rejects_changes() {
+        let decision = signed_decision();
+        let err = verify_decision(&decision, b"other-key").unwrap_err();
+        assert_eq!(err.to_string(), "Signature error: Decision signature does not match");
+        let tamperings: [fn(&mut Decision); 4] = [
+            |d| d.rule_id = "small".to_string(),
+            |d| { d.outcome.insert("action".to_string(), serde_json::json!("approve")); },
+            |d| d.timestamp += 1,
+            |d| d.payload_sha.push('0'),
+        ];
+        for tamper in tamperings {
+            let mut altered = decision.clone();
+            tamper(&mut altered);
+            assert!(verify_decision(&altered, b"audit-key").is_err());
+        }
+        let unsigned = Decision { signature: None, ..decision };
+        assert_eq!(verify_decision(&unsigned, b"audit-key").unwrap_err().to_string(), "Signature error: Decision is not signed");
+    }
+
+    #[test]
+    fn test_decisions_without_a_signing_key_are_unsigned() {
+        let mut engine = RuleEngine::new();
+        engine.load_ruleset(parse_yaml(SOURCE).unwrap()).unwrap();
+        assert_eq!(engine.evaluate(&large_event()).unwrap().unwrap().signature, None);
+        engine.set_decision_signing_key(Some(b"audit-key"));
+        engine.set_decision_signing_key(None);
+        assert_eq!(engine.evaluate(&large_event()).unwrap().unwrap().signature, None);
+    }
+
+    #[test]
+    fn test_signed_decisions_survive_the_audit_log() {
+        // The export writes `1.0` as `1`; the signature covers the same form.
+        let mut engine = RuleEngine::new();
+        let outcome = "{ action: \"review\", fee: 1.0, queue: \"manual\", limits: { daily: 2.5 } }";
+        let source = SOURCE.replace("{ action: \"review\" }", outcome);
+        engine.load_ruleset(parse_yaml(&source).unwrap()).unwrap();
+        engine.set_decision_signing_key(Some(b"audit-key"));
+        engine.enable_audit_log(4, false);
+        let decision = engine.evaluate(&large_event()).unwrap().unwrap();
+        assert!(verify_decision(&decision, b"audit-key").is_ok());
+
+        let mut exported = Vec::new();
+        assert_eq!(engine.export_audit_jsonl(&mut exported).unwrap(), 1);
+        let line = String::from_utf8(exported).unwrap();
+        assert!(line.contains(r#""fee":1,"#), "{}", line);
+        let record: crate::audit::AuditRecord = serde_json::from_str(line.trim_end()).unwrap();
+        let read_back = record.decision.unwrap();
+        assert_eq!(read_back.outcome.len(), 4);
+        assert!(verify_decision(&read_back, b"audit-key").is_ok());
+    }
+}
@@ -0,0 +1,101 @@
+//! `{{field}}` interpolation for outcome strings, such as
+//! `"Blocked transfer of {{amount}} for {{customer.id}}"`.
+//!
+//! Placeholders take any field path and may be padded with spaces. `\{{`
+//! writes a literal `{{`; any other backslash or lone brace is plain text.
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field(String),
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = source;
+        while let Some(pos) = rest.find("{{") {
+            if rest[..pos].ends_with('\\') {
+                text.push_str(&rest[..pos - 1]);
+                text.push_str("{{");
+                rest = &rest[pos + 2..];
+                continue;
+            }
+            text.push_str(&rest[..pos]);
+            let after = &rest[pos + 2..];
+            let end = after.find("}}")
+                .ok_or_else(|| format!("unclosed '{{{{' in \"{}\"", source))?;
+            let field = after[..end].trim();
+            if field.is_empty() || field.contains("{{") {
+                return Err(format!("empty or nested placeholder in \"{}\"", source));
+            }
+            if !text.is_empty() {
+                parts.push(Part::Text(std::mem::take(&mut text)));
+            }
+            parts.push(Part::Field(field.to_string()));
+            rest = &after[end + 2..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Field paths the template reads, in order.
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Field(field) => Some(field.as_str()),
+            Part::Text(_) => None,
+        })
+    }
+
+    /// Whether some rendering could equal `text`, treating each placeholder
+    /// as any string.
+    pub fn can_render(&self, text: &str) -> bool {
+        let mut rest = text;
+        let mut after_field = false;
+        for (i, part) in self.parts.iter().enumerate() {
+            match part {
+                Part::Field(_) => after_field = true,
+                Part::Text(literal) if i + 1 == self.parts.len() => {
+                    return if after_field { rest.ends_with(literal.as_str()) } else { rest == literal };
+                },
+                Part::Text(literal) if after_field => match rest.find(literal.as_str()) {
+                    Some(pos) => rest = &rest[pos + literal.len()..],
+                    None => return false,
+                },
+                Part::Text(literal) => match rest.strip_prefix(literal.as_str()) {
+                    Some(tail) => rest = tail,
+                    None => return false,
+                },
+            }
+        }
+        after_field || rest.is_empty()
+    }
+
+    /// Renders the template. Strings are inserted without quotes, other
+    /// values as compact JSON; missing and null fields become `missing`.
+    pub fn render<'a>(&self, lookup: impl Fn(&str) -> Option<&'a Value>, missing: &str) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Field(field) => match lookup(field) {
+                    Some(Value::String(s)) => out.push_str(s),
+                    Some(Value::Null) | None => out.push_str(missing),
+                    Some(value) => out.push_str(&value.to_string()),
+                },
+            }
+        }
+        out
+    }
+}
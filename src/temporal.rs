@@ -0,0 +1,88 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+
+/// Parses an ISO 8601 / RFC 3339 datetime or a plain `YYYY-MM-DD` date.
+/// Datetimes without an offset and date-only values are taken as UTC, with
+/// dates at midnight.
+pub fn parse_timestamp_str(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(s, format) {
+            return Some(Utc.from_utc_datetime(&naive));
+        }
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|naive| Utc.from_utc_datetime(&naive))
+}
+
+/// Reads a payload timestamp: an ISO string or a number of epoch seconds.
+pub fn parse_timestamp(value: &serde_json::Value) -> Option<DateTime<Utc>> {
+    match value {
+        serde_json::Value::String(s) => parse_timestamp_str(s),
+        serde_json::Value::Number(n) => {
+            let secs = n.as_f64()?;
+            if !secs.is_finite() {
+                return None;
+            }
+            let nanos = ((secs - secs.floor()) * 1e9) as u32;
+            Utc.timestamp_opt(secs.floor() as i64, nanos).single()
+        },
+        _ => None,
+    }
+}
+
+/// Parses a compact duration such as `"30d"`, `"12h"`, `"45m"`, `"90s"` or
+/// `"2w"`.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let unit_start = s.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = s.split_at(unit_start);
+    let amount: i64 = amount.parse().ok()?;
+    match unit {
+        "s" => Duration::try_seconds(amount),
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        "w" => Duration::try_weeks(amount),
+        _ => None,
+    }
+}
+
+/// Parses a weekday name, full (`"monday"`) or abbreviated (`"mon"`), in any
+/// case.
+pub fn parse_weekday(s: &str) -> Option<Weekday> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Parses an `HH:MM` wall-clock time.
+pub fn parse_clock_time(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M").ok()
+}
+
+/// Parses an IANA timezone name such as `"Europe/Berlin"`; `None` means UTC.
+pub fn parse_timezone(name: Option<&str>) -> Option<Tz> {
+    match name {
+        Some(name) => name.parse().ok(),
+        None => Some(Tz::UTC),
+    }
+}
+
+/// Weekday and wall-clock time of `instant` in the given timezone.
+pub fn local_weekday_and_time(instant: DateTime<Utc>, tz: Tz) -> (Weekday, NaiveTime) {
+    let local = instant.with_timezone(&tz);
+    (local.weekday(), local.time())
+}
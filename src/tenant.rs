@@ -0,0 +1,160 @@
+//! One engine per tenant behind a shared handle, so that a service can hold
+//! many tenants' rulesets and swap any one of them while others evaluate.
+
+use crate::engine::{Decision, EngineError, RuleEngine, RuleSet};
+use crate::lock;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Tenants' engines keyed by tenant ID. Every method takes `&self`: a new
+/// ruleset is validated before the map is locked, and evaluation only holds
+/// the lock long enough to clone the tenant's engine handle, so neither
+/// blocks other tenants for the duration of a load or an evaluation.
+#[derive(Default)]
+pub struct TenantRuleEngine {
+    template: RuleEngine,
+    tenants: RwLock<HashMap<String, Arc<RuleEngine>>>,
+}
+
+impl TenantRuleEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gives every new tenant a copy of `template`'s settings.
+    pub fn with_template(template: RuleEngine) -> Self {
+        Self { template, tenants: RwLock::default() }
+    }
+
+    /// Loads or replaces the tenant's ruleset, validating it against a copy
+    /// of the tenant's current engine, or of the template for a new tenant,
+    /// so settings and rollback history carry over. In-flight evaluations
+    /// finish against the ruleset they started with.
+    pub fn load_ruleset_for(&self, tenant_id: &str, ruleset: RuleSet) -> Result<(), EngineError> {
+        let current = self.read().get(tenant_id).cloned();
+        let mut engine = current.map_or_else(|| self.template.clone(), |engine| RuleEngine::clone(&engine));
+        engine.load_ruleset(ruleset)?;
+        self.write().insert(tenant_id.to_string(), Arc::new(engine));
+        Ok(())
+    }
+
+    pub fn evaluate_for(
+        &self,
+        tenant_id: &str,
+        payload: &HashMap<String, serde_json::Value>,
+    ) -> Result<Option<Decision>, EngineError> {
+        self.engine_for(tenant_id)?.evaluate(payload)
+    }
+
+    /// The tenant's engine, for calls beyond [`TenantRuleEngine::evaluate_for`].
+    pub fn engine_for(&self, tenant_id: &str) -> Result<Arc<RuleEngine>, EngineError> {
+        self.read()
+            .get(tenant_id)
+            .cloned()
+            .ok_or_else(|| EngineError::Execution(format!("Unknown tenant: {}", tenant_id)))
+    }
+
+    pub fn ruleset_sha_for(&self, tenant_id: &str) -> Option<String> {
+        self.read().get(tenant_id).and_then(|engine| engine.get_ruleset_sha().cloned())
+    }
+
+    /// Tenant IDs in sorted order.
+    pub fn list_tenants(&self) -> Vec<String> {
+        let mut tenants: Vec<String> = self.read().keys().cloned().collect();
+        tenants.sort();
+        tenants
+    }
+
+    /// Removes the tenant, returning whether it was loaded.
+    pub fn remove_tenant(&self, tenant_id: &str) -> bool {
+        self.write().remove(tenant_id).is_some()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Arc<RuleEngine>>> {
+        lock::read(&self.tenants)
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, Arc<RuleEngine>>> {
+        lock::write(&self.tenants)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{payload, ruleset};
+    use serde_json::json;
+
+    fn limit_ruleset(limit: i64) -> RuleSet {
+        ruleset(&format!("over_{}", limit), &format!(r#"{{ type: "greater_than", field: "amount", value: {} }}"#, limit))
+    }
+
+    /// Acme reviews amounts over 100, Globex amounts over 1000.
+    fn two_tenants() -> TenantRuleEngine {
+        let tenants = TenantRuleEngine::new();
+        tenants.load_ruleset_for("acme", limit_ruleset(100)).unwrap();
+        tenants.load_ruleset_for("globex", limit_ruleset(1000)).unwrap();
+        tenants
+    }
+
+    fn event() -> HashMap<String, serde_json::Value> {
+        payload(json!({"amount": 500}))
+    }
+
+    fn winner(tenants: &TenantRuleEngine, tenant: &str) -> Option<String> {
+        tenants.evaluate_for(tenant, &event()).unwrap().map(|d| d.rule_id)
+    }
+
+    #[test]
+    fn test_tenants_decide_by_their_own_rules() {
+        let tenants = two_tenants();
+        assert_eq!(winner(&tenants, "acme").as_deref(), Some("over_100"));
+        assert_eq!(winner(&tenants, "globex"), None);
+        assert_eq!(tenants.list_tenants(), vec!["acme", "globex"]);
+        assert_ne!(tenants.ruleset_sha_for("acme"), tenants.ruleset_sha_for("globex"));
+    }
+
+    #[test]
+    fn test_tenant_hot_swap() {
+        // An engine handle taken before the swap keeps its ruleset.
+        let tenants = two_tenants();
+        let globex_sha = tenants.ruleset_sha_for("globex");
+        let before = tenants.engine_for("acme").unwrap();
+        tenants.load_ruleset_for("acme", limit_ruleset(800)).unwrap();
+        assert_eq!(winner(&tenants, "acme"), None);
+        assert_eq!(before.evaluate(&event()).unwrap().unwrap().rule_id, "over_100");
+        assert_eq!(tenants.ruleset_sha_for("globex"), globex_sha);
+
+        let mut invalid = limit_ruleset(1);
+        invalid.rules.push(invalid.rules[0].clone());
+        assert!(tenants.load_ruleset_for("globex", invalid).is_err());
+        assert_eq!(tenants.ruleset_sha_for("globex"), globex_sha);
+    }
+
+    #[test]
+    fn test_tenant_engines_keep_their_settings() {
+        let mut template = RuleEngine::new();
+        template.set_coerce_numeric_strings(true);
+        let tenants = TenantRuleEngine::with_template(template);
+        let event = payload(json!({"amount": "500"}));
+
+        tenants.load_ruleset_for("acme", limit_ruleset(100)).unwrap();
+        let first_sha = tenants.ruleset_sha_for("acme").unwrap();
+        tenants.load_ruleset_for("acme", limit_ruleset(200)).unwrap();
+        let decision = tenants.evaluate_for("acme", &event).unwrap().unwrap();
+        assert_eq!(decision.rule_id, "over_200");
+        let engine = tenants.engine_for("acme").unwrap();
+        assert_eq!(engine.history().iter().map(|entry| &entry.sha).collect::<Vec<_>>(), vec![&first_sha]);
+    }
+
+    #[test]
+    fn test_tenant_removal() {
+        let tenants = two_tenants();
+        assert!(tenants.remove_tenant("acme"));
+        assert!(!tenants.remove_tenant("acme"));
+        assert_eq!(tenants.list_tenants(), vec!["globex"]);
+        let err = tenants.evaluate_for("acme", &event()).unwrap_err();
+        assert_eq!(err.to_string(), "Execution error: Unknown tenant: acme");
+        assert_eq!(tenants.ruleset_sha_for("acme"), None);
+    }
+}
@@ -0,0 +1,27 @@
+//! Fixtures shared by the unit tests.
+
+use crate::dsl::parse_yaml;
+use crate::engine::RuleSet;
+use std::collections::HashMap;
+
+pub(crate) fn payload(value: serde_json::Value) -> HashMap<String, serde_json::Value> {
+    serde_json::from_value(value).unwrap()
+}
+
+/// A ruleset of one rule, `rule_id`, that decides `{rule: <rule_id>}` when
+/// `when`, an inline YAML condition, holds.
+pub(crate) fn ruleset(rule_id: &str, when: &str) -> RuleSet {
+    parse_yaml(&format!(r#"
+version: "1.0"
+metadata: {{}}
+rules:
+  - id: "{}"
+    when: {}
+    then: {{ outcome: {{ rule: "{}" }} }}
+"#, rule_id, when, rule_id)).unwrap()
+}
+
+/// [`ruleset`] deciding whenever the payload has `field`.
+pub(crate) fn exists_ruleset(rule_id: &str, field: &str) -> RuleSet {
+    ruleset(rule_id, &format!(r#"{{ type: "exists", field: "{}" }}"#, field))
+}